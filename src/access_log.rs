@@ -0,0 +1,229 @@
+use axum::{
+    body::{Body, HttpBody},
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, Request},
+    middleware::Next,
+    response::Response,
+};
+use httpdate::HttpDate;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Layout of access log lines, those of Apache's `LogFormat` presets
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogFormat {
+    /// `127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /heroes/ HTTP/1.1" 200 2326`
+    Common,
+    /// `Common`, followed by the quoted `Referer` and `User-Agent`
+    Combined,
+}
+
+impl FromStr for AccessLogFormat {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "common" => Ok(AccessLogFormat::Common),
+            "combined" => Ok(AccessLogFormat::Combined),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Writer of one access log line per request, for tools reading Apache's formats
+pub struct AccessLog {
+    format: AccessLogFormat,
+    sink: Sink,
+}
+
+enum Sink {
+    Stdout,
+    Capture(Arc<Mutex<Vec<String>>>),
+}
+
+impl AccessLog {
+    /// Access log printed to stdout, apart from the tracing output on stderr
+    pub fn stdout(format: AccessLogFormat) -> Self {
+        AccessLog {
+            format,
+            sink: Sink::Stdout,
+        }
+    }
+
+    fn write(&self, line: String) {
+        match &self.sink {
+            Sink::Stdout => println!("{}", line),
+            Sink::Capture(lines) => {
+                if let Ok(mut lines) = lines.lock() {
+                    lines.push(line);
+                }
+            }
+        }
+    }
+}
+
+/// Middleware writing the access log line of every request once it's answered
+///
+/// The client address is `-` when the server doesn't provide the connection info, and the
+/// size is that of the body, `-` when it's empty or streamed. Neither the identity nor the
+/// user of the request are known, both are always `-`.
+pub async fn access_log(
+    State(log): State<Arc<AccessLog>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let time = SystemTime::now();
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or_else(
+            || "-".to_string(),
+            |ConnectInfo(addr)| addr.ip().to_string(),
+        );
+    let request_line = format!(
+        "{} {} {:?}",
+        request.method(),
+        request.uri(),
+        request.version()
+    );
+    let referer = header_field(request.headers(), header::REFERER);
+    let user_agent = header_field(request.headers(), header::USER_AGENT);
+
+    let response = next.run(request).await;
+
+    let size = match response.body().size_hint().exact() {
+        Some(0) | None => "-".to_string(),
+        Some(size) => size.to_string(),
+    };
+    let mut line = format!(
+        "{} - - [{}] \"{}\" {} {}",
+        client,
+        timestamp(time),
+        escape(&request_line),
+        response.status().as_u16(),
+        size
+    );
+    if log.format == AccessLogFormat::Combined {
+        line.push_str(&format!(
+            " \"{}\" \"{}\"",
+            escape(&referer),
+            escape(&user_agent)
+        ));
+    }
+    log.write(line);
+    response
+}
+
+/// Value of the header `name`, `-` when missing or not text
+fn header_field(headers: &HeaderMap, name: header::HeaderName) -> String {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map_or_else(|| "-".to_string(), str::to_string)
+}
+
+/// `value` fit for a quoted field: quotes and backslashes are escaped like Apache does,
+/// and control characters like `logging::sanitize` does
+fn escape(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\").replace('"', "\\\"");
+    crate::logging::sanitize(&escaped).into_owned()
+}
+
+/// `time` as `10/Oct/2000:13:55:36 +0000`, rearranged from its HTTP date
+/// `Tue, 10 Oct 2000 13:55:36 GMT`
+fn timestamp(time: SystemTime) -> String {
+    let date = HttpDate::from(time).to_string();
+    let parts: Vec<&str> = date.split(' ').collect();
+    match parts[..] {
+        [_, day, month, year, clock, _] => format!("{}/{}/{}:{} +0000", day, month, year, clock),
+        _ => date,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, middleware, routing::get, Router};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn logged_app(format: AccessLogFormat) -> (Router, Arc<Mutex<Vec<String>>>) {
+        let lines = Arc::new(Mutex::new(vec![]));
+        let log = AccessLog {
+            format,
+            sink: Sink::Capture(lines.clone()),
+        };
+        let app = Router::new()
+            .route("/heroes/", get(|| async { "[\"Storm\"]" }))
+            .route("/gone", get(|| async { StatusCode::NOT_FOUND }))
+            .layer(middleware::from_fn_with_state(Arc::new(log), access_log));
+        (app, lines)
+    }
+
+    fn request(uri: &str) -> Request<Body> {
+        let mut request = Request::builder()
+            .uri(uri)
+            .header(header::REFERER, "http://heroes.example/")
+            .header(header::USER_AGENT, "curl/8.0 \"quoted\"")
+            .body(Body::empty())
+            .unwrap();
+        let addr: SocketAddr = ([10, 0, 0, 7], 51234).into();
+        request.extensions_mut().insert(ConnectInfo(addr));
+        request
+    }
+
+    /// `line` with its timestamp replaced by `[time]`
+    fn without_time(line: &str) -> String {
+        let (start, rest) = line.split_once('[').unwrap();
+        let (_, end) = rest.split_once(']').unwrap();
+        format!("{}[time]{}", start, end)
+    }
+
+    #[test]
+    fn timestamps_follow_the_common_log_format() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(971_186_136);
+
+        assert_eq!(timestamp(time), "10/Oct/2000:13:55:36 +0000");
+    }
+
+    #[tokio::test]
+    async fn requests_are_logged_in_the_common_log_format() {
+        let (app, lines) = logged_app(AccessLogFormat::Common);
+
+        app.clone()
+            .oneshot(request("/heroes/?name=St"))
+            .await
+            .unwrap();
+        app.oneshot(request("/gone")).await.unwrap();
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(
+            lines
+                .iter()
+                .map(|line| without_time(line))
+                .collect::<Vec<_>>(),
+            vec![
+                "10.0.0.7 - - [time] \"GET /heroes/?name=St HTTP/1.1\" 200 9",
+                "10.0.0.7 - - [time] \"GET /gone HTTP/1.1\" 404 -",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn combined_format_adds_the_referer_and_user_agent() {
+        let (app, lines) = logged_app(AccessLogFormat::Combined);
+
+        app.oneshot(request("/heroes/")).await.unwrap();
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(
+            without_time(&lines[0]),
+            "10.0.0.7 - - [time] \"GET /heroes/ HTTP/1.1\" 200 9 \
+             \"http://heroes.example/\" \"curl/8.0 \\\"quoted\\\"\""
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::{config::Config, error::ApiError};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+pub const API_VERSION_HEADER: &str = "api-version";
+
+/// Values of `Api-Version` the heroes routes answer to, the version of their `/api/v1/` path
+pub const SUPPORTED_VERSIONS: [&str; 1] = ["1"];
+
+/// Middleware refusing requests to the heroes routes without a supported `Api-Version`
+/// header, when `REQUIRE_API_VERSION` is set: `400` without one, `406` for another version
+pub async fn require_api_version(
+    State(config): State<Arc<Config>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    if !config.require_api_version {
+        return next.run(request).await;
+    }
+    let version = match request.headers().get(API_VERSION_HEADER) {
+        Some(version) => version.to_str().unwrap_or_default().trim(),
+        None => return ApiError::bad_request("the Api-Version header is required").into_response(),
+    };
+    if !SUPPORTED_VERSIONS.contains(&version) {
+        return ApiError::new(
+            StatusCode::NOT_ACCEPTABLE,
+            "unsupported_api_version",
+            format!(
+                "Api-Version '{}' is not supported, use one of: {}",
+                version,
+                SUPPORTED_VERSIONS.join(", ")
+            ),
+        )
+        .into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use rstest::rstest;
+    use tower::ServiceExt;
+
+    fn app(required: bool) -> Router {
+        let config = Config {
+            require_api_version: required,
+            ..Default::default()
+        };
+        Router::new()
+            .route("/heroes", get(|| async { "Storm" }))
+            .layer(middleware::from_fn_with_state(
+                Arc::new(config),
+                require_api_version,
+            ))
+    }
+
+    async fn status(required: bool, version: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().uri("/heroes");
+        if let Some(version) = version {
+            request = request.header(API_VERSION_HEADER, version);
+        }
+        let request = request.body(Body::empty()).unwrap();
+
+        app(required).oneshot(request).await.unwrap().status()
+    }
+
+    #[rstest]
+    #[case(Some("1"), StatusCode::OK)]
+    #[case(None, StatusCode::BAD_REQUEST)]
+    #[case(Some("2"), StatusCode::NOT_ACCEPTABLE)]
+    #[tokio::test]
+    async fn version_is_checked_when_required(
+        #[case] version: Option<&str>,
+        #[case] expected: StatusCode,
+    ) {
+        assert_eq!(status(true, version).await, expected);
+    }
+
+    #[rstest]
+    #[case(None)]
+    #[case(Some("2"))]
+    #[tokio::test]
+    async fn version_is_ignored_by_default(#[case] version: Option<&str>) {
+        assert_eq!(status(false, version).await, StatusCode::OK);
+    }
+}
@@ -0,0 +1,434 @@
+use crate::hero_query::NumericField;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::{
+    tenant, DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats,
+    HeroesRepositoryTrait, TagChanges, UpsertReport,
+};
+use axum::async_trait;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Kind of change applied to a hero
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+}
+
+impl AuditAction {
+    /// Name of the action, as serialized
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::Create => "create",
+            AuditAction::Update => "update",
+            AuditAction::Delete => "delete",
+        }
+    }
+}
+
+/// One change of a hero, with its state before and after the change
+#[derive(Serialize, Debug, Clone)]
+pub struct AuditEvent {
+    /// milliseconds since the unix epoch
+    pub timestamp: u64,
+    pub action: AuditAction,
+    pub before: Option<Hero>,
+    pub after: Option<Hero>,
+}
+
+impl AuditEvent {
+    fn now(action: AuditAction, before: Option<Hero>, after: Option<Hero>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        AuditEvent {
+            timestamp,
+            action,
+            before,
+            after,
+        }
+    }
+}
+
+/// Store of the changes applied to heroes
+#[async_trait]
+pub trait AuditLogTrait {
+    async fn record(&self, hero_id: &str, event: AuditEvent);
+    /// Events recorded for a hero, oldest first
+    async fn history(&self, hero_id: &str) -> Vec<AuditEvent>;
+}
+
+pub type DynAuditLog = Arc<dyn AuditLogTrait + Send + Sync>;
+
+/// Audit log kept in memory: history is lost on restart
+///
+/// Histories are kept per tenant, as hero ids are only unique within a tenant.
+#[derive(Default)]
+pub struct InMemoryAuditLog {
+    events: RwLock<HashMap<String, Vec<AuditEvent>>>,
+}
+
+#[async_trait]
+impl AuditLogTrait for InMemoryAuditLog {
+    async fn record(&self, hero_id: &str, event: AuditEvent) {
+        if let Ok(mut events) = self.events.write() {
+            events
+                .entry(tenant::scoped(hero_id))
+                .or_default()
+                .push(event);
+        }
+    }
+
+    async fn history(&self, hero_id: &str) -> Vec<AuditEvent> {
+        self.events
+            .read()
+            .ok()
+            .and_then(|events| events.get(&tenant::scoped(hero_id)).cloned())
+            .unwrap_or_default()
+    }
+}
+
+/// Repository decorator recording every successful write to an audit log
+pub struct AuditedHeroesRepository<R> {
+    inner: R,
+    audit_log: DynAuditLog,
+}
+
+impl<R> AuditedHeroesRepository<R> {
+    pub fn new(inner: R, audit_log: DynAuditLog) -> Self {
+        AuditedHeroesRepository { inner, audit_log }
+    }
+}
+
+impl<R> AuditedHeroesRepository<R> {
+    async fn record_all(&self, events: Vec<(String, AuditEvent)>) {
+        for (id, event) in events {
+            self.audit_log.record(&id, event).await;
+        }
+    }
+}
+
+/// Events of a batch write turning the heroes `before` into the heroes `after`, with the id
+/// of their hero; those of `before` missing from `after` were deleted when `deleting`,
+/// otherwise they were left as they were
+pub fn batch_events(
+    before: Vec<Hero>,
+    after: Vec<Hero>,
+    deleting: bool,
+) -> Vec<(String, AuditEvent)> {
+    let mut before: HashMap<String, Hero> = before
+        .into_iter()
+        .map(|hero| (hero.id.clone(), hero))
+        .collect();
+    let mut events: Vec<(String, AuditEvent)> = after
+        .into_iter()
+        .map(|hero| {
+            let event = match before.remove(&hero.id) {
+                Some(previous) => {
+                    AuditEvent::now(AuditAction::Update, Some(previous), Some(hero.clone()))
+                }
+                None => AuditEvent::now(AuditAction::Create, None, Some(hero.clone())),
+            };
+            (hero.id, event)
+        })
+        .collect();
+    if deleting {
+        events.extend(
+            before
+                .into_iter()
+                .map(|(id, hero)| (id, AuditEvent::now(AuditAction::Delete, Some(hero), None))),
+        );
+    }
+    events
+}
+
+#[async_trait]
+impl<R: HeroesRepositoryTrait + Send + Sync> HeroesRepositoryTrait for AuditedHeroesRepository<R> {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_name(name).await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.inner.get_by_id(id).await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        let created = self.inner.create(hero).await?;
+        self.audit_log
+            .record(
+                &created.id,
+                AuditEvent::now(AuditAction::Create, None, Some(created.clone())),
+            )
+            .await;
+        Ok(created)
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        let created = self.inner.create_if_absent(id, hero).await?;
+        self.audit_log
+            .record(
+                &created.id,
+                AuditEvent::now(AuditAction::Create, None, Some(created.clone())),
+            )
+            .await;
+        Ok(created)
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        // best effort: the before state is read separately from the update itself
+        let before = self.inner.get_by_id(id).await.ok();
+        let updated = self.inner.update(id, hero).await?;
+        self.audit_log
+            .record(
+                id,
+                AuditEvent::now(AuditAction::Update, before, Some(updated.clone())),
+            )
+            .await;
+        Ok(updated)
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        let deleted = self.inner.delete(id).await?;
+        self.audit_log
+            .record(
+                id,
+                AuditEvent::now(AuditAction::Delete, Some(deleted.clone()), None),
+            )
+            .await;
+        Ok(deleted)
+    }
+
+    // one hero at a time, so each deletion is audited with the hero it removed
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        let heroes = match self.inner.get_by_name(name).await {
+            Err(DataAccessError::NotFound) => vec![],
+            heroes => heroes?,
+        };
+        let mut deleted = vec![];
+        for hero in heroes {
+            match self.delete(&hero.id).await {
+                Ok(hero) => deleted.push(hero),
+                Err(DataAccessError::NotFound) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(deleted)
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.inner.count_by_initial().await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.inner.stats().await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.inner.dataset_digest().await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        self.inner.stream_all()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.search(term).await
+    }
+
+    // heroes left out of the new dataset are audited as deleted, the others as written
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        let before: Vec<Hero> = self
+            .inner
+            .stream_all()
+            .try_collect()
+            .await
+            .unwrap_or_default();
+        self.inner.replace_all(heroes.clone()).await?;
+        self.record_all(batch_events(before, heroes, true)).await;
+        Ok(())
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        let mut ids: Vec<String> = heroes.iter().map(|hero| hero.id.clone()).collect();
+        ids.sort();
+        ids.dedup();
+        let before = self.inner.get_by_ids(&ids).await.unwrap_or_default();
+        let report = self.inner.upsert_many(heroes).await?;
+        // read back, for the `updated_at` the repository gave them
+        let after = match self.inner.get_by_ids(&ids).await {
+            Ok(after) => after,
+            Err(_) => crate::last_occurrences(heroes)
+                .into_iter()
+                .cloned()
+                .collect(),
+        };
+        self.record_all(batch_events(before, after, false)).await;
+        Ok(report)
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_tag(tag).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_ids(ids).await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.inner.get_page(name, limit, offset).await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_name_regex(regex).await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_range(field, range).await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.inner.ping().await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        let before = self.inner.get_by_id(id).await.ok();
+        let updated = self.inner.update_tags(id, changes).await?;
+        self.audit_log
+            .record(
+                id,
+                AuditEvent::now(AuditAction::Update, before, Some(updated.clone())),
+            )
+            .await;
+        Ok(updated)
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        let before = self.inner.get_by_id(id).await.ok();
+        let updated = self.inner.update_with_tags(id, hero, tags).await?;
+        self.audit_log
+            .record(
+                id,
+                AuditEvent::now(AuditAction::Update, before, Some(updated.clone())),
+            )
+            .await;
+        Ok(updated)
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.inner.record_view(id).await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.inner.get_changes_since(since).await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.inner.get_with_neighbors(id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HeroName, InMemoryHeroesRepository, MockHeroesRepositoryTrait};
+
+    #[tokio::test]
+    async fn failed_write_is_not_recorded() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock
+            .expect_delete()
+            .return_once(|_| Err(DataAccessError::NotFound));
+
+        let audit_log = Arc::new(InMemoryAuditLog::default());
+        let repo = AuditedHeroesRepository::new(repo_mock, audit_log.clone());
+
+        assert!(repo.delete("1").await.is_err());
+        assert!(audit_log.history("1").await.is_empty());
+    }
+
+    fn hero(id: &str, name: &str) -> Hero {
+        Hero {
+            id: id.to_string(),
+            name: HeroName::new(name).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn actions(history: &[AuditEvent]) -> Vec<AuditAction> {
+        history.iter().map(|event| event.action).collect()
+    }
+
+    #[tokio::test]
+    async fn every_upserted_hero_is_recorded() {
+        let audit_log = Arc::new(InMemoryAuditLog::default());
+        let repo =
+            AuditedHeroesRepository::new(InMemoryHeroesRepository::default(), audit_log.clone());
+
+        repo.upsert_many(&[hero("1", "Diana Prince"), hero("7", "Storm")])
+            .await
+            .unwrap();
+
+        let updated = audit_log.history("1").await;
+        assert_eq!(actions(&updated), [AuditAction::Update]);
+        assert_eq!(
+            updated[0].before.as_ref().unwrap().name.as_str(),
+            "Wonder Woman"
+        );
+        assert_eq!(
+            updated[0].after.as_ref().unwrap().name.as_str(),
+            "Diana Prince"
+        );
+        let created = audit_log.history("7").await;
+        assert_eq!(actions(&created), [AuditAction::Create]);
+        assert!(created[0].after.as_ref().unwrap().updated_at.is_some());
+        assert!(audit_log.history("2").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn replacing_every_hero_records_each_change() {
+        let audit_log = Arc::new(InMemoryAuditLog::default());
+        let repo =
+            AuditedHeroesRepository::new(InMemoryHeroesRepository::default(), audit_log.clone());
+
+        repo.replace_all(vec![hero("1", "Diana Prince"), hero("7", "Storm")])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            actions(&audit_log.history("1").await),
+            [AuditAction::Update]
+        );
+        assert_eq!(
+            actions(&audit_log.history("7").await),
+            [AuditAction::Create]
+        );
+        let deleted = audit_log.history("2").await;
+        assert_eq!(actions(&deleted), [AuditAction::Delete]);
+        assert_eq!(
+            deleted[0].before.as_ref().unwrap().name.as_str(),
+            "Deadpool"
+        );
+    }
+}
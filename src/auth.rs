@@ -0,0 +1,152 @@
+use crate::{config::Config, error::ApiError};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+/// Middleware letting through only requests carrying `Authorization: Bearer <ADMIN_TOKEN>`
+///
+/// Without a configured token every request is rejected.
+pub async fn require_admin_token(
+    State(config): State<Arc<Config>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    match role(&config, &request) {
+        Role::Admin => next.run(request).await,
+        Role::Anonymous => ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "unauthorized",
+            "a valid admin bearer token is required",
+        )
+        .into_response(),
+    }
+}
+
+/// Who a request comes from, as far as the api can tell
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Role {
+    #[default]
+    Anonymous,
+    /// carrying `Authorization: Bearer <ADMIN_TOKEN>`
+    Admin,
+}
+
+/// Middleware adding the `Role` of requests to their extensions, for what's below to adapt
+/// responses to it; unlike `require_admin_token`, it lets every request through
+pub async fn identify_role(
+    State(config): State<Arc<Config>>,
+    mut request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let role = role(&config, &request);
+    request.extensions_mut().insert(role);
+    next.run(request).await
+}
+
+fn role(config: &Config, request: &Request<Body>) -> Role {
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match (config.admin_token.as_deref(), provided) {
+        (Some(expected), Some(provided)) if constant_time_eq(expected, provided) => Role::Admin,
+        _ => Role::Anonymous,
+    }
+}
+
+/// Whether `a` and `b` are equal, taking as long whatever their first difference
+///
+/// Only their length may leak, which says little about a random token.
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let difference = a
+        .bytes()
+        .zip(b.bytes())
+        .fold(0, |difference, (a, b)| difference | (a ^ b));
+    // `black_box` keeps the optimizer from returning as soon as a difference is found
+    a.len() == b.len() && std::hint::black_box(difference) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use rstest::rstest;
+    use tower::ServiceExt;
+
+    #[rstest]
+    #[case("s3cret", StatusCode::OK)]
+    #[case("s3creT", StatusCode::UNAUTHORIZED)]
+    #[case("s3cre", StatusCode::UNAUTHORIZED)]
+    #[case("s3cret!", StatusCode::UNAUTHORIZED)]
+    #[case("", StatusCode::UNAUTHORIZED)]
+    #[tokio::test]
+    async fn only_the_configured_token_is_accepted(
+        #[case] token: &str,
+        #[case] status: StatusCode,
+    ) {
+        let config = Config {
+            admin_token: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+        let app =
+            Router::new()
+                .route("/", get(|| async { "ok" }))
+                .layer(middleware::from_fn_with_state(
+                    Arc::new(config),
+                    require_admin_token,
+                ));
+        let request = Request::builder()
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), status);
+    }
+
+    #[rstest]
+    #[case(Some("Bearer s3cret"), Role::Admin)]
+    #[case(Some("Bearer nope"), Role::Anonymous)]
+    #[case(None, Role::Anonymous)]
+    #[tokio::test]
+    async fn role_is_added_to_every_request(
+        #[case] authorization: Option<&str>,
+        #[case] expected: Role,
+    ) {
+        let config = Config {
+            admin_token: Some("s3cret".to_string()),
+            ..Default::default()
+        };
+        let app = Router::new()
+            .route(
+                "/",
+                get(|request: Request<Body>| async move {
+                    format!("{:?}", request.extensions().get::<Role>())
+                }),
+            )
+            .layer(middleware::from_fn_with_state(
+                Arc::new(config),
+                identify_role,
+            ));
+        let mut request = Request::builder();
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, format!("{:?}", Some(expected)));
+    }
+}
@@ -0,0 +1,395 @@
+use crate::hero_query::NumericField;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::task::BackgroundTasks;
+use crate::{
+    tenant, DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats,
+    HeroesRepositoryTrait, TagChanges, UpsertReport,
+};
+use axum::async_trait;
+use futures::stream::BoxStream;
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::{self, Duration, Instant, MissedTickBehavior};
+
+/// Heroes found by a `get_by_name` call, and what's needed to make it again
+struct Entry {
+    tenant: Option<String>,
+    name: String,
+    heroes: Vec<Hero>,
+    fetched: Instant,
+    /// read since it was fetched, so worth refreshing before it expires
+    hot: bool,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<String, Entry>,
+    /// bumped by every write, so queries started before it aren't cached after it
+    generation: u64,
+}
+
+struct Cache<R> {
+    inner: R,
+    ttl: Option<Duration>,
+    entries: Mutex<Entries>,
+}
+
+/// Repository decorator caching `get_by_name` results for `ttl`
+///
+/// Any write empties the cache. Entries are otherwise only dropped once expired, and the
+/// hot ones are re-fetched beforehand by the task of `spawn_refresh`, so their readers
+/// never wait for the inner repository. Queries of different tenants are never shared.
+/// Without a `ttl` nothing is cached.
+pub struct CachingHeroesRepository<R>(Arc<Cache<R>>);
+
+impl<R> CachingHeroesRepository<R> {
+    pub fn new(inner: R, ttl: Option<Duration>) -> Self {
+        CachingHeroesRepository(Arc::new(Cache {
+            inner,
+            ttl,
+            entries: Mutex::default(),
+        }))
+    }
+}
+
+impl<R: HeroesRepositoryTrait + Send + Sync + 'static> CachingHeroesRepository<R> {
+    /// Spawn the task refreshing hot entries, every `interval` until `shutdown` completes
+    ///
+    /// It's a critical task of `tasks`: readers wait for the repository while it's down.
+    pub fn spawn_refresh(
+        &self,
+        tasks: &Arc<BackgroundTasks>,
+        interval: Duration,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> JoinHandle<()> {
+        let cache = self.0.clone();
+        // a restarted task still stops with the first one
+        let shutdown = shutdown.shared();
+        tasks.spawn_critical("cache_refresh", move || {
+            let (cache, shutdown) = (cache.clone(), shutdown.clone());
+            async move {
+                let mut rounds = time::interval(interval);
+                rounds.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                tokio::pin!(shutdown);
+                loop {
+                    tokio::select! {
+                        _ = &mut shutdown => break,
+                        _ = rounds.tick() => cache.refresh_expiring(interval).await,
+                    }
+                }
+            }
+        })
+    }
+}
+
+impl<R: HeroesRepositoryTrait> Cache<R> {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        let Some(ttl) = self.ttl else {
+            return self.inner.get_by_name(name).await;
+        };
+        let key = tenant::scoped(name);
+        let generation = {
+            let mut entries = self
+                .entries
+                .lock()
+                .map_err(|_| DataAccessError::TechnicalError)?;
+            let cached = entries.by_key.get_mut(&key);
+            if let Some(entry) = cached.filter(|entry| entry.fetched.elapsed() < ttl) {
+                entry.hot = true;
+                return Ok(entry.heroes.clone());
+            }
+            entries.generation
+        };
+
+        let heroes = self.inner.get_by_name(name).await?;
+        self.store(generation, key, tenant::current(), name, heroes.clone());
+        Ok(heroes)
+    }
+
+    /// Cache `heroes`, unless a write happened since `generation`
+    fn store(
+        &self,
+        generation: u64,
+        key: String,
+        tenant: Option<String>,
+        name: &str,
+        heroes: Vec<Hero>,
+    ) {
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.generation == generation {
+                let entry = Entry {
+                    tenant,
+                    name: name.to_string(),
+                    heroes,
+                    fetched: Instant::now(),
+                    hot: false,
+                };
+                entries.by_key.insert(key, entry);
+            }
+        }
+    }
+
+    fn invalidate(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.by_key.clear();
+            entries.generation += 1;
+        }
+    }
+
+    /// Drop the expired entries and re-fetch the hot ones expiring before the next round,
+    /// `interval` from now
+    async fn refresh_expiring(&self, interval: Duration) {
+        let Some(ttl) = self.ttl else {
+            return;
+        };
+        let (generation, due) = {
+            let Ok(mut entries) = self.entries.lock() else {
+                return;
+            };
+            entries
+                .by_key
+                .retain(|_, entry| entry.fetched.elapsed() < ttl);
+            let due: Vec<(String, Option<String>, String)> = entries
+                .by_key
+                .iter()
+                .filter(|(_, entry)| entry.hot && entry.fetched.elapsed() + interval >= ttl)
+                .map(|(key, entry)| (key.clone(), entry.tenant.clone(), entry.name.clone()))
+                .collect();
+            (entries.generation, due)
+        };
+
+        for (key, tenant, name) in due {
+            match tenant::run_as(tenant.clone(), self.inner.get_by_name(&name)).await {
+                Ok(heroes) => self.store(generation, key, tenant, &name, heroes),
+                // the entry expires as if it wasn't hot
+                Err(error) => tracing::warn!(?error, name, "cache refresh failed"),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<R: HeroesRepositoryTrait + Send + Sync> HeroesRepositoryTrait for CachingHeroesRepository<R> {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.0.get_by_name(name).await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.0.inner.get_by_id(id).await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        let result = self.0.inner.create(hero).await;
+        self.0.invalidate();
+        result
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        let result = self.0.inner.create_if_absent(id, hero).await;
+        self.0.invalidate();
+        result
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        let result = self.0.inner.update(id, hero).await;
+        self.0.invalidate();
+        result
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        let result = self.0.inner.delete(id).await;
+        self.0.invalidate();
+        result
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        let result = self.0.inner.delete_by_name(name).await;
+        self.0.invalidate();
+        result
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.0.inner.count_by_initial().await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.0.inner.stats().await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.0.inner.dataset_digest().await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        self.0.inner.stream_all()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.0.inner.search(term).await
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        let result = self.0.inner.replace_all(heroes).await;
+        self.0.invalidate();
+        result
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        let result = self.0.inner.upsert_many(heroes).await;
+        self.0.invalidate();
+        result
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.0.inner.get_by_tag(tag).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.0.inner.get_by_ids(ids).await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.0.inner.get_page(name, limit, offset).await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.0.inner.get_by_name_regex(regex).await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.0.inner.get_by_range(field, range).await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.0.inner.ping().await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        let result = self.0.inner.update_tags(id, changes).await;
+        self.0.invalidate();
+        result
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        let result = self.0.inner.update_with_tags(id, hero, tags).await;
+        self.0.invalidate();
+        result
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.0.inner.record_view(id).await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.0.inner.get_changes_since(since).await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.0.inner.get_with_neighbors(id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fn_repository::FnHeroesRepository;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const TTL: Duration = Duration::from_secs(10);
+
+    /// Cache of a repository counting its `get_by_name` calls, other methods set by `with`
+    fn repository(
+        with: impl FnOnce(FnHeroesRepository) -> FnHeroesRepository,
+    ) -> (
+        CachingHeroesRepository<FnHeroesRepository>,
+        Arc<AtomicUsize>,
+    ) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let inner = FnHeroesRepository::new().on_get_by_name(move |_| {
+            counted.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![Hero::default()])
+        });
+        (CachingHeroesRepository::new(with(inner), Some(TTL)), calls)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fresh_entries_are_served_from_the_cache() {
+        let (repo, calls) = repository(|repo| repo);
+
+        repo.get_by_name("W%").await.unwrap();
+        repo.get_by_name("W%").await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        time::advance(TTL).await;
+        repo.get_by_name("W%").await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hot_entries_are_refreshed_before_they_expire() {
+        let (repo, calls) = repository(|repo| repo);
+        let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+        let refresh = repo.spawn_refresh(&Default::default(), Duration::from_secs(1), async {
+            let _ = stopped.await;
+        });
+        repo.get_by_name("W%").await.unwrap();
+        repo.get_by_name("W%").await.unwrap();
+
+        // the last round before expiry, 9s after the fetch, refreshes the entry on its own
+        time::sleep(Duration::from_millis(9_500)).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        time::sleep(Duration::from_secs(1)).await;
+        repo.get_by_name("W%").await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        stop.send(()).unwrap();
+        refresh.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cold_entries_are_left_to_expire() {
+        let (repo, calls) = repository(|repo| repo);
+        let refresh = repo.spawn_refresh(
+            &Default::default(),
+            Duration::from_secs(1),
+            std::future::pending(),
+        );
+        repo.get_by_name("W%").await.unwrap();
+
+        time::sleep(TTL * 2).await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        refresh.abort();
+    }
+
+    #[tokio::test]
+    async fn writes_empty_the_cache() {
+        let (repo, calls) = repository(|repo| repo.on_delete(|_| Ok(Hero::default())));
+
+        repo.get_by_name("W%").await.unwrap();
+        repo.delete("1").await.unwrap();
+        repo.get_by_name("W%").await.unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
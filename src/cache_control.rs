@@ -0,0 +1,50 @@
+use crate::{config::Config, tenant};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderValue, Method, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Middleware marking successful `GET`s with the configured `CACHE_CONTROL`
+///
+/// Only meant for routes whose reads may be shared by caches: hero listings are the same
+/// for every client of a tenant. A `Cache-Control` set by the route itself, such as the
+/// `no-cache` of event streams, is kept.
+pub async fn cacheable_reads(
+    State(config): State<Arc<Config>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let is_read = matches!(*request.method(), Method::GET | Method::HEAD);
+    let mut response = next.run(request).await;
+
+    let cache_control = config
+        .cache_control
+        .as_deref()
+        .and_then(|value| HeaderValue::from_str(value).ok());
+    if let Some(cache_control) = cache_control {
+        let decided = response.headers().contains_key(header::CACHE_CONTROL);
+        if is_read && response.status().is_success() && !decided {
+            let headers = response.headers_mut();
+            headers.insert(header::CACHE_CONTROL, cache_control);
+            headers.append(
+                header::VARY,
+                HeaderValue::from_static(tenant::TENANT_HEADER),
+            );
+        }
+    }
+    response
+}
+
+/// Middleware making responses uncacheable unless a route decided otherwise
+pub async fn no_store_by_default(request: Request<Body>, next: Next<Body>) -> Response {
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .entry(header::CACHE_CONTROL)
+        .or_insert(HeaderValue::from_static("no-store"));
+    response
+}
@@ -0,0 +1,397 @@
+use crate::hero_query::NumericField;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::{
+    DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats, HeroesRepositoryTrait,
+    TagChanges, UpsertReport,
+};
+use axum::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// State of a `CircuitBreaker`, as reported by `GET /health/ready`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// calls go through
+    Closed,
+    /// calls fail right away with `Unavailable`, until the cooldown is over
+    Open,
+    /// a single trial call goes through, closing the circuit if it succeeds
+    HalfOpen,
+}
+
+#[derive(Debug)]
+enum Circuit {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// a trial call is in flight; another may go at `retry_at` if it never reports back
+    HalfOpen {
+        retry_at: Instant,
+    },
+}
+
+/// Breaker opening after `threshold` consecutive technical errors of the repository
+///
+/// Once open, calls are refused for `cooldown`; the first call after it is a trial, others
+/// being refused until its outcome closes the circuit again or opens it for another
+/// cooldown. Only `TechnicalError`s count: any other outcome is the repository answering.
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    circuit: Mutex<Circuit>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold,
+            cooldown,
+            circuit: Mutex::new(Circuit::Closed { failures: 0 }),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        match *self.circuit() {
+            Circuit::Closed { .. } => CircuitState::Closed,
+            Circuit::Open { .. } => CircuitState::Open,
+            Circuit::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+
+    fn circuit(&self) -> std::sync::MutexGuard<'_, Circuit> {
+        self.circuit
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Whether a call may go to the repository at `now`
+    fn admit(&self, now: Instant) -> bool {
+        let mut circuit = self.circuit();
+        match *circuit {
+            Circuit::Closed { .. } => true,
+            Circuit::Open { until } | Circuit::HalfOpen { retry_at: until } if now < until => false,
+            Circuit::Open { .. } | Circuit::HalfOpen { .. } => {
+                tracing::info!("circuit breaker half-open, trying the repository");
+                *circuit = Circuit::HalfOpen {
+                    retry_at: now + self.cooldown,
+                };
+                true
+            }
+        }
+    }
+
+    /// Count the outcome of an admitted call which ended at `now`
+    pub(crate) fn record(&self, now: Instant, failed: bool) {
+        let mut circuit = self.circuit();
+        match (&mut *circuit, failed) {
+            (Circuit::Closed { failures }, true) => {
+                *failures += 1;
+                if *failures >= self.threshold {
+                    tracing::warn!(failures = *failures, "circuit breaker opened");
+                    *circuit = Circuit::Open {
+                        until: now + self.cooldown,
+                    };
+                }
+            }
+            (Circuit::HalfOpen { .. }, true) => {
+                tracing::warn!("trial call failed, circuit breaker opened again");
+                *circuit = Circuit::Open {
+                    until: now + self.cooldown,
+                };
+            }
+            (Circuit::HalfOpen { .. }, false) => {
+                tracing::info!("trial call succeeded, circuit breaker closed");
+                *circuit = Circuit::Closed { failures: 0 };
+            }
+            (Circuit::Closed { failures }, false) => *failures = 0,
+            // calls admitted before the circuit opened don't change anything
+            (Circuit::Open { .. }, _) => {}
+        }
+    }
+}
+
+/// Repository decorator failing fast with `Unavailable` while its `CircuitBreaker` is open,
+/// sparing a failing repository the load of calls bound to fail; without a breaker, calls
+/// all go through
+///
+/// `stream_all` is refused while the circuit is open, but its outcome isn't counted: the
+/// stream is only consumed after the call returned.
+pub struct CircuitBreakerHeroesRepository<R> {
+    inner: R,
+    breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl<R> CircuitBreakerHeroesRepository<R> {
+    pub fn new(inner: R, breaker: Option<Arc<CircuitBreaker>>) -> Self {
+        CircuitBreakerHeroesRepository { inner, breaker }
+    }
+
+    async fn guarded<T>(
+        &self,
+        call: impl Future<Output = Result<T, DataAccessError>>,
+    ) -> Result<T, DataAccessError> {
+        let Some(breaker) = &self.breaker else {
+            return call.await;
+        };
+        if !breaker.admit(Instant::now()) {
+            return Err(DataAccessError::Unavailable);
+        }
+        let result = call.await;
+        let failed = matches!(result, Err(DataAccessError::TechnicalError));
+        breaker.record(Instant::now(), failed);
+        result
+    }
+}
+
+#[async_trait]
+impl<R: HeroesRepositoryTrait + Send + Sync> HeroesRepositoryTrait
+    for CircuitBreakerHeroesRepository<R>
+{
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.guarded(self.inner.get_by_name(name)).await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.guarded(self.inner.get_by_id(id)).await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.guarded(self.inner.create(hero)).await
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.guarded(self.inner.create_if_absent(id, hero)).await
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.guarded(self.inner.update(id, hero)).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.guarded(self.inner.delete(id)).await
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.guarded(self.inner.delete_by_name(name)).await
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.guarded(self.inner.count_by_initial()).await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.guarded(self.inner.stats()).await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.guarded(self.inner.dataset_digest()).await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        let refused = self
+            .breaker
+            .as_ref()
+            .is_some_and(|breaker| !breaker.admit(Instant::now()));
+        if refused {
+            return stream::once(async { Err(DataAccessError::Unavailable) }).boxed();
+        }
+        self.inner.stream_all()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.guarded(self.inner.search(term)).await
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        self.guarded(self.inner.replace_all(heroes)).await
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        self.guarded(self.inner.upsert_many(heroes)).await
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.guarded(self.inner.get_by_tag(tag)).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.guarded(self.inner.get_by_ids(ids)).await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.guarded(self.inner.get_page(name, limit, offset)).await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.guarded(self.inner.get_by_name_regex(regex)).await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.guarded(self.inner.get_by_range(field, range)).await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.guarded(self.inner.ping()).await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        self.guarded(self.inner.update_tags(id, changes)).await
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        self.guarded(self.inner.update_with_tags(id, hero, tags))
+            .await
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.guarded(self.inner.record_view(id)).await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.guarded(self.inner.get_changes_since(since)).await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.guarded(self.inner.get_with_neighbors(id)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fn_repository::FnHeroesRepository;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+    const COOLDOWN: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn circuit_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3, COOLDOWN);
+        let start = Instant::now();
+
+        breaker.record(start, true);
+        breaker.record(start, true);
+        breaker.record(start, false); // the count starts over
+        breaker.record(start, true);
+        breaker.record(start, true);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.admit(start));
+
+        breaker.record(start, true);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.admit(start + COOLDOWN - Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn circuit_half_opens_after_the_cooldown_for_a_single_trial() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        let start = Instant::now();
+        breaker.record(start, true);
+
+        let after_cooldown = start + COOLDOWN;
+        assert!(breaker.admit(after_cooldown));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(!breaker.admit(after_cooldown)); // the trial is in flight
+
+        breaker.record(after_cooldown, true);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.admit(after_cooldown + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn successful_trial_closes_the_circuit() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        let start = Instant::now();
+        breaker.record(start, true);
+
+        assert!(breaker.admit(start + COOLDOWN));
+        breaker.record(start + COOLDOWN, false);
+
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.admit(start + COOLDOWN));
+    }
+
+    #[test]
+    fn lost_trial_is_retried_after_a_cooldown() {
+        let breaker = CircuitBreaker::new(1, COOLDOWN);
+        let start = Instant::now();
+        breaker.record(start, true);
+
+        // the trial never reports back, e.g. its request was cancelled
+        assert!(breaker.admit(start + COOLDOWN));
+
+        assert!(breaker.admit(start + COOLDOWN * 2));
+    }
+
+    #[tokio::test]
+    async fn open_circuit_spares_the_repository() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let failing = Arc::new(AtomicBool::new(true));
+        let (counted, failed) = (calls.clone(), failing.clone());
+        let inner = FnHeroesRepository::new().on_get_by_id(move |_| {
+            counted.fetch_add(1, Ordering::SeqCst);
+            if failed.load(Ordering::SeqCst) {
+                Err(DataAccessError::TechnicalError)
+            } else {
+                Err(DataAccessError::NotFound)
+            }
+        });
+        let breaker = Arc::new(CircuitBreaker::new(2, Duration::ZERO));
+        let repository = CircuitBreakerHeroesRepository::new(inner, Some(breaker.clone()));
+
+        for _ in 0..2 {
+            assert!(matches!(
+                repository.get_by_id("1").await,
+                Err(DataAccessError::TechnicalError)
+            ));
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // without a cooldown the next call is a trial, which a recovered repository passes
+        failing.store(false, Ordering::SeqCst);
+        assert!(matches!(
+            repository.get_by_id("1").await,
+            Err(DataAccessError::NotFound)
+        ));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn calls_are_refused_while_open() {
+        let breaker = Arc::new(CircuitBreaker::new(1, COOLDOWN));
+        breaker.record(Instant::now(), true);
+        // FnHeroesRepository panics on calls it has no closure for
+        let repository =
+            CircuitBreakerHeroesRepository::new(FnHeroesRepository::new(), Some(breaker));
+
+        assert!(matches!(
+            repository.get_by_id("1").await,
+            Err(DataAccessError::Unavailable)
+        ));
+        let streamed: Vec<_> = repository.stream_all().collect().await;
+        assert!(matches!(streamed[..], [Err(DataAccessError::Unavailable)]));
+    }
+}
@@ -0,0 +1,264 @@
+use crate::hero_query::NumericField;
+use crate::metrics::AppMetrics;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::{
+    tenant, DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats,
+    HeroesRepositoryTrait, TagChanges, UpsertReport,
+};
+use axum::async_trait;
+use futures::future::{BoxFuture, FutureExt, Shared};
+use futures::stream::BoxStream;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+type SharedQuery = Shared<BoxFuture<'static, Result<Vec<Hero>, DataAccessError>>>;
+
+/// Repository decorator deduplicating concurrent identical `get_by_name` calls ("single flight")
+///
+/// While a query is in flight, identical queries wait for its result instead of reaching
+/// the inner repository. Queries of different tenants are never shared. Other methods are
+/// passed through.
+///
+/// Queries which reached the inner repository and the ones which waited for another are
+/// counted in `metrics`.
+pub struct CoalescingHeroesRepository<R> {
+    inner: Arc<R>,
+    in_flight: Mutex<HashMap<String, SharedQuery>>,
+    metrics: Arc<AppMetrics>,
+}
+
+impl<R> CoalescingHeroesRepository<R> {
+    pub fn new(inner: R, metrics: Arc<AppMetrics>) -> Self {
+        CoalescingHeroesRepository {
+            inner: Arc::new(inner),
+            in_flight: Mutex::new(HashMap::new()),
+            metrics,
+        }
+    }
+}
+
+#[async_trait]
+impl<R: HeroesRepositoryTrait + Send + Sync + 'static> HeroesRepositoryTrait
+    for CoalescingHeroesRepository<R>
+{
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        let key = tenant::scoped(name);
+        let query = {
+            let mut in_flight = self
+                .in_flight
+                .lock()
+                .map_err(|_| DataAccessError::TechnicalError)?;
+            let mut started = false;
+            let query = in_flight
+                .entry(key.clone())
+                .or_insert_with(|| {
+                    started = true;
+                    let inner = self.inner.clone();
+                    let name = name.to_string();
+                    async move { inner.get_by_name(&name).await }
+                        .boxed()
+                        .shared()
+                })
+                .clone();
+            AppMetrics::increment(if started {
+                &self.metrics.backend_calls
+            } else {
+                &self.metrics.coalesced
+            });
+            query
+        };
+
+        let result = query.clone().await;
+
+        // the first awaiter to finish retires the query, unless a newer one already replaced it
+        if let Ok(mut in_flight) = self.in_flight.lock() {
+            if in_flight
+                .get(&key)
+                .is_some_and(|current| current.ptr_eq(&query))
+            {
+                in_flight.remove(&key);
+            }
+        }
+        result
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.inner.get_by_id(id).await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.inner.create(hero).await
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.inner.create_if_absent(id, hero).await
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.inner.update(id, hero).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.inner.delete(id).await
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.delete_by_name(name).await
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.inner.count_by_initial().await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.inner.stats().await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.inner.dataset_digest().await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        self.inner.stream_all()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.search(term).await
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        self.inner.replace_all(heroes).await
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        self.inner.upsert_many(heroes).await
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_tag(tag).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_ids(ids).await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.inner.get_page(name, limit, offset).await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_name_regex(regex).await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_range(field, range).await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.inner.ping().await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        self.inner.update_tags(id, changes).await
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        self.inner.update_with_tags(id, hero, tags).await
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.inner.record_view(id).await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.inner.get_changes_since(since).await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.inner.get_with_neighbors(id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metered::MeteredHeroesRepository;
+    use crate::InMemoryHeroesRepository;
+    use futures::future::join_all;
+    use std::sync::atomic::Ordering;
+
+    /// Coalescing in front of the in-memory repository, whose `get_by_name` awaits a 100ms
+    /// sleep; the calls reaching it are counted in the returned metrics
+    fn coalescing(
+        metrics: Arc<AppMetrics>,
+    ) -> (
+        CoalescingHeroesRepository<impl HeroesRepositoryTrait>,
+        Arc<AppMetrics>,
+    ) {
+        let inner_metrics = Arc::new(AppMetrics::default());
+        let inner = MeteredHeroesRepository::new(
+            InMemoryHeroesRepository::default(),
+            inner_metrics.clone(),
+        );
+        (
+            CoalescingHeroesRepository::new(inner, metrics),
+            inner_metrics,
+        )
+    }
+
+    fn inner_calls(inner_metrics: &AppMetrics) -> u64 {
+        inner_metrics
+            .repository_latency("get_by_name")
+            .map_or(0, |latency| latency.count)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn concurrent_identical_queries_reach_the_inner_repository_once() {
+        let (repo, inner_metrics) = coalescing(Arc::default());
+
+        // polled together on this task, so all of them start while the first is in flight
+        let results = join_all((0..20).map(|_| repo.get_by_name("Wonder%"))).await;
+
+        assert_eq!(inner_calls(&inner_metrics), 1);
+        assert!(results.iter().all(
+            |result| matches!(result, Ok(heroes) if heroes[0].name.as_str() == "Wonder Woman")
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn coalesced_queries_are_counted() {
+        let metrics = Arc::new(AppMetrics::default());
+        let (repo, _) = coalescing(metrics.clone());
+
+        let _ = join_all((0..5).map(|_| repo.get_by_name("Wonder%"))).await;
+        let _ = repo.get_by_name("Dead%").await;
+
+        assert_eq!(metrics.coalesced.load(Ordering::Relaxed), 4);
+        assert_eq!(metrics.backend_calls.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn different_or_later_queries_are_not_coalesced() {
+        let (repo, inner_metrics) = coalescing(Arc::default());
+
+        let _ = tokio::join!(repo.get_by_name("Wonder%"), repo.get_by_name("Dead%"));
+        let _ = repo.get_by_name("Wonder%").await;
+
+        assert_eq!(inner_calls(&inner_metrics), 3);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::Hero;
+use serde::{Deserialize, Serialize};
+
+/// What `GET /heroes/:id?include=` adds to the hero
+#[derive(Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Include {
+    /// the `ComputedFields` of the hero, as a `_computed` object
+    Computed,
+}
+
+/// Fields derived from a hero on each request, never stored
+#[derive(Serialize, Debug, Eq, PartialEq)]
+pub struct ComputedFields {
+    /// in characters, not bytes
+    pub name_length: usize,
+    /// first letter of the name in uppercase, the key `/facets/initial` counts it under
+    pub initial: Option<char>,
+}
+
+impl ComputedFields {
+    pub fn of(hero: &Hero) -> Self {
+        let initial = hero
+            .name
+            .chars()
+            .next()
+            .map(|initial| initial.to_uppercase().next().unwrap_or(initial));
+        ComputedFields {
+            name_length: hero.name.chars().count(),
+            initial,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hero_name::HeroName;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("Storm", 5, 'S')]
+    #[case("wonder woman", 12, 'W')]
+    #[case("Éclair", 6, 'É')]
+    fn fields_are_computed_from_the_name(
+        #[case] name: &str,
+        #[case] name_length: usize,
+        #[case] initial: char,
+    ) {
+        let hero = Hero {
+            id: "1".to_string(),
+            name: HeroName::new(name).unwrap(),
+            updated_at: None,
+            tags: vec![],
+            power_level: 0,
+        };
+
+        assert_eq!(
+            ComputedFields::of(&hero),
+            ComputedFields {
+                name_length,
+                initial: Some(initial),
+            }
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use crate::error::ApiError;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+
+/// Cap on the number of requests handled at the same time
+pub struct ConcurrencyLimit {
+    permits: Arc<Semaphore>,
+    retry_after_secs: u64,
+}
+
+impl ConcurrencyLimit {
+    pub fn new(max_concurrent: usize, retry_after_secs: u64) -> Self {
+        ConcurrencyLimit {
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+            retry_after_secs,
+        }
+    }
+}
+
+/// Middleware shedding load with `503` instead of queueing requests once the cap is reached
+///
+/// There's no way to know when a slot frees up, so `Retry-After` is the configured
+/// `OVERLOAD_RETRY_AFTER_SECS`.
+pub async fn limit_concurrency(
+    State(limit): State<Arc<ConcurrencyLimit>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    match limit.permits.clone().try_acquire_owned() {
+        Ok(_permit) => next.run(request).await,
+        Err(_) => ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "overloaded",
+            "too many requests in progress, try again later",
+        )
+        .with_retry_after(limit.retry_after_secs)
+        .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::header, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn request_over_the_cap_is_answered_with_retry_after() {
+        let limit = Arc::new(ConcurrencyLimit::new(1, 3));
+        let app =
+            Router::new()
+                .route("/", get(|| async { "ok" }))
+                .layer(middleware::from_fn_with_state(
+                    limit.clone(),
+                    limit_concurrency,
+                ));
+        // an in-flight request holds the only slot
+        let _in_flight = limit.permits.clone().try_acquire_owned().unwrap();
+
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "3");
+    }
+}
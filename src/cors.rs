@@ -0,0 +1,93 @@
+use crate::config::Config;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+const ALLOWED_METHODS: &str = "GET, POST, PUT, PATCH, DELETE";
+
+/// Middleware answering CORS preflight requests and decorating responses to allowed origins
+pub async fn cors(
+    State(config): State<Arc<Config>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let cors = &config.cors;
+    let allowed_origin = request
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|origin| origin.to_str().ok())
+        .and_then(|origin| {
+            if cors.allows_any_origin() {
+                Some("*")
+            } else {
+                cors.allowed_origins
+                    .iter()
+                    .find(|allowed| *allowed == origin)
+                    .map(String::as_str)
+            }
+        })
+        .and_then(|origin| HeaderValue::from_str(origin).ok());
+
+    let Some(allowed_origin) = allowed_origin else {
+        return next.run(request).await;
+    };
+
+    let is_preflight = request.method() == Method::OPTIONS
+        && request
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+    let mut response = if is_preflight {
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        let headers = response.headers_mut();
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_METHODS,
+            HeaderValue::from_static(ALLOWED_METHODS),
+        );
+        if let Some(requested_headers) = request
+            .headers()
+            .get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+        {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_HEADERS,
+                requested_headers.clone(),
+            );
+        }
+        if let Some(max_age) = cors.max_age {
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age));
+        }
+        response
+    } else {
+        next.run(request).await
+    };
+
+    add_origin_headers(
+        response.headers_mut(),
+        allowed_origin,
+        cors.allow_credentials,
+    );
+    response
+}
+
+fn add_origin_headers(
+    headers: &mut HeaderMap,
+    allowed_origin: HeaderValue,
+    allow_credentials: bool,
+) {
+    if allowed_origin != "*" {
+        // the response depends on the origin: caches must not share it across origins
+        headers.append(header::VARY, HeaderValue::from_static("origin"));
+    }
+    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allowed_origin);
+    if allow_credentials {
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+            HeaderValue::from_static("true"),
+        );
+    }
+}
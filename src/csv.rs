@@ -0,0 +1,173 @@
+use crate::error::ApiError;
+use axum::{
+    async_trait,
+    body::Body,
+    extract::FromRequest,
+    http::{header, Request, StatusCode},
+};
+use std::mem;
+
+/// Media type of CSV bodies
+pub const CONTENT_TYPE: &str = "text/csv";
+
+/// Format one CSV record (RFC 4180), terminated by CRLF
+///
+/// Fields containing a separator, a quote or a line break are quoted, quotes being doubled.
+pub fn write_row(fields: &[&str]) -> String {
+    let mut row = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\r', '\n']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+    row.push_str("\r\n");
+    row
+}
+
+/// Records of a CSV document (RFC 4180), each a list of fields
+///
+/// Lines may end with CRLF or a bare LF and blank lines are skipped. A quote opening a
+/// field must be closed, and nothing but a separator may follow it: `Err` tells the line
+/// of the first such problem.
+pub fn read_records(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    // inside a quoted field, and the quoted field has been closed
+    let (mut quoted, mut closed) = (false, false);
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => (quoted, closed) = (false, true),
+                c => {
+                    line += usize::from(c == '\n');
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+        match c {
+            ',' => {
+                record.push(mem::take(&mut field));
+                closed = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                if !(record.is_empty() && field.is_empty() && !closed) {
+                    record.push(mem::take(&mut field));
+                    records.push(mem::take(&mut record));
+                }
+                closed = false;
+                line += 1;
+            }
+            '"' if field.is_empty() && !closed => quoted = true,
+            _ if closed => {
+                return Err(format!(
+                    "unexpected text after a quoted field on line {}",
+                    line
+                ))
+            }
+            '"' => return Err(format!("unexpected quote in a field on line {}", line)),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("unterminated quoted field on line {}", line));
+    }
+    if !(record.is_empty() && field.is_empty() && !closed) {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Body of a request sent as `text/csv`
+///
+/// Other content types are rejected with `415`, bodies which aren't UTF-8 with `400`.
+pub struct CsvBody(pub String);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S, Body> for CsvBody {
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request<Body>, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(str::trim);
+        if !content_type.is_some_and(|value| value.eq_ignore_ascii_case(CONTENT_TYPE)) {
+            return Err(ApiError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                format!("expected a {} body", CONTENT_TYPE),
+            ));
+        }
+        String::from_request(request, state)
+            .await
+            .map(CsvBody)
+            .map_err(|rejection| ApiError::bad_request(rejection.body_text()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn special_characters_are_quoted() {
+        assert_eq!(write_row(&["1", "Wonder Woman"]), "1,Wonder Woman\r\n");
+        assert_eq!(
+            write_row(&["2", "Dr. \"Strange\", MD"]),
+            "2,\"Dr. \"\"Strange\"\", MD\"\r\n"
+        );
+    }
+
+    #[test]
+    fn records_are_read() {
+        let text = "id,name\r\n1,Wonder Woman\n\n2,\"Dr. \"\"Strange\"\", MD\"\n3,\"Multi\nline\"";
+
+        assert_eq!(
+            read_records(text).unwrap(),
+            vec![
+                vec!["id", "name"],
+                vec!["1", "Wonder Woman"],
+                vec!["2", "Dr. \"Strange\", MD"],
+                vec!["3", "Multi\nline"],
+            ]
+        );
+        assert_eq!(read_records("").unwrap(), Vec::<Vec<String>>::new());
+        assert_eq!(read_records("name,\"\"").unwrap(), vec![vec!["name", ""]]);
+    }
+
+    #[rstest]
+    #[case("name\n\"Storm", "unterminated quoted field on line 2")]
+    #[case("name\nSt\"orm", "unexpected quote in a field on line 2")]
+    #[case("name\n\"Storm\"y", "unexpected text after a quoted field on line 2")]
+    fn malformed_records_are_rejected(#[case] text: &str, #[case] reason: &str) {
+        assert_eq!(read_records(text).unwrap_err(), reason);
+    }
+
+    #[test]
+    fn written_rows_are_read_back() {
+        let row = write_row(&["2", "Dr. \"Strange\", MD"]);
+
+        assert_eq!(
+            read_records(&row).unwrap(),
+            vec![vec!["2", "Dr. \"Strange\", MD"]]
+        );
+    }
+}
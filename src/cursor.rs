@@ -0,0 +1,196 @@
+use std::fmt::Write as _;
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const BLOCK: usize = 64;
+
+/// SHA-256 of `bytes`, as specified by FIPS 180-4
+fn sha256(bytes: &[u8]) -> [u8; 32] {
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+    let mut message = bytes.to_vec();
+    message.push(0x80);
+    while message.len() % BLOCK != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((bytes.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks_exact(BLOCK) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (total, value) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *total = total.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// HMAC-SHA256 of `message` with `key`, as specified by RFC 2104
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut padded = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        padded[..32].copy_from_slice(&sha256(key));
+    } else {
+        padded[..key.len()].copy_from_slice(key);
+    }
+    let pad = |byte: u8| padded.iter().map(move |k| k ^ byte);
+
+    let inner: Vec<u8> = pad(0x36).chain(message.iter().copied()).collect();
+    let outer: Vec<u8> = pad(0x5c).chain(sha256(&inner)).collect();
+    sha256(&outer)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Opaque cursor holding `position`, like `3432.<signature>`, signed with HMAC-SHA256
+///
+/// No state is kept: any instance with the same `key`, restarted or not, trusts the
+/// position of the cursors it verifies.
+pub fn sign(key: &[u8], position: &str) -> String {
+    let signature = hmac_sha256(key, position.as_bytes());
+    format!("{}.{}", to_hex(position.as_bytes()), to_hex(&signature))
+}
+
+/// Position held by `cursor`, `None` when it wasn't signed by `sign` with `key`
+///
+/// Signatures are compared in constant time, not to tell how much of a forged one is right.
+pub fn verify(key: &[u8], cursor: &str) -> Option<String> {
+    let (position, signature) = cursor.split_once('.')?;
+    let position = from_hex(position)?;
+    let signature = from_hex(signature)?;
+    let expected = hmac_sha256(key, &position);
+    if signature.len() != expected.len() {
+        return None;
+    }
+    let difference = signature
+        .iter()
+        .zip(expected)
+        .fold(0, |difference, (a, b)| difference | (a ^ b));
+    if difference != 0 {
+        return None;
+    }
+    String::from_utf8(position).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        b"",
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    )]
+    #[case(
+        b"abc",
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    )]
+    #[case(
+        b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+        "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+    )]
+    fn sha256_matches_the_fips_examples(#[case] message: &[u8], #[case] expected: &str) {
+        assert_eq!(to_hex(&sha256(message)), expected);
+    }
+
+    #[rstest]
+    // RFC 4231, test cases 2 and 6
+    #[case(
+        b"Jefe".to_vec(),
+        b"what do ya want for nothing?".to_vec(),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    )]
+    #[case(
+        vec![0xaa; 131],
+        b"Test Using Larger Than Block-Size Key - Hash Key First".to_vec(),
+        "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    )]
+    fn hmac_matches_the_rfc_examples(
+        #[case] key: Vec<u8>,
+        #[case] message: Vec<u8>,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(to_hex(&hmac_sha256(&key, &message)), expected);
+    }
+
+    #[test]
+    fn signed_positions_are_verified() {
+        let cursor = sign(b"k3y", "42");
+
+        assert_eq!(verify(b"k3y", &cursor).as_deref(), Some("42"));
+    }
+
+    #[rstest]
+    #[case::other_position(sign(b"k3y", "42").replacen("3432", "3433", 1))]
+    #[case::other_key(sign(b"other", "42"))]
+    #[case::truncated(sign(b"k3y", "42")[..20].to_string())]
+    #[case::not_hex("zz.zz".to_string())]
+    #[case::no_signature("3432".to_string())]
+    fn tampered_cursors_are_refused(#[case] cursor: String) {
+        assert_eq!(verify(b"k3y", &cursor), None);
+    }
+}
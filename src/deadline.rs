@@ -0,0 +1,58 @@
+use crate::{config::Config, error::ApiError};
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const DEADLINE_HEADER: &str = "x-request-deadline-ms";
+
+/// Time the caller is willing to wait for the repository
+///
+/// Taken from the `X-Request-Deadline-Ms` header, never longer than the configured
+/// timeout which also applies when the header is absent: `READ_TIMEOUT_MS` for reads,
+/// `WRITE_TIMEOUT_MS` for writes, `REQUEST_TIMEOUT_MS` when those are unset.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Deadline(pub Duration);
+
+impl Deadline {
+    /// Run `call`, answering `504` if it doesn't complete in time
+    pub async fn run<T>(self, call: impl Future<Output = T>) -> Result<T, ApiError> {
+        tokio::time::timeout(self.0, call).await.map_err(|_| {
+            ApiError::new(
+                StatusCode::GATEWAY_TIMEOUT,
+                "timeout",
+                format!("no answer within {}ms", self.0.as_millis()),
+            )
+        })
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Deadline
+where
+    Arc<Config>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = Arc::<Config>::from_ref(state);
+        let default = Duration::from_millis(config.timeout_ms(&parts.method));
+
+        match parts.headers.get(DEADLINE_HEADER) {
+            None => Ok(Deadline(default)),
+            Some(value) => value
+                .to_str()
+                .ok()
+                .and_then(|value| value.parse::<u64>().ok())
+                .map(|millis| Deadline(Duration::from_millis(millis).min(default)))
+                .ok_or_else(|| {
+                    ApiError::bad_request("X-Request-Deadline-Ms must be a number of milliseconds")
+                }),
+        }
+    }
+}
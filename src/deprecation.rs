@@ -0,0 +1,100 @@
+use crate::config::Config;
+use axum::{
+    body::Body,
+    extract::{OriginalUri, State},
+    http::{header, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Prefix of the unversioned routes, kept for the clients which haven't moved yet
+pub const LEGACY_PREFIX: &str = "/heroes/";
+/// Prefix of the same routes in the current version of the api
+pub const CURRENT_PREFIX: &str = "/api/v1/heroes/";
+
+/// Middleware marking responses of the legacy `/heroes/` routes as deprecated
+///
+/// Adds `Deprecation: true`, a `Link` to the same route under `/api/v1/heroes/` and, when
+/// `LEGACY_SUNSET` is set, the `Sunset` date after which the legacy routes may be removed.
+pub async fn legacy_routes(
+    State(config): State<Arc<Config>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    // nested routers only see the end of the path
+    let path = match request.extensions().get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.path().to_string(),
+        None => request.uri().path().to_string(),
+    };
+    let mut response = next.run(request).await;
+
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    if let Some(sunset) = config.legacy_sunset {
+        if let Ok(value) = HeaderValue::from_str(&sunset.to_string()) {
+            headers.insert("sunset", value);
+        }
+    }
+    let successor = path
+        .strip_prefix(LEGACY_PREFIX)
+        .map(|rest| format!("<{}{}>; rel=\"successor-version\"", CURRENT_PREFIX, rest));
+    if let Some(Ok(link)) = successor.map(HeaderValue::try_from) {
+        // paged listings have `Link`s of their own
+        headers.append(header::LINK, link);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app(config: Config) -> Router {
+        let routes = || Router::new().route("/:id", get(|| async { "Storm" }));
+        Router::new()
+            .nest(
+                "/heroes/",
+                routes().layer(middleware::from_fn_with_state(
+                    Arc::new(config),
+                    legacy_routes,
+                )),
+            )
+            .nest("/api/v1/heroes/", routes())
+    }
+
+    fn get_request(uri: &str) -> Request<Body> {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn legacy_routes_point_to_their_successor() {
+        let config = Config {
+            legacy_sunset: Some("Sun, 31 Jan 2027 00:00:00 GMT".parse().unwrap()),
+            ..Default::default()
+        };
+
+        let response = app(config).oneshot(get_request("/heroes/7")).await.unwrap();
+
+        let headers = response.headers();
+        assert_eq!(headers["deprecation"], "true");
+        assert_eq!(headers["sunset"], "Sun, 31 Jan 2027 00:00:00 GMT");
+        assert_eq!(
+            headers[header::LINK],
+            "</api/v1/heroes/7>; rel=\"successor-version\""
+        );
+    }
+
+    #[tokio::test]
+    async fn current_routes_are_not_deprecated() {
+        let response = app(Config::default())
+            .oneshot(get_request("/api/v1/heroes/7"))
+            .await
+            .unwrap();
+
+        assert!(!response.headers().contains_key("deprecation"));
+        assert!(!response.headers().contains_key(header::LINK));
+    }
+}
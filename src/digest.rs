@@ -0,0 +1,65 @@
+use crate::Hero;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a of `bytes`: unlike `DefaultHasher`, the same in every build and release of Rust,
+/// so digests can be compared across instances and deployments
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Hex digest of `heroes`, whatever their order
+///
+/// Every field of a hero counts, `updated_at` included. The digests of the heroes are
+/// sorted before being hashed together, so listing the same heroes in another order
+/// doesn't change it. Not a cryptographic hash: it detects changes, not tampering.
+pub fn of<'a>(heroes: impl IntoIterator<Item = &'a Hero>) -> String {
+    let mut digests: Vec<u64> = heroes
+        .into_iter()
+        .map(|hero| fnv1a(&serde_json::to_vec(hero).unwrap_or_default()))
+        .collect();
+    digests.sort_unstable();
+    let bytes: Vec<u8> = digests
+        .iter()
+        .flat_map(|digest| digest.to_be_bytes())
+        .collect();
+    format!("{:016x}", fnv1a(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hero_name::HeroName;
+
+    fn hero(id: &str, name: &str) -> Hero {
+        Hero {
+            id: id.to_string(),
+            name: HeroName::new(name).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn order_of_the_heroes_does_not_matter() {
+        let (storm, deadpool) = (hero("1", "Storm"), hero("2", "Deadpool"));
+
+        assert_eq!(of([&storm, &deadpool]), of([&deadpool, &storm]));
+    }
+
+    #[test]
+    fn any_field_changes_the_digest() {
+        let storm = hero("1", "Storm");
+        let renamed = hero("1", "Ororo Munroe");
+        let touched = Hero {
+            updated_at: Some(1),
+            ..storm.clone()
+        };
+
+        assert_ne!(of([&storm]), of([&renamed]));
+        assert_ne!(of([&storm]), of([&touched]));
+        assert_ne!(of([&storm]), of([]));
+    }
+}
@@ -0,0 +1,234 @@
+use crate::{i18n, request_id, DataAccessError};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+/// Error returned to api clients, serialized as `{ "error": "...", "message": "..." }`
+#[derive(Serialize, Debug)]
+pub struct ApiError {
+    #[serde(skip)]
+    pub status: StatusCode,
+    /// machine readable error code
+    pub error: &'static str,
+    /// human readable explanation, in the language of the request when translated
+    pub message: String,
+    /// id of the failed request, to be quoted when reaching support
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// seconds the client should wait before retrying, sent as `Retry-After`
+    #[serde(skip)]
+    pub retry_after: Option<u64>,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, error: &'static str, message: impl Into<String>) -> Self {
+        ApiError {
+            status,
+            error,
+            message: i18n::localize(message.into()),
+            request_id: None,
+            retry_after: None,
+        }
+    }
+
+    /// Tell the client to wait `seconds` before trying again
+    pub fn with_retry_after(mut self, seconds: u64) -> Self {
+        self.retry_after = Some(seconds);
+        self
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", message)
+    }
+
+    pub fn internal() -> Self {
+        Self::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "unexpected error while accessing heroes",
+        )
+    }
+}
+
+/// Status of the response to a repository error, replacing the one `ApiError` picks
+pub type StatusMapper = fn(&DataAccessError) -> StatusCode;
+
+tokio::task_local! {
+    static STATUS_MAPPER: StatusMapper;
+}
+
+/// Middleware making `mapper` pick the status of the repository errors of the request
+pub async fn map_statuses(
+    State(mapper): State<StatusMapper>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    STATUS_MAPPER.scope(mapper, next.run(request)).await
+}
+
+/// Status `ApiError` answers `error` with when no `StatusMapper` overrides it, for mappers
+/// only changing some
+pub fn default_status(error: &DataAccessError) -> StatusCode {
+    default_error(error).status
+}
+
+impl From<DataAccessError> for ApiError {
+    fn from(error: DataAccessError) -> Self {
+        let mut api_error = default_error(&error);
+        if let Ok(status) = STATUS_MAPPER.try_with(|mapper| mapper(&error)) {
+            api_error.status = status;
+        }
+        api_error
+    }
+}
+
+impl ApiError {
+    /// The error `error` is answered with, its status mapped like `From<DataAccessError>`
+    /// does, explained with `message` rather than the generic one
+    pub fn from_data_error(error: DataAccessError, message: impl Into<String>) -> Self {
+        ApiError {
+            message: i18n::localize(message.into()),
+            ..ApiError::from(error)
+        }
+    }
+}
+
+fn default_error(error: &DataAccessError) -> ApiError {
+    match error {
+        DataAccessError::NotFound => ApiError::not_found("hero not found"),
+        DataAccessError::Gone => ApiError::new(StatusCode::GONE, "gone", "hero was deleted"),
+        DataAccessError::ReadOnly => ApiError::new(
+            StatusCode::FORBIDDEN,
+            "read_only",
+            "the service is in read-only mode, heroes can't be changed for now",
+        ),
+        DataAccessError::QuotaExceeded => ApiError::new(
+            StatusCode::FORBIDDEN,
+            "quota_exceeded",
+            "the quota of stored heroes is reached, delete some before adding others",
+        ),
+        DataAccessError::Unavailable => ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "unavailable",
+            "heroes can't be reached for now, try again later",
+        ),
+        DataAccessError::AlreadyExists => ApiError::new(
+            StatusCode::PRECONDITION_FAILED,
+            "precondition_failed",
+            "a hero with this id exists already",
+        ),
+        DataAccessError::TenantLimitReached => ApiError::new(
+            StatusCode::FORBIDDEN,
+            "tenant_limit_reached",
+            "no more tenants are accepted, use an existing one",
+        ),
+        DataAccessError::Unchanged => ApiError::new(
+            StatusCode::NOT_MODIFIED,
+            "not_modified",
+            "the hero is already in this state",
+        ),
+        _ => ApiError::internal(),
+    }
+}
+
+impl DataAccessError {
+    /// Error an upstream answered with `status`, the inverse of `From<DataAccessError>`
+    ///
+    /// Statuses several errors share, like the `403` of `ReadOnly` and `QuotaExceeded`, and
+    /// the ones no error is answered with, like `409`, are an `OtherError`.
+    pub fn from_status(status: StatusCode) -> DataAccessError {
+        match status {
+            StatusCode::NOT_FOUND => DataAccessError::NotFound,
+            StatusCode::GONE => DataAccessError::Gone,
+            StatusCode::PRECONDITION_FAILED => DataAccessError::AlreadyExists,
+            StatusCode::NOT_MODIFIED => DataAccessError::Unchanged,
+            StatusCode::SERVICE_UNAVAILABLE => DataAccessError::Unavailable,
+            status if status.is_server_error() => DataAccessError::TechnicalError,
+            _ => DataAccessError::OtherError,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        Suggested {
+            error: self,
+            suggestions: vec![],
+        }
+        .into_response()
+    }
+}
+
+/// `ApiError` telling what the client may have meant in a `suggestions` array, like the
+/// names close to an unmatched name filter
+///
+/// Kept apart from `ApiError`, which every fallible handler returns, to keep it small.
+#[derive(Serialize, Debug)]
+pub struct Suggested {
+    #[serde(flatten)]
+    pub error: ApiError,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+}
+
+impl IntoResponse for Suggested {
+    fn into_response(mut self) -> Response {
+        let error = &mut self.error;
+        error.request_id = error.request_id.take().or_else(request_id::current);
+        let mut response = (error.status, Json(&self)).into_response();
+        if let Some(seconds) = self.error.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, seconds.into());
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(StatusCode::NOT_FOUND, "NotFound")]
+    #[case(StatusCode::GONE, "Gone")]
+    #[case(StatusCode::PRECONDITION_FAILED, "AlreadyExists")]
+    #[case(StatusCode::SERVICE_UNAVAILABLE, "Unavailable")]
+    #[case(StatusCode::INTERNAL_SERVER_ERROR, "TechnicalError")]
+    #[case(StatusCode::BAD_GATEWAY, "TechnicalError")]
+    #[case(StatusCode::CONFLICT, "OtherError")]
+    #[case(StatusCode::FORBIDDEN, "OtherError")]
+    #[case(StatusCode::BAD_REQUEST, "OtherError")]
+    fn statuses_are_mapped_back(#[case] status: StatusCode, #[case] expected: &str) {
+        assert_eq!(
+            format!("{:?}", DataAccessError::from_status(status)),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(DataAccessError::NotFound)]
+    #[case(DataAccessError::Gone)]
+    #[case(DataAccessError::AlreadyExists)]
+    #[case(DataAccessError::Unavailable)]
+    #[case(DataAccessError::TechnicalError)]
+    fn mapping_back_undoes_the_forward_mapping(#[case] error: DataAccessError) {
+        let status = ApiError::from(error.clone()).status;
+
+        assert_eq!(
+            format!("{:?}", DataAccessError::from_status(status)),
+            format!("{:?}", error)
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use crate::feature::{self, Feature};
+use crate::{
+    audit::{self, AuditAction},
+    config::Config,
+    error::ApiError,
+    field_access, tenant, Hero,
+};
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::stream::{self, Stream};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Most events kept for a slow subscriber before it starts missing some
+const CAPACITY: usize = 64;
+
+/// Change of a hero, as pushed to live subscribers: `{ "action": "update", "hero": {...} }`
+#[derive(Serialize, Debug, Clone)]
+pub struct HeroEvent {
+    pub action: AuditAction,
+    pub hero: Hero,
+    /// tenant the hero belongs to, subscribers only see the events of their own tenant
+    #[serde(skip)]
+    pub tenant: Option<String>,
+}
+
+/// Channel write handlers publish hero changes to, for every live subscriber
+#[derive(Clone)]
+pub struct HeroEvents {
+    sender: broadcast::Sender<HeroEvent>,
+}
+
+impl Default for HeroEvents {
+    fn default() -> Self {
+        HeroEvents {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl HeroEvents {
+    /// Notify subscribers of a change made on behalf of the current tenant
+    pub fn publish(&self, action: AuditAction, hero: &Hero) {
+        // no subscriber is not an error: the event is just dropped
+        let _ = self.sender.send(HeroEvent {
+            action,
+            hero: hero.clone(),
+            tenant: tenant::current(),
+        });
+    }
+
+    /// Notify subscribers of every change of a batch write turning the heroes `before`
+    /// into the heroes `after`, as `audit::batch_events` tells them
+    pub fn publish_batch(&self, before: Vec<Hero>, after: Vec<Hero>, deleting: bool) {
+        for (_, event) in audit::batch_events(before, after, deleting) {
+            if let Some(hero) = event.after.or(event.before) {
+                self.publish(event.action, &hero);
+            }
+        }
+    }
+
+    /// Receive the changes published from now on; filter them with `HeroEvent::tenant`
+    pub fn subscribe(&self) -> broadcast::Receiver<HeroEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// `GET /heroes/events/sse`: hero changes of the caller's tenant as Server-Sent Events
+///
+/// Each change is an event named after its action, with the `HeroEvent` as JSON data;
+/// keep-alive comments are sent while nothing changes so proxies don't close the stream.
+/// Heroes are sent without the fields restricted to admins, unless the caller is one.
+pub async fn sse(
+    State(events): State<HeroEvents>,
+    State(config): State<Arc<Config>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, serde_json::Error>>>, ApiError> {
+    feature::require(&config, Feature::Events)?;
+    let tenant = tenant::current();
+    let restricted = field_access::restricted();
+    let changes = stream::unfold(events.subscribe(), move |mut receiver| {
+        let tenant = tenant.clone();
+        let restricted = restricted.clone();
+        async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) if event.tenant == tenant => {
+                        let frame = serde_json::to_value(&event).and_then(|mut data| {
+                            field_access::strip(&mut data, &restricted);
+                            Event::default()
+                                .event(event.action.as_str())
+                                .json_data(data)
+                        });
+                        return Some((frame, receiver));
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(missed)) => {
+                        tracing::warn!(missed, "event subscriber too slow, changes skipped");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        }
+    });
+    Ok(Sse::new(changes).keep_alive(KeepAlive::default()))
+}
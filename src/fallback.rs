@@ -0,0 +1,246 @@
+use crate::hero_query::NumericField;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::{
+    DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats, HeroesRepositoryTrait,
+    TagChanges, UpsertReport,
+};
+use axum::async_trait;
+use futures::stream::BoxStream;
+use std::future::Future;
+
+/// Repository reading from a secondary (replica, cache...) when the primary fails
+///
+/// Only technical errors trigger the fallback: a `NotFound` from the primary is an answer,
+/// not a failure. Writes and `stream_all` always go to the primary, so the secondary is
+/// never modified through this repository.
+pub struct FallbackHeroesRepository<P, S> {
+    primary: P,
+    secondary: S,
+}
+
+impl<P, S> FallbackHeroesRepository<P, S> {
+    pub fn new(primary: P, secondary: S) -> Self {
+        FallbackHeroesRepository { primary, secondary }
+    }
+}
+
+async fn or_fallback<T, F: Future<Output = Result<T, DataAccessError>>>(
+    method: &'static str,
+    primary: F,
+    secondary: impl FnOnce() -> F,
+) -> Result<T, DataAccessError> {
+    match primary.await {
+        Err(DataAccessError::TechnicalError) => {
+            tracing::warn!(method, "primary repository failed, using fallback");
+            secondary().await
+        }
+        result => result,
+    }
+}
+
+#[async_trait]
+impl<P, S> HeroesRepositoryTrait for FallbackHeroesRepository<P, S>
+where
+    P: HeroesRepositoryTrait + Send + Sync,
+    S: HeroesRepositoryTrait + Send + Sync,
+{
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        or_fallback("get_by_name", self.primary.get_by_name(name), || {
+            self.secondary.get_by_name(name)
+        })
+        .await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        or_fallback("get_by_id", self.primary.get_by_id(id), || {
+            self.secondary.get_by_id(id)
+        })
+        .await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.primary.create(hero).await
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.primary.create_if_absent(id, hero).await
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.primary.update(id, hero).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.primary.delete(id).await
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.primary.delete_by_name(name).await
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        or_fallback("count_by_initial", self.primary.count_by_initial(), || {
+            self.secondary.count_by_initial()
+        })
+        .await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        or_fallback("stats", self.primary.stats(), || self.secondary.stats()).await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        or_fallback("dataset_digest", self.primary.dataset_digest(), || {
+            self.secondary.dataset_digest()
+        })
+        .await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        self.primary.stream_all()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        or_fallback("search", self.primary.search(term), || {
+            self.secondary.search(term)
+        })
+        .await
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        self.primary.replace_all(heroes).await
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        self.primary.upsert_many(heroes).await
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        or_fallback("get_by_tag", self.primary.get_by_tag(tag), || {
+            self.secondary.get_by_tag(tag)
+        })
+        .await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        or_fallback("get_by_ids", self.primary.get_by_ids(ids), || {
+            self.secondary.get_by_ids(ids)
+        })
+        .await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        or_fallback(
+            "get_page",
+            self.primary.get_page(name, limit, offset),
+            || self.secondary.get_page(name, limit, offset),
+        )
+        .await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        or_fallback(
+            "get_by_name_regex",
+            self.primary.get_by_name_regex(regex),
+            || self.secondary.get_by_name_regex(regex),
+        )
+        .await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        or_fallback(
+            "get_by_range",
+            self.primary.get_by_range(field, range),
+            || self.secondary.get_by_range(field, range),
+        )
+        .await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        or_fallback("ping", self.primary.ping(), || self.secondary.ping()).await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        self.primary.update_tags(id, changes).await
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        self.primary.update_with_tags(id, hero, tags).await
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.primary.record_view(id).await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        or_fallback(
+            "get_changes_since",
+            self.primary.get_changes_since(since),
+            || self.secondary.get_changes_since(since),
+        )
+        .await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        or_fallback(
+            "get_with_neighbors",
+            self.primary.get_with_neighbors(id),
+            || self.secondary.get_with_neighbors(id),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MockHeroesRepositoryTrait;
+
+    #[tokio::test]
+    async fn secondary_answers_when_primary_fails() {
+        let mut primary = MockHeroesRepositoryTrait::new();
+        primary
+            .expect_get_by_name()
+            .returning(|_| Err(DataAccessError::TechnicalError));
+        let mut secondary = MockHeroesRepositoryTrait::new();
+        secondary
+            .expect_get_by_name()
+            .times(1)
+            .returning(|_| Ok(vec![Hero::default()]));
+        let repo = FallbackHeroesRepository::new(primary, secondary);
+
+        let result = repo.get_by_name("Wonder%").await;
+
+        assert!(matches!(result, Ok(heroes) if heroes == vec![Hero::default()]));
+    }
+
+    #[tokio::test]
+    async fn not_found_is_not_overridden_by_the_secondary() {
+        let mut primary = MockHeroesRepositoryTrait::new();
+        primary
+            .expect_get_by_id()
+            .returning(|_| Err(DataAccessError::NotFound));
+        let mut secondary = MockHeroesRepositoryTrait::new();
+        secondary.expect_get_by_id().never();
+        let repo = FallbackHeroesRepository::new(primary, secondary);
+
+        let result = repo.get_by_id("42").await;
+
+        assert!(matches!(result, Err(DataAccessError::NotFound)));
+    }
+}
@@ -0,0 +1,52 @@
+use crate::{config::Config, error::ApiError};
+use axum::http::StatusCode;
+use serde::{Serialize, Serializer};
+use std::str::FromStr;
+
+/// Optional parts of the api which can be turned off with `DISABLED_FEATURES`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Feature {
+    /// `GET /heroes/export.csv`
+    CsvExport,
+    /// `GET /heroes/events/sse`
+    Events,
+}
+
+impl Feature {
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::CsvExport => "csv_export",
+            Feature::Events => "events",
+        }
+    }
+}
+
+impl FromStr for Feature {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "csv_export" => Ok(Feature::CsvExport),
+            "events" => Ok(Feature::Events),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Serialize for Feature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+/// `501` when `feature` is disabled, so clients can tell it from a wrong url
+pub fn require(config: &Config, feature: Feature) -> Result<(), ApiError> {
+    if config.disabled_features.contains(&feature) {
+        return Err(ApiError::new(
+            StatusCode::NOT_IMPLEMENTED,
+            "feature_disabled",
+            format!("{} is disabled on this server", feature.name()),
+        ));
+    }
+    Ok(())
+}
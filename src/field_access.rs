@@ -0,0 +1,134 @@
+use crate::{auth::Role, config::Config};
+use axum::{
+    body::{self, Body, Full},
+    extract::State,
+    http::{header, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use std::sync::Arc;
+
+tokio::task_local! {
+    static RESTRICTED: Vec<String>;
+}
+
+/// Fields the current request mustn't be answered with, none outside of `restrict_fields`
+pub fn restricted() -> Vec<String> {
+    RESTRICTED.try_with(Vec::clone).unwrap_or_default()
+}
+
+/// Remove the `restricted` fields of the current request from `value`, as it's serialized
+/// in a format `restrict_fields` can't read back, like CSV or XML
+///
+/// Streams, whose items are serialized once the request is handled, `strip` the fields
+/// they captured with `restricted` instead.
+pub fn strip_restricted(value: &mut Value) {
+    let _ = RESTRICTED.try_with(|fields| strip(value, fields));
+}
+
+/// Middleware leaving the `RESTRICTED_FIELDS` out of responses to callers below
+/// `Role::Admin`
+///
+/// The role is the one `auth::identify_role` added to the request, anonymous without it.
+/// Handlers serializing heroes otherwise than as json call `strip_restricted`; json bodies
+/// are stripped here, from every object, so heroes are stripped wherever they are:
+/// listings, maps of heroes, contexts, changes... Other bodies are sent as they are.
+pub async fn restrict_fields(
+    State(config): State<Arc<Config>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let role = request
+        .extensions()
+        .get::<Role>()
+        .copied()
+        .unwrap_or_default();
+    if config.restricted_fields.is_empty() || role == Role::Admin {
+        return next.run(request).await;
+    }
+    // the body of responses to `HEAD` is already gone, there's nothing to strip
+    let is_head = request.method() == Method::HEAD;
+    let response = RESTRICTED
+        .scope(config.restricted_fields.clone(), next.run(request))
+        .await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if is_head || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = hyper::body::to_bytes(body).await else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let Ok(mut value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, body::boxed(Full::from(bytes)));
+    };
+    strip(&mut value, &config.restricted_fields);
+    // only `?pretty=true` bodies span several lines
+    let stripped = if bytes.contains(&b'\n') {
+        serde_json::to_vec_pretty(&value)
+    } else {
+        serde_json::to_vec(&value)
+    };
+    let Ok(stripped) = stripped else {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    // the length of the unstripped body
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, body::boxed(Full::from(stripped)))
+}
+
+/// Remove `fields` from every object in `value`, however deeply nested
+pub fn strip(value: &mut Value, fields: &[String]) {
+    match value {
+        Value::Object(object) => {
+            object.retain(|key, _| !fields.contains(key));
+            object.values_mut().for_each(|value| strip(value, fields));
+        }
+        Value::Array(values) => values.iter_mut().for_each(|value| strip(value, fields)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn fields_are_stripped_from_nested_objects() {
+        let mut value = json!({
+            "prev": null,
+            "current": { "id": "1", "name": "Storm", "power_level": 85 },
+            "next": { "id": "2", "name": "Rogue", "power_level": 70, "tags": ["x-men"] },
+        });
+
+        strip(&mut value, &["power_level".to_string(), "tags".to_string()]);
+
+        assert_eq!(
+            value,
+            json!({
+                "prev": null,
+                "current": { "id": "1", "name": "Storm" },
+                "next": { "id": "2", "name": "Rogue" },
+            })
+        );
+    }
+
+    #[test]
+    fn fields_are_stripped_from_every_hero_of_a_listing() {
+        let mut value = json!([
+            { "id": "1", "power_level": 85 },
+            { "id": "2", "power_level": 70 },
+        ]);
+
+        strip(&mut value, &["power_level".to_string()]);
+
+        assert_eq!(value, json!([{ "id": "1" }, { "id": "2" }]));
+    }
+}
@@ -0,0 +1,48 @@
+use serde::{de, Deserialize, Deserializer};
+
+/// Accepted spellings of boolean query flags, as listed in error messages
+pub const ACCEPTED: &str = "true, false, 1, 0, yes or no";
+
+/// Read a boolean query flag: `true`/`false`, `1`/`0` or `yes`/`no`, in any case
+pub fn parse(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" => Some(true),
+        "false" | "0" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// `deserialize_with` helper for `Option<bool>` query fields, to be combined with
+/// `#[serde(default)]` so an absent flag is `None`
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<bool>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse(&value).map(Some).ok_or_else(|| {
+        de::Error::custom(format!("invalid flag '{}', expected {}", value, ACCEPTED))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("true", true)]
+    #[case("TRUE", true)]
+    #[case("1", true)]
+    #[case("yes", true)]
+    #[case("false", false)]
+    #[case("0", false)]
+    #[case("No", false)]
+    fn accepted_forms_are_parsed(#[case] value: &str, #[case] expected: bool) {
+        assert_eq!(parse(value), Some(expected));
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("maybe")]
+    #[case("2")]
+    fn other_values_are_rejected(#[case] value: &str) {
+        assert_eq!(parse(value), None);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{
+    DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroesRepositoryTrait, TagChanges,
+    UpsertReport,
+};
+use axum::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+
+type Handler<A, T> = Box<dyn Fn(A) -> Result<T, DataAccessError> + Send + Sync>;
+
+/// Test repository answering with closures, for tests needing a behavior or two without
+/// the ceremony of mockall expectations:
+///
+/// ```ignore
+/// FnHeroesRepository::new().on_get_by_name(|_| Ok(vec![Hero::default()]))
+/// ```
+///
+/// Calling a method without a closure panics. Methods with a default implementation in
+/// `HeroesRepositoryTrait`, like `search`, use it: they are built on the closures.
+#[derive(Default)]
+pub struct FnHeroesRepository {
+    get_by_name: Option<Handler<String, Vec<Hero>>>,
+    get_by_id: Option<Handler<String, Hero>>,
+    create: Option<Handler<HeroPayload, Hero>>,
+    create_if_absent: Option<Handler<(String, HeroPayload), Hero>>,
+    update: Option<Handler<(String, HeroPayload), Hero>>,
+    delete: Option<Handler<String, Hero>>,
+    stream_all: Option<Handler<(), Vec<Hero>>>,
+    replace_all: Option<Handler<Vec<Hero>, ()>>,
+    upsert_many: Option<Handler<Vec<Hero>, UpsertReport>>,
+    get_with_neighbors: Option<Handler<String, HeroContext>>,
+    update_tags: Option<Handler<(String, TagChanges), Hero>>,
+    record_view: Option<Handler<String, u64>>,
+    get_changes_since: Option<Handler<u64, Vec<HeroChange>>>,
+}
+
+fn call<A, T>(
+    handler: &Option<Handler<A, T>>,
+    method: &str,
+    argument: A,
+) -> Result<T, DataAccessError> {
+    match handler {
+        Some(handler) => handler(argument),
+        None => panic!(
+            "FnHeroesRepository::{} called without on_{}",
+            method, method
+        ),
+    }
+}
+
+impl FnHeroesRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn on_get_by_name(
+        mut self,
+        handler: impl Fn(&str) -> Result<Vec<Hero>, DataAccessError> + Send + Sync + 'static,
+    ) -> Self {
+        self.get_by_name = Some(Box::new(move |name: String| handler(&name)));
+        self
+    }
+
+    pub fn on_get_by_id(
+        mut self,
+        handler: impl Fn(&str) -> Result<Hero, DataAccessError> + Send + Sync + 'static,
+    ) -> Self {
+        self.get_by_id = Some(Box::new(move |id: String| handler(&id)));
+        self
+    }
+
+    pub fn on_create(
+        mut self,
+        handler: impl Fn(HeroPayload) -> Result<Hero, DataAccessError> + Send + Sync + 'static,
+    ) -> Self {
+        self.create = Some(Box::new(handler));
+        self
+    }
+
+    pub fn on_create_if_absent(
+        mut self,
+        handler: impl Fn(&str, HeroPayload) -> Result<Hero, DataAccessError> + Send + Sync + 'static,
+    ) -> Self {
+        self.create_if_absent = Some(Box::new(move |(id, hero): (String, HeroPayload)| {
+            handler(&id, hero)
+        }));
+        self
+    }
+
+    pub fn on_update(
+        mut self,
+        handler: impl Fn(&str, HeroPayload) -> Result<Hero, DataAccessError> + Send + Sync + 'static,
+    ) -> Self {
+        self.update = Some(Box::new(move |(id, hero): (String, HeroPayload)| {
+            handler(&id, hero)
+        }));
+        self
+    }
+
+    pub fn on_delete(
+        mut self,
+        handler: impl Fn(&str) -> Result<Hero, DataAccessError> + Send + Sync + 'static,
+    ) -> Self {
+        self.delete = Some(Box::new(move |id: String| handler(&id)));
+        self
+    }
+
+    /// Heroes produced by `stream_all`; an error is produced as the only item
+    pub fn on_stream_all(
+        mut self,
+        handler: impl Fn() -> Result<Vec<Hero>, DataAccessError> + Send + Sync + 'static,
+    ) -> Self {
+        self.stream_all = Some(Box::new(move |()| handler()));
+        self
+    }
+
+    pub fn on_replace_all(
+        mut self,
+        handler: impl Fn(Vec<Hero>) -> Result<(), DataAccessError> + Send + Sync + 'static,
+    ) -> Self {
+        self.replace_all = Some(Box::new(handler));
+        self
+    }
+
+    pub fn on_upsert_many(
+        mut self,
+        handler: impl Fn(&[Hero]) -> Result<UpsertReport, DataAccessError> + Send + Sync + 'static,
+    ) -> Self {
+        self.upsert_many = Some(Box::new(move |heroes: Vec<Hero>| handler(&heroes)));
+        self
+    }
+
+    pub fn on_get_with_neighbors(
+        mut self,
+        handler: impl Fn(&str) -> Result<HeroContext, DataAccessError> + Send + Sync + 'static,
+    ) -> Self {
+        self.get_with_neighbors = Some(Box::new(move |id: String| handler(&id)));
+        self
+    }
+
+    pub fn on_update_tags(
+        mut self,
+        handler: impl Fn(&str, TagChanges) -> Result<Hero, DataAccessError> + Send + Sync + 'static,
+    ) -> Self {
+        self.update_tags = Some(Box::new(move |(id, changes): (String, TagChanges)| {
+            handler(&id, changes)
+        }));
+        self
+    }
+
+    pub fn on_record_view(
+        mut self,
+        handler: impl Fn(&str) -> Result<u64, DataAccessError> + Send + Sync + 'static,
+    ) -> Self {
+        self.record_view = Some(Box::new(move |id: String| handler(&id)));
+        self
+    }
+
+    pub fn on_get_changes_since(
+        mut self,
+        handler: impl Fn(u64) -> Result<Vec<HeroChange>, DataAccessError> + Send + Sync + 'static,
+    ) -> Self {
+        self.get_changes_since = Some(Box::new(handler));
+        self
+    }
+}
+
+#[async_trait]
+impl HeroesRepositoryTrait for FnHeroesRepository {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        call(&self.get_by_name, "get_by_name", name.to_string())
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        call(&self.get_by_id, "get_by_id", id.to_string())
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        call(&self.create, "create", hero)
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        call(
+            &self.create_if_absent,
+            "create_if_absent",
+            (id.to_string(), hero),
+        )
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        call(&self.update, "update", (id.to_string(), hero))
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        call(&self.delete, "delete", id.to_string())
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        match call(&self.stream_all, "stream_all", ()) {
+            Ok(heroes) => stream::iter(heroes.into_iter().map(Ok)).boxed(),
+            Err(error) => stream::once(async { Err(error) }).boxed(),
+        }
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        call(&self.replace_all, "replace_all", heroes)
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        call(&self.upsert_many, "upsert_many", heroes.to_vec())
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        call(
+            &self.get_with_neighbors,
+            "get_with_neighbors",
+            id.to_string(),
+        )
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        call(&self.update_tags, "update_tags", (id.to_string(), changes))
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        call(&self.record_view, "record_view", id.to_string())
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        call(&self.get_changes_since, "get_changes_since", since)
+    }
+}
@@ -0,0 +1,274 @@
+use crate::{csv, field_access};
+use axum::{
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::Value;
+
+/// A representation responses can be rendered in, chosen from the `Accept` header
+pub struct ResponseFormat {
+    /// media type matched against `Accept`
+    pub media_type: &'static str,
+    /// `Content-Type` of the rendered body
+    pub content_type: &'static str,
+    render: fn(&Value, bool) -> Result<String, serde_json::Error>,
+}
+
+/// Registered formats; the first one is used when the client accepts none of them
+pub const FORMATS: &[ResponseFormat] = &[
+    ResponseFormat {
+        media_type: "application/json",
+        content_type: "application/json",
+        render: render_json,
+    },
+    ResponseFormat {
+        media_type: "text/csv",
+        content_type: "text/csv; charset=utf-8",
+        render: render_csv,
+    },
+    ResponseFormat {
+        media_type: "application/xml",
+        content_type: "application/xml",
+        render: render_xml,
+    },
+];
+
+/// Format to answer with, given the `Accept` header of the request
+///
+/// Each format gets the `q` of the most specific media range matching it, so
+/// `text/*;q=0.5, text/csv;q=0` refuses CSV. The format with the highest `q` wins, ties
+/// going to the range listed first, then to the order of `FORMATS`.
+pub fn select(headers: &HeaderMap) -> &'static ResponseFormat {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let ranges: Vec<(String, f32)> = accept.split(',').map(media_range).collect();
+
+    FORMATS
+        .iter()
+        .filter_map(|format| {
+            let (position, quality) = ranges
+                .iter()
+                .enumerate()
+                .filter_map(|(position, (range, quality))| {
+                    specificity(range, format).map(|specificity| (specificity, position, *quality))
+                })
+                .max_by_key(|(specificity, _, _)| *specificity)
+                .map(|(_, position, quality)| (position, quality))?;
+            (quality > 0.0).then_some((format, position, quality))
+        })
+        // `max_by` keeps the last of equal elements, `min_by` the first
+        .min_by(|(_, a_position, a_quality), (_, b_position, b_quality)| {
+            b_quality
+                .total_cmp(a_quality)
+                .then(a_position.cmp(b_position))
+        })
+        .map_or(&FORMATS[0], |(format, _, _)| format)
+}
+
+/// Lowercase media range of an `Accept` entry and its `q`, 1 when it has none
+fn media_range(entry: &str) -> (String, f32) {
+    let mut parts = entry.split(';').map(str::trim);
+    let range = parts.next().unwrap_or_default().to_ascii_lowercase();
+    let quality = match parts.find_map(|part| part.strip_prefix("q=")) {
+        Some(quality) => quality.parse().unwrap_or(0.0),
+        None => 1.0,
+    };
+    (range, quality)
+}
+
+/// How precisely `range` designates `format`: `*/*` least, the media type itself most;
+/// `None` when it doesn't match
+fn specificity(range: &str, format: &ResponseFormat) -> Option<u8> {
+    match range.strip_suffix("/*") {
+        Some("*") => Some(0),
+        Some(kind) => (format.media_type.split('/').next() == Some(kind)).then_some(1),
+        None => (range == format.media_type).then_some(2),
+    }
+}
+
+/// Respond with `data` in the format the client prefers; `pretty` indents json
+///
+/// The fields restricted to admins are left out whatever the format.
+pub fn negotiate<T: Serialize>(headers: &HeaderMap, pretty: bool, data: &T) -> Response {
+    let format = select(headers);
+    let body = serde_json::to_value(data).and_then(|mut value| {
+        field_access::strip_restricted(&mut value);
+        (format.render)(&value, pretty)
+    });
+
+    match body {
+        Ok(body) => ([(header::CONTENT_TYPE, format.content_type)], body).into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+fn render_json(value: &Value, pretty: bool) -> Result<String, serde_json::Error> {
+    if pretty {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    }
+}
+
+/// One row per element of an array (or a single row for anything else), the header listing
+/// every field met; nested values are written as json
+fn render_csv(value: &Value, _pretty: bool) -> Result<String, serde_json::Error> {
+    let rows = match value {
+        Value::Array(items) => items.iter().collect(),
+        other => vec![other],
+    };
+
+    let mut columns: Vec<&str> = vec![];
+    for row in &rows {
+        if let Value::Object(fields) = row {
+            for key in fields.keys() {
+                if !columns.contains(&key.as_str()) {
+                    columns.push(key);
+                }
+            }
+        }
+    }
+
+    let mut body = csv::write_row(&columns);
+    for row in rows {
+        let cells: Vec<String> = match row {
+            Value::Object(fields) => columns
+                .iter()
+                .map(|column| fields.get(*column).map(csv_cell).unwrap_or_default())
+                .collect(),
+            scalar => vec![csv_cell(scalar)],
+        };
+        let cells: Vec<&str> = cells.iter().map(String::as_str).collect();
+        body.push_str(&csv::write_row(&cells));
+    }
+    Ok(body)
+}
+
+fn csv_cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Arrays become `<list>` of `<item>`s and objects an element per field
+fn render_xml(value: &Value, _pretty: bool) -> Result<String, serde_json::Error> {
+    let mut body = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let root = if value.is_array() { "list" } else { "item" };
+    write_xml_element(&mut body, root, value);
+    Ok(body)
+}
+
+fn write_xml_element(out: &mut String, name: &str, value: &Value) {
+    out.push('<');
+    out.push_str(name);
+    out.push('>');
+    match value {
+        Value::Null => {}
+        Value::Array(items) => {
+            for item in items {
+                write_xml_element(out, "item", item);
+            }
+        }
+        Value::Object(fields) => {
+            for (key, field) in fields {
+                write_xml_element(out, key, field);
+            }
+        }
+        Value::String(text) => push_xml_escaped(out, text),
+        scalar => out.push_str(&scalar.to_string()),
+    }
+    out.push_str("</");
+    out.push_str(name);
+    out.push('>');
+}
+
+fn push_xml_escaped(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    fn accepting(accept: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, accept.parse().unwrap());
+        headers
+    }
+
+    async fn rendered(accept: &str) -> (String, String) {
+        let data = json!([{ "id": "1", "name": "Tom & Jerry" }, { "id": "2", "name": "Deadpool" }]);
+        let response = negotiate(&accepting(accept), false, &data);
+        let content_type = response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (content_type, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn json_is_rendered() {
+        let (content_type, body) = rendered("application/json").await;
+
+        assert_eq!(content_type, "application/json");
+        assert_eq!(
+            body,
+            r#"[{"id":"1","name":"Tom & Jerry"},{"id":"2","name":"Deadpool"}]"#
+        );
+    }
+
+    #[tokio::test]
+    async fn csv_is_rendered() {
+        let (content_type, body) = rendered("text/csv").await;
+
+        assert_eq!(content_type, "text/csv; charset=utf-8");
+        assert_eq!(body, "id,name\r\n1,Tom & Jerry\r\n2,Deadpool\r\n");
+    }
+
+    #[tokio::test]
+    async fn xml_is_rendered() {
+        let (content_type, body) = rendered("application/xml").await;
+
+        assert_eq!(content_type, "application/xml");
+        assert_eq!(
+            body,
+            concat!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+                "<list><item><id>1</id><name>Tom &amp; Jerry</name></item>",
+                "<item><id>2</id><name>Deadpool</name></item></list>"
+            )
+        );
+    }
+
+    #[rstest]
+    #[case("", "application/json")] // no Accept header value
+    #[case("image/png", "application/json")] // nothing acceptable: default
+    #[case("*/*", "application/json")]
+    #[case("text/*", "text/csv")]
+    #[case("image/png, application/xml, text/csv", "application/xml")] // first listed on ties
+    #[case("text/csv;q=0.9, application/json;q=1.0", "application/json")]
+    #[case("application/xml;q=0.5, text/*;q=0.8", "text/csv")]
+    #[case("*/*;q=0.1, application/xml", "application/xml")]
+    #[case("application/json;q=0, text/csv;q=0.2", "text/csv")] // refused json
+    #[case("*/*, application/json;q=0", "text/csv")] // refused json, most specific range wins
+    fn format_is_selected_from_accept(#[case] accept: &str, #[case] expected: &str) {
+        assert_eq!(select(&accepting(accept)).media_type, expected);
+    }
+}
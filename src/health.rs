@@ -0,0 +1,393 @@
+use crate::circuit_breaker::{CircuitBreaker, CircuitState};
+use crate::task::{BackgroundTasks, PanicPolicy};
+use crate::{config::Config, deadline::Deadline, DataAccessError, DynHeroesRepository};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Id looked up by the deep readiness check, no hero is expected to have it
+const PROBE_ID: &str = "readiness-probe";
+
+/// How thoroughly `GET /health/ready` checks the repository
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadinessDepth {
+    /// only `ping` the repository, cheap enough for frequent probes
+    #[default]
+    Shallow,
+    /// also run an actual query, catching missing tables or permissions a ping doesn't
+    Deep,
+}
+
+impl FromStr for ReadinessDepth {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "shallow" => Ok(ReadinessDepth::Shallow),
+            "deep" => Ok(ReadinessDepth::Deep),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Failed,
+}
+
+/// Body of `GET /health/ready`: `{ "status": "ready", "checks": { "repository_ping": "ok" } }`
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub status: &'static str,
+    pub depth: ReadinessDepth,
+    pub checks: BTreeMap<&'static str, CheckStatus>,
+    /// with `CIRCUIT_BREAKER_THRESHOLD`; the ping fails while the circuit is open
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker: Option<CircuitState>,
+}
+
+/// Whether the instance is done warming up, done from the start when there's nothing to warm
+#[derive(Debug)]
+pub struct WarmUp {
+    done: AtomicBool,
+}
+
+impl Default for WarmUp {
+    fn default() -> Self {
+        WarmUp {
+            done: AtomicBool::new(true),
+        }
+    }
+}
+
+impl WarmUp {
+    /// Warm-up yet to be `run`
+    pub fn pending() -> Self {
+        WarmUp {
+            done: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+
+    /// Run `warm_up`, the instance being done warming up once it ends, however it went
+    pub async fn run(&self, warm_up: impl Future<Output = ()>) {
+        warm_up.await;
+        self.done.store(true, Ordering::Release);
+    }
+}
+
+/// List the heroes like `GET /heroes/` does, so the first requests find caches filled
+pub async fn warm_up(repo: DynHeroesRepository) {
+    let started = Instant::now();
+    match repo.get_by_name("%").await {
+        Ok(heroes) => tracing::info!(
+            heroes = heroes.len(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "warmed up"
+        ),
+        Err(DataAccessError::NotFound) => tracing::info!(heroes = 0, "warmed up"),
+        Err(error) => tracing::warn!(?error, "warm-up failed, serving cold"),
+    }
+}
+
+/// `GET /health`: the process is up and answering, whether or not it's ready to serve
+pub async fn live() -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "status": "alive" }))
+}
+
+/// `GET /health/ready`: whether the service can serve requests, `503` naming the failed
+/// checks otherwise
+///
+/// Which checks run depends on `READINESS_DEPTH`; each gets the request deadline. With
+/// `CRITICAL_TASK_PANIC=unready`, the `background_tasks` check fails once one panicked.
+/// With `WARM_UP`, the `warm_up` check fails until it's done. The state of the circuit
+/// breaker is reported along, when there is one.
+pub async fn ready(
+    State(repo): State<DynHeroesRepository>,
+    State(config): State<Arc<Config>>,
+    State(tasks): State<Arc<BackgroundTasks>>,
+    State(warm_up): State<Arc<WarmUp>>,
+    State(breaker): State<Option<Arc<CircuitBreaker>>>,
+    deadline: Deadline,
+) -> impl IntoResponse {
+    let mut checks = BTreeMap::new();
+    if config.warm_up {
+        let status = if warm_up.is_done() {
+            CheckStatus::Ok
+        } else {
+            CheckStatus::Failed
+        };
+        checks.insert("warm_up", status);
+    }
+    let ping = deadline.run(repo.ping()).await;
+    checks.insert("repository_ping", status_of(ping.ok()));
+    if config.readiness_depth == ReadinessDepth::Deep {
+        let query = deadline.run(repo.get_by_id(PROBE_ID)).await;
+        let query = query.ok().map(|found| match found {
+            // the query went through, whether it found anything doesn't matter
+            Err(DataAccessError::NotFound | DataAccessError::Gone) => Ok(()),
+            found => found.map(|_| ()),
+        });
+        checks.insert("repository_query", status_of(query));
+    }
+    if tasks.on_panic() == PanicPolicy::Unready {
+        let failed = tasks.failed();
+        if !failed.is_empty() {
+            tracing::warn!(?failed, "background tasks panicked");
+        }
+        let status = if failed.is_empty() {
+            CheckStatus::Ok
+        } else {
+            CheckStatus::Failed
+        };
+        checks.insert("background_tasks", status);
+    }
+
+    let ready = checks.values().all(|status| *status == CheckStatus::Ok);
+    let (code, status) = if ready {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "unavailable")
+    };
+    let readiness = Readiness {
+        status,
+        depth: config.readiness_depth,
+        checks,
+        circuit_breaker: breaker.map(|breaker| breaker.state()),
+    };
+    (code, Json(readiness))
+}
+
+/// When the service started, kept in the app state to report its uptime
+#[derive(Debug, Clone, Copy)]
+pub struct Started(pub Instant);
+
+impl Default for Started {
+    fn default() -> Self {
+        Started(Instant::now())
+    }
+}
+
+/// Body of `GET /health/info`
+#[derive(Debug, Serialize)]
+pub struct Info {
+    pub version: &'static str,
+    /// seconds since the service started, with a millisecond precision
+    pub uptime_secs: f64,
+    /// `in-memory`, or `upstream` when heroes are stored by another instance
+    pub backend: &'static str,
+}
+
+/// `GET /health/info`: what's running and for how long, for dashboards
+pub async fn info(State(started): State<Started>, State(config): State<Arc<Config>>) -> Json<Info> {
+    let uptime_ms = started.0.elapsed().as_millis();
+    Json(Info {
+        version: env!("CARGO_PKG_VERSION"),
+        uptime_secs: uptime_ms as f64 / 1000.0,
+        backend: config.backend(),
+    })
+}
+
+/// `None` when the check timed out
+fn status_of(result: Option<Result<(), DataAccessError>>) -> CheckStatus {
+    match result {
+        Some(Ok(())) => CheckStatus::Ok,
+        Some(Err(error)) => {
+            tracing::warn!(?error, "readiness check failed");
+            CheckStatus::Failed
+        }
+        None => {
+            tracing::warn!("readiness check timed out");
+            CheckStatus::Failed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fn_repository::FnHeroesRepository;
+    use serde_json::Value;
+    use std::time::Duration;
+
+    async fn readiness(repo: FnHeroesRepository, depth: ReadinessDepth) -> (StatusCode, Value) {
+        readiness_with_tasks(repo, depth, Default::default()).await
+    }
+
+    async fn readiness_with_tasks(
+        repo: FnHeroesRepository,
+        depth: ReadinessDepth,
+        tasks: Arc<BackgroundTasks>,
+    ) -> (StatusCode, Value) {
+        let config = Config {
+            readiness_depth: depth,
+            ..Default::default()
+        };
+        readiness_with(repo, config, tasks, Default::default(), None).await
+    }
+
+    async fn readiness_with(
+        repo: FnHeroesRepository,
+        config: Config,
+        tasks: Arc<BackgroundTasks>,
+        warm_up: Arc<WarmUp>,
+        breaker: Option<Arc<CircuitBreaker>>,
+    ) -> (StatusCode, Value) {
+        let response = ready(
+            State(Arc::new(repo)),
+            State(Arc::new(config)),
+            State(tasks),
+            State(warm_up),
+            State(breaker),
+            Deadline(Duration::from_secs(1)),
+        )
+        .await
+        .into_response();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn uptime_grows_from_the_start() {
+        let started = Started::default();
+        let config = Arc::new(Config::default());
+
+        let Json(first) = info(State(started), State(config.clone())).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let Json(second) = info(State(started), State(config)).await;
+
+        assert!(first.uptime_secs >= 0.0);
+        assert!(second.uptime_secs > first.uptime_secs);
+        assert_eq!(second.backend, "in-memory");
+        assert_eq!(second.version, env!("CARGO_PKG_VERSION"));
+    }
+
+    #[tokio::test]
+    async fn shallow_check_does_not_query() {
+        // FnHeroesRepository panics on get_by_id, having no closure for it
+        let (status, body) = readiness(FnHeroesRepository::new(), ReadinessDepth::Shallow).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body["checks"],
+            serde_json::json!({ "repository_ping": "ok" })
+        );
+    }
+
+    #[tokio::test]
+    async fn deep_check_passes_when_the_query_goes_through() {
+        let repo = FnHeroesRepository::new().on_get_by_id(|_| Err(DataAccessError::NotFound));
+
+        let (status, body) = readiness(repo, ReadinessDepth::Deep).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ready");
+        assert_eq!(body["checks"]["repository_query"], "ok");
+    }
+
+    #[tokio::test]
+    async fn failing_deep_check_is_named() {
+        let repo = FnHeroesRepository::new().on_get_by_id(|_| Err(DataAccessError::TechnicalError));
+
+        let (status, body) = readiness(repo, ReadinessDepth::Deep).await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "unavailable");
+        assert_eq!(
+            body["checks"],
+            serde_json::json!({ "repository_ping": "ok", "repository_query": "failed" })
+        );
+    }
+
+    #[tokio::test]
+    async fn panicked_critical_task_makes_the_instance_unready() {
+        let tasks = Arc::new(BackgroundTasks::new(PanicPolicy::Unready));
+        let check = || async {
+            let repo = FnHeroesRepository::new();
+            readiness_with_tasks(repo, ReadinessDepth::Shallow, tasks.clone()).await
+        };
+
+        let (status, body) = check().await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["checks"]["background_tasks"], "ok");
+
+        let task = tasks.spawn_critical("cache_refresh", || async { panic!("poisoned") });
+        task.await.unwrap();
+        let (status, body) = check().await;
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["checks"]["background_tasks"], "failed");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn instance_is_unready_until_warmed_up() {
+        let warm_up = Arc::new(WarmUp::pending());
+        let config = Config {
+            warm_up: true,
+            ..Default::default()
+        };
+        let check = || {
+            readiness_with(
+                FnHeroesRepository::new(),
+                config.clone(),
+                Default::default(),
+                warm_up.clone(),
+                None,
+            )
+        };
+        let slow = tokio::spawn({
+            let warm_up = warm_up.clone();
+            async move {
+                warm_up
+                    .run(tokio::time::sleep(Duration::from_secs(5)))
+                    .await
+            }
+        });
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let (status, body) = check().await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["checks"]["warm_up"], "failed");
+        assert_eq!(live().await.0["status"], "alive");
+
+        slow.await.unwrap();
+        let (status, body) = check().await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["checks"]["warm_up"], "ok");
+    }
+
+    #[tokio::test]
+    async fn circuit_breaker_state_is_reported() {
+        let (_, body) = readiness(FnHeroesRepository::new(), ReadinessDepth::Shallow).await;
+        assert!(body.get("circuit_breaker").is_none());
+
+        let breaker = Arc::new(CircuitBreaker::new(1, Duration::from_secs(30)));
+        let check = || {
+            readiness_with(
+                FnHeroesRepository::new(),
+                Config::default(),
+                Default::default(),
+                Default::default(),
+                Some(breaker.clone()),
+            )
+        };
+        let (_, body) = check().await;
+        assert_eq!(body["circuit_breaker"], "closed");
+
+        breaker.record(std::time::Instant::now(), true);
+        let (_, body) = check().await;
+        assert_eq!(body["circuit_breaker"], "open");
+    }
+}
@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Deref;
+
+/// Name of a hero, always normalized: trimmed, with inner whitespace collapsed to single spaces
+///
+/// Deserializing goes through the same normalization and rejects blank names.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+#[cfg_attr(test, derive(Default))]
+#[serde(try_from = "String", into = "String")]
+pub struct HeroName(String);
+
+/// Error for a name made only of whitespace
+#[derive(Debug, Eq, PartialEq)]
+pub struct BlankHeroName;
+
+impl fmt::Display for BlankHeroName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "hero name must not be blank")
+    }
+}
+
+/// Longest accepted name, in characters
+pub const MAX_LEN: usize = 100;
+
+impl HeroName {
+    pub fn new(name: &str) -> Result<Self, BlankHeroName> {
+        let normalized = name.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if normalized.is_empty() {
+            Err(BlankHeroName)
+        } else {
+            Ok(HeroName(normalized))
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Reasons why the name can't be stored, empty when it can
+    ///
+    /// `%` is refused because it's the wildcard of name filters: such a name couldn't be
+    /// looked up exactly.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if self.0.chars().count() > MAX_LEN {
+            problems.push(format!("name must not exceed {} characters", MAX_LEN));
+        }
+        if self.0.contains('%') {
+            problems.push("name must not contain '%'".to_string());
+        }
+        if self.0.chars().any(char::is_control) {
+            problems.push("name must not contain control characters".to_string());
+        }
+        problems
+    }
+}
+
+/// Collapse whitespace runs of a name filter to single spaces, like stored names
+///
+/// Unlike `HeroName::new` the edges are kept: they are significant in a prefix filter.
+pub fn normalize_filter(filter: &str) -> String {
+    let mut normalized = String::with_capacity(filter.len());
+    let mut previous_was_space = false;
+    for c in filter.chars() {
+        if c.is_whitespace() {
+            if !previous_was_space {
+                normalized.push(' ');
+            }
+            previous_was_space = true;
+        } else {
+            normalized.push(c);
+            previous_was_space = false;
+        }
+    }
+    normalized
+}
+
+/// Escape the `%` of a name filter, so it matches a literal `%` instead of any suffix
+pub fn escape_wildcards(filter: &str) -> String {
+    filter.replace('%', "\\%")
+}
+
+/// Case-insensitive form of a name or filter, used for matching
+///
+/// Relies on Unicode lowercase mapping, which handles accented and non-latin letters
+/// ("É" and "é", "Ч" and "ч") but not multi-character folds: "STRASSE" doesn't match "straße".
+/// No Unicode normalization (NFC) is applied, so a precomposed "é" and "e" followed by a
+/// combining accent are different names.
+pub fn fold_case(name: &str) -> String {
+    name.to_lowercase()
+}
+
+/// How alike two names are, from 0 (nothing in common) to 1 (same name, ignoring case)
+///
+/// One minus the edit (Levenshtein) distance between the case folded names, relative to
+/// the longest one, counted in characters.
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = fold_case(a).chars().collect();
+    let b: Vec<char> = fold_case(b).chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+/// `similarity` of `partial` to `name` or to its start, whichever is closest, for partial
+/// names as typed in filters: "Wondr" is far from "Wonder Woman" but close to "Wonde"
+pub fn prefix_similarity(partial: &str, name: &str) -> f64 {
+    let start: String = name.chars().take(partial.chars().count()).collect();
+    similarity(partial, name).max(similarity(partial, &start))
+}
+
+impl Deref for HeroName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for HeroName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<String> for HeroName {
+    type Error = BlankHeroName;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        HeroName::new(&name)
+    }
+}
+
+impl From<HeroName> for String {
+    fn from(name: HeroName) -> Self {
+        name.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messy_name_is_normalized() {
+        let name = HeroName::new("  Wonder \t  Woman ").unwrap();
+
+        assert_eq!(name.as_str(), "Wonder Woman");
+    }
+
+    #[test]
+    fn problems_are_all_reported() {
+        let name = HeroName::new(&format!("100%{}", "!".repeat(MAX_LEN))).unwrap();
+
+        assert_eq!(
+            name.problems(),
+            vec![
+                "name must not exceed 100 characters".to_string(),
+                "name must not contain '%'".to_string()
+            ]
+        );
+        assert!(HeroName::new("Élodie").unwrap().problems().is_empty());
+    }
+
+    #[test]
+    fn similarity_is_relative_to_the_longest_name() {
+        assert_eq!(similarity("Spider-Man", "spider-man"), 1.0);
+        assert_eq!(similarity("Spider-Man", "Superman"), 0.6);
+        assert_eq!(similarity("Ōkami", "Okami"), 0.8);
+        assert_eq!(similarity("abc", "xyz"), 0.0);
+    }
+
+    #[test]
+    fn prefix_similarity_compares_the_start_of_names() {
+        assert_eq!(prefix_similarity("Wondr", "Wonder Woman"), 0.8);
+        assert_eq!(prefix_similarity("Spider-Man", "spider-man"), 1.0);
+        assert_eq!(prefix_similarity("abc", "xyz"), 0.0);
+    }
+
+    #[test]
+    fn blank_name_is_rejected() {
+        assert_eq!(HeroName::new(" \t\n "), Err(BlankHeroName));
+        assert!(serde_json::from_str::<HeroName>("\"   \"").is_err());
+    }
+}
@@ -0,0 +1,587 @@
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::pagination::{HeroRange, Pagination};
+use crate::sort::{SortField, SortOrder};
+use crate::{config::Config, error::ApiError, Hero};
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Query},
+    http::request::Parts,
+};
+use serde::{Deserialize, Serialize, Serializer};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Pairs of listing parameters contradicting each other, so never accepted together
+const CONFLICTING_PARAMS: &[(&str, &str)] =
+    &[("q", "name"), ("q", "name_regex"), ("name", "name_regex")];
+
+/// Parameters whose values each turn into a filter, so capped at `Config::max_param_values`
+const CAPPED_PARAMS: &[&str] = &["id", "tag"];
+
+/// Parameters taking one value, so refused when repeated rather than picking one of them;
+/// the `_gte` and `_lte` bounds of the `NumericField`s are too
+const SINGULAR_PARAMS: &[&str] = &[
+    "name",
+    "q",
+    "name_regex",
+    "limit",
+    "offset",
+    "sort",
+    "tag_mode",
+    "shape",
+];
+
+/// Hero fields listings may sort or filter on, when allowed by `QUERY_FIELDS`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QueryField {
+    Id,
+    Name,
+    UpdatedAt,
+    Tags,
+    PowerLevel,
+}
+
+impl QueryField {
+    pub const ALL: [QueryField; 5] = [
+        QueryField::Id,
+        QueryField::Name,
+        QueryField::UpdatedAt,
+        QueryField::Tags,
+        QueryField::PowerLevel,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            QueryField::Id => "id",
+            QueryField::Name => "name",
+            QueryField::UpdatedAt => "updated_at",
+            QueryField::Tags => "tags",
+            QueryField::PowerLevel => "power_level",
+        }
+    }
+}
+
+impl From<SortField> for QueryField {
+    fn from(field: SortField) -> Self {
+        match field {
+            SortField::Id => QueryField::Id,
+            SortField::Name => QueryField::Name,
+            SortField::UpdatedAt => QueryField::UpdatedAt,
+            SortField::PowerLevel => QueryField::PowerLevel,
+        }
+    }
+}
+
+impl FromStr for QueryField {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        QueryField::ALL
+            .into_iter()
+            .find(|field| field.name() == value)
+            .ok_or(())
+    }
+}
+
+impl Serialize for QueryField {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+/// Numeric hero fields listings may be filtered on by range, `?<field>_gte=&<field>_lte=`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NumericField {
+    UpdatedAt,
+    PowerLevel,
+}
+
+impl NumericField {
+    pub const ALL: [NumericField; 2] = [NumericField::UpdatedAt, NumericField::PowerLevel];
+
+    /// Prefix of the range parameters on the field
+    pub fn name(self) -> &'static str {
+        match self {
+            NumericField::UpdatedAt => "updated_at",
+            NumericField::PowerLevel => "power",
+        }
+    }
+
+    pub fn value(self, hero: &Hero) -> Option<u64> {
+        match self {
+            NumericField::UpdatedAt => hero.updated_at,
+            NumericField::PowerLevel => Some(hero.power_level.into()),
+        }
+    }
+}
+
+impl From<NumericField> for QueryField {
+    fn from(field: NumericField) -> Self {
+        match field {
+            NumericField::UpdatedAt => QueryField::UpdatedAt,
+            NumericField::PowerLevel => QueryField::PowerLevel,
+        }
+    }
+}
+
+/// Listing parameters as sent, before validation
+#[derive(Deserialize)]
+struct Params {
+    name: Option<String>,
+    /// search term matching either an id or the beginning of a name
+    q: Option<String>,
+    /// regular expression names must match, see `NameRegex`
+    name_regex: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    /// `name`, `-name`, `name,-id`... overriding `DEFAULT_SORT`
+    sort: Option<String>,
+    #[serde(default)]
+    tag_mode: TagMode,
+    #[serde(default)]
+    shape: Shape,
+}
+
+/// Whether heroes need all the `tag`s of the query (default) or any of them
+#[derive(Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMode {
+    #[default]
+    All,
+    Any,
+}
+
+/// Layout of a listing: an array of heroes (default) or an object of heroes keyed by id
+#[derive(Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    #[default]
+    Array,
+    Map,
+}
+
+/// How a listing selects heroes, before tags narrow them down
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeroFilter {
+    /// `?q=`: heroes whose id is the term or whose name starts with it
+    Search(String),
+    /// `?name_regex=`: heroes whose name matches the regular expression
+    Regex(NameRegex),
+    /// `?name=`, or no filter at all: a `get_by_name` filter, wildcards included
+    Name(String),
+}
+
+/// Validated parameters of `GET /heroes/`
+///
+/// Rejects the request with a `400` naming the problem when any parameter is invalid,
+/// whatever the others are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeroQuery {
+    pub filter: HeroFilter,
+    /// lowercase `tag`s, which may be repeated
+    pub tags: Vec<String>,
+    pub tag_mode: TagMode,
+    /// `?<field>_gte=&<field>_lte=`, in the order of `NumericField::ALL`
+    pub ranges: Vec<(NumericField, NumericRange<u64>)>,
+    /// `?sort=`, or `DEFAULT_SORT` when absent
+    pub sort: Option<SortOrder>,
+    /// from `limit` and `offset`, or from the `Range` header
+    pub pagination: Pagination,
+    /// `Range` header, answered with `206 Partial Content`
+    pub range: Option<HeroRange>,
+    /// `?shape=`
+    pub shape: Shape,
+}
+
+impl HeroQuery {
+    fn parse(
+        pairs: &[(String, String)],
+        params: Params,
+        range: Option<HeroRange>,
+        config: &Config,
+    ) -> Result<HeroQuery, ApiError> {
+        check_conflicts(pairs)?;
+        let pagination = match range {
+            Some(_) if params.limit.is_some() || params.offset.is_some() => {
+                let message = "the Range header can't be combined with limit and offset";
+                return Err(ApiError::bad_request(message));
+            }
+            Some(range) => range.pagination(),
+            None => Pagination::parse(params.limit, params.offset, config.max_offset)?,
+        };
+        let sort = match params.sort.as_deref().map(str::parse::<SortOrder>) {
+            Some(sort) => Some(sort.map_err(ApiError::bad_request)?),
+            None => config.default_sort.clone(),
+        };
+        check_fields(&params, pairs, sort.as_ref(), config)?;
+        let filter = match (params.q, params.name_regex) {
+            (Some(term), _) => HeroFilter::Search(term),
+            (None, Some(pattern)) => HeroFilter::Regex(NameRegex::new(&pattern)?),
+            (None, None) => HeroFilter::Name(crate::name_filter(params.name.as_deref(), config)?),
+        };
+        // `tag` may be repeated, which `Params` can't express
+        let tags = pairs
+            .iter()
+            .filter(|(key, _)| key == "tag")
+            .map(|(_, tag)| tag.trim().to_lowercase())
+            .collect();
+        let mut ranges = vec![];
+        for field in NumericField::ALL {
+            if let Some(range) = NumericRange::parse(pairs, field.name())? {
+                ranges.push((field, range));
+            }
+        }
+        Ok(HeroQuery {
+            filter,
+            tags,
+            tag_mode: params.tag_mode,
+            ranges,
+            sort,
+            pagination,
+            range,
+            shape: params.shape,
+        })
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for HeroQuery
+where
+    Arc<Config>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = Arc::<Config>::from_ref(state);
+        let invalid = |rejection: axum::extract::rejection::QueryRejection| {
+            ApiError::bad_request(rejection.body_text())
+        };
+        let Query(pairs) = Query::<Vec<(String, String)>>::from_request_parts(parts, state)
+            .await
+            .map_err(invalid)?;
+        // before `Params`, which won't even parse repeated single-valued parameters
+        check_counts(&pairs, config.max_param_values)?;
+        check_singular(&pairs)?;
+        let Query(params) = Query::<Params>::from_request_parts(parts, state)
+            .await
+            .map_err(invalid)?;
+        let range = HeroRange::from_headers(&parts.headers, config.max_offset)?;
+        HeroQuery::parse(&pairs, params, range, &config)
+    }
+}
+
+/// `400` naming the first pair of `CONFLICTING_PARAMS` found among the query parameters
+fn check_conflicts(params: &[(String, String)]) -> Result<(), ApiError> {
+    let given = |name: &str| params.iter().any(|(key, _)| key == name);
+    let conflict = CONFLICTING_PARAMS
+        .iter()
+        .find(|(a, b)| given(a) && given(b));
+    match conflict {
+        Some((a, b)) => {
+            let message = format!("{} and {} can't be combined", a, b);
+            Err(ApiError::bad_request(message))
+        }
+        None => Ok(()),
+    }
+}
+
+/// `400` naming the first field sorted or filtered on which `QUERY_FIELDS` doesn't allow
+///
+/// The `DEFAULT_SORT` is left alone: it's the deployment's own choice.
+fn check_fields(
+    params: &Params,
+    pairs: &[(String, String)],
+    sort: Option<&SortOrder>,
+    config: &Config,
+) -> Result<(), ApiError> {
+    let allowed = |field: &QueryField| config.query_fields.contains(field);
+    let mut filtered = vec![];
+    if params.name.is_some() || params.name_regex.is_some() {
+        filtered.push(QueryField::Name);
+    }
+    // search terms match ids too
+    if params.q.is_some() {
+        filtered.extend([QueryField::Name, QueryField::Id]);
+    }
+    if pairs.iter().any(|(key, _)| key == "tag") {
+        filtered.push(QueryField::Tags);
+    }
+    let bounded = |field: &NumericField| {
+        let (gte, lte) = (
+            format!("{}_gte", field.name()),
+            format!("{}_lte", field.name()),
+        );
+        pairs.iter().any(|(key, _)| *key == gte || *key == lte)
+    };
+    filtered.extend(
+        NumericField::ALL
+            .iter()
+            .filter(|field| bounded(field))
+            .map(|field| QueryField::from(*field)),
+    );
+    if let Some(field) = filtered.into_iter().find(|field| !allowed(field)) {
+        let message = format!("heroes can't be filtered by {} here", field.name());
+        return Err(ApiError::bad_request(message));
+    }
+
+    // `sort` is the `DEFAULT_SORT` when `?sort=` is absent
+    let requested = match (&params.sort, sort) {
+        (Some(_), Some(sort)) => &sort.0[..],
+        _ => &[],
+    };
+    let forbidden = requested
+        .iter()
+        .map(|key| QueryField::from(key.field))
+        .find(|field| !allowed(field));
+    match forbidden {
+        Some(field) => {
+            let message = format!("heroes can't be sorted by {} here", field.name());
+            Err(ApiError::bad_request(message))
+        }
+        None => Ok(()),
+    }
+}
+
+/// `400` naming the first of `CAPPED_PARAMS` given more than `max` times
+fn check_counts(params: &[(String, String)], max: usize) -> Result<(), ApiError> {
+    let count = |name: &str| params.iter().filter(|(key, _)| key == name).count();
+    match CAPPED_PARAMS.iter().find(|name| count(name) > max) {
+        Some(name) => {
+            let message = format!("at most {} {} parameters are accepted", max, name);
+            Err(ApiError::bad_request(message))
+        }
+        None => Ok(()),
+    }
+}
+
+/// `400` naming the first of `SINGULAR_PARAMS` or numeric bounds given more than once
+fn check_singular(params: &[(String, String)]) -> Result<(), ApiError> {
+    let bounds = NumericField::ALL
+        .iter()
+        .flat_map(|field| ["gte", "lte"].map(|bound| format!("{}_{}", field.name(), bound)));
+    let mut singular = SINGULAR_PARAMS
+        .iter()
+        .map(|name| name.to_string())
+        .chain(bounds);
+    let repeated = |name: &String| params.iter().filter(|(key, _)| key == name).count() > 1;
+    match singular.find(repeated) {
+        Some(name) => Err(ApiError::bad_request(format!(
+            "{} must be given at most once",
+            name
+        ))),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sort::SortKey;
+    use axum::http::{Request, StatusCode};
+    use rstest::rstest;
+
+    fn query(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    async fn extract(uri: &str) -> Result<HeroQuery, ApiError> {
+        let (mut parts, ()) = Request::builder().uri(uri).body(()).unwrap().into_parts();
+        HeroQuery::from_request_parts(&mut parts, &Arc::new(Config::default())).await
+    }
+
+    #[rstest]
+    #[case(&[("q", "Wonder"), ("name", "Wonder")])]
+    #[case(&[("name", ""), ("limit", "1"), ("q", "1")])] // whatever the values and order
+    fn conflicting_params_are_named(#[case] pairs: &[(&str, &str)]) {
+        let error = check_conflicts(&query(pairs)).unwrap_err();
+
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.message, "q and name can't be combined");
+    }
+
+    #[test]
+    fn compatible_params_are_accepted() {
+        let pairs = [("q", "D"), ("tag", "antihero"), ("limit", "1")];
+
+        assert!(check_conflicts(&query(&pairs)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn every_listing_param_is_extracted() {
+        let uri = "/?name=Dead&tag=Mercenary&tag=villain&tag_mode=any&sort=-name&limit=5&offset=10&shape=map";
+
+        let query = extract(uri).await.unwrap();
+
+        assert_eq!(
+            query,
+            HeroQuery {
+                filter: HeroFilter::Name("Dead%".to_string()),
+                tags: vec!["mercenary".to_string(), "villain".to_string()],
+                tag_mode: TagMode::Any,
+                ranges: vec![],
+                sort: Some(SortOrder(vec![SortKey {
+                    field: SortField::Name,
+                    descending: true,
+                }])),
+                pagination: Pagination {
+                    limit: Some(5),
+                    offset: 10,
+                },
+                range: None,
+                shape: Shape::Map,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn numeric_ranges_are_extracted() {
+        let query = extract("/?updated_at_gte=10&updated_at_lte=20")
+            .await
+            .unwrap();
+
+        let range = NumericRange {
+            gte: Some(10),
+            lte: Some(20),
+        };
+        assert_eq!(query.ranges, vec![(NumericField::UpdatedAt, range)]);
+    }
+
+    #[tokio::test]
+    async fn search_term_replaces_the_name_filter() {
+        let query = extract("/?q=Dea").await.unwrap();
+
+        assert_eq!(query.filter, HeroFilter::Search("Dea".to_string()));
+        assert_eq!(query.pagination, Pagination::default());
+    }
+
+    #[rstest]
+    #[case("/?name=", "name filter must not be empty")]
+    #[case("/?name=Dead&offset=5", "offset requires a limit")]
+    #[case(
+        "/?sort=power&limit=5",
+        "can't sort by 'power', expected id, name, updated_at or power_level"
+    )]
+    #[case("/?q=Dead&sort=name&name=Dead", "q and name can't be combined")]
+    #[case(
+        "/?name_regex=%5E(Dead&name=Dead",
+        "name and name_regex can't be combined"
+    )]
+    #[case("/?name_regex=%5E(Dead", "invalid name_regex: unclosed group")]
+    #[case(
+        "/?updated_at_gte=20&updated_at_lte=10",
+        "updated_at_gte (20) must not be greater than updated_at_lte (10)"
+    )]
+    #[case(
+        "/?updated_at_gte=yesterday",
+        "updated_at_gte must be a number, not 'yesterday'"
+    )]
+    #[tokio::test]
+    async fn invalid_params_are_a_bad_request(#[case] uri: &str, #[case] message: &str) {
+        let error = extract(uri).await.unwrap_err();
+
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.message, message);
+    }
+
+    #[tokio::test]
+    async fn range_header_replaces_limit_and_offset() {
+        let request = |uri: &str| {
+            let request = Request::builder().uri(uri).header("range", "heroes=10-14");
+            request.body(()).unwrap().into_parts().0
+        };
+        let config = Arc::new(Config::default());
+
+        let query = HeroQuery::from_request_parts(&mut request("/"), &config).await;
+        let pagination = Pagination {
+            limit: Some(5),
+            offset: 10,
+        };
+        assert_eq!(query.unwrap().pagination, pagination);
+
+        let query = HeroQuery::from_request_parts(&mut request("/?limit=5"), &config).await;
+        assert_eq!(query.unwrap_err().status, StatusCode::BAD_REQUEST);
+    }
+
+    #[rstest]
+    #[case("/?sort=-id&name=W", None)]
+    #[case("/?q=7", None)]
+    #[case(
+        "/?sort=name,updated_at",
+        Some("heroes can't be sorted by updated_at here")
+    )]
+    #[case("/?tag=villain", Some("heroes can't be filtered by tags here"))]
+    #[case(
+        "/?updated_at_lte=10",
+        Some("heroes can't be filtered by updated_at here")
+    )]
+    #[tokio::test]
+    async fn only_allowed_fields_are_queried(#[case] uri: &str, #[case] error: Option<&str>) {
+        let config = Config {
+            query_fields: vec![QueryField::Id, QueryField::Name],
+            ..Default::default()
+        };
+        let (mut parts, ()) = Request::builder().uri(uri).body(()).unwrap().into_parts();
+
+        let query = HeroQuery::from_request_parts(&mut parts, &Arc::new(config)).await;
+
+        assert_eq!(query.err().map(|error| error.message).as_deref(), error);
+    }
+
+    #[tokio::test]
+    async fn too_many_values_are_a_bad_request() {
+        let config = Config::default();
+        let tags = |count: usize| -> String {
+            (0..count)
+                .map(|tag| format!("tag=t{}", tag))
+                .collect::<Vec<_>>()
+                .join("&")
+        };
+
+        let accepted = extract(&format!("/?{}", tags(config.max_param_values))).await;
+        assert!(accepted.is_ok());
+
+        let error = extract(&format!("/?{}", tags(config.max_param_values + 1)))
+            .await
+            .unwrap_err();
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.message, "at most 20 tag parameters are accepted");
+    }
+
+    #[rstest]
+    #[case("/?name=Wonder&name=Dead", "name must be given at most once")]
+    #[case("/?limit=10&limit=20", "limit must be given at most once")]
+    #[case("/?sort=name&tag=a&sort=-id", "sort must be given at most once")]
+    #[case("/?power_gte=1&power_gte=2", "power_gte must be given at most once")]
+    #[tokio::test]
+    async fn repeated_singular_params_are_a_bad_request(#[case] uri: &str, #[case] message: &str) {
+        let error = extract(uri).await.unwrap_err();
+
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.message, message);
+    }
+
+    #[tokio::test]
+    async fn repeated_multi_valued_params_are_accepted() {
+        let query = extract("/?tag=villain&tag=mercenary&limit=10")
+            .await
+            .unwrap();
+
+        assert_eq!(query.tags, ["villain", "mercenary"]);
+    }
+
+    #[tokio::test]
+    async fn malformed_params_are_a_bad_request() {
+        let error = extract("/?limit=ten&tag_mode=some&shape=list")
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.error, "bad_request");
+    }
+}
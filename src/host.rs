@@ -0,0 +1,99 @@
+use crate::{config::Config, error::ApiError};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, uri::Authority, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::Arc;
+
+/// Middleware answering `421` to requests for a host missing from `ALLOWED_HOSTS`
+///
+/// The host is the `Host` header, or the authority of the uri for HTTP/2 requests which
+/// have none. An allowed value without a port allows the host on every port. Requests
+/// naming no host at all are refused too; the check is skipped when the list is empty.
+pub async fn allowed_hosts(
+    State(config): State<Arc<Config>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    if config.allowed_hosts.is_empty() {
+        return next.run(request).await;
+    }
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Authority>().ok())
+        .or_else(|| request.uri().authority().cloned());
+    let allowed = host.is_some_and(|host| {
+        config.allowed_hosts.iter().any(|allowed| {
+            allowed.eq_ignore_ascii_case(host.as_str()) || allowed.eq_ignore_ascii_case(host.host())
+        })
+    });
+    if !allowed {
+        return ApiError::new(
+            StatusCode::MISDIRECTED_REQUEST,
+            "misdirected_request",
+            "requests to this host aren't served here",
+        )
+        .into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use rstest::rstest;
+    use tower::ServiceExt;
+
+    async fn status_for(allowed: &[&str], host: Option<&str>) -> StatusCode {
+        let config = Config {
+            allowed_hosts: allowed.iter().map(|host| host.to_string()).collect(),
+            ..Default::default()
+        };
+        let app = Router::new().route("/", get(|| async { "Storm" })).layer(
+            middleware::from_fn_with_state(Arc::new(config), allowed_hosts),
+        );
+        let mut request = Request::builder().uri("/");
+        if let Some(host) = host {
+            request = request.header(header::HOST, host);
+        }
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        response.status()
+    }
+
+    #[rstest]
+    #[case(Some("heroes.example"))]
+    #[case(Some("Heroes.Example:8080"))]
+    #[case(Some("localhost:3000"))]
+    #[tokio::test]
+    async fn allowed_hosts_are_served(#[case] host: Option<&str>) {
+        let status = status_for(&["heroes.example", "localhost:3000"], host).await;
+
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[rstest]
+    #[case(Some("evil.example"))]
+    #[case(Some("localhost:8080"))] // only port 3000 is allowed
+    #[case(None)]
+    #[tokio::test]
+    async fn other_hosts_are_misdirected(#[case] host: Option<&str>) {
+        let status = status_for(&["heroes.example", "localhost:3000"], host).await;
+
+        assert_eq!(status, StatusCode::MISDIRECTED_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn any_host_is_served_without_an_allowlist() {
+        assert_eq!(status_for(&[], Some("evil.example")).await, StatusCode::OK);
+        assert_eq!(status_for(&[], None).await, StatusCode::OK);
+    }
+}
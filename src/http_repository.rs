@@ -0,0 +1,372 @@
+use crate::{
+    merge_patch, tenant, DataAccessError, DeletionReport, Hero, HeroChange, HeroContext,
+    HeroPayload, HeroesRepositoryTrait, TagChanges, UpsertReport, ViewCount,
+};
+use axum::async_trait;
+use axum::http::{header, request, Method, Request};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use hyper::{body::Bytes, client::HttpConnector, Body, Client};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::time;
+
+/// Prefix of the heroes routes of the upstream api
+const HEROES_PATH: &str = "/api/v1/heroes/";
+
+/// Repository calling the heroes api of an upstream instance of this service, for gateways
+///
+/// Failures are translated back from statuses by `DataAccessError::from_status`. Calls the
+/// upstream doesn't answer within `timeout`, or which can't even connect, fail with
+/// `Unavailable`. The tenant of the
+/// request is passed along in `X-Tenant-Id`.
+///
+/// Listings are capped by the `MAX_RESULTS` of the upstream, and its dataset can't be
+/// replaced from here. Only plain `http` upstreams are supported: no TLS is compiled in.
+#[derive(Clone)]
+pub struct HttpHeroesRepository {
+    client: Client<HttpConnector>,
+    /// like `http://heroes:8080`, without a trailing `/`
+    base_url: String,
+    timeout: Duration,
+}
+
+impl HttpHeroesRepository {
+    pub fn new(base_url: &str, timeout: Duration) -> Self {
+        HttpHeroesRepository {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            timeout,
+        }
+    }
+
+    /// Request for `method path` of the upstream, on behalf of the current tenant
+    fn request(&self, method: Method, path: &str) -> request::Builder {
+        let request = Request::builder()
+            .method(method)
+            .uri(format!("{}{}", self.base_url, path));
+        match tenant::current() {
+            Some(tenant) => request.header(tenant::TENANT_HEADER, tenant),
+            None => request,
+        }
+    }
+
+    /// Body of the successful answer of the upstream to `request`
+    async fn send(
+        &self,
+        request: request::Builder,
+        body: Option<Value>,
+    ) -> Result<Bytes, DataAccessError> {
+        let typed = request
+            .headers_ref()
+            .is_some_and(|headers| headers.contains_key(header::CONTENT_TYPE));
+        let request = match body {
+            Some(body) if typed => request.body(Body::from(body.to_string())),
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .map_err(|_| DataAccessError::OtherError)?;
+
+        let exchange = async {
+            let response = self.client.request(request).await?;
+            let status = response.status();
+            let body = hyper::body::to_bytes(response.into_body()).await?;
+            Ok::<_, hyper::Error>((status, body))
+        };
+        match time::timeout(self.timeout, exchange).await {
+            Ok(Ok((status, body))) if status.is_success() => Ok(body),
+            Ok(Ok((status, _))) => Err(DataAccessError::from_status(status)),
+            Ok(Err(error)) if error.is_connect() => {
+                tracing::warn!(%error, "can't connect to the upstream heroes api");
+                Err(DataAccessError::Unavailable)
+            }
+            Ok(Err(error)) => {
+                tracing::warn!(%error, "upstream heroes api call failed");
+                Err(DataAccessError::TechnicalError)
+            }
+            Err(_) => Err(DataAccessError::Unavailable),
+        }
+    }
+
+    /// The successful answer of the upstream to `request`, parsed as json
+    async fn call<T: DeserializeOwned>(
+        &self,
+        request: request::Builder,
+        body: Option<Value>,
+    ) -> Result<T, DataAccessError> {
+        let body = self.send(request, body).await?;
+        serde_json::from_slice(&body).map_err(|_| DataAccessError::TechnicalError)
+    }
+
+    /// Heroes of a listing, which the upstream answers with `404` when empty
+    async fn list(&self, query: &[(&str, &str)]) -> Result<Vec<Hero>, DataAccessError> {
+        let query = serde_urlencoded::to_string(query).map_err(|_| DataAccessError::OtherError)?;
+        let path = format!("{}?{}", HEROES_PATH, query);
+        match self.call(self.request(Method::GET, &path), None).await {
+            Err(DataAccessError::NotFound) => Ok(vec![]),
+            result => result,
+        }
+    }
+}
+
+/// Path of the hero `id` in the upstream api, followed by `rest`
+fn hero_path(id: &str, rest: &str) -> String {
+    let mut path = HEROES_PATH.to_string();
+    for byte in id.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                path.push(byte as char)
+            }
+            byte => path.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    path.push_str(rest);
+    path
+}
+
+fn json(value: impl serde::Serialize) -> Result<Option<Value>, DataAccessError> {
+    serde_json::to_value(value)
+        .map(Some)
+        .map_err(|_| DataAccessError::OtherError)
+}
+
+#[async_trait]
+impl HeroesRepositoryTrait for HttpHeroesRepository {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.list(&[("name", name)]).await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.call(self.request(Method::GET, &hero_path(id, "")), None)
+            .await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.call(self.request(Method::POST, HEROES_PATH), json(hero)?)
+            .await
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        let request = self
+            .request(Method::POST, HEROES_PATH)
+            .header(header::IF_NONE_MATCH, "*");
+        let body = serde_json::json!({ "id": id, "name": hero.name });
+        self.call(request, Some(body)).await
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.call(self.request(Method::PUT, &hero_path(id, "")), json(hero)?)
+            .await
+    }
+
+    /// The upstream answers deletions without a body, so the hero is read beforehand
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        let hero = self.get_by_id(id).await?;
+        self.send(self.request(Method::DELETE, &hero_path(id, "")), None)
+            .await?;
+        Ok(hero)
+    }
+
+    /// The upstream only answers how many heroes it deleted, so they're listed beforehand
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        let heroes = match self.get_by_name(name).await {
+            Err(DataAccessError::NotFound) => vec![],
+            heroes => heroes?,
+        };
+        let query = serde_urlencoded::to_string([("name", name), ("confirm", "true")])
+            .map_err(|_| DataAccessError::OtherError)?;
+        let path = format!("{}?{}", HEROES_PATH, query);
+        let _: DeletionReport = self.call(self.request(Method::DELETE, &path), None).await?;
+        Ok(heroes)
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        let repo = self.clone();
+        let heroes = async move {
+            let heroes = repo.get_by_name("%").await?;
+            Ok(stream::iter(heroes.into_iter().map(Ok)))
+        };
+        stream::once(heroes).try_flatten().boxed()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.list(&[("q", term)]).await
+    }
+
+    async fn replace_all(&self, _heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        // the upstream reload endpoint is reserved to its admins
+        Err(DataAccessError::OtherError)
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        let path = format!("{}batch", HEROES_PATH);
+        self.call(self.request(Method::PUT, &path), json(heroes)?)
+            .await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.send(self.request(Method::GET, "/health/ready"), None)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        self.call(
+            self.request(Method::PATCH, &hero_path(id, "/tags")),
+            json(changes)?,
+        )
+        .await
+    }
+
+    /// A single merge patch, which the upstream answers with `304` when it changes nothing
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        let request = self
+            .request(Method::PATCH, &hero_path(id, ""))
+            .header(header::CONTENT_TYPE, merge_patch::CONTENT_TYPE);
+        let patch = serde_json::json!({
+            "name": hero.name,
+            "power_level": hero.power_level,
+            "tags": tags,
+        });
+        self.call(request, Some(patch)).await
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        let response: ViewCount = self
+            .call(self.request(Method::POST, &hero_path(id, "/view")), None)
+            .await?;
+        Ok(response.views)
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        let path = format!("{}changes?since={}", HEROES_PATH, since);
+        self.call(self.request(Method::GET, &path), None).await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.call(self.request(Method::GET, &hero_path(id, "/context")), None)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HeroName;
+    use axum::http::StatusCode;
+    use axum::{extract::Path, response::IntoResponse, routing::get, Json, Router};
+    use rstest::rstest;
+    use std::net::{SocketAddr, TcpListener};
+
+    /// Upstream answering `GET /api/v1/heroes/{status}` with that status, `slow` after a while
+    async fn get_hero(Path(id): Path<String>) -> axum::response::Response {
+        match id.as_str() {
+            "slow" => {
+                time::sleep(Duration::from_secs(5)).await;
+                StatusCode::OK.into_response()
+            }
+            "7" => Json(Hero {
+                id: id.clone(),
+                name: HeroName::new("Storm").unwrap(),
+                ..Default::default()
+            })
+            .into_response(),
+            status => StatusCode::from_bytes(status.as_bytes())
+                .unwrap()
+                .into_response(),
+        }
+    }
+
+    /// Upstream applying a merge patch the way the service does, refusing other bodies
+    async fn patch_hero(
+        Path(id): Path<String>,
+        headers: axum::http::HeaderMap,
+        Json(patch): Json<serde_json::Value>,
+    ) -> axum::response::Response {
+        if headers[header::CONTENT_TYPE] != merge_patch::CONTENT_TYPE {
+            return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+        }
+        Json(Hero {
+            id,
+            name: HeroName::new(patch["name"].as_str().unwrap()).unwrap(),
+            tags: serde_json::from_value(patch["tags"].clone()).unwrap(),
+            ..Default::default()
+        })
+        .into_response()
+    }
+
+    fn upstream() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/api/v1/heroes/:id", get(get_hero).patch(patch_hero));
+        let server = axum::Server::from_tcp(listener).unwrap();
+        tokio::spawn(server.serve(app.into_make_service()));
+        addr
+    }
+
+    fn repository(addr: SocketAddr) -> HttpHeroesRepository {
+        HttpHeroesRepository::new(&format!("http://{}/", addr), Duration::from_millis(200))
+    }
+
+    #[tokio::test]
+    async fn upstream_heroes_are_returned() {
+        let hero = repository(upstream()).get_by_id("7").await.unwrap();
+
+        assert_eq!(hero.name.as_str(), "Storm");
+    }
+
+    #[tokio::test]
+    async fn name_and_tags_are_updated_with_a_single_merge_patch() {
+        let hero = HeroPayload {
+            name: HeroName::new("Ororo").unwrap(),
+            power_level: 0,
+        };
+
+        let updated = repository(upstream())
+            .update_with_tags("7", hero, vec!["x-men".to_string()])
+            .await
+            .unwrap();
+
+        assert_eq!(updated.name.as_str(), "Ororo");
+        assert_eq!(updated.tags, ["x-men"]);
+    }
+
+    #[rstest]
+    #[case("404", "NotFound")]
+    #[case("410", "Gone")]
+    #[case("500", "TechnicalError")]
+    #[case("503", "Unavailable")]
+    #[case("400", "OtherError")]
+    #[case("slow", "Unavailable")]
+    #[tokio::test]
+    async fn upstream_failures_are_translated(#[case] id: &str, #[case] expected: &str) {
+        let error = repository(upstream()).get_by_id(id).await.unwrap_err();
+
+        assert_eq!(format!("{:?}", error), expected);
+    }
+
+    #[tokio::test]
+    async fn unreachable_upstream_is_unavailable() {
+        // bound then released, so nothing listens there
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let error = repository(addr).get_by_id("7").await.unwrap_err();
+
+        assert!(matches!(error, DataAccessError::Unavailable));
+    }
+
+    #[test]
+    fn ids_are_escaped_in_paths() {
+        assert_eq!(hero_path("a b/c", "/tags"), "/api/v1/heroes/a%20b%2Fc/tags");
+    }
+}
@@ -0,0 +1,202 @@
+use axum::{
+    body::Body,
+    http::{header, Request},
+    middleware::Next,
+    response::Response,
+};
+
+/// Languages error messages are written in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    French,
+}
+
+impl Language {
+    /// Language of a tag like `fr` or `fr-CA`, going by its primary subtag
+    fn from_tag(tag: &str) -> Option<Language> {
+        let primary = tag.split('-').next().unwrap_or_default();
+        if primary.eq_ignore_ascii_case("en") {
+            Some(Language::English)
+        } else if primary.eq_ignore_ascii_case("fr") {
+            Some(Language::French)
+        } else {
+            None
+        }
+    }
+}
+
+/// English messages and their translations; messages left out, like the ones quoting the
+/// request, are sent in English whatever the language
+const CATALOG: &[(&str, &str)] = &[
+    ("hero not found", "héros introuvable"),
+    ("hero was deleted", "ce héros a été supprimé"),
+    (
+        "the service is in read-only mode, heroes can't be changed for now",
+        "le service est en lecture seule, les héros ne peuvent pas être modifiés pour le moment",
+    ),
+    (
+        "the quota of stored heroes is reached, delete some before adding others",
+        "le quota de héros est atteint, supprimez-en avant d'en ajouter d'autres",
+    ),
+    (
+        "no more tenants are accepted, use an existing one",
+        "plus aucun tenant n'est accepté, utilisez-en un existant",
+    ),
+    (
+        "heroes can't be reached for now, try again later",
+        "les héros sont inaccessibles pour le moment, réessayez plus tard",
+    ),
+    (
+        "a hero with this id exists already",
+        "un héros a déjà cet id",
+    ),
+    (
+        "the hero is already in this state",
+        "le héros est déjà dans cet état",
+    ),
+    (
+        "unexpected error while accessing heroes",
+        "erreur inattendue en accédant aux héros",
+    ),
+    (
+        "name filter must not be empty",
+        "le filtre de nom ne doit pas être vide",
+    ),
+    (
+        "tags must not be blank",
+        "les tags ne doivent pas être vides",
+    ),
+    (
+        "deleting heroes by name needs confirm=true",
+        "supprimer des héros par nom nécessite confirm=true",
+    ),
+    (
+        "limit must be greater than 0",
+        "limit doit être supérieur à 0",
+    ),
+    ("offset requires a limit", "offset nécessite un limit"),
+    (
+        "offset must not be negative",
+        "offset ne doit pas être négatif",
+    ),
+    (
+        "the X-Tenant-Id header is required",
+        "l'en-tête X-Tenant-Id est obligatoire",
+    ),
+    (
+        "a valid admin bearer token is required",
+        "un jeton d'administration valide est requis",
+    ),
+    (
+        "requests to this host aren't served here",
+        "les requêtes vers cet hôte ne sont pas servies ici",
+    ),
+    (
+        "too many requests, slow down",
+        "trop de requêtes, ralentissez",
+    ),
+    (
+        "too many requests in progress, try again later",
+        "trop de requêtes en cours, réessayez plus tard",
+    ),
+    ("at least one id is required", "au moins un id est requis"),
+];
+
+tokio::task_local! {
+    static LANGUAGE: Language;
+}
+
+/// Language of the request being handled by the current task, English outside of requests
+pub fn current() -> Language {
+    LANGUAGE.try_with(|language| *language).unwrap_or_default()
+}
+
+/// `message` in the language of the current request, when the catalog has it
+pub fn localize(message: String) -> String {
+    let language = current();
+    if language == Language::English {
+        return message;
+    }
+    CATALOG
+        .iter()
+        .find(|(english, _)| *english == message)
+        .map_or(message, |(_, french)| french.to_string())
+}
+
+/// Preferred supported language of an `Accept-Language` header, English when none is
+///
+/// Ranges are weighed by their `q`, the first one winning ties; `*` and languages without
+/// a catalog are skipped.
+pub fn negotiate(accept_language: &str) -> Language {
+    let mut best: Option<(f32, Language)> = None;
+    for range in accept_language.split(',') {
+        let mut parts = range.split(';').map(str::trim);
+        let tag = parts.next().unwrap_or_default();
+        let quality = match parts.find_map(|part| part.strip_prefix("q=")) {
+            Some(quality) => quality.parse().unwrap_or(0.0),
+            None => 1.0,
+        };
+        let Some(language) = Language::from_tag(tag) else {
+            continue;
+        };
+        if quality > 0.0 && best.is_none_or(|(best, _)| quality > best) {
+            best = Some((quality, language));
+        }
+    }
+    best.map_or(Language::English, |(_, language)| language)
+}
+
+/// Middleware making the language negotiated from `Accept-Language` the `current()` one
+/// while the request is handled
+pub async fn language(request: Request<Body>, next: Next<Body>) -> Response {
+    let language = request
+        .headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(Language::English, negotiate);
+    LANGUAGE.scope(language, next.run(request)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("fr", Language::French)]
+    #[case("fr-CA, en;q=0.8", Language::French)]
+    #[case("en-US,fr;q=0.9", Language::English)]
+    #[case("de, fr;q=0.5", Language::French)]
+    #[case("fr;q=0, en;q=0.1", Language::English)]
+    #[case("de, *", Language::English)]
+    #[case("", Language::English)]
+    fn preferred_supported_language_is_negotiated(
+        #[case] accept_language: &str,
+        #[case] expected: Language,
+    ) {
+        assert_eq!(negotiate(accept_language), expected);
+    }
+
+    #[tokio::test]
+    async fn messages_follow_the_current_language() {
+        let localize_in =
+            |language| LANGUAGE.scope(language, async { localize("hero not found".to_string()) });
+
+        assert_eq!(localize_in(Language::French).await, "héros introuvable");
+        assert_eq!(localize_in(Language::English).await, "hero not found");
+        assert_eq!(localize("hero not found".to_string()), "hero not found");
+    }
+
+    #[tokio::test]
+    async fn messages_missing_from_the_catalog_stay_in_english() {
+        let message = LANGUAGE
+            .scope(Language::French, async {
+                localize("no heroes match filter 'Bat%'".to_string())
+            })
+            .await;
+
+        assert_eq!(message, "no heroes match filter 'Bat%'");
+    }
+}
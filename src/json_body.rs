@@ -0,0 +1,129 @@
+use crate::error::ApiError;
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::FromRequest,
+    http::{header, HeaderMap, Request},
+    response::IntoResponse,
+    Json,
+};
+use serde::de::DeserializeOwned;
+
+/// Body of a request sent as JSON, which must be UTF-8
+///
+/// A `charset` other than `utf-8` in `Content-Type`, or bytes which aren't UTF-8, are a
+/// `400` telling so, instead of a serde error pointing somewhere in the body. Valid bodies
+/// are then extracted like `axum::Json` would, with the same rejections.
+pub struct JsonBody<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned, S: Send + Sync> FromRequest<S, Body> for JsonBody<T> {
+    type Rejection = axum::response::Response;
+
+    async fn from_request(request: Request<Body>, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(charset) = charset(request.headers()) {
+            if !charset.eq_ignore_ascii_case("utf-8") {
+                return Err(ApiError::bad_request(format!(
+                    "JSON bodies must be UTF-8, not {}",
+                    charset
+                ))
+                .into_response());
+            }
+        }
+        let headers = request.headers().clone();
+        let body = Bytes::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        if let Err(error) = std::str::from_utf8(&body) {
+            return Err(ApiError::bad_request(format!(
+                "request body is not valid UTF-8: invalid byte at offset {}",
+                error.valid_up_to()
+            ))
+            .into_response());
+        }
+        // `Json` only looks at the `Content-Type` besides the body
+        let mut request = Request::new(Body::from(body));
+        *request.headers_mut() = headers;
+        let Json(value) = Json::from_request(request, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        Ok(JsonBody(value))
+    }
+}
+
+/// `charset` parameter of the `Content-Type` header, unquoted
+fn charset(headers: &HeaderMap) -> Option<String> {
+    let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+    content_type.split(';').skip(1).find_map(|parameter| {
+        let (name, value) = parameter.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::StatusCode, routing::post, Router};
+    use rstest::rstest;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    async fn post_json(content_type: &str, body: &'static [u8]) -> (StatusCode, String) {
+        let app = Router::new().route(
+            "/",
+            post(|JsonBody(value): JsonBody<Value>| async move { value.to_string() }),
+        );
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header(header::CONTENT_TYPE, content_type)
+            .body(Body::from(body))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[rstest]
+    #[case("application/json")]
+    #[case("application/json; charset=utf-8")]
+    #[case("application/json; charset=\"UTF-8\"")]
+    #[tokio::test]
+    async fn utf8_bodies_are_extracted(#[case] content_type: &str) {
+        let (status, body) = post_json(content_type, "{\"name\":\"Zoë\"}".as_bytes()).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"name":"Zoë"}"#);
+    }
+
+    #[tokio::test]
+    async fn invalid_utf8_is_a_bad_request() {
+        // "Zoë" in latin-1
+        let (status, body) = post_json("application/json", b"{\"name\":\"Zo\xEB\"}").await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("request body is not valid UTF-8: invalid byte at offset 11"));
+    }
+
+    #[tokio::test]
+    async fn other_charsets_are_a_bad_request() {
+        let (status, body) = post_json(
+            "application/json; charset=iso-8859-1",
+            b"{\"name\":\"Storm\"}",
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("JSON bodies must be UTF-8, not iso-8859-1"));
+    }
+
+    #[tokio::test]
+    async fn other_rejections_are_those_of_axum() {
+        let (status, _) = post_json("text/plain", b"{}").await;
+
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
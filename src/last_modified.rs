@@ -0,0 +1,46 @@
+use crate::Hero;
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Most recent `updated_at` of `heroes`, truncated to the second resolution of http dates
+///
+/// Removing a hero doesn't make a listing more recent, so a client holding a copy of a
+/// listing may be told it's unchanged after a delete until another hero of it is written.
+pub fn of(heroes: &[Hero]) -> Option<SystemTime> {
+    heroes
+        .iter()
+        .filter_map(|hero| hero.updated_at)
+        .max()
+        .map(|millis| UNIX_EPOCH + Duration::from_secs(millis / 1_000))
+}
+
+/// Answer `304` if the client's copy, dated by `If-Modified-Since`, is still current;
+/// otherwise send `response` with its `Last-Modified` date
+///
+/// Unparsable `If-Modified-Since` dates are ignored, as the http spec requires.
+pub fn conditional(
+    request_headers: &HeaderMap,
+    last_modified: Option<SystemTime>,
+    response: impl IntoResponse,
+) -> Response {
+    let Some(last_modified) = last_modified else {
+        return response.into_response();
+    };
+
+    let if_modified_since = request_headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok());
+    let mut response = match if_modified_since {
+        Some(since) if last_modified <= since => StatusCode::NOT_MODIFIED.into_response(),
+        _ => response.into_response(),
+    };
+
+    if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(last_modified)) {
+        response.headers_mut().insert(header::LAST_MODIFIED, value);
+    }
+    response
+}
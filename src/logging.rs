@@ -0,0 +1,249 @@
+use axum::http::HeaderMap;
+use std::borrow::Cow;
+use std::fmt::{self, Write as _};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{span, Event, Level, Metadata, Subscriber};
+
+/// Minimal `tracing` subscriber printing each event as one line:
+/// `LEVEL target: message key=value ...`
+///
+/// Spans are not tracked; only events are written.
+pub struct LineSubscriber {
+    max_level: Level,
+    sink: Sink,
+    next_span_id: AtomicU64,
+}
+
+enum Sink {
+    Stderr,
+    Capture(Arc<Mutex<Vec<String>>>),
+}
+
+impl LineSubscriber {
+    fn new(max_level: Level, sink: Sink) -> Self {
+        LineSubscriber {
+            max_level,
+            sink,
+            next_span_id: AtomicU64::new(1),
+        }
+    }
+}
+
+/// Install the stderr subscriber for the whole process
+pub fn init(max_level: Level) {
+    let subscriber = LineSubscriber::new(max_level, Sink::Stderr);
+    if tracing::subscriber::set_global_default(subscriber).is_err() {
+        eprintln!("a tracing subscriber is already installed");
+    }
+}
+
+/// Lines logged on the current thread while the returned guard is alive
+#[derive(Clone, Default)]
+pub struct CapturedLogs {
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl CapturedLogs {
+    pub fn lines(&self) -> Vec<String> {
+        self.lines
+            .lock()
+            .map(|lines| lines.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn contains(&self, needle: &str) -> bool {
+        self.lines().iter().any(|line| line.contains(needle))
+    }
+}
+
+/// Capture every event logged on the current thread (tests run on a current-thread runtime)
+pub fn capture() -> (CapturedLogs, tracing::subscriber::DefaultGuard) {
+    let logs = CapturedLogs::default();
+    let subscriber = LineSubscriber::new(Level::TRACE, Sink::Capture(logs.lines.clone()));
+    let guard = tracing::subscriber::set_default(subscriber);
+    (logs, guard)
+}
+
+/// Escape control characters of a user provided value so it can't forge or break log lines
+///
+/// `"a\nb"` becomes `a\\nb`; values without control characters are returned as is.
+pub fn sanitize(value: &str) -> Cow<'_, str> {
+    if !value.chars().any(char::is_control) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 8);
+    for c in value.chars() {
+        if c.is_control() {
+            escaped.extend(c.escape_default());
+        } else {
+            escaped.push(c);
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Headers and query parameters never logged verbatim, whatever `LOG_REDACT` says
+pub const ALWAYS_REDACTED: &[&str] = &["authorization", "cookie"];
+
+fn is_sensitive(name: &str, sensitive: &[String]) -> bool {
+    ALWAYS_REDACTED
+        .iter()
+        .copied()
+        .chain(sensitive.iter().map(String::as_str))
+        .any(|sensitive| sensitive.eq_ignore_ascii_case(name))
+}
+
+/// Query string fit for logs: values of sensitive parameters become `***`, the rest is
+/// sanitized
+pub fn redact_query(query: &str, sensitive: &[String]) -> String {
+    let pairs = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if is_sensitive(name, sensitive) => format!("{}=***", name),
+            _ => pair.to_string(),
+        });
+    sanitize(&pairs.collect::<Vec<_>>().join("&")).into_owned()
+}
+
+/// `name: value` list of request headers fit for logs, values of sensitive ones as `***`
+pub fn redact_headers(headers: &HeaderMap, sensitive: &[String]) -> String {
+    let lines = headers.iter().map(|(name, value)| {
+        let value = if is_sensitive(name.as_str(), sensitive) {
+            "***"
+        } else {
+            value.to_str().unwrap_or("<binary>")
+        };
+        format!("{}: {}", name, value)
+    });
+    sanitize(&lines.collect::<Vec<_>>().join("; ")).into_owned()
+}
+
+/// Collects the fields of an event, the `message` field first
+#[derive(Default)]
+struct LineVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for LineVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            // messages may be formatted from user input
+            let message = format!("{:?}", value);
+            self.message.push_str(&sanitize(&message));
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+impl Subscriber for LineSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= &self.max_level
+    }
+
+    fn new_span(&self, _span: &span::Attributes<'_>) -> span::Id {
+        span::Id::from_u64(self.next_span_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut visitor = LineVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let line = format!(
+            "{} {}: {}{}",
+            metadata.level(),
+            metadata.target(),
+            visitor.message,
+            visitor.fields
+        );
+
+        match &self.sink {
+            Sink::Stderr => eprintln!("{}", line),
+            Sink::Capture(lines) => {
+                if let Ok(mut lines) = lines.lock() {
+                    lines.push(line);
+                }
+            }
+        }
+    }
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_formatted_as_lines() {
+        let (logs, _guard) = capture();
+
+        tracing::warn!(target: "heroes", method = "get_by_name", "slow call");
+
+        assert_eq!(
+            logs.lines(),
+            vec!["WARN heroes: slow call method=\"get_by_name\"".to_string()]
+        );
+    }
+
+    #[test]
+    fn control_characters_are_escaped() {
+        assert_eq!(sanitize("Wonder%"), "Wonder%");
+        assert_eq!(
+            sanitize("x\nINFO forged: line\r\u{1b}[31m"),
+            "x\\nINFO forged: line\\r\\u{1b}[31m"
+        );
+    }
+
+    #[test]
+    fn sensitive_query_values_are_redacted() {
+        let sensitive = vec!["api_key".to_string()];
+
+        assert_eq!(
+            redact_query("name=Wonder&API_KEY=s3cr3t&cookie=c", &sensitive),
+            "name=Wonder&API_KEY=***&cookie=***"
+        );
+    }
+
+    #[test]
+    fn sensitive_headers_are_redacted() {
+        let mut headers = HeaderMap::new();
+        headers.insert("accept", "application/json".parse().unwrap());
+        headers.insert("authorization", "Bearer s3cr3t".parse().unwrap());
+        headers.insert("x-api-key", "k3y".parse().unwrap());
+
+        assert_eq!(
+            redact_headers(&headers, &["X-Api-Key".to_string()]),
+            "accept: application/json; authorization: ***; x-api-key: ***"
+        );
+    }
+
+    #[test]
+    fn formatted_message_stays_on_one_line() {
+        let (logs, _guard) = capture();
+        let input = "Wonder\nINFO heroes: forged";
+
+        tracing::info!("looking for {}", input);
+
+        assert_eq!(logs.lines().len(), 1);
+        assert!(!logs.lines()[0].contains('\n'));
+    }
+}
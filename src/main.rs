@@ -1,16 +1,123 @@
 #![allow(dead_code)]
+mod access_log;
+mod api_version;
+mod audit;
+mod auth;
+mod cache;
+mod cache_control;
+mod circuit_breaker;
+mod coalescing;
+mod computed;
+mod concurrency;
+mod config;
+mod cors;
+mod csv;
+mod cursor;
+mod deadline;
+mod deprecation;
+mod digest;
+mod error;
+mod events;
+mod fallback;
+mod feature;
+mod field_access;
+mod flag;
+#[cfg(test)]
+mod fn_repository;
+mod format;
+mod health;
+mod hero_name;
+mod hero_query;
+mod host;
+mod http_repository;
+mod i18n;
+mod json_body;
+mod last_modified;
+mod logging;
+mod merge_patch;
+mod metered;
+mod metrics;
+mod name_regex;
+mod numeric_range;
+mod pagination;
+mod pretty;
+mod quota;
+mod rate_limit;
+mod read_only;
+mod read_your_writes;
+mod recording;
+mod reload;
+mod request_id;
+mod response_size;
+mod retry;
+mod routes;
+mod security_headers;
+mod seed;
+mod server;
+mod slow_query;
+mod sort;
+mod task;
+mod tenant;
+mod timeout;
+mod timing;
+mod trace;
+
+use access_log::AccessLog;
+use audit::{AuditAction, AuditedHeroesRepository, DynAuditLog, InMemoryAuditLog};
 use axum::{
     async_trait,
-    extract::{Query, State},
-    http::StatusCode,
-    response::IntoResponse,
-    routing::get,
-    Json, Router,
+    body::{Bytes, StreamBody},
+    extract::{rejection::QueryRejection, FromRef, OriginalUri, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{get, patch, post},
+    Extension, Router,
 };
-use axum_macros::debug_handler;
+// `debug_handler` only checks the handlers of debug builds: in release it expands to the bare
+// function, so it costs release builds nothing
+use axum_macros::{debug_handler, FromRef};
+use cache::CachingHeroesRepository;
+use circuit_breaker::{CircuitBreaker, CircuitBreakerHeroesRepository};
+use coalescing::CoalescingHeroesRepository;
+use computed::{ComputedFields, Include};
+use concurrency::ConcurrencyLimit;
+use config::Config;
+use csv::CsvBody;
+use deadline::Deadline;
+use error::{ApiError, StatusMapper, Suggested};
+use events::HeroEvents;
+use feature::Feature;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use hero_name::HeroName;
+use hero_query::{HeroFilter, HeroQuery, NumericField, Shape, TagMode};
+use http_repository::HttpHeroesRepository;
+use json_body::JsonBody;
+use merge_patch::MergePatch;
+use metered::MeteredHeroesRepository;
+use metrics::AppMetrics;
+use name_regex::NameRegex;
+use numeric_range::NumericRange;
+use pagination::{PageFormat, Pagination};
+use pretty::{Pretty, PrettyJson};
+use quota::QuotaHeroesRepository;
+use rate_limit::{GroupRateLimiter, RateLimiter};
+use read_only::ReadOnlyHeroesRepository;
+use reload::LiveConfig;
+use retry::{RetryBudget, RetryingHeroesRepository};
+use routes::RouteManifest;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use serde_json::Value;
+use slow_query::SlowQueryHeroesRepository;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{net::SocketAddr, sync::Arc};
+use task::BackgroundTasks;
+use tenant::TenantScopedHeroesRepository;
+use timeout::TimeoutHeroesRepository;
+use timing::{Phase, Timing};
 use tokio::time;
 
 #[cfg(test)]
@@ -18,73 +125,925 @@ use mockall::automock;
 
 #[tokio::main]
 async fn main() {
-    let repo: HeroesRepositoryState = Arc::new(HeroesRepository());
+    logging::init(tracing::Level::INFO);
+
+    let config = Config::from_env().expect("invalid configuration");
+    let audit_log: DynAuditLog = Arc::new(InMemoryAuditLog::default());
+    let metrics = Arc::new(AppMetrics::default());
+    // an upstream api keeps the datasets of its tenants apart itself
+    let store: DynHeroesRepository = match &config.upstream_url {
+        Some(url) => Arc::new(HttpHeroesRepository::new(
+            url,
+            Duration::from_millis(config.upstream_timeout_ms),
+        )),
+        None => {
+            let seed = match config.seed_file.as_deref().map(seed::load).transpose() {
+                Ok(seed) => seed,
+                Err(error) => {
+                    tracing::error!("{}", error);
+                    std::process::exit(1);
+                }
+            };
+            // every tenant starts from the seed, or the built-in heroes without one
+            let new_partition = move || match &seed {
+                Some(heroes) => InMemoryHeroesRepository::new(heroes.clone()),
+                None => InMemoryHeroesRepository::default(),
+            };
+            Arc::new(TenantScopedHeroesRepository::new(
+                config.max_tenants,
+                new_partition,
+            ))
+        }
+    };
+    let breaker = config.circuit_breaker_threshold.map(|threshold| {
+        let cooldown = Duration::from_millis(config.circuit_breaker_cooldown_ms);
+        Arc::new(CircuitBreaker::new(threshold, cooldown))
+    });
+    let repo = CachingHeroesRepository::new(
+        CoalescingHeroesRepository::new(
+            AuditedHeroesRepository::new(
+                MeteredHeroesRepository::new(
+                    SlowQueryHeroesRepository::new(
+                        QuotaHeroesRepository::new(
+                            CircuitBreakerHeroesRepository::new(
+                                RetryingHeroesRepository::new(
+                                    TimeoutHeroesRepository::new(
+                                        store,
+                                        config.repository_timeouts_ms.clone(),
+                                    ),
+                                    config.repository_retries,
+                                    RetryBudget::new(
+                                        config.retry_budget,
+                                        Duration::from_secs(config.retry_budget_window_secs),
+                                    ),
+                                ),
+                                breaker.clone(),
+                            ),
+                            config.max_heroes_per_tenant,
+                        ),
+                        Duration::from_millis(config.slow_query_ms),
+                    ),
+                    metrics.clone(),
+                ),
+                audit_log.clone(),
+            ),
+            metrics.clone(),
+        ),
+        config.cache_ttl_ms.map(Duration::from_millis),
+    );
+    let tasks = Arc::new(BackgroundTasks::new(config.critical_task_panic));
+    let (stop_refresh, refresh_stopped) = tokio::sync::oneshot::channel::<()>();
+    let refresh = config.cache_ttl_ms.map(|_| {
+        let interval = Duration::from_millis(config.cache_refresh_interval_ms);
+        repo.spawn_refresh(&tasks, interval, async {
+            let _ = refresh_stopped.await;
+        })
+    });
+    let repo: DynHeroesRepository = if config.read_only {
+        tracing::warn!("read-only mode: every write will be refused");
+        Arc::new(ReadOnlyHeroesRepository::new(repo))
+    } else {
+        Arc::new(repo)
+    };
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
+    let server = server::bind(addr)
+        .map_err(|error| error.to_string())
+        .and_then(|listener| server::build(listener, &config).map_err(|error| error.to_string()));
+    let server = match server {
+        Ok(server) => server,
+        Err(error) => {
+            tracing::error!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    log_startup(addr, &config);
+    let drain = Duration::from_secs(config.shutdown_drain_secs);
 
-    let app = Router::new()
-        .nest("/heroes/", heroes_routes())
-        .with_state(repo);
+    // the server answers from the start, only ready once warmed up
+    let warm_up = Arc::new(if config.warm_up {
+        health::WarmUp::pending()
+    } else {
+        health::WarmUp::default()
+    });
+    if config.warm_up {
+        let (repo, warm_up) = (repo.clone(), warm_up.clone());
+        tasks.spawn("warm_up", async move {
+            warm_up.run(health::warm_up(repo)).await;
+        });
+    }
+    let state = AppState {
+        repo,
+        audit_log,
+        metrics: metrics.clone(),
+        events: Default::default(),
+        started: Default::default(),
+        tasks,
+        warm_up,
+        breaker,
+        config: Arc::new(LiveConfig::new(config)),
+    };
+    let (tasks, config) = (state.tasks.clone(), state.config.clone());
+    tasks.spawn("config_reload", reload::reload_on_hangup(config));
+
+    let app = build_app(state);
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
     println!("Listening on {}", addr);
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+    let shutdown = async {
+        server::shutdown_signal().await;
+        let _ = stop_refresh.send(());
+    };
+    if let Err(error) = server::serve(server, app, shutdown, drain, metrics).await {
+        tracing::error!("server stopped: {}", error);
+        std::process::exit(1);
+    }
+    if let Some(refresh) = refresh {
+        let _ = refresh.await;
+    }
+}
+
+/// One line with the effective settings, secrets redacted, to spot misconfigurations at boot
+fn log_startup(addr: SocketAddr, config: &Config) {
+    tracing::info!(
+        listen = %addr,
+        backend = config.backend(),
+        config = %config.redacted(),
+        "starting"
+    );
+}
+
+/// Assemble the complete application: routes and the middlewares enabled by the configuration
+fn build_app(state: AppState) -> Router {
+    build_app_with(state, Customizations::default())
+}
+
+/// Layer of an application embedding the service, e.g. its own authentication
+type CustomLayer = Box<dyn FnOnce(Router<AppState>) -> Router<AppState> + Send>;
+
+/// Changes to the app built by `build_app_with`, none by default
+#[derive(Default)]
+struct Customizations {
+    /// Picks the status of responses to repository errors, e.g. to answer `NotFound` with
+    /// `200` where missing heroes are expected
+    status_mapper: Option<StatusMapper>,
+    /// Applied to the whole router, inside the request id, i18n, tracing, security headers
+    /// and host checks, so its responses get them too, but outside the rate limits,
+    /// concurrency limit, tenant, CORS and role identification: it sees every request
+    /// before them, including those they would refuse, and its refusals don't count
+    /// against the rate limits. Layers added to the router `build_app` returns run before
+    /// everything instead, request ids included
+    layer: Option<CustomLayer>,
+}
+
+/// `build_app` with the given `customizations`
+fn build_app_with(state: AppState, customizations: Customizations) -> Router {
+    // the middlewares in place are picked once, they read the live configuration themselves
+    let config = state.config.current();
+    let mut manifest = RouteManifest::default();
+    let m = &mut manifest;
+    let cors = &config.cors;
+    let heroes_cors = |routes: Router<AppState>| {
+        if cors.is_enabled() && cors.heroes_only {
+            // outermost, so preflights don't need an api version
+            routes.layer(middleware::from_fn_with_state(state.clone(), cors::cors))
+        } else {
+            routes
+        }
+    };
+    let mut app = Router::new()
+        .route(m.add(&["GET"], "/"), get(get_root))
+        .route(m.add(&["GET"], "/version"), get(get_version))
+        .route(m.add(&["GET"], "/metrics"), get(get_metrics))
+        .route(m.add(&["GET"], "/health"), get(health::live))
+        .route(m.add(&["GET"], "/health/ready"), get(health::ready))
+        .route(m.add(&["GET"], "/health/info"), get(health::info))
+        .nest(
+            deprecation::LEGACY_PREFIX,
+            heroes_cors(
+                m.nest(deprecation::LEGACY_PREFIX, heroes_routes)
+                    .layer(middleware::from_fn_with_state(
+                        state.clone(),
+                        cache_control::cacheable_reads,
+                    ))
+                    .layer(middleware::from_fn_with_state(
+                        state.clone(),
+                        api_version::require_api_version,
+                    ))
+                    .layer(middleware::from_fn_with_state(
+                        state.clone(),
+                        deprecation::legacy_routes,
+                    )),
+            ),
+        )
+        .nest(
+            deprecation::CURRENT_PREFIX,
+            heroes_cors(
+                m.nest(deprecation::CURRENT_PREFIX, heroes_routes)
+                    .layer(middleware::from_fn_with_state(
+                        state.clone(),
+                        cache_control::cacheable_reads,
+                    ))
+                    .layer(middleware::from_fn_with_state(
+                        state.clone(),
+                        api_version::require_api_version,
+                    )),
+            ),
+        )
+        .nest("/admin/", m.nest("/admin/", |m| admin_routes(m, &state)))
+        .nest("/debug/", m.nest("/debug/", |m| debug_routes(m, &state)))
+        // a route layer, to know the route of requests
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            response_size::response_size,
+        ))
+        .layer(middleware::from_fn(cache_control::no_store_by_default))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            field_access::restrict_fields,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::identify_role,
+        ));
+
+    if cors.is_enabled() && !cors.heroes_only {
+        app = app.layer(middleware::from_fn_with_state(state.clone(), cors::cors));
+    }
+
+    app = app
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            tenant::tenant,
+        ))
+        .layer(middleware::from_fn(read_your_writes::session));
+
+    if let Some(max_concurrent) = config.max_concurrent_requests {
+        let limit = ConcurrencyLimit::new(max_concurrent, config.overload_retry_after_secs);
+        app = app.layer(middleware::from_fn_with_state(
+            Arc::new(limit),
+            concurrency::limit_concurrency,
+        ));
+    }
+
+    if let Some(limit) = config.rate_limit_requests {
+        let limiter = RateLimiter::new(limit, Duration::from_secs(config.rate_limit_window_secs));
+        app = app.layer(middleware::from_fn_with_state(
+            Arc::new(limiter),
+            rate_limit::rate_limit,
+        ));
+    }
+
+    let groups = (
+        config.rate_limit_read_requests,
+        config.rate_limit_write_requests,
+    );
+    if groups != (None, None) {
+        let limiter = GroupRateLimiter::new(
+            groups.0,
+            groups.1,
+            Duration::from_secs(config.rate_limit_window_secs),
+        );
+        app = app.layer(middleware::from_fn_with_state(
+            Arc::new(limiter),
+            rate_limit::rate_limit_per_group,
+        ));
+    }
+
+    if let Some(layer) = customizations.layer {
+        app = layer(app);
+    }
+
+    if let Some(mapper) = customizations.status_mapper {
+        app = app.layer(middleware::from_fn_with_state(mapper, error::map_statuses));
+    }
+
+    app = app
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            host::allowed_hosts,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            security_headers::security_headers,
+        ))
+        .layer(middleware::from_fn_with_state(state.clone(), trace::trace))
+        .layer(middleware::from_fn(i18n::language))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            request_id::request_id,
+        ));
+
+    // outermost, to log the responses as sent
+    if let Some(format) = config.access_log {
+        app = app.layer(middleware::from_fn_with_state(
+            Arc::new(AccessLog::stdout(format)),
+            access_log::access_log,
+        ));
+    }
+
+    app.layer(Extension(Arc::new(manifest))).with_state(state)
+}
+
+/// Maintenance endpoints, only reachable with the admin token
+fn admin_routes(m: &mut RouteManifest, state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route(m.add(&["POST"], "/heroes/reload"), post(reload_heroes))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_admin_token,
+        ))
 }
 
-fn heroes_routes() -> Router<DynHeroesRepository> {
-    Router::new().route("/", get(get_heroes))
+/// Troubleshooting endpoints, only reachable with the admin token
+fn debug_routes(m: &mut RouteManifest, state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route(m.add(&["GET"], "/config"), get(get_config))
+        .route(m.add(&["GET"], "/routes"), get(get_routes))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_admin_token,
+        ))
+}
+
+fn heroes_routes(m: &mut RouteManifest) -> Router<AppState> {
+    Router::new()
+        .route(
+            m.add(&["GET", "POST", "DELETE"], "/"),
+            get(get_heroes).post(create_hero).delete(delete_heroes),
+        )
+        .route(m.add(&["POST"], "/validate"), post(validate_hero))
+        .route(m.add(&["GET"], "/page"), get(get_hero_page))
+        .route(
+            m.add(&["GET", "PUT"], "/batch"),
+            get(get_heroes_batch).put(upsert_heroes),
+        )
+        .route(m.add(&["POST"], "/import"), post(import_heroes))
+        .route(
+            m.add(&["GET", "PUT", "PATCH", "DELETE"], "/:id"),
+            get(get_hero)
+                .put(update_hero)
+                .patch(patch_hero)
+                .delete(delete_hero),
+        )
+        .route(m.add(&["GET"], "/export"), get(export_heroes_page))
+        .route(m.add(&["GET"], "/export.csv"), get(export_heroes_csv))
+        .route(m.add(&["GET"], "/events/sse"), get(events::sse))
+        .route(m.add(&["GET"], "/facets/initial"), get(get_initial_facets))
+        .route(m.add(&["GET"], "/stats"), get(get_hero_stats))
+        .route(m.add(&["GET"], "/digest"), get(get_dataset_digest))
+        .route(m.add(&["GET"], "/compare"), get(compare_heroes))
+        .route(m.add(&["GET"], "/changes"), get(get_hero_changes))
+        .route(m.add(&["GET"], "/:id/history"), get(get_hero_history))
+        .route(m.add(&["GET"], "/:id/similar"), get(get_similar_heroes))
+        .route(m.add(&["GET"], "/:id/context"), get(get_hero_context))
+        .route(m.add(&["PATCH"], "/:id/tags"), patch(update_hero_tags))
+        .route(m.add(&["POST"], "/:id/view"), post(record_hero_view))
 }
 // Hero is the model we want to store in the database
-#[derive(Serialize)]
-#[cfg_attr(test, derive(Deserialize, Eq, PartialEq, Debug, Copy, Clone, Default))]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(test, derive(Eq, PartialEq, Default))]
 pub struct Hero {
-    pub id: &'static str,
-    pub name: &'static str,
+    pub id: String,
+    pub name: HeroName,
+    /// milliseconds since the unix epoch of the last write, when the repository tracks it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<u64>,
+    /// categories of the hero, lowercase and sorted, each at most once
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// from 0 to `MAX_POWER_LEVEL`, 0 for heroes stored before it was tracked
+    #[serde(default)]
+    pub power_level: u32,
+}
+
+/// Highest `power_level` of a hero
+pub const MAX_POWER_LEVEL: u32 = 100;
+
+/// Body of create and update requests: a hero without its id
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub struct HeroPayload {
+    pub name: HeroName,
+    #[serde(default)]
+    pub power_level: u32,
+}
+
+impl HeroPayload {
+    /// Reasons why the hero can't be stored, empty when it can
+    fn problems(&self) -> Vec<String> {
+        let mut problems = self.name.problems();
+        if self.power_level > MAX_POWER_LEVEL {
+            problems.push(format!(
+                "power_level must be between 0 and {}",
+                MAX_POWER_LEVEL
+            ));
+        }
+        problems
+    }
+}
+
+/// Body of create requests, whose `id` is only used along with `If-None-Match: *`; without
+/// it, the repository chooses the id
+#[derive(Deserialize, Debug)]
+struct NewHero {
+    id: Option<String>,
+    name: HeroName,
+    #[serde(default)]
+    power_level: u32,
+}
+
+/// Hero of a batch upsert, whose `updated_at` is the repository's to set
+#[derive(Deserialize, Debug)]
+struct BatchHero {
+    id: String,
+    name: HeroName,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    power_level: u32,
+}
+
+/// Body of tag updates: tags to add to and to remove from a hero
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[cfg_attr(test, derive(Eq, PartialEq))]
+pub struct TagChanges {
+    #[serde(default)]
+    pub add: Vec<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// Body of bulk deletes: how many heroes were deleted
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct DeletionReport {
+    pub deleted: u64,
+}
+
+/// Body of CSV imports: how many rows were created, why the others weren't
+#[derive(Serialize, Debug, Default)]
+struct ImportReport {
+    created: u64,
+    failed: Vec<ImportFailure>,
+}
+
+/// A row of a CSV import which wasn't created; row 1 is the one after the header
+#[derive(Serialize, Debug)]
+struct ImportFailure {
+    row: u64,
+    reason: String,
+}
+
+/// Body of view recordings: how many views the hero has had
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ViewCount {
+    pub id: String,
+    pub views: u64,
+}
+
+/// Change applied to a hero, numbered by `seq` in the order changes were applied
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HeroChange {
+    /// greater than the `seq` of every earlier change, the watermark of `get_changes_since`
+    pub seq: u64,
+    pub action: AuditAction,
+    /// the hero as changed, its last version for a delete
+    pub hero: Hero,
+}
+
+/// Outcome of `upsert_many`: how many heroes were new, how many replaced an existing one
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct UpsertReport {
+    pub created: u64,
+    pub updated: u64,
+}
+
+/// Aggregates of the whole dataset; without heroes, counts are 0 and names `null`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct HeroStats {
+    pub total: u64,
+    pub per_tag: BTreeMap<String, u64>,
+    /// name with the most characters, the first one of the dataset on ties
+    pub longest_name: Option<String>,
+    /// name with the fewest characters, the first one of the dataset on ties
+    pub shortest_name: Option<String>,
+}
+
+impl HeroStats {
+    /// Stats of the heroes counted so far and `hero`
+    fn with(mut self, hero: &Hero) -> Self {
+        self.total += 1;
+        for tag in &hero.tags {
+            *self.per_tag.entry(tag.clone()).or_insert(0) += 1;
+        }
+        let length = hero.name.chars().count();
+        if self
+            .longest_name
+            .as_ref()
+            .is_none_or(|longest| length > longest.chars().count())
+        {
+            self.longest_name = Some(hero.name.to_string());
+        }
+        if self
+            .shortest_name
+            .as_ref()
+            .is_none_or(|shortest| length < shortest.chars().count())
+        {
+            self.shortest_name = Some(hero.name.to_string());
+        }
+        self
+    }
+}
+
+/// A hero with its alphabetical neighbors, `None` at either end of the list
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HeroContext {
+    pub prev: Option<Hero>,
+    pub current: Hero,
+    pub next: Option<Hero>,
 }
 
 /// Error that may happen during data access
+#[derive(Serialize, Deserialize, Debug, Clone)]
 enum DataAccessError {
     NotFound,
     TechnicalError,
     OtherError,
+    /// The service is in read-only mode and refuses writes
+    ReadOnly,
+    /// The hero existed but was deleted, unlike `NotFound` ids which never existed
+    Gone,
+    /// The tenant already stores as many heroes as it's allowed to
+    QuotaExceeded,
+    /// The store can't be reached for now, e.g. an upstream api timing out
+    Unavailable,
+    /// A hero already has the id a conditional create asked for
+    AlreadyExists,
+    /// An update would leave the hero as it is, so nothing was written
+    Unchanged,
+    /// The tenant would need a dataset of its own, but as many as allowed exist already
+    TenantLimitReached,
+}
+
+impl IntoResponse for DataAccessError {
+    fn into_response(self) -> Response {
+        ApiError::from(self).into_response()
+    }
 }
 
 #[cfg_attr(test, automock)]
 #[async_trait]
 trait HeroesRepositoryTrait {
+    /// Heroes named `name`, or whose name starts with it when it ends with `%`; an escaped
+    /// `\%` stands for a literal `%`, like with SQL's `LIKE ... ESCAPE '\'`
     async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError>;
+    /// The hero with the given id; `Gone` rather than `NotFound` if it was deleted, when
+    /// the repository keeps track of deletions
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError>;
+    /// Store a new hero, the repository chooses its id
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError>;
+    /// Store a new hero under the id chosen by the caller, `AlreadyExists` when a hero has
+    /// it; checking and storing are one step, so concurrent calls can't both succeed
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError>;
+    /// Replace the hero with the given id and return its new version; `Unchanged` when it
+    /// already has this name and power level, comparing and writing being one step
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError>;
+    /// Remove the hero with the given id and return its last version
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError>;
+    /// Every hero, produced one at a time so large datasets needn't be buffered
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>>;
+    /// Atomically swap the whole dataset for `heroes`
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError>;
+    /// Create or replace heroes by id, in one go; when an id appears several times in
+    /// `heroes`, its last hero wins
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError>;
+    /// The hero with the given id and the ones before and after it in alphabetical order
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError>;
+    /// Add and remove tags of a hero; removing a tag it doesn't have is not an error, but
+    /// changes leaving its tags as they were are `Unchanged`
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError>;
+    /// Add a view to the hero with the given id and return its views so far, in one step
+    /// so concurrent views are all counted
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError>;
+    /// Creates, updates and deletes with a `seq` greater than `since`, in `seq` order; with
+    /// `since` the `seq` of the last change seen, a client stays in sync without timestamps
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError>;
+
+    // The methods below have default implementations built on the ones above, so a minimal
+    // repository can skip them; repositories able to answer them in one query should not.
+
+    /// Number of heroes per (uppercased) first letter of their name, sorted by letter
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        let mut counts = BTreeMap::new();
+        let mut heroes = self.stream_all();
+        while let Some(hero) = heroes.next().await {
+            if let Some(initial) = hero?.name.chars().next() {
+                let initial = initial.to_uppercase().next().unwrap_or(initial);
+                *counts.entry(initial).or_insert(0) += 1;
+            }
+        }
+        Ok(counts.into_iter().collect())
+    }
+    /// Totals of the dataset, for dashboards
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.stream_all()
+            .try_fold(HeroStats::default(), |stats, hero| {
+                futures::future::ready(Ok(stats.with(&hero)))
+            })
+            .await
+    }
+    /// Digest of every hero, whatever their order, changing with any write; clients compare
+    /// it to the one they last got to find out cheaply whether the dataset changed
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        let heroes: Vec<Hero> = self.stream_all().try_collect().await?;
+        Ok(digest::of(&heroes))
+    }
+    /// Heroes whose id equals `term` or whose name starts with it, each hero at most once
+    ///
+    /// By default a `%` in `term` acts as a wildcard, like in `get_by_name` filters.
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        let mut found = match self.get_by_id(term).await {
+            Ok(hero) => vec![hero],
+            Err(DataAccessError::NotFound | DataAccessError::Gone) => vec![],
+            Err(error) => return Err(error),
+        };
+        match self.get_by_name(&format!("{}%", term)).await {
+            // the hero with id `term`, if any, was found already
+            Ok(heroes) => found.extend(heroes.into_iter().filter(|hero| hero.id != term)),
+            Err(DataAccessError::NotFound) => {}
+            Err(error) => return Err(error),
+        }
+        if found.is_empty() {
+            Err(DataAccessError::NotFound)
+        } else {
+            Ok(found)
+        }
+    }
+    /// Heroes with the given ids, in the order of `ids`; unknown ids are skipped
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        let mut found = Vec::with_capacity(ids.len());
+        for id in ids {
+            match self.get_by_id(id).await {
+                Ok(hero) => found.push(hero),
+                Err(DataAccessError::NotFound | DataAccessError::Gone) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(found)
+    }
+    /// One page of the heroes matching the `get_by_name` filter `name`, with the number of
+    /// heroes matching it; no match is an empty page rather than `NotFound`
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        let heroes = match self.get_by_name(name).await {
+            Err(DataAccessError::NotFound) => vec![],
+            heroes => heroes?,
+        };
+        let total = heroes.len() as u64;
+        Ok((Pagination { limit, offset }.apply(heroes), total))
+    }
+    /// Heroes having the given (lowercase) tag
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        let tag = tag.to_lowercase();
+        let found: Vec<Hero> = self
+            .stream_all()
+            .try_filter(|hero| futures::future::ready(hero.tags.contains(&tag)))
+            .try_collect()
+            .await?;
+        if found.is_empty() {
+            Err(DataAccessError::NotFound)
+        } else {
+            Ok(found)
+        }
+    }
+    /// Heroes whose name matches `regex`; a database would rather match in its query, e.g.
+    /// with the `~*` operator of PostgreSQL and `regex.as_str()`
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        let found: Vec<Hero> = self
+            .stream_all()
+            .try_filter(|hero| futures::future::ready(regex.is_match(&hero.name)))
+            .try_collect()
+            .await?;
+        if found.is_empty() {
+            Err(DataAccessError::NotFound)
+        } else {
+            Ok(found)
+        }
+    }
+    /// Heroes whose `field` is within `range`, heroes without a value for it never being
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        let found: Vec<Hero> = self
+            .stream_all()
+            .try_filter(|hero| {
+                let value = field.value(hero);
+                futures::future::ready(value.is_some_and(|value| range.contains(value)))
+            })
+            .try_collect()
+            .await?;
+        if found.is_empty() {
+            Err(DataAccessError::NotFound)
+        } else {
+            Ok(found)
+        }
+    }
+    /// Delete the heroes matching the `get_by_name` filter `name`, answering the ones which
+    /// were; matching nothing deletes nothing rather than being `NotFound`
+    ///
+    /// The heroes rather than their count, so that a deletion event is published for each;
+    /// callers only needing the count take their `len()`.
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        let heroes = match self.get_by_name(name).await {
+            Err(DataAccessError::NotFound) => vec![],
+            heroes => heroes?,
+        };
+        let mut deleted = vec![];
+        for hero in heroes {
+            match self.delete(&hero.id).await {
+                Ok(hero) => deleted.push(hero),
+                // deleted meanwhile
+                Err(DataAccessError::NotFound) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(deleted)
+    }
+    /// Replace the name, power level and tags of a hero in one write, so that a merge patch
+    /// is a single change of the hero; `Unchanged` when it already is in this state
+    ///
+    /// This default is not that single write: it reads the hero, then `update`s it and
+    /// `update_tags` it, so a failure in between leaves half of the change applied and each
+    /// write bumps `updated_at`. Repositories able to write it at once override it, as the
+    /// in-memory and http ones do.
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        let current = self.get_by_id(id).await?;
+        let missing = |tags: &[String], from: &[String]| -> Vec<String> {
+            let missing = tags.iter().filter(|tag| !from.contains(tag));
+            missing.cloned().collect()
+        };
+        let changes = TagChanges {
+            add: missing(&tags, &current.tags),
+            remove: missing(&current.tags, &tags),
+        };
+        let updated = match self.update(id, hero).await {
+            Err(DataAccessError::Unchanged) => None,
+            updated => Some(updated?),
+        };
+        match updated {
+            _ if !changes.add.is_empty() || !changes.remove.is_empty() => {
+                self.update_tags(id, changes).await
+            }
+            Some(updated) => Ok(updated),
+            None => Err(DataAccessError::Unchanged),
+        }
+    }
+    /// Cheap connectivity check, like pinging a connection pool; repositories without a
+    /// connection to lose are always reachable
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        Ok(())
+    }
 }
 
 /// Dummy implementation for our repository
 /// In real life, this repository would access a database with persisted heroes.
-struct HeroesRepository();
+///
+/// Heroes are kept and listed in id order, numeric ids first and by value, so a page
+/// (`limit`/`offset`) doesn't shift when heroes are created: they get the highest id and
+/// come last. Deleting a hero still moves the following ones one place forward.
+struct InMemoryHeroesRepository {
+    heroes: RwLock<Vec<Hero>>,
+    next_id: AtomicU64,
+    /// ids of deleted heroes, answered with `Gone`
+    deleted: RwLock<HashSet<String>>,
+    /// views per hero id, forgotten when the hero is deleted
+    views: Mutex<HashMap<String, u64>>,
+    /// every change, the one with `seq` n at index n - 1; logged under the lock of the
+    /// heroes, so the order of the log is the order changes were applied in
+    changes: Mutex<Vec<HeroChange>>,
+}
 
-#[async_trait]
-impl HeroesRepositoryTrait for HeroesRepository {
-    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
-        const HEROES: [Hero; 2] = [
+impl InMemoryHeroesRepository {
+    fn new(heroes: Vec<Hero>) -> Self {
+        let repo = InMemoryHeroesRepository {
+            heroes: RwLock::new(vec![]),
+            next_id: AtomicU64::new(1),
+            deleted: RwLock::default(),
+            views: Mutex::default(),
+            changes: Mutex::default(),
+        };
+        // a fresh lock can't be poisoned
+        let _ = repo.store(heroes);
+        repo
+    }
+
+    fn is_deleted(&self, id: &str) -> Result<bool, DataAccessError> {
+        Ok(self
+            .deleted
+            .read()
+            .map_err(|_| DataAccessError::TechnicalError)?
+            .contains(id))
+    }
+
+    fn changes(&self) -> Result<MutexGuard<'_, Vec<HeroChange>>, DataAccessError> {
+        self.changes
+            .lock()
+            .map_err(|_| DataAccessError::TechnicalError)
+    }
+
+    /// Replace the dataset, keeping generated ids clear of its numeric ids; the heroes left
+    /// out are deleted like by `delete`, the others logged as created or updated
+    fn store(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        let next_id = heroes
+            .iter()
+            .filter_map(|hero| hero.id.parse::<u64>().ok())
+            .max()
+            .map_or(1, |max_id| max_id + 1);
+        let mut heroes = heroes;
+        heroes.sort_by(|a, b| id_order(&a.id, &b.id));
+        let mut stored = self
+            .heroes
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let mut deleted = self
+            .deleted
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let mut views = self
+            .views
+            .lock()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let mut changes = self.changes()?;
+        let kept: HashSet<&str> = heroes.iter().map(|hero| hero.id.as_str()).collect();
+        let before: HashSet<&str> = stored.iter().map(|hero| hero.id.as_str()).collect();
+        for hero in stored.iter() {
+            if !kept.contains(hero.id.as_str()) {
+                deleted.insert(hero.id.clone());
+                views.remove(&hero.id);
+                log_change(&mut changes, AuditAction::Delete, hero);
+            }
+        }
+        for hero in &heroes {
+            deleted.remove(&hero.id);
+            let action = match before.contains(hero.id.as_str()) {
+                true => AuditAction::Update,
+                false => AuditAction::Create,
+            };
+            log_change(&mut changes, action, hero);
+        }
+        *stored = heroes;
+        self.next_id.fetch_max(next_id, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl Default for InMemoryHeroesRepository {
+    fn default() -> Self {
+        let now = Some(now_millis());
+        InMemoryHeroesRepository::new(vec![
             Hero {
-                id: "1",
-                name: "Wonder Woman",
+                id: "1".to_string(),
+                name: HeroName::new("Wonder Woman").unwrap(),
+                updated_at: now,
+                tags: vec![],
+                power_level: 92,
             },
             Hero {
-                id: "2",
-                name: "Deadpool",
+                id: "2".to_string(),
+                name: HeroName::new("Deadpool").unwrap(),
+                updated_at: now,
+                tags: vec![],
+                power_level: 74,
             },
-        ];
+        ])
+    }
+}
+
+#[async_trait]
+impl HeroesRepositoryTrait for InMemoryHeroesRepository {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
         //simulate read from db
         time::sleep(Duration::from_millis(100)).await;
 
-        let found_heroes: Vec<Hero> = HEROES
-            .into_iter()
-            .filter(|hero: &Hero| {
-                if let Some(stripped_name) = name.strip_suffix('%') {
-                    hero.name.starts_with(stripped_name)
-                } else {
-                    hero.name == name
-                }
-            })
+        let matches = name_matcher(name);
+        let found_heroes: Vec<Hero> = self
+            .heroes
+            .read()
+            .map_err(|_| DataAccessError::TechnicalError)?
+            .iter()
+            .filter(|hero: &&Hero| matches(hero))
+            .cloned()
             .collect::<Vec<Hero>>();
 
         if found_heroes.is_empty() {
@@ -93,105 +1052,5705 @@ impl HeroesRepositoryTrait for HeroesRepository {
             Ok(found_heroes)
         }
     }
-}
 
-type HeroesRepositoryState = Arc<HeroesRepository>;
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        //simulate read from db
+        time::sleep(Duration::from_millis(100)).await;
 
-#[derive(Deserialize)]
-pub struct GetHeroFilter {
-    name: Option<String>,
-}
+        let matches = name_matcher(name);
+        let heroes = self
+            .heroes
+            .read()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let total = heroes.iter().filter(|hero| matches(hero)).count() as u64;
+        let page = heroes
+            .iter()
+            .filter(|hero| matches(hero))
+            .skip(offset as usize)
+            .take(limit.map_or(usize::MAX, |limit| limit as usize))
+            .cloned()
+            .collect();
+        Ok((page, total))
+    }
 
-type DynHeroesRepository = Arc<dyn HeroesRepositoryTrait + Send + Sync>;
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        let found = self
+            .heroes
+            .read()
+            .map_err(|_| DataAccessError::TechnicalError)?
+            .iter()
+            .find(|hero| hero.id == id)
+            .cloned();
+        match found {
+            Some(hero) => Ok(hero),
+            None if self.is_deleted(id)? => Err(DataAccessError::Gone),
+            None => Err(DataAccessError::NotFound),
+        }
+    }
 
-#[debug_handler]
-async fn get_heroes(
-    State(repo): State<DynHeroesRepository>,
-    filter: Query<GetHeroFilter>,
-) -> impl IntoResponse {
-    let mut name_filter = filter.name.to_owned().unwrap_or("%".to_string());
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        let hero = Hero {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed).to_string(),
+            name: hero.name,
+            updated_at: Some(now_millis()),
+            tags: vec![],
+            power_level: hero.power_level,
+        };
+        let mut heroes = self
+            .heroes
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let position = heroes.partition_point(|stored| id_order(&stored.id, &hero.id).is_lt());
+        log_change(&mut *self.changes()?, AuditAction::Create, &hero);
+        heroes.insert(position, hero.clone());
+        Ok(hero)
+    }
 
-    if !name_filter.ends_with('%') {
-        name_filter.push('%');
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        let hero = Hero {
+            id: id.to_string(),
+            name: hero.name,
+            updated_at: Some(now_millis()),
+            tags: vec![],
+            power_level: hero.power_level,
+        };
+        let mut heroes = self
+            .heroes
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let position = heroes.partition_point(|stored| id_order(&stored.id, &hero.id).is_lt());
+        if heroes
+            .get(position)
+            .is_some_and(|stored| stored.id == hero.id)
+        {
+            return Err(DataAccessError::AlreadyExists);
+        }
+        self.deleted
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?
+            .remove(&hero.id);
+        if let Ok(id) = hero.id.parse::<u64>() {
+            self.next_id.fetch_max(id + 1, Ordering::Relaxed);
+        }
+        log_change(&mut *self.changes()?, AuditAction::Create, &hero);
+        heroes.insert(position, hero.clone());
+        Ok(hero)
     }
 
-    let result = repo.get_by_name(name_filter.as_str()).await;
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        let mut heroes = self
+            .heroes
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let stored = heroes
+            .iter_mut()
+            .find(|stored| stored.id == id)
+            .ok_or(DataAccessError::NotFound)?;
+        if stored.name == hero.name && stored.power_level == hero.power_level {
+            return Err(DataAccessError::Unchanged);
+        }
+        stored.name = hero.name;
+        stored.power_level = hero.power_level;
+        stored.updated_at = Some(now_millis());
+        log_change(&mut *self.changes()?, AuditAction::Update, stored);
+        Ok(stored.clone())
+    }
 
-    match result {
-        Err(DataAccessError::NotFound) => StatusCode::NOT_FOUND.into_response(),
-        Ok(heroes) => Json(heroes).into_response(),
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        let mut heroes = self
+            .heroes
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let position = heroes
+            .iter()
+            .position(|hero| hero.id == id)
+            .ok_or(DataAccessError::NotFound)?;
+        self.deleted
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?
+            .insert(id.to_string());
+        self.views
+            .lock()
+            .map_err(|_| DataAccessError::TechnicalError)?
+            .remove(id);
+        let hero = heroes.remove(position);
+        log_change(&mut *self.changes()?, AuditAction::Delete, &hero);
+        Ok(hero)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use mockall::predicate::*;
-    use axum::{body::Body, http::Request};
-    use rstest::rstest;
-    use serde_json::Value;
-    use tower::ServiceExt;
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        let matches = name_matcher(name);
+        let mut heroes = self
+            .heroes
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let mut deleted = self
+            .deleted
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let mut views = self
+            .views
+            .lock()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let mut changes = self.changes()?;
+        let mut removed = vec![];
+        heroes.retain(|hero| {
+            let matched = matches(hero);
+            if matched {
+                deleted.insert(hero.id.clone());
+                views.remove(&hero.id);
+                log_change(&mut changes, AuditAction::Delete, hero);
+                removed.push(hero.clone());
+            }
+            !matched
+        });
+        Ok(removed)
+    }
 
-    fn send_get_request(uri: &str) -> Request<Body> {
-        Request::builder()
-            .uri(uri)
-            .method("GET")
-            .body(Body::empty())
-            .unwrap()
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        let mut counts = BTreeMap::new();
+        for hero in self
+            .heroes
+            .read()
+            .map_err(|_| DataAccessError::TechnicalError)?
+            .iter()
+        {
+            if let Some(initial) = hero.name.chars().next() {
+                let initial = initial.to_uppercase().next().unwrap_or(initial);
+                *counts.entry(initial).or_insert(0) += 1;
+            }
+        }
+        Ok(counts.into_iter().collect())
     }
-    #[rstest]
-    #[case("/?name=Wonder", "Wonder%", )] // verify that % is appended to the filter
-    #[case("/?name=Wonder%", "Wonder%")] // verify that % is not appended to the filter if it already ends with %
-    #[case("/", "%")] // verify that % is used as the default filter
-    #[tokio::test]
-    async fn get_by_name_success(#[case] uri: &'static str, #[case] expected_filter: &'static str) {
-        let dummy_heroes = vec![Default::default()];
 
-        let mut repo_mock = MockHeroesRepositoryTrait::new();
-        let result = Ok(dummy_heroes.clone());
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        Ok(self
+            .heroes
+            .read()
+            .map_err(|_| DataAccessError::TechnicalError)?
+            .iter()
+            .fold(HeroStats::default(), HeroStats::with))
+    }
 
-        repo_mock
-            .expect_get_by_name()
-            .with(eq(expected_filter))
-            .return_once(move |_| result);
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        let heroes = self
+            .heroes
+            .read()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        Ok(digest::of(heroes.iter()))
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        match self.heroes.read() {
+            // a snapshot keeps the stream independent of later writes
+            Ok(heroes) => stream::iter(heroes.clone().into_iter().map(Ok)).boxed(),
+            Err(_) => stream::once(async { Err(DataAccessError::TechnicalError) }).boxed(),
+        }
+    }
 
-        let repo = Arc::new(repo_mock) as DynHeroesRepository;
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        //simulate read from db
+        time::sleep(Duration::from_millis(100)).await;
 
-        let app = heroes_routes().with_state(repo);
+        let prefix = hero_name::fold_case(&hero_name::normalize_filter(term));
+        let found_heroes: Vec<Hero> = self
+            .heroes
+            .read()
+            .map_err(|_| DataAccessError::TechnicalError)?
+            .iter()
+            .filter(|hero| {
+                hero.id == term || hero_name::fold_case(&hero.name).starts_with(prefix.as_str())
+            })
+            .cloned()
+            .collect();
 
-        let response = app.oneshot(send_get_request(uri)).await.unwrap();
+        if found_heroes.is_empty() {
+            Err(DataAccessError::NotFound)
+        } else {
+            Ok(found_heroes)
+        }
+    }
 
-        assert_eq!(response.status(), StatusCode::OK);
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        self.store(heroes)
+    }
 
-        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
-        let body: Value = serde_json::from_slice(&body).unwrap();
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        let updated_at = Some(now_millis());
+        let mut report = UpsertReport::default();
+        let mut stored = self
+            .heroes
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let mut deleted = self
+            .deleted
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let mut changes = self.changes()?;
+        for hero in last_occurrences(heroes) {
+            let hero = Hero {
+                updated_at,
+                ..hero.clone()
+            };
+            deleted.remove(&hero.id);
+            if let Ok(id) = hero.id.parse::<u64>() {
+                self.next_id.fetch_max(id + 1, Ordering::Relaxed);
+            }
+            let position = stored.partition_point(|other| id_order(&other.id, &hero.id).is_lt());
+            match stored.get_mut(position) {
+                Some(existing) if existing.id == hero.id => {
+                    log_change(&mut changes, AuditAction::Update, &hero);
+                    *existing = hero;
+                    report.updated += 1;
+                }
+                _ => {
+                    log_change(&mut changes, AuditAction::Create, &hero);
+                    stored.insert(position, hero);
+                    report.created += 1;
+                }
+            }
+        }
+        Ok(report)
+    }
 
-        assert!(matches!(body, Value::Array{ .. })); // don't care what the result is: just check we receive json array
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        let tag = tag.to_lowercase();
+        let found_heroes: Vec<Hero> = self
+            .heroes
+            .read()
+            .map_err(|_| DataAccessError::TechnicalError)?
+            .iter()
+            .filter(|hero| hero.tags.contains(&tag))
+            .cloned()
+            .collect();
+
+        if found_heroes.is_empty() {
+            Err(DataAccessError::NotFound)
+        } else {
+            Ok(found_heroes)
+        }
     }
 
-    #[rstest]
-    #[case(DataAccessError::NotFound, StatusCode::NOT_FOUND)]
-    #[case(DataAccessError::TechnicalError, StatusCode::INTERNAL_SERVER_ERROR)]
-    #[tokio::test]
-    async fn get_by_name_failure(#[case] db_result: DataAccessError, #[case] expected_status: StatusCode) {
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        let mut heroes = self
+            .heroes
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let stored = heroes
+            .iter_mut()
+            .find(|stored| stored.id == id)
+            .ok_or(DataAccessError::NotFound)?;
+        let mut tags = stored.tags.clone();
+        for tag in changes.add {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        tags.retain(|tag| !changes.remove.contains(tag));
+        tags.sort();
+        if tags == stored.tags {
+            return Err(DataAccessError::Unchanged);
+        }
+        stored.tags = tags;
+        stored.updated_at = Some(now_millis());
+        log_change(&mut *self.changes()?, AuditAction::Update, stored);
+        Ok(stored.clone())
+    }
 
-        let mut repo_mock = MockHeroesRepositoryTrait::new();
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        mut tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        tags.sort();
+        tags.dedup();
+        let mut heroes = self
+            .heroes
+            .write()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let stored = heroes
+            .iter_mut()
+            .find(|stored| stored.id == id)
+            .ok_or(DataAccessError::NotFound)?;
+        if stored.name == hero.name && stored.power_level == hero.power_level && stored.tags == tags
+        {
+            return Err(DataAccessError::Unchanged);
+        }
+        stored.name = hero.name;
+        stored.power_level = hero.power_level;
+        stored.tags = tags;
+        stored.updated_at = Some(now_millis());
+        log_change(&mut *self.changes()?, AuditAction::Update, stored);
+        Ok(stored.clone())
+    }
 
-        repo_mock
-            .expect_get_by_name()
-            .with(eq("Spider%"))
-            .return_once(move |_| Err(db_result));
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        // the heroes stay locked so the hero can't be deleted meanwhile
+        let heroes = self
+            .heroes
+            .read()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        if !heroes.iter().any(|hero| hero.id == id) {
+            return Err(if self.is_deleted(id)? {
+                DataAccessError::Gone
+            } else {
+                DataAccessError::NotFound
+            });
+        }
+        let mut views = self
+            .views
+            .lock()
+            .map_err(|_| DataAccessError::TechnicalError)?;
+        let count = views.entry(id.to_string()).or_insert(0);
+        *count += 1;
+        Ok(*count)
+    }
 
-        let repo = Arc::new(repo_mock) as DynHeroesRepository;
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        let changes = self.changes()?;
+        let start = usize::try_from(since).map_or(changes.len(), |since| since.min(changes.len()));
+        Ok(changes[start..].to_vec())
+    }
 
-        let app = heroes_routes().with_state(repo);
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        let mut heroes = self
+            .heroes
+            .read()
+            .map_err(|_| DataAccessError::TechnicalError)?
+            .clone();
+        heroes.sort_by_cached_key(|hero| (hero_name::fold_case(&hero.name), hero.id.clone()));
 
-        let response = app
-            .oneshot(send_get_request("/?name=Spider"))
-            .await
-            .unwrap();
+        let position = heroes
+            .iter()
+            .position(|hero| hero.id == id)
+            .ok_or(DataAccessError::NotFound)?;
+        let next = heroes.get(position + 1).cloned();
+        let prev = position.checked_sub(1).map(|before| heroes[before].clone());
+        Ok(HeroContext {
+            prev,
+            current: heroes.swap_remove(position),
+            next,
+        })
+    }
+}
 
-        assert_eq!(response.status(), expected_status);
+/// Add the change of `hero` by `action` to the change log `changes`
+fn log_change(changes: &mut Vec<HeroChange>, action: AuditAction, hero: &Hero) {
+    changes.push(HeroChange {
+        seq: changes.len() as u64 + 1,
+        action,
+        hero: hero.clone(),
+    });
+}
+
+/// `get_by_name` filter as a predicate: exact name, or name prefix with a trailing `%`;
+/// an escaped `\%` is a literal `%`
+fn name_matcher(filter: &str) -> impl Fn(&Hero) -> bool {
+    // stored names are normalized, so must be the filter; matching ignores case
+    let filter = hero_name::fold_case(&hero_name::normalize_filter(filter));
+    let prefix = match filter.strip_suffix('%') {
+        Some(prefix) if !prefix.ends_with('\\') => Some(prefix.replace("\\%", "%")),
+        _ => None,
+    };
+    let exact = filter.replace("\\%", "%");
+    move |hero| {
+        let hero_name = hero_name::fold_case(&hero.name);
+        match &prefix {
+            Some(prefix) => hero_name.starts_with(prefix.as_str()),
+            None => hero_name == exact,
+        }
+    }
+}
+
+/// Order of hero ids: numeric ones first by value, so "10" comes after "9", then the others
+fn id_order(a: &str, b: &str) -> std::cmp::Ordering {
+    match (a.parse::<u64>(), b.parse::<u64>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => std::cmp::Ordering::Less,
+        (Err(_), Ok(_)) => std::cmp::Ordering::Greater,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+/// `heroes` without the ones whose id appears earlier on, in their order
+fn first_occurrences(heroes: Vec<Hero>) -> Vec<Hero> {
+    let mut seen = HashSet::new();
+    heroes
+        .into_iter()
+        .filter(|hero| seen.insert(hero.id.clone()))
+        .collect()
+}
+
+/// `heroes` without the ones whose id appears again later on
+fn last_occurrences(heroes: &[Hero]) -> Vec<&Hero> {
+    let last: HashMap<&str, usize> = heroes
+        .iter()
+        .enumerate()
+        .map(|(index, hero)| (hero.id.as_str(), index))
+        .collect();
+    heroes
+        .iter()
+        .enumerate()
+        .filter(|(index, hero)| last[hero.id.as_str()] == *index)
+        .map(|(_, hero)| hero)
+        .collect()
+}
+
+const RESULT_TRUNCATED_HEADER: &str = "x-result-truncated";
+const APPLIED_FILTER_HEADER: &str = "x-applied-filter";
+const ROW_LIMIT_HEADER: &str = "x-row-limit";
+
+/// Milliseconds since the unix epoch
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+type DynHeroesRepository = Arc<dyn HeroesRepositoryTrait + Send + Sync>;
+
+// lets decorators wrap a repository chosen at runtime
+#[async_trait]
+impl<R: HeroesRepositoryTrait + Send + Sync + ?Sized> HeroesRepositoryTrait for Arc<R> {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        (**self).get_by_name(name).await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        (**self).get_by_id(id).await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        (**self).create(hero).await
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        (**self).create_if_absent(id, hero).await
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        (**self).update(id, hero).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        (**self).delete(id).await
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        (**self).delete_by_name(name).await
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        (**self).count_by_initial().await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        (**self).stats().await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        (**self).dataset_digest().await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        (**self).stream_all()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        (**self).search(term).await
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        (**self).replace_all(heroes).await
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        (**self).upsert_many(heroes).await
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        (**self).get_by_tag(tag).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        (**self).get_by_ids(ids).await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        (**self).get_page(name, limit, offset).await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        (**self).get_by_name_regex(regex).await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        (**self).get_by_range(field, range).await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        (**self).ping().await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        (**self).update_tags(id, changes).await
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        (**self).update_with_tags(id, hero, tags).await
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        (**self).record_view(id).await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        (**self).get_changes_since(since).await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        (**self).get_with_neighbors(id).await
+    }
+}
+
+/// State shared by all handlers; each handler extracts only the parts it needs
+#[derive(Clone, FromRef)]
+struct AppState {
+    repo: DynHeroesRepository,
+    audit_log: DynAuditLog,
+    metrics: Arc<AppMetrics>,
+    events: HeroEvents,
+    started: health::Started,
+    tasks: Arc<BackgroundTasks>,
+    warm_up: Arc<health::WarmUp>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    config: Arc<LiveConfig>,
+}
+
+// handlers and middlewares extracting the configuration get the current one
+impl FromRef<AppState> for Arc<Config> {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.current()
+    }
+}
+
+impl FromRef<AppState> for response_size::ResponseSize {
+    fn from_ref(state: &AppState) -> Self {
+        response_size::ResponseSize {
+            metrics: state.metrics.clone(),
+            config: state.config.current(),
+        }
+    }
+}
+
+#[debug_handler(state = AppState)]
+async fn get_heroes(
+    State(repo): State<DynHeroesRepository>,
+    State(metrics): State<Arc<AppMetrics>>,
+    State(config): State<Arc<Config>>,
+    deadline: Deadline,
+    pretty: Pretty,
+    headers: HeaderMap,
+    query: HeroQuery,
+) -> impl IntoResponse {
+    let respond = |mut heroes: Vec<Hero>| {
+        if config.dedupe_results {
+            heroes = first_occurrences(heroes);
+        }
+        if let Some(sort) = &query.sort {
+            sort.apply(&mut heroes);
+        }
+        let total = heroes.len() as u64;
+        let page = query.pagination.apply(heroes);
+        let Some(range) = query.range else {
+            return listing(&headers, &config, pretty, query.shape, page);
+        };
+        let served = page.len().min(config.max_results) as u64;
+        match range.content_range(served, total) {
+            Ok(content_range) => {
+                let mut response = listing(&headers, &config, pretty, query.shape, page);
+                if response.status() == StatusCode::OK {
+                    *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+                    if let Ok(value) = HeaderValue::from_str(&content_range) {
+                        response.headers_mut().insert(header::CONTENT_RANGE, value);
+                    }
+                }
+                response
+            }
+            Err((error, content_range)) => {
+                ([(header::CONTENT_RANGE, content_range)], error).into_response()
+            }
+        }
+    };
+
+    let (found, unmatched) = match &query.filter {
+        HeroFilter::Search(term) => (
+            repo.search(term),
+            format!("no heroes match search '{}'", term),
+        ),
+        HeroFilter::Regex(regex) => (
+            repo.get_by_name_regex(regex),
+            format!("no heroes match name_regex '{}'", regex.as_str()),
+        ),
+        HeroFilter::Name(name_filter) => {
+            tracing::debug!(filter = %logging::sanitize(name_filter), "listing heroes by name");
+            (
+                repo.get_by_name(name_filter),
+                format!("no heroes match filter '{}'", name_filter),
+            )
+        }
+    };
+
+    let result = deadline.run(async {
+        let heroes = found.await;
+        let heroes = filter_by_tags(&repo, heroes, &query.tags, query.tag_mode).await;
+        filter_by_ranges(&repo, heroes, &query.ranges).await
+    });
+    let result = match result.await {
+        Ok(result) => metrics.observe(non_empty(result)),
+        Err(timeout) => return timeout.into_response(),
+    };
+
+    let mut response = match (result, &query.filter) {
+        (Err(DataAccessError::NotFound), HeroFilter::Name(name_filter)) if config.suggest_names => {
+            Suggested {
+                error: ApiError::from_data_error(DataAccessError::NotFound, unmatched),
+                suggestions: suggest_names(&repo, deadline, name_filter).await,
+            }
+            .into_response()
+        }
+        (Err(DataAccessError::NotFound), _) => {
+            ApiError::from_data_error(DataAccessError::NotFound, unmatched).into_response()
+        }
+        (Ok(heroes), _) => respond(heroes),
+        (Err(error), _) => ApiError::from(error).into_response(),
+    };
+    if let (true, HeroFilter::Name(name_filter)) = (config.applied_filter_header, &query.filter) {
+        // names aren't limited to ascii, the header value is their utf-8
+        if let Ok(value) = HeaderValue::from_bytes(name_filter.as_bytes()) {
+            response.headers_mut().insert(APPLIED_FILTER_HEADER, value);
+        }
+    }
+    response
+}
+
+/// Most names suggested for a name filter matching no hero
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Names of the heroes closest to `name_filter`, most similar first, for clients who
+/// mistyped it; none when the heroes can't be read in time
+async fn suggest_names(
+    repo: &DynHeroesRepository,
+    deadline: Deadline,
+    name_filter: &str,
+) -> Vec<String> {
+    let partial = name_filter.replace('%', "");
+    if partial.is_empty() {
+        return vec![];
+    }
+    let heroes = deadline.run(repo.stream_all().try_collect::<Vec<Hero>>());
+    let Ok(Ok(heroes)) = heroes.await else {
+        return vec![];
+    };
+
+    let mut close: Vec<(f64, String)> = heroes
+        .into_iter()
+        .map(|hero| {
+            let similarity = hero_name::prefix_similarity(&partial, &hero.name);
+            (similarity, hero.name.into())
+        })
+        .filter(|(similarity, _)| *similarity >= SIMILARITY_THRESHOLD)
+        .collect();
+    close.sort_by(|(a, a_name), (b, b_name)| b.total_cmp(a).then_with(|| a_name.cmp(b_name)));
+    close.dedup_by(|(_, a), (_, b)| a == b);
+    close
+        .into_iter()
+        .map(|(_, name)| name)
+        .take(MAX_SUGGESTIONS)
+        .collect()
+}
+
+/// `get_by_name` filter for the `name` query parameter, following the configuration
+fn name_filter(name: Option<&str>, config: &Config) -> Result<String, ApiError> {
+    let name = match name {
+        // an explicitly blank filter is most likely a client mistake, unless configured otherwise
+        Some("") if config.reject_empty_name => {
+            return Err(ApiError::bad_request("name filter must not be empty"))
+        }
+        Some("") | None => "%",
+        Some(name) if config.disable_wildcards => return Ok(hero_name::escape_wildcards(name)),
+        Some(name) => name,
+    };
+    if config.disable_wildcards {
+        return Ok(name.to_string());
+    }
+
+    let append = config.auto_append_wildcard && !name.ends_with('%');
+    // each wildcard multiplies the ways a `LIKE` pattern may match
+    if name.matches('%').count() + usize::from(append) > config.max_name_wildcards {
+        return Err(ApiError::bad_request(format!(
+            "name filters accept at most {} % wildcards",
+            config.max_name_wildcards
+        )));
+    }
+    // one allocation, with room for the appended wildcard
+    let mut name_filter = String::with_capacity(name.len() + usize::from(append));
+    name_filter.push_str(name);
+    if append {
+        name_filter.push('%');
+    }
+    Ok(name_filter)
+}
+
+/// Query of `GET /heroes/page`
+#[derive(Deserialize, Debug)]
+pub struct PageQuery {
+    name: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// One page of heroes with the number of heroes matching the filter
+#[derive(Serialize, Debug)]
+struct Page {
+    items: Vec<Hero>,
+    total: u64,
+    limit: u64,
+    offset: u64,
+}
+
+/// `GET /heroes/page`: the heroes matching `name` one page at a time, with their total
+///
+/// Pages hold at most `MAX_RESULTS` heroes, which is also the `limit` when none is given.
+/// The total is either part of an envelope or sent in headers, see `PageFormat`.
+#[debug_handler(state = AppState)]
+async fn get_hero_page(
+    State(repo): State<DynHeroesRepository>,
+    State(config): State<Arc<Config>>,
+    deadline: Deadline,
+    pretty: Pretty,
+    headers: HeaderMap,
+    OriginalUri(uri): OriginalUri,
+    Query(query): Query<PageQuery>,
+) -> Result<Response, ApiError> {
+    let pagination = Pagination::parse(query.limit, query.offset, config.max_offset)?;
+    let max_results = config.max_results as u64;
+    let limit = pagination
+        .limit
+        .map_or(max_results, |limit| limit.min(max_results));
+    let filter = name_filter(query.name.as_deref(), &config)?;
+
+    let page = repo.get_page(&filter, Some(limit), pagination.offset);
+    let (items, total) = deadline.run(page).await??;
+    if total == 0 {
+        let message = format!("no heroes match filter '{}'", filter);
+        return Err(ApiError::from_data_error(
+            DataAccessError::NotFound,
+            message,
+        ));
+    }
+    let offset = pagination.offset;
+    match PageFormat::negotiate(&headers, config.page_format) {
+        PageFormat::Envelope => Ok(pretty
+            .json(Page {
+                items,
+                total,
+                limit,
+                offset,
+            })
+            .into_response()),
+        PageFormat::Headers => {
+            let mut response = pretty.json(items).into_response();
+            let response_headers = response.headers_mut();
+            response_headers.insert(pagination::TOTAL_COUNT_HEADER, total.into());
+            let origin = pagination::origin(&headers);
+            let links = pagination::links(&origin, &uri, limit, offset, total);
+            if let Some(links) = links.and_then(|links| HeaderValue::from_str(&links).ok()) {
+                response_headers.insert(header::LINK, links);
+            }
+            Ok(response)
+        }
+    }
+}
+
+/// Keep the heroes having the requested tags: all of them, or any of them with `TagMode::Any`
+async fn filter_by_tags(
+    repo: &DynHeroesRepository,
+    heroes: Result<Vec<Hero>, DataAccessError>,
+    tags: &[String],
+    mode: TagMode,
+) -> Result<Vec<Hero>, DataAccessError> {
+    let heroes = heroes?;
+    if tags.is_empty() {
+        return Ok(heroes);
+    }
+
+    let mut matching: Option<HashSet<String>> = None;
+    for tag in tags {
+        let tagged: HashSet<String> = match repo.get_by_tag(tag).await {
+            Ok(tagged) => tagged.into_iter().map(|hero| hero.id).collect(),
+            Err(DataAccessError::NotFound) => HashSet::new(),
+            Err(error) => return Err(error),
+        };
+        matching = Some(match (matching, mode) {
+            (None, _) => tagged,
+            (Some(matching), TagMode::All) => &matching & &tagged,
+            (Some(matching), TagMode::Any) => &matching | &tagged,
+        });
+    }
+    let matching = matching.unwrap_or_default();
+    Ok(heroes
+        .into_iter()
+        .filter(|hero| matching.contains(&hero.id))
+        .collect())
+}
+
+/// Keep the heroes whose fields are within every requested range
+async fn filter_by_ranges(
+    repo: &DynHeroesRepository,
+    heroes: Result<Vec<Hero>, DataAccessError>,
+    ranges: &[(NumericField, NumericRange<u64>)],
+) -> Result<Vec<Hero>, DataAccessError> {
+    let mut heroes = heroes?;
+    for (field, range) in ranges {
+        let within: HashSet<String> = match repo.get_by_range(*field, *range).await {
+            Ok(within) => within.into_iter().map(|hero| hero.id).collect(),
+            Err(DataAccessError::NotFound) => return Ok(vec![]),
+            Err(error) => return Err(error),
+        };
+        heroes.retain(|hero| within.contains(&hero.id));
+    }
+    Ok(heroes)
+}
+
+/// Listings answer `404` when nothing matches, whether the repository reports it with
+/// `NotFound` or with an empty list, so clients see the same behavior with any repository
+fn non_empty(result: Result<Vec<Hero>, DataAccessError>) -> Result<Vec<Hero>, DataAccessError> {
+    match result {
+        Ok(heroes) if heroes.is_empty() => Err(DataAccessError::NotFound),
+        result => result,
+    }
+}
+
+/// Listing response in the format negotiated with `Accept`, dated with `Last-Modified`
+/// and honoring `If-Modified-Since`
+///
+/// At most `MAX_RESULTS` heroes are sent; `X-Result-Truncated: true` tells when some were left out.
+fn listing(
+    headers: &HeaderMap,
+    config: &Config,
+    pretty: Pretty,
+    shape: Shape,
+    mut heroes: Vec<Hero>,
+) -> Response {
+    let truncated = heroes.len() > config.max_results;
+    if truncated {
+        tracing::warn!(
+            found = heroes.len(),
+            max_results = config.max_results,
+            "listing truncated"
+        );
+        heroes.truncate(config.max_results);
+    }
+
+    let last_modified = last_modified::of(&heroes);
+    let response = match shape {
+        Shape::Array => format::negotiate(headers, pretty.0, &heroes),
+        // ids are unique, so no hero is lost
+        Shape::Map => {
+            let by_id: BTreeMap<&str, &Hero> =
+                heroes.iter().map(|hero| (hero.id.as_str(), hero)).collect();
+            format::negotiate(headers, pretty.0, &by_id)
+        }
+    };
+    let mut response = last_modified::conditional(headers, last_modified, response);
+    if truncated {
+        response
+            .headers_mut()
+            .insert(RESULT_TRUNCATED_HEADER, HeaderValue::from_static("true"));
+    }
+    response
+}
+
+/// Number of heroes per initial, as a json object: `{ "D": 1, "W": 1 }`
+#[debug_handler(state = AppState)]
+async fn get_initial_facets(
+    State(repo): State<DynHeroesRepository>,
+    pretty: Pretty,
+) -> Result<PrettyJson<BTreeMap<String, u64>>, ApiError> {
+    let counts = repo.count_by_initial().await?;
+    Ok(pretty.json(
+        counts
+            .into_iter()
+            .map(|(initial, count)| (initial.to_string(), count))
+            .collect(),
+    ))
+}
+
+/// Totals of the dataset: heroes, heroes per tag, longest and shortest names
+#[debug_handler(state = AppState)]
+async fn get_hero_stats(
+    State(repo): State<DynHeroesRepository>,
+    pretty: Pretty,
+) -> Result<PrettyJson<HeroStats>, ApiError> {
+    Ok(pretty.json(repo.stats().await?))
+}
+
+/// `GET /heroes/digest`: `{ "digest": "..." }`, the same as long as no hero is written
+#[debug_handler(state = AppState)]
+async fn get_dataset_digest(
+    State(repo): State<DynHeroesRepository>,
+    deadline: Deadline,
+    pretty: Pretty,
+) -> Result<impl IntoResponse, ApiError> {
+    let digest = deadline.run(repo.dataset_digest()).await??;
+    Ok(pretty.json(serde_json::json!({ "digest": digest })))
+}
+
+/// Query of `GET /heroes/changes`
+#[derive(Deserialize, Debug)]
+pub struct ChangesQuery {
+    #[serde(default)]
+    since: u64,
+}
+
+/// `GET /heroes/changes?since=N`: the changes after the one with `seq` `N`, oldest first
+///
+/// Without `since`, every change from the first one; clients keep the `seq` of the last
+/// change they got and ask for the ones after it next time.
+#[debug_handler(state = AppState)]
+async fn get_hero_changes(
+    State(repo): State<DynHeroesRepository>,
+    deadline: Deadline,
+    pretty: Pretty,
+    Query(query): Query<ChangesQuery>,
+) -> Result<PrettyJson<Vec<HeroChange>>, ApiError> {
+    let changes = deadline.run(repo.get_changes_since(query.since)).await??;
+    Ok(pretty.json(changes))
+}
+
+/// Query of `GET /heroes/export`
+#[derive(Deserialize, Debug)]
+pub struct ExportPageQuery {
+    cursor: Option<String>,
+    limit: Option<u64>,
+}
+
+/// One page of an export, with the cursor of the next one
+#[derive(Serialize, Debug)]
+struct ExportPage {
+    items: Vec<Hero>,
+    /// `None` on the last page
+    next: Option<String>,
+}
+
+/// Position of a hero in exports: numeric ids in numeric order, and a total order of the
+/// others, so a position stays meaningful whatever was written since
+fn export_position(id: &str) -> (usize, &str) {
+    (id.len(), id)
+}
+
+/// `GET /heroes/export`: the whole dataset, `limit` heroes at a time, for long exports
+///
+/// Each page but the last comes with a `next` cursor, sent back as `?cursor=` to get the
+/// following heroes. Cursors are signed with `EXPORT_CURSOR_KEY` and hold the position of
+/// the last hero exported, so they outlive restarts; forged ones are answered with `400`.
+/// Heroes created behind the position while exporting are left out.
+#[debug_handler(state = AppState)]
+async fn export_heroes_page(
+    State(repo): State<DynHeroesRepository>,
+    State(config): State<Arc<Config>>,
+    deadline: Deadline,
+    pretty: Pretty,
+    Query(query): Query<ExportPageQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Some(key) = config.export_cursor_key.as_deref().map(str::as_bytes) else {
+        return Err(ApiError::new(
+            StatusCode::NOT_IMPLEMENTED,
+            "feature_disabled",
+            "exports are disabled on this server, EXPORT_CURSOR_KEY is unset",
+        ));
+    };
+    if query.limit == Some(0) {
+        return Err(ApiError::bad_request("limit must be greater than 0"));
+    }
+    let limit = query.limit.map_or(config.max_results, |limit| {
+        limit.min(config.max_results as u64) as usize
+    });
+    let after = match &query.cursor {
+        Some(token) => Some(
+            cursor::verify(key, token).ok_or_else(|| ApiError::bad_request("invalid cursor"))?,
+        ),
+        None => None,
+    };
+
+    let mut heroes: Vec<Hero> = deadline.run(repo.stream_all().try_collect()).await??;
+    if let Some(after) = &after {
+        heroes.retain(|hero| export_position(&hero.id) > export_position(after));
+    }
+    heroes.sort_by(|a, b| export_position(&a.id).cmp(&export_position(&b.id)));
+    let more = heroes.len() > limit;
+    heroes.truncate(limit);
+    let next = heroes
+        .last()
+        .filter(|_| more)
+        .map(|last| cursor::sign(key, &last.id));
+    Ok(pretty.json(ExportPage {
+        items: heroes,
+        next,
+    }))
+}
+
+/// Query of `GET /heroes/export.csv`
+#[derive(Deserialize, Debug)]
+pub struct ExportQuery {
+    limit: Option<u64>,
+}
+
+/// Whole dataset as a CSV download, streamed row by row
+///
+/// The download ends after `limit` heroes when one is given, and after `MAX_STREAM_ROWS`
+/// at most; the limit it ends at is sent in `X-Row-Limit`, rows being sent before the
+/// repository tells whether there are more.
+#[debug_handler(state = AppState)]
+async fn export_heroes_csv(
+    State(repo): State<DynHeroesRepository>,
+    State(config): State<Arc<Config>>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    feature::require(&config, Feature::CsvExport)?;
+    if query.limit == Some(0) {
+        return Err(ApiError::bad_request("limit must be greater than 0"));
+    }
+    let limit = match (query.limit, config.max_stream_rows) {
+        (Some(limit), Some(max)) => Some(limit.min(max)),
+        (limit, max) => limit.or(max),
+    };
+    let header_row = stream::once(async { Ok(csv::write_row(&["id", "name"])) });
+    let heroes = match limit {
+        Some(limit) => repo.stream_all().take(limit as usize).boxed(),
+        None => repo.stream_all(),
+    };
+    let hero_rows = heroes.map(|hero| {
+        hero.map(|hero| csv::write_row(&[&hero.id, &hero.name]))
+            // failing the body aborts the download, the status line being already sent
+            .map_err(|error| std::io::Error::other(format!("{:?}", error)))
+    });
+
+    let mut headers = HeaderMap::new();
+    if let Some(limit) = limit {
+        headers.insert(ROW_LIMIT_HEADER, limit.into());
+    }
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"heroes.csv\"",
+            ),
+        ],
+        headers,
+        StreamBody::new(header_row.chain(hero_rows).map(|row| row.map(Bytes::from))),
+    ))
+}
+
+/// Query of `GET /heroes/:id`
+#[derive(Deserialize, Debug)]
+pub struct HeroDetailQuery {
+    include: Option<Include>,
+}
+
+/// The hero, with its `_computed` fields on `?include=computed`, or `{"hero": null}`
+/// for a missing one when `missing_hero_as_null` is configured
+#[debug_handler(state = AppState)]
+async fn get_hero(
+    State(repo): State<DynHeroesRepository>,
+    State(metrics): State<Arc<AppMetrics>>,
+    State(config): State<Arc<Config>>,
+    deadline: Deadline,
+    mut timing: Timing,
+    Path(id): Path<String>,
+    Query(query): Query<HeroDetailQuery>,
+) -> Result<Response, ApiError> {
+    let result = timing
+        .measure(Phase::Repository, deadline.run(repo.get_by_id(&id)))
+        .await?;
+    let hero = match metrics.observe(result) {
+        Err(DataAccessError::NotFound) if config.missing_hero_as_null => {
+            return Ok(timing.json(serde_json::json!({ "hero": null })));
+        }
+        result => result?,
+    };
+    if query.include != Some(Include::Computed) {
+        return Ok(timing.json(hero));
+    }
+    let computed = ComputedFields::of(&hero);
+    let mut value = serde_json::to_value(hero).map_err(|_| ApiError::internal())?;
+    if let Value::Object(fields) = &mut value {
+        fields.insert(
+            "_computed".to_string(),
+            serde_json::to_value(computed).unwrap_or_default(),
+        );
+    }
+    Ok(timing.json(value))
+}
+
+#[debug_handler(state = AppState)]
+async fn create_hero(
+    State(repo): State<DynHeroesRepository>,
+    State(metrics): State<Arc<AppMetrics>>,
+    State(events): State<HeroEvents>,
+    deadline: Deadline,
+    mut timing: Timing,
+    headers: HeaderMap,
+    JsonBody(payload): JsonBody<NewHero>,
+) -> Result<impl IntoResponse, ApiError> {
+    // `If-None-Match: *` asks for the id of the body, and for nothing if a hero has it
+    let if_absent = headers
+        .get(header::IF_NONE_MATCH)
+        .is_some_and(|value| value.as_bytes().trim_ascii() == b"*");
+    let NewHero {
+        id,
+        name,
+        power_level,
+    } = payload;
+    let payload = HeroPayload { name, power_level };
+    let errors = timing
+        .measure(
+            Phase::Validation,
+            deadline.run(validation_errors(&repo, &payload)),
+        )
+        .await??;
+    if !errors.is_empty() {
+        return Err(invalid_hero(&errors));
+    }
+    let id = match (if_absent, id) {
+        (true, Some(id)) if !id.is_empty() => Some(id),
+        (true, _) => {
+            return Err(ApiError::bad_request(
+                "If-None-Match: * needs the id of the hero to create",
+            ))
+        }
+        (false, _) => None,
+    };
+    let created = async {
+        match &id {
+            Some(id) => repo.create_if_absent(id, payload).await,
+            None => repo.create(payload).await,
+        }
+    };
+    let hero = timing
+        .measure(Phase::Repository, deadline.run(created))
+        .await??;
+    AppMetrics::increment(&metrics.heroes_created);
+    events.publish(AuditAction::Create, &hero);
+    Ok((StatusCode::CREATED, timing.json(hero)))
+}
+
+#[debug_handler(state = AppState)]
+async fn update_hero(
+    State(repo): State<DynHeroesRepository>,
+    State(metrics): State<Arc<AppMetrics>>,
+    State(events): State<HeroEvents>,
+    deadline: Deadline,
+    mut timing: Timing,
+    Path(id): Path<String>,
+    JsonBody(payload): JsonBody<HeroPayload>,
+) -> Result<Response, ApiError> {
+    let errors = timing
+        .measure(Phase::Validation, async { payload.problems() })
+        .await;
+    if !errors.is_empty() {
+        return Err(invalid_hero(&errors));
+    }
+    let result = timing
+        .measure(Phase::Repository, deadline.run(repo.update(&id, payload)))
+        .await?;
+    // an update changing nothing is not written, so `updated_at` and the audit log only
+    // record actual changes
+    if let Err(DataAccessError::Unchanged) = result {
+        return Ok(StatusCode::NOT_MODIFIED.into_response());
+    }
+    let hero = metrics.observe(result)?;
+    AppMetrics::increment(&metrics.heroes_updated);
+    events.publish(AuditAction::Update, &hero);
+    Ok(timing.json(hero))
+}
+
+/// Run the validation of `create` without storing anything, always answering `200`:
+/// `{ "valid": true }` or `{ "valid": false, "errors": [...] }`
+#[debug_handler(state = AppState)]
+async fn validate_hero(
+    State(repo): State<DynHeroesRepository>,
+    deadline: Deadline,
+    pretty: Pretty,
+    JsonBody(payload): JsonBody<Value>,
+) -> Result<impl IntoResponse, ApiError> {
+    let errors = match serde_json::from_value::<HeroPayload>(payload) {
+        Ok(payload) => deadline.run(validation_errors(&repo, &payload)).await??,
+        Err(error) => vec![error.to_string()],
+    };
+    Ok(pretty.json(if errors.is_empty() {
+        serde_json::json!({ "valid": true })
+    } else {
+        serde_json::json!({ "valid": false, "errors": errors })
+    }))
+}
+
+/// Reasons why `payload` can't be created, empty when it can
+async fn validation_errors(
+    repo: &DynHeroesRepository,
+    payload: &HeroPayload,
+) -> Result<Vec<String>, DataAccessError> {
+    let mut errors = payload.problems();
+    if errors.is_empty() {
+        // without '%' the name is matched exactly
+        match repo.get_by_name(&payload.name).await {
+            Ok(heroes) if !heroes.is_empty() => {
+                errors.push(format!("a hero named '{}' already exists", payload.name))
+            }
+            Ok(_) | Err(DataAccessError::NotFound) => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(errors)
+}
+
+fn invalid_hero(errors: &[String]) -> ApiError {
+    ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        "invalid_hero",
+        errors.join("; "),
+    )
+}
+
+#[debug_handler(state = AppState)]
+async fn delete_hero(
+    State(repo): State<DynHeroesRepository>,
+    State(metrics): State<Arc<AppMetrics>>,
+    State(events): State<HeroEvents>,
+    deadline: Deadline,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    let hero = metrics.observe(deadline.run(repo.delete(&id)).await?)?;
+    AppMetrics::increment(&metrics.heroes_deleted);
+    events.publish(AuditAction::Delete, &hero);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Query of `DELETE /heroes/`
+#[derive(Deserialize, Debug)]
+struct BulkDeleteQuery {
+    name: Option<String>,
+    /// required, as a name filter like `%` matches every hero; any form of `flag::parse`
+    #[serde(default, deserialize_with = "flag::deserialize")]
+    confirm: Option<bool>,
+}
+
+/// Delete every hero matching the name filter, answering how many were deleted
+#[debug_handler(state = AppState)]
+async fn delete_heroes(
+    State(repo): State<DynHeroesRepository>,
+    State(metrics): State<Arc<AppMetrics>>,
+    State(events): State<HeroEvents>,
+    State(config): State<Arc<Config>>,
+    deadline: Deadline,
+    pretty: Pretty,
+    query: Result<Query<BulkDeleteQuery>, QueryRejection>,
+) -> Result<PrettyJson<DeletionReport>, ApiError> {
+    let Query(query) = query.map_err(|rejection| ApiError::bad_request(rejection.body_text()))?;
+    if !query.confirm.unwrap_or_default() {
+        return Err(ApiError::bad_request(
+            "deleting heroes by name needs confirm=true",
+        ));
+    }
+    let filter = name_filter(query.name.as_deref(), &config)?;
+    let heroes = deadline.run(repo.delete_by_name(&filter)).await??;
+    for hero in &heroes {
+        events.publish(AuditAction::Delete, hero);
+    }
+    let deleted = heroes.len() as u64;
+    AppMetrics::add(&metrics.heroes_deleted, deleted);
+    Ok(pretty.json(DeletionReport { deleted }))
+}
+
+/// `GET /heroes/batch?id=2&id=1`: the heroes with these ids, unknown ones being left out
+///
+/// Heroes are sorted by id like listings, or follow the order of the `id`s with
+/// `?ordered=true`. With `?strict=true`, unknown ids are answered with `404` instead,
+/// whose message lists them.
+#[debug_handler(state = AppState)]
+async fn get_heroes_batch(
+    State(repo): State<DynHeroesRepository>,
+    State(config): State<Arc<Config>>,
+    deadline: Deadline,
+    pretty: Pretty,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> Result<PrettyJson<Vec<Hero>>, ApiError> {
+    let ids: Vec<String> = pairs
+        .iter()
+        .filter(|(key, _)| key == "id")
+        .map(|(_, id)| id.clone())
+        .collect();
+    if ids.is_empty() {
+        return Err(ApiError::bad_request("at least one id is required"));
+    }
+    if ids.len() > config.max_param_values {
+        let message = format!(
+            "at most {} id parameters are accepted",
+            config.max_param_values
+        );
+        return Err(ApiError::bad_request(message));
+    }
+    let flag_param = |name: &str| -> Result<bool, ApiError> {
+        match pairs.iter().find(|(key, _)| key == name) {
+            None => Ok(false),
+            Some((_, value)) => {
+                let invalid =
+                    || ApiError::bad_request(format!("{} must be {}", name, flag::ACCEPTED));
+                flag::parse(value).ok_or_else(invalid)
+            }
+        }
+    };
+    let ordered = flag_param("ordered")?;
+    let strict = flag_param("strict")?;
+
+    let mut heroes = deadline.run(repo.get_by_ids(&ids)).await??;
+    if strict {
+        let found: HashSet<&str> = heroes.iter().map(|hero| hero.id.as_str()).collect();
+        let mut missing: Vec<String> = vec![];
+        for id in &ids {
+            if !found.contains(id.as_str()) && !missing.contains(id) {
+                missing.push(id.clone());
+            }
+        }
+        if !missing.is_empty() {
+            let message = format!("no heroes have the ids {}", missing.join(", "));
+            return Err(ApiError::from_data_error(
+                DataAccessError::NotFound,
+                message,
+            ));
+        }
+    }
+    if ordered {
+        heroes = in_order_of(heroes, &ids);
+    } else {
+        heroes.sort_by(|a, b| id_order(&a.id, &b.id));
+    }
+    Ok(pretty.json(heroes))
+}
+
+/// `heroes` in the order of `ids`, whatever order the repository found them in
+fn in_order_of(heroes: Vec<Hero>, ids: &[String]) -> Vec<Hero> {
+    let by_id: HashMap<&str, &Hero> = heroes.iter().map(|hero| (hero.id.as_str(), hero)).collect();
+    ids.iter()
+        .filter_map(|id| by_id.get(id.as_str()).map(|hero| (*hero).clone()))
+        .collect()
+}
+
+/// Create or replace heroes by id, answering how many were created and updated
+#[debug_handler(state = AppState)]
+async fn upsert_heroes(
+    State(repo): State<DynHeroesRepository>,
+    State(metrics): State<Arc<AppMetrics>>,
+    State(events): State<HeroEvents>,
+    deadline: Deadline,
+    pretty: Pretty,
+    JsonBody(batch): JsonBody<Vec<BatchHero>>,
+) -> Result<PrettyJson<UpsertReport>, ApiError> {
+    let invalid = |index: usize, problem: &str| {
+        ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "invalid_batch",
+            format!("hero at index {} {}", index, problem),
+        )
+    };
+    let mut heroes = Vec::with_capacity(batch.len());
+    for (index, hero) in batch.into_iter().enumerate() {
+        if hero.id.is_empty() {
+            return Err(invalid(index, "has an empty id"));
+        }
+        let hero = Hero {
+            id: hero.id,
+            name: hero.name,
+            updated_at: None,
+            tags: hero.tags,
+            power_level: hero.power_level,
+        };
+        match stored_hero(hero) {
+            Ok(hero) => heroes.push(hero),
+            Err(problems) => {
+                let problem = format!("is invalid: {}", problems.join("; "));
+                return Err(invalid(index, &problem));
+            }
+        }
+    }
+    let mut ids: Vec<String> = heroes.iter().map(|hero| hero.id.clone()).collect();
+    ids.sort();
+    ids.dedup();
+    let before = deadline.run(repo.get_by_ids(&ids)).await??;
+    let report = deadline.run(repo.upsert_many(&heroes)).await??;
+    AppMetrics::add(&metrics.heroes_created, report.created);
+    AppMetrics::add(&metrics.heroes_updated, report.updated);
+    // read back, for the `updated_at` the repository gave them
+    let after = match deadline.run(repo.get_by_ids(&ids)).await {
+        Ok(Ok(after)) => after,
+        _ => last_occurrences(&heroes).into_iter().cloned().collect(),
+    };
+    events.publish_batch(before, after, false);
+    Ok(pretty.json(report))
+}
+
+/// Create the heroes of a `text/csv` upload, whose header has a `name` column and maybe an
+/// `id` one, like `export.csv`
+///
+/// Rows are validated and created one at a time, those failing being reported along with
+/// the reason; a malformed document is a `400` creating nothing. A repository failing for
+/// another reason than the row itself ends the import, keeping the heroes created so far.
+#[debug_handler(state = AppState)]
+async fn import_heroes(
+    State(repo): State<DynHeroesRepository>,
+    State(metrics): State<Arc<AppMetrics>>,
+    State(events): State<HeroEvents>,
+    deadline: Deadline,
+    pretty: Pretty,
+    CsvBody(body): CsvBody,
+) -> Result<PrettyJson<ImportReport>, ApiError> {
+    let mut records = csv::read_records(&body)
+        .map_err(|reason| ApiError::bad_request(format!("malformed CSV: {}", reason)))?
+        .into_iter();
+    let header = records.next().unwrap_or_default();
+    if let Some(unknown) = header
+        .iter()
+        .find(|column| !matches!(column.as_str(), "id" | "name"))
+    {
+        return Err(ApiError::bad_request(format!(
+            "unknown CSV column '{}'",
+            unknown
+        )));
+    }
+    let column = |name: &str| header.iter().position(|column| column == name);
+    let Some(name_column) = column("name") else {
+        return Err(ApiError::bad_request("the CSV header needs a name column"));
+    };
+    let id_column = column("id");
+
+    let mut report = ImportReport::default();
+    for (row, record) in (1..).zip(records) {
+        if record.len() != header.len() {
+            let reason = format!("expected {} fields, found {}", header.len(), record.len());
+            report.failed.push(ImportFailure { row, reason });
+            continue;
+        }
+        let payload = match HeroName::new(&record[name_column]) {
+            Ok(name) => HeroPayload {
+                name,
+                power_level: 0,
+            },
+            Err(error) => {
+                let reason = error.to_string();
+                report.failed.push(ImportFailure { row, reason });
+                continue;
+            }
+        };
+        let errors = deadline.run(validation_errors(&repo, &payload)).await??;
+        if !errors.is_empty() {
+            let reason = errors.join("; ");
+            report.failed.push(ImportFailure { row, reason });
+            continue;
+        }
+        let created = match id_column.map(|id| record[id].as_str()) {
+            Some(id) if !id.is_empty() => deadline.run(repo.create_if_absent(id, payload)).await?,
+            _ => deadline.run(repo.create(payload)).await?,
+        };
+        match created {
+            Ok(hero) => {
+                report.created += 1;
+                AppMetrics::increment(&metrics.heroes_created);
+                events.publish(AuditAction::Create, &hero);
+            }
+            Err(error @ DataAccessError::AlreadyExists) => {
+                let reason = ApiError::from(error).message;
+                report.failed.push(ImportFailure { row, reason });
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(pretty.json(report))
+}
+
+/// Replace the whole dataset; nothing changes unless every hero is valid
+#[debug_handler(state = AppState)]
+async fn reload_heroes(
+    State(repo): State<DynHeroesRepository>,
+    State(events): State<HeroEvents>,
+    JsonBody(heroes): JsonBody<Vec<Hero>>,
+) -> Result<StatusCode, ApiError> {
+    let heroes = checked_dataset(heroes).map_err(|problem| {
+        ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_dataset", problem)
+    })?;
+
+    let before: Vec<Hero> = repo.stream_all().try_collect().await.unwrap_or_default();
+    repo.replace_all(heroes.clone()).await?;
+    events.publish_batch(before, heroes, true);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// `hero` as it's stored, its tags normalized, sorted and each kept once, or the reasons
+/// why it can't be: those of its name and power level, or a blank tag
+fn stored_hero(mut hero: Hero) -> Result<Hero, Vec<String>> {
+    let payload = HeroPayload {
+        name: hero.name,
+        power_level: hero.power_level,
+    };
+    let mut problems = payload.problems();
+    let mut tags = normalize_tags(hero.tags).unwrap_or_else(|error| {
+        problems.push(error.message);
+        vec![]
+    });
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    tags.sort();
+    tags.dedup();
+    hero.name = payload.name;
+    hero.tags = tags;
+    Ok(hero)
+}
+
+/// `heroes` as stored by a whole dataset, or why they can't make one: an empty or a
+/// duplicated id, or a hero refused by `stored_hero`
+fn checked_dataset(heroes: Vec<Hero>) -> Result<Vec<Hero>, String> {
+    let mut ids = HashSet::new();
+    let mut dataset = Vec::with_capacity(heroes.len());
+    for (index, hero) in heroes.into_iter().enumerate() {
+        let problem = if hero.id.is_empty() {
+            "has an empty id".to_string()
+        } else if !ids.insert(hero.id.clone()) {
+            "duplicates the id of a previous hero".to_string()
+        } else {
+            match stored_hero(hero) {
+                Ok(hero) => {
+                    dataset.push(hero);
+                    continue;
+                }
+                Err(problems) => format!("is invalid: {}", problems.join("; ")),
+            }
+        };
+        return Err(format!("hero at index {} {}", index, problem));
+    }
+    Ok(dataset)
+}
+
+/// Entry point of the api: `{ "links": { ... } }` to its top-level resources, absolute
+/// when the request has a `Host`, like the `Link` headers of pages
+#[debug_handler(state = AppState)]
+async fn get_root(headers: HeaderMap, pretty: Pretty) -> impl IntoResponse {
+    let origin = pagination::origin(&headers);
+    let link = |path: &str| format!("{}{}", origin, path);
+    pretty.json(serde_json::json!({
+        "links": {
+            "heroes": link(deprecation::CURRENT_PREFIX),
+            "health": link("/health"),
+            "metrics": link("/metrics"),
+            "version": link("/version"),
+        }
+    }))
+}
+
+/// Build information, to check which build is live
+#[debug_handler(state = AppState)]
+async fn get_version(pretty: Pretty) -> impl IntoResponse {
+    pretty.json(serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_sha": option_env!("GIT_SHA").unwrap_or("unknown"),
+        "built_at": option_env!("BUILT_AT").unwrap_or("unknown"),
+    }))
+}
+
+/// Business counters, in the Prometheus text format
+#[debug_handler(state = AppState)]
+async fn get_metrics(State(metrics): State<Arc<AppMetrics>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
+}
+
+/// Method and path of every route of the application, in registration order
+#[debug_handler(state = AppState)]
+async fn get_routes(
+    Extension(manifest): Extension<Arc<RouteManifest>>,
+    pretty: Pretty,
+) -> impl IntoResponse {
+    pretty.json(manifest.routes().to_vec())
+}
+
+/// Effective configuration, secrets redacted
+#[debug_handler(state = AppState)]
+async fn get_config(State(config): State<Arc<Config>>, pretty: Pretty) -> impl IntoResponse {
+    pretty.json(config.redacted())
+}
+
+/// Add and remove tags of a hero, answering its new version, or `304` without writing
+/// anything when its tags stay as they were
+#[debug_handler(state = AppState)]
+async fn update_hero_tags(
+    State(repo): State<DynHeroesRepository>,
+    State(events): State<HeroEvents>,
+    deadline: Deadline,
+    pretty: Pretty,
+    Path(id): Path<String>,
+    JsonBody(changes): JsonBody<TagChanges>,
+) -> Result<Response, ApiError> {
+    let changes = TagChanges {
+        add: normalize_tags(changes.add)?,
+        remove: normalize_tags(changes.remove)?,
+    };
+    let result = deadline.run(repo.update_tags(&id, changes)).await?;
+    if let Err(DataAccessError::Unchanged) = result {
+        return Ok(StatusCode::NOT_MODIFIED.into_response());
+    }
+    let hero = result?;
+    events.publish(AuditAction::Update, &hero);
+    Ok(pretty.json(hero).into_response())
+}
+
+/// Count a view of a hero, answering its views so far
+#[debug_handler(state = AppState)]
+async fn record_hero_view(
+    State(repo): State<DynHeroesRepository>,
+    deadline: Deadline,
+    pretty: Pretty,
+    Path(id): Path<String>,
+) -> Result<PrettyJson<ViewCount>, ApiError> {
+    let views = deadline.run(repo.record_view(&id)).await??;
+    Ok(pretty.json(ViewCount { id, views }))
+}
+
+/// Tags as stored: trimmed and lowercase, `422` when one is blank
+fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, ApiError> {
+    tags.iter()
+        .map(|tag| match tag.trim() {
+            "" => Err(ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "invalid_tags",
+                "tags must not be blank",
+            )),
+            tag => Ok(tag.to_lowercase()),
+        })
+        .collect()
+}
+
+/// Fields of a hero a merge patch may change
+#[derive(Serialize, Deserialize)]
+struct PatchableHero {
+    name: HeroName,
+    /// cleared by a `null` patch
+    #[serde(default)]
+    tags: Vec<String>,
+    /// back to 0 with a `null` patch
+    #[serde(default)]
+    power_level: u32,
+}
+
+/// Apply an `application/merge-patch+json` body (RFC 7386) to the name, tags and power level
+/// of a hero: fields set in the patch replace the hero's, `null` ones clear them, absent
+/// ones are kept. A patch changing nothing is answered with `304` and writes nothing
+#[debug_handler(state = AppState)]
+async fn patch_hero(
+    State(repo): State<DynHeroesRepository>,
+    State(metrics): State<Arc<AppMetrics>>,
+    State(events): State<HeroEvents>,
+    deadline: Deadline,
+    pretty: Pretty,
+    Path(id): Path<String>,
+    MergePatch(patch): MergePatch,
+) -> Result<Response, ApiError> {
+    let hero = deadline.run(repo.get_by_id(&id)).await??;
+    let mut document = serde_json::to_value(PatchableHero {
+        name: hero.name.clone(),
+        tags: hero.tags.clone(),
+        power_level: hero.power_level,
+    })
+    .map_err(|_| ApiError::internal())?;
+    merge_patch::apply(&mut document, &patch);
+    let patched: PatchableHero =
+        serde_json::from_value(document).map_err(|error| invalid_hero(&[error.to_string()]))?;
+    let payload = HeroPayload {
+        name: patched.name,
+        power_level: patched.power_level,
+    };
+    let errors = payload.problems();
+    if !errors.is_empty() {
+        return Err(invalid_hero(&errors));
+    }
+    let tags = normalize_tags(patched.tags)?;
+
+    // one write, compared with the hero as it is when written
+    let result = deadline
+        .run(repo.update_with_tags(&id, payload, tags))
+        .await?;
+    if let Err(DataAccessError::Unchanged) = result {
+        return Ok(StatusCode::NOT_MODIFIED.into_response());
+    }
+    let hero = metrics.observe(result)?;
+    AppMetrics::increment(&metrics.heroes_updated);
+    events.publish(AuditAction::Update, &hero);
+    Ok(pretty.json(hero).into_response())
+}
+
+/// The hero and its alphabetical neighbors, for prev/next navigation
+#[debug_handler(state = AppState)]
+async fn get_hero_context(
+    State(repo): State<DynHeroesRepository>,
+    deadline: Deadline,
+    pretty: Pretty,
+    Path(id): Path<String>,
+) -> Result<PrettyJson<HeroContext>, ApiError> {
+    Ok(pretty.json(deadline.run(repo.get_with_neighbors(&id)).await??))
+}
+
+/// Query of `GET /heroes/compare`
+#[derive(Deserialize, Debug)]
+pub struct CompareQuery {
+    a: String,
+    b: String,
+}
+
+/// How hero `a` compares to hero `b`, each difference being the one of `a` minus the one
+/// of `b`
+#[derive(Serialize, Debug, Eq, PartialEq)]
+struct Comparison {
+    power_level_difference: i64,
+    name_length_difference: i64,
+}
+
+impl Comparison {
+    fn of(a: &Hero, b: &Hero) -> Self {
+        let name_length = |hero: &Hero| hero.name.chars().count() as i64;
+        Comparison {
+            power_level_difference: i64::from(a.power_level) - i64::from(b.power_level),
+            name_length_difference: name_length(a) - name_length(b),
+        }
+    }
+}
+
+/// Two heroes side by side, with how they compare
+#[derive(Serialize, Debug)]
+struct Versus {
+    a: Hero,
+    b: Hero,
+    comparison: Comparison,
+}
+
+/// `GET /heroes/compare?a=1&b=2`: heroes `a` and `b` side by side, for versus views
+///
+/// Both heroes are fetched at once; when either is missing the `404` names the ids and the
+/// parameters which gave them, like `no heroes have the ids 7 (b)`.
+#[debug_handler(state = AppState)]
+async fn compare_heroes(
+    State(repo): State<DynHeroesRepository>,
+    deadline: Deadline,
+    pretty: Pretty,
+    Query(query): Query<CompareQuery>,
+) -> Result<PrettyJson<Versus>, ApiError> {
+    let both = async { futures::join!(repo.get_by_id(&query.a), repo.get_by_id(&query.b)) };
+    let (a, b) = deadline.run(both).await?;
+
+    let missing: Vec<String> = [("a", &a, &query.a), ("b", &b, &query.b)]
+        .into_iter()
+        .filter(|(_, hero, _)| matches!(hero, Err(DataAccessError::NotFound)))
+        .map(|(param, _, id)| format!("{} ({})", id, param))
+        .collect();
+    if !missing.is_empty() {
+        let message = format!("no heroes have the ids {}", missing.join(", "));
+        return Err(ApiError::from_data_error(
+            DataAccessError::NotFound,
+            message,
+        ));
+    }
+    let (a, b) = (a?, b?);
+    let comparison = Comparison::of(&a, &b);
+    Ok(pretty.json(Versus { a, b, comparison }))
+}
+
+/// Lowest `hero_name::similarity` of the heroes listed as similar to another
+const SIMILARITY_THRESHOLD: f64 = 0.6;
+
+/// Other heroes whose name is close to the one of the given hero, most similar first
+#[debug_handler(state = AppState)]
+async fn get_similar_heroes(
+    State(repo): State<DynHeroesRepository>,
+    deadline: Deadline,
+    pretty: Pretty,
+    Path(id): Path<String>,
+) -> Result<PrettyJson<Vec<Hero>>, ApiError> {
+    let base = deadline.run(repo.get_by_id(&id)).await??;
+    let heroes: Vec<Hero> = deadline.run(repo.stream_all().try_collect()).await??;
+
+    let mut similar: Vec<(f64, Hero)> = heroes
+        .into_iter()
+        .filter(|hero| hero.id != base.id)
+        .map(|hero| (hero_name::similarity(&base.name, &hero.name), hero))
+        .filter(|(similarity, _)| *similarity >= SIMILARITY_THRESHOLD)
+        .collect();
+    similar.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    Ok(pretty.json(similar.into_iter().map(|(_, hero)| hero).collect()))
+}
+
+#[debug_handler(state = AppState)]
+async fn get_hero_history(
+    State(audit_log): State<DynAuditLog>,
+    pretty: Pretty,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    pretty.json(audit_log.history(&id).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockall::predicate::*;
+    use axum::{body::Body, http::Request};
+    use rstest::rstest;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use fn_repository::FnHeroesRepository;
+
+    fn send_get_request(uri: &str) -> Request<Body> {
+        Request::builder()
+            .uri(uri)
+            .method("GET")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn send_json_request(method: &str, uri: &str, body: Value) -> Request<Body> {
+        Request::builder()
+            .uri(uri)
+            .method(method)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn body_json(response: Response) -> Value {
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn app_with_config(
+        repo: impl HeroesRepositoryTrait + Send + Sync + 'static,
+        config: Config,
+    ) -> Router {
+        let state = AppState {
+            repo: Arc::new(repo),
+            audit_log: Arc::new(InMemoryAuditLog::default()),
+            metrics: Default::default(),
+            events: Default::default(),
+            started: Default::default(),
+            tasks: Default::default(),
+            warm_up: Default::default(),
+            breaker: None,
+            config: Arc::new(LiveConfig::new(config)),
+        };
+        heroes_routes(&mut RouteManifest::default()).with_state(state)
+    }
+
+    fn app(repo: impl HeroesRepositoryTrait + Send + Sync + 'static) -> Router {
+        app_with_config(repo, Config::default())
+    }
+
+    fn state_with_config(config: Config) -> AppState {
+        AppState {
+            repo: Arc::new(MockHeroesRepositoryTrait::new()),
+            audit_log: Arc::new(InMemoryAuditLog::default()),
+            metrics: Default::default(),
+            events: Default::default(),
+            started: Default::default(),
+            tasks: Default::default(),
+            warm_up: Default::default(),
+            breaker: None,
+            config: Arc::new(LiveConfig::new(config)),
+        }
+    }
+    #[rstest]
+    #[case("/?name=Wonder", "Wonder%", )] // verify that % is appended to the filter
+    #[case("/?name=Wonder%", "Wonder%")] // verify that % is not appended to the filter if it already ends with %
+    #[case("/", "%")] // verify that % is used as the default filter
+    #[tokio::test]
+    async fn get_by_name_success(#[case] uri: &'static str, #[case] expected_filter: &'static str) {
+        let dummy_heroes = vec![Default::default()];
+
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        let result = Ok(dummy_heroes.clone());
+
+        repo_mock
+            .expect_get_by_name()
+            .with(eq(expected_filter))
+            .return_once(move |_| result);
+
+        let app = app(repo_mock);
+
+        let response = app.oneshot(send_get_request(uri)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+
+        assert!(matches!(body, Value::Array{ .. })); // don't care what the result is: just check we receive json array
+    }
+
+    #[rstest]
+    #[case(DataAccessError::NotFound, StatusCode::NOT_FOUND)]
+    #[case(DataAccessError::TechnicalError, StatusCode::INTERNAL_SERVER_ERROR)]
+    #[tokio::test]
+    async fn get_by_name_failure(#[case] db_result: DataAccessError, #[case] expected_status: StatusCode) {
+
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+
+        repo_mock
+            .expect_get_by_name()
+            .with(eq("Spider%"))
+            .return_once(move |_| Err(db_result));
+
+        let app = app(repo_mock);
+
+        let response = app
+            .oneshot(send_get_request("/?name=Spider"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), expected_status);
+    }
+
+    #[tokio::test]
+    async fn empty_name_is_rejected_by_default() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock.expect_get_by_name().never();
+
+        let response = app(repo_mock)
+            .oneshot(send_get_request("/?name="))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn empty_name_lists_all_when_allowed() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock
+            .expect_get_by_name()
+            .with(eq("%"))
+            .return_once(|_| Ok(vec![Default::default()]));
+
+        let config = Config {
+            reject_empty_name: false,
+            ..Default::default()
+        };
+        let response = app_with_config(repo_mock, config)
+            .oneshot(send_get_request("/?name="))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn update_is_visible_in_history() {
+        let audit_log: DynAuditLog = Arc::new(InMemoryAuditLog::default());
+        let state = AppState {
+            repo: Arc::new(AuditedHeroesRepository::new(
+                InMemoryHeroesRepository::default(),
+                audit_log.clone(),
+            )),
+            audit_log,
+            metrics: Default::default(),
+            events: Default::default(),
+            started: Default::default(),
+            tasks: Default::default(),
+            warm_up: Default::default(),
+            breaker: None,
+            config: Arc::new(LiveConfig::new(Config::default())),
+        };
+        let app = heroes_routes(&mut RouteManifest::default()).with_state(state);
+
+        let update = send_json_request("PUT", "/1", serde_json::json!({ "name": "Diana Prince" }));
+        let response = app.clone().oneshot(update).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app.oneshot(send_get_request("/1/history")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let history = body_json(response).await;
+        assert_eq!(history.as_array().unwrap().len(), 1);
+        assert_eq!(history[0]["action"], "update");
+        assert_eq!(history[0]["before"]["name"], "Wonder Woman");
+        assert_eq!(history[0]["after"]["name"], "Diana Prince");
+    }
+
+    #[rstest]
+    #[case::everywhere(false)]
+    #[case::heroes_only(true)]
+    #[tokio::test]
+    async fn cors_preflight_advertises_max_age(#[case] heroes_only: bool) {
+        let config = Config {
+            cors: config::CorsConfig {
+                allowed_origins: vec!["https://heroes.example".to_string()],
+                max_age: Some(600),
+                allow_credentials: true,
+                heroes_only,
+            },
+            ..Default::default()
+        };
+        let state = state_with_config(config);
+
+        let preflight = Request::builder()
+            .uri("/heroes/")
+            .method("OPTIONS")
+            .header("origin", "https://heroes.example")
+            .header("access-control-request-method", "GET")
+            .body(Body::empty())
+            .unwrap();
+        let response = build_app(state).oneshot(preflight).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let headers = response.headers();
+        assert_eq!(
+            headers["access-control-allow-origin"],
+            "https://heroes.example"
+        );
+        assert_eq!(headers["access-control-max-age"], "600");
+        assert_eq!(headers["access-control-allow-credentials"], "true");
+    }
+
+    #[rstest]
+    #[case("/heroes/", true)]
+    #[case("/metrics", false)]
+    #[case("/health", false)]
+    #[tokio::test]
+    async fn cors_can_be_limited_to_the_heroes_routes(#[case] uri: &str, #[case] cors: bool) {
+        let config = Config {
+            cors: config::CorsConfig {
+                allowed_origins: vec!["*".to_string()],
+                heroes_only: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(config)
+        };
+
+        let request = Request::builder()
+            .uri(uri)
+            .header("origin", "https://heroes.example")
+            .body(Body::empty())
+            .unwrap();
+        let response = build_app(state).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers.contains_key("access-control-allow-origin"), cors);
+    }
+
+    #[tokio::test]
+    async fn not_found_body_names_the_filter() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock
+            .expect_get_by_name()
+            .return_once(|_| Err(DataAccessError::NotFound));
+
+        let response = app(repo_mock)
+            .oneshot(send_get_request("/?name=Spider"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = body_json(response).await;
+        assert_eq!(body["error"], "not_found");
+        assert_eq!(body["message"], "no heroes match filter 'Spider%'");
+    }
+
+    #[tokio::test]
+    async fn initial_facets_count_the_fixture() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request("/facets/initial"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(response).await,
+            serde_json::json!({ "D": 1, "W": 1 })
+        );
+    }
+
+    #[tokio::test]
+    async fn computed_fields_are_included_on_request() {
+        let app = app(InMemoryHeroesRepository::default());
+
+        let response = app
+            .clone()
+            .oneshot(send_get_request("/1?include=computed"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["name"], "Wonder Woman");
+        assert_eq!(
+            body["_computed"],
+            serde_json::json!({ "name_length": 12, "initial": "W" })
+        );
+
+        let response = app.oneshot(send_get_request("/1")).await.unwrap();
+        assert!(body_json(response).await.get("_computed").is_none());
+    }
+
+    #[rstest]
+    #[case(false, StatusCode::NOT_FOUND)]
+    #[case(true, StatusCode::OK)]
+    #[tokio::test]
+    async fn missing_hero_is_null_when_configured(
+        #[case] missing_hero_as_null: bool,
+        #[case] expected_status: StatusCode,
+    ) {
+        let config = Config {
+            missing_hero_as_null,
+            ..Default::default()
+        };
+        let app = app_with_config(InMemoryHeroesRepository::default(), config);
+
+        let response = app.clone().oneshot(send_get_request("/404")).await.unwrap();
+        assert_eq!(response.status(), expected_status);
+        if missing_hero_as_null {
+            assert_eq!(
+                body_json(response).await,
+                serde_json::json!({ "hero": null })
+            );
+        }
+
+        // heroes which exist are sent as they are in both modes
+        let response = app.oneshot(send_get_request("/1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await["name"], "Wonder Woman");
+    }
+
+    #[tokio::test]
+    async fn initial_facets_of_empty_repository() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock
+            .expect_count_by_initial()
+            .return_once(|| Ok(vec![]));
+
+        let response = app(repo_mock)
+            .oneshot(send_get_request("/facets/initial"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await, serde_json::json!({}));
+    }
+
+    #[tokio::test]
+    async fn stats_aggregate_the_fixture() {
+        let response = app(tagged_heroes())
+            .oneshot(send_get_request("/stats"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(response).await,
+            serde_json::json!({
+                "total": 4,
+                "per_tag": { "amazon": 1, "antihero": 1, "hero": 1, "mercenary": 2, "villain": 2 },
+                "longest_name": "Wonder Woman",
+                "shortest_name": "Joker",
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn digest_changes_with_the_dataset_only() {
+        let digest = |app: Router| async move {
+            let response = app.oneshot(send_get_request("/digest")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            body_json(response).await["digest"].clone()
+        };
+        let heroes = app(tagged_heroes());
+
+        let before = digest(heroes.clone()).await;
+        assert_eq!(digest(heroes.clone()).await, before);
+        // same heroes, other repository
+        assert_eq!(digest(app(tagged_heroes())).await, before);
+
+        let create = send_json_request("POST", "/", serde_json::json!({ "name": "Storm" }));
+        let response = heroes.clone().oneshot(create).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_ne!(digest(heroes).await, before);
+    }
+
+    #[rstest]
+    #[case(true, Some(serde_json::json!(["Wonder Woman"])))]
+    #[case(false, None)]
+    #[tokio::test]
+    async fn unmatched_name_filter_suggests_close_names(
+        #[case] suggest_names: bool,
+        #[case] expected: Option<Value>,
+    ) {
+        let config = Config {
+            suggest_names,
+            ..Default::default()
+        };
+        let response = app_with_config(tagged_heroes(), config)
+            .oneshot(send_get_request("/?name=Wondr"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = body_json(response).await;
+        assert_eq!(body["message"], "no heroes match filter 'Wondr%'");
+        assert_eq!(body.get("suggestions").cloned(), expected);
+    }
+
+    fn rivals() -> InMemoryHeroesRepository {
+        let hero = |id: &str, name: &str, power_level| Hero {
+            id: id.to_string(),
+            name: HeroName::new(name).unwrap(),
+            power_level,
+            ..Default::default()
+        };
+        InMemoryHeroesRepository::new(vec![
+            hero("1", "Wonder Woman", 90),
+            hero("2", "Deadpool", 75),
+        ])
+    }
+
+    #[tokio::test]
+    async fn heroes_are_compared_side_by_side() {
+        let response = app(rivals())
+            .oneshot(send_get_request("/compare?a=1&b=2"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let versus = body_json(response).await;
+        assert_eq!(versus["a"]["name"], "Wonder Woman");
+        assert_eq!(versus["b"]["name"], "Deadpool");
+        assert_eq!(
+            versus["comparison"],
+            serde_json::json!({ "power_level_difference": 15, "name_length_difference": 4 })
+        );
+    }
+
+    #[rstest]
+    #[case("/compare?a=1&b=7", "no heroes have the ids 7 (b)")]
+    #[case("/compare?a=9&b=7", "no heroes have the ids 9 (a), 7 (b)")]
+    #[tokio::test]
+    async fn comparison_names_the_missing_heroes(#[case] uri: &str, #[case] message: &str) {
+        let response = app(rivals()).oneshot(send_get_request(uri)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body_json(response).await["message"], message);
+    }
+
+    fn export_app(key: Option<&str>) -> Router {
+        let config = Config {
+            export_cursor_key: key.map(str::to_string),
+            ..Default::default()
+        };
+        app_with_config(tagged_heroes(), config)
+    }
+
+    /// Ids of an export page, with its `next` cursor
+    async fn export_page(app: Router, uri: &str) -> (Vec<String>, Option<String>) {
+        let response = app.oneshot(send_get_request(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let page = body_json(response).await;
+        let ids = page["items"].as_array().unwrap().iter();
+        let ids = ids.map(|hero| hero["id"].as_str().unwrap().to_string());
+        (ids.collect(), page["next"].as_str().map(str::to_string))
+    }
+
+    #[tokio::test]
+    async fn export_resumes_from_its_cursor() {
+        let app = export_app(Some("k3y"));
+
+        let (first, next) = export_page(app.clone(), "/export?limit=3").await;
+        let uri = format!("/export?limit=3&cursor={}", next.unwrap());
+        let (rest, next) = export_page(app, &uri).await;
+
+        assert_eq!(first, ["1", "2", "3"]);
+        assert_eq!(rest, ["4"]);
+        assert_eq!(next, None);
+    }
+
+    #[tokio::test]
+    async fn export_cursor_survives_a_restart() {
+        let (_, next) = export_page(export_app(Some("k3y")), "/export?limit=2").await;
+
+        // a new instance, with nothing but the key in common
+        let uri = format!("/export?cursor={}", next.unwrap());
+        let (rest, _) = export_page(export_app(Some("k3y")), &uri).await;
+
+        assert_eq!(rest, ["3", "4"]);
+    }
+
+    #[rstest]
+    #[case::tampered(Some("k3y"), true, StatusCode::BAD_REQUEST)]
+    #[case::other_key(Some("other"), false, StatusCode::BAD_REQUEST)]
+    #[case::no_key(None, false, StatusCode::NOT_IMPLEMENTED)]
+    #[tokio::test]
+    async fn export_refuses_cursors_it_did_not_sign(
+        #[case] key: Option<&str>,
+        #[case] tamper: bool,
+        #[case] expected: StatusCode,
+    ) {
+        let (_, next) = export_page(export_app(Some("k3y")), "/export?limit=1").await;
+        let mut cursor = next.unwrap();
+        if tamper {
+            // "1" becomes "3", skipping a hero
+            cursor = cursor.replacen("31", "33", 1);
+        }
+
+        let uri = format!("/export?cursor={}", cursor);
+        let response = export_app(key)
+            .oneshot(send_get_request(&uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), expected);
+    }
+
+    #[tokio::test]
+    async fn stats_of_empty_repository_are_zeroed() {
+        let response = app(InMemoryHeroesRepository::new(vec![]))
+            .oneshot(send_get_request("/stats"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(response).await,
+            serde_json::json!({
+                "total": 0,
+                "per_tag": {},
+                "longest_name": null,
+                "shortest_name": null,
+            })
+        );
+    }
+
+    #[rstest]
+    #[case(true, StatusCode::OK)]
+    #[case(false, StatusCode::NOT_FOUND)] // exact matching: "Wonder" is not "Wonder Woman"
+    #[tokio::test]
+    async fn auto_append_wildcard_can_be_disabled(
+        #[case] auto_append_wildcard: bool,
+        #[case] expected_status: StatusCode,
+    ) {
+        let config = Config {
+            auto_append_wildcard,
+            ..Default::default()
+        };
+
+        let response = app_with_config(InMemoryHeroesRepository::default(), config)
+            .oneshot(send_get_request("/?name=Wonder"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), expected_status);
+    }
+
+    #[rstest]
+    #[case("/?name=Wonder%25", StatusCode::NOT_FOUND)]
+    #[case("/?name=Wonder", StatusCode::NOT_FOUND)]
+    #[case("/?name=Wonder%20Woman", StatusCode::OK)]
+    #[case("/", StatusCode::OK)]
+    #[tokio::test]
+    async fn disabled_wildcards_match_names_literally(
+        #[case] uri: &str,
+        #[case] expected_status: StatusCode,
+    ) {
+        let config = Config {
+            disable_wildcards: true,
+            ..Default::default()
+        };
+
+        let response = app_with_config(InMemoryHeroesRepository::default(), config)
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), expected_status);
+    }
+
+    #[rstest]
+    #[case("/?name=%25Wonder%25Wo", false)] // three with the appended one
+    #[case("/?name=%25a%25b%25c%25d%25", true)]
+    #[tokio::test]
+    async fn wildcards_of_name_filters_are_capped(#[case] uri: &str, #[case] rejected: bool) {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status() == StatusCode::BAD_REQUEST, rejected);
+        if rejected {
+            assert_eq!(
+                body_json(response).await["message"],
+                "name filters accept at most 4 % wildcards"
+            );
+        }
+    }
+
+    #[rstest]
+    #[case(true, &["1", "2", "3"])]
+    #[case(false, &["1", "2", "1", "3", "2"])]
+    #[tokio::test]
+    async fn duplicate_heroes_are_folded_when_configured(
+        #[case] dedupe_results: bool,
+        #[case] expected_ids: &[&str],
+    ) {
+        let hero = |id: &str, name: &str| Hero {
+            id: id.to_string(),
+            name: HeroName::new(name).unwrap(),
+            ..Default::default()
+        };
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock.expect_get_by_name().return_once(move |_| {
+            Ok(vec![
+                hero("1", "Wonder Woman"),
+                hero("2", "Deadpool"),
+                // rows of a join, the first occurrence is kept
+                hero("1", "Wonder Woman (duplicate)"),
+                hero("3", "Storm"),
+                hero("2", "Deadpool"),
+            ])
+        });
+        let config = Config {
+            dedupe_results,
+            ..Default::default()
+        };
+
+        let response = app_with_config(repo_mock, config)
+            .oneshot(send_get_request("/"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let heroes = body_json(response).await;
+        let ids: Vec<&str> = heroes
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hero| hero["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, expected_ids);
+        assert_eq!(heroes[0]["name"], "Wonder Woman");
+    }
+
+    #[rstest]
+    #[case(true, true)]
+    #[case(false, false)] // left out in production, whatever the client asks for
+    #[tokio::test]
+    async fn timing_is_broken_down_when_enabled(#[case] debug_timing: bool, #[case] sent: bool) {
+        let config = Config {
+            debug_timing,
+            ..Default::default()
+        };
+
+        let response = app_with_config(InMemoryHeroesRepository::default(), config)
+            .oneshot(send_get_request("/1?debug=timing"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["id"], "1");
+        let timing = &body["_timing"];
+        assert_eq!(timing.is_object(), sent);
+        for phase in ["validation_ms", "repository_ms", "serialization_ms"] {
+            assert_eq!(timing[phase].is_f64(), sent, "{}", phase);
+        }
+    }
+
+    #[rstest]
+    #[case(None, true, false, Ok("%"))]
+    #[case(Some(""), true, false, Err(()))] // REJECT_EMPTY_NAME is on by default
+    #[case(Some("Wonder"), true, false, Ok("Wonder%"))]
+    #[case(Some("Wonder%"), true, false, Ok("Wonder%"))]
+    #[case(Some("Wonder"), false, false, Ok("Wonder"))]
+    #[case(Some("%Won%der"), true, false, Ok("%Won%der%"))]
+    #[case(Some("Wonder%"), true, true, Ok("Wonder\\%"))]
+    #[case(None, true, true, Ok("%"))]
+    #[case(Some("%a%b%c%d"), true, false, Err(()))]
+    #[case(Some("%a%b%c%d"), false, false, Ok("%a%b%c%d"))]
+    fn name_filter_follows_the_configuration(
+        #[case] name: Option<&str>,
+        #[case] auto_append_wildcard: bool,
+        #[case] disable_wildcards: bool,
+        #[case] expected: Result<&str, ()>,
+    ) {
+        let config = Config {
+            auto_append_wildcard,
+            disable_wildcards,
+            ..Default::default()
+        };
+
+        let filter = name_filter(name, &config);
+
+        assert_eq!(filter.as_deref().map_err(|_| ()), expected);
+    }
+
+    #[rstest]
+    #[case("/", None)]
+    #[case("/?name=Wonder", Some("Wonder"))]
+    #[case("/?name=Batman", Some("Batman"))]
+    #[tokio::test]
+    async fn applied_filter_is_echoed_when_configured(
+        #[case] uri: &str,
+        #[case] name: Option<&str>,
+    ) {
+        let config = Config {
+            applied_filter_header: true,
+            ..Default::default()
+        };
+        let expected = name_filter(name, &config).unwrap();
+
+        let response = app_with_config(InMemoryHeroesRepository::default(), config)
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.headers()[APPLIED_FILTER_HEADER], expected.as_str());
+    }
+
+    #[tokio::test]
+    async fn applied_filter_is_not_echoed_by_default() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request("/"))
+            .await
+            .unwrap();
+
+        assert!(!response.headers().contains_key(APPLIED_FILTER_HEADER));
+    }
+
+    #[tokio::test]
+    async fn exact_filter_is_passed_as_is_without_auto_append() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock
+            .expect_get_by_name()
+            .with(eq("Wonder Woman"))
+            .return_once(|_| Ok(vec![Default::default()]));
+
+        let config = Config {
+            auto_append_wildcard: false,
+            ..Default::default()
+        };
+        let response = app_with_config(repo_mock, config)
+            .oneshot(send_get_request("/?name=Wonder%20Woman"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[rstest]
+    #[case(None, StatusCode::UNAUTHORIZED)]
+    #[case(Some("Bearer wrong"), StatusCode::UNAUTHORIZED)]
+    #[case(Some("Bearer s3cr3t"), StatusCode::OK)]
+    #[tokio::test]
+    async fn debug_config_requires_the_admin_token(
+        #[case] authorization: Option<&'static str>,
+        #[case] expected_status: StatusCode,
+    ) {
+        let config = Config {
+            admin_token: Some("s3cr3t".to_string()),
+            ..Default::default()
+        };
+        let mut request = Request::builder().uri("/debug/config");
+        if let Some(authorization) = authorization {
+            request = request.header("authorization", authorization);
+        }
+        let request = request.body(Body::empty()).unwrap();
+
+        let response = build_app(state_with_config(config))
+            .oneshot(request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), expected_status);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("s3cr3t"));
+    }
+
+    #[rstest]
+    #[case("/api/v1/heroes/", StatusCode::OK)]
+    #[case("/api/v1/heroes/?name=Storm", StatusCode::NOT_FOUND)]
+    #[case("/api/v1/heroes/1", StatusCode::OK)]
+    #[case("/api/v1/heroes/42", StatusCode::NOT_FOUND)]
+    #[case("/api/v1/heroes/export.csv", StatusCode::OK)]
+    #[tokio::test]
+    async fn head_is_answered_like_get_without_a_body(
+        #[case] uri: &str,
+        #[case] expected_status: StatusCode,
+    ) {
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(Config::default())
+        };
+        let app = build_app(state);
+        let request = |method: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let get = app.clone().oneshot(request("GET")).await.unwrap();
+        let head = app.oneshot(request("HEAD")).await.unwrap();
+
+        assert_eq!(head.status(), expected_status);
+        assert_eq!(get.status(), expected_status);
+        let length = head.headers().get(header::CONTENT_LENGTH).cloned();
+        assert_eq!(length, get.headers().get(header::CONTENT_LENGTH).cloned());
+        let head_body = hyper::body::to_bytes(head.into_body()).await.unwrap();
+        let get_body = hyper::body::to_bytes(get.into_body()).await.unwrap();
+        assert!(head_body.is_empty());
+        // streamed bodies have no length to tell
+        if let Some(length) = length {
+            assert_eq!(length, get_body.len().to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_layer_applies_to_every_route() {
+        let customizations = Customizations {
+            layer: Some(Box::new(|router| {
+                router.layer(middleware::map_response(|mut response: Response| async {
+                    let embedder = HeaderValue::from_static("acme");
+                    response.headers_mut().insert("x-embedded-by", embedder);
+                    response
+                }))
+            })),
+            ..Default::default()
+        };
+        let app = build_app_with(state_with_config(Config::default()), customizations);
+
+        for uri in ["/version", "/api/v1/heroes/unknown/route"] {
+            let response = app.clone().oneshot(send_get_request(uri)).await.unwrap();
+
+            assert_eq!(response.headers()["x-embedded-by"], "acme");
+            // the security headers layer is outside the custom one
+            assert_eq!(
+                response.headers()[header::X_CONTENT_TYPE_OPTIONS],
+                "nosniff"
+            );
+        }
+    }
+
+    #[rstest]
+    #[case("/api/v1/heroes/42", "hero not found")]
+    #[case("/api/v1/heroes/?name=Nobody", "no heroes match filter 'Nobody%'")]
+    #[case("/api/v1/heroes/page?name=Nobody", "no heroes match filter 'Nobody%'")]
+    #[case("/api/v1/heroes/batch?id=42&strict=true", "no heroes have the ids 42")]
+    #[case("/api/v1/heroes/compare?a=1&b=42", "no heroes have the ids 42 (b)")]
+    #[tokio::test]
+    async fn status_mapper_overrides_the_status_of_repository_errors(
+        #[case] uri: &str,
+        #[case] message: &str,
+    ) {
+        fn missing_is_fine(error: &DataAccessError) -> StatusCode {
+            match error {
+                DataAccessError::NotFound => StatusCode::OK,
+                error => error::default_status(error),
+            }
+        }
+        let state = || AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(Config::default())
+        };
+
+        let customizations = Customizations {
+            status_mapper: Some(missing_is_fine),
+            ..Default::default()
+        };
+        let mapped = build_app_with(state(), customizations)
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+        let unmapped = build_app(state())
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        assert_eq!(mapped.status(), StatusCode::OK);
+        let mapped = body_json(mapped).await;
+        assert_eq!(mapped["error"], "not_found");
+        assert_eq!(mapped["message"], message);
+        assert_eq!(unmapped.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn every_response_gets_the_security_headers() {
+        let config = Config {
+            hsts_max_age_secs: Some(600),
+            ..Default::default()
+        };
+
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(config)
+        };
+
+        let response = build_app(state)
+            .oneshot(send_get_request("/api/v1/heroes/42"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let headers = response.headers();
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+        assert_eq!(headers[header::STRICT_TRANSPORT_SECURITY], "max-age=600");
+    }
+
+    #[tokio::test]
+    async fn registered_routes_are_listed() {
+        let request = Request::builder()
+            .uri("/debug/routes")
+            .header("authorization", "Bearer s3cr3t")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app_with_admin_token().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let routes = body_json(response).await;
+        let routes = routes.as_array().unwrap();
+        for expected in [
+            serde_json::json!({"method": "GET", "path": "/heroes/"}),
+            serde_json::json!({"method": "DELETE", "path": "/api/v1/heroes/:id"}),
+            serde_json::json!({"method": "GET", "path": "/health/ready"}),
+            serde_json::json!({"method": "GET", "path": "/debug/routes"}),
+        ] {
+            assert!(routes.contains(&expected), "{} is missing", expected);
+        }
+    }
+
+    #[rstest]
+    #[case("/?limit=1", StatusCode::OK)]
+    #[case("/?limit=1&offset=1", StatusCode::OK)]
+    #[case("/?limit=0", StatusCode::BAD_REQUEST)]
+    #[case("/?offset=1", StatusCode::BAD_REQUEST)]
+    #[tokio::test]
+    async fn listing_is_paginated(#[case] uri: &'static str, #[case] expected_status: StatusCode) {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), expected_status);
+        if expected_status == StatusCode::OK {
+            assert_eq!(body_json(response).await.as_array().unwrap().len(), 1);
+        }
+    }
+
+    fn ranged_request(range: &str) -> Request<Body> {
+        Request::builder()
+            .uri("/")
+            .header(header::RANGE, range)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn ranged_listing_is_partial_content() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(ranged_request("heroes=1-19"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "heroes 1-1/2");
+        let heroes = body_json(response).await;
+        assert_eq!(heroes.as_array().unwrap().len(), 1);
+        assert_eq!(heroes[0]["name"], "Deadpool");
+    }
+
+    #[tokio::test]
+    async fn range_ending_at_u64_max_serves_every_hero() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(ranged_request("heroes=0-18446744073709551615"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "heroes 0-1/2");
+        assert_eq!(body_json(response).await.as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn range_past_the_last_hero_is_not_satisfiable() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(ranged_request("heroes=2-5"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "heroes */2");
+        assert_eq!(body_json(response).await["error"], "range_not_satisfiable");
+    }
+
+    #[tokio::test]
+    async fn csv_export_streams_every_hero() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request("/export.csv"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["content-disposition"],
+            "attachment; filename=\"heroes.csv\""
+        );
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let rows: Vec<&str> = body.lines().collect();
+        assert_eq!(rows[0], "id,name");
+        assert_eq!(rows.len(), 3);
+    }
+
+    #[rstest]
+    #[case("/export.csv?limit=2", None, 2)]
+    #[case("/export.csv", Some(3), 3)]
+    #[case("/export.csv?limit=10", Some(3), 3)] // the cap wins over larger limits
+    #[tokio::test]
+    async fn csv_export_stops_at_its_limit(
+        #[case] uri: &str,
+        #[case] max_stream_rows: Option<u64>,
+        #[case] expected_rows: usize,
+    ) {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        // a table without end, which only a limit stops
+        repo_mock
+            .expect_stream_all()
+            .returning(|| stream::repeat_with(|| Ok(Hero::default())).boxed());
+        let config = Config {
+            max_stream_rows,
+            ..Default::default()
+        };
+
+        let response = app_with_config(repo_mock, config)
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[ROW_LIMIT_HEADER],
+            expected_rows.to_string()
+        );
+        let body = time::timeout(
+            Duration::from_secs(1),
+            hyper::body::to_bytes(response.into_body()),
+        )
+        .await
+        .expect("the stream ends at the limit")
+        .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(body.lines().count(), 1 + expected_rows);
+    }
+
+    #[tokio::test]
+    async fn csv_export_limit_must_be_positive() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request("/export.csv?limit=0"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn csv_rows_are_sent_as_the_repository_produces_them() {
+        use hyper::body::HttpBody;
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        // a repository still busy producing the rest of its heroes
+        repo_mock.expect_stream_all().returning(|| {
+            stream::iter([Ok(Hero::default())])
+                .chain(stream::pending())
+                .boxed()
+        });
+
+        let response = app(repo_mock)
+            .oneshot(send_get_request("/export.csv"))
+            .await
+            .unwrap();
+
+        let mut body = response.into_body();
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            let chunk = time::timeout(Duration::from_secs(1), body.data())
+                .await
+                .expect("rows are sent before the stream ends");
+            received.extend_from_slice(&chunk.unwrap().unwrap());
+        }
+        assert_eq!(String::from_utf8(received).unwrap(), "id,name\r\n,\r\n");
+    }
+
+    #[tokio::test]
+    async fn hero_bodies_must_be_utf8() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header("content-type", "application/json")
+            .body(Body::from(&b"{\"name\":\"Zo\xEB\"}"[..]))
+            .unwrap();
+
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_json(response).await["message"],
+            "request body is not valid UTF-8: invalid byte at offset 11"
+        );
+    }
+
+    fn csv_import_request(body: &'static str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri("/import")
+            .header("content-type", "text/csv")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn csv_import_creates_every_row() {
+        let app = app(InMemoryHeroesRepository::default());
+
+        let response = app
+            .clone()
+            .oneshot(csv_import_request(
+                "name,id\r\nStorm,\r\n\"Rogue, Anna\",x-1\r\n",
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(response).await,
+            serde_json::json!({ "created": 2, "failed": [] })
+        );
+        let created = app.oneshot(send_get_request("/x-1")).await.unwrap();
+        assert_eq!(body_json(created).await["name"], "Rogue, Anna");
+    }
+
+    #[tokio::test]
+    async fn csv_import_reports_bad_rows() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(csv_import_request(
+                "id,name\n1,Storm\n9,   \n10,Rogue,extra\n11,Wonder Woman\n12,Hawkgirl\n",
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(response).await,
+            serde_json::json!({
+                "created": 1,
+                "failed": [
+                    { "row": 1, "reason": "a hero with this id exists already" },
+                    { "row": 2, "reason": "hero name must not be blank" },
+                    { "row": 3, "reason": "expected 2 fields, found 3" },
+                    { "row": 4, "reason": "a hero named 'Wonder Woman' already exists" },
+                ]
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("name\n\"Storm", "malformed CSV: unterminated quoted field on line 2")]
+    #[case("name,power\nStorm,9", "unknown CSV column 'power'")]
+    #[case("id\n1", "the CSV header needs a name column")]
+    #[tokio::test]
+    async fn malformed_csv_import_is_a_bad_request(
+        #[case] body: &'static str,
+        #[case] message: &str,
+    ) {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(csv_import_request(body))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(body_json(response).await["message"], message);
+    }
+
+    #[rstest]
+    #[case(Feature::CsvExport, "/export.csv")]
+    #[case(Feature::Events, "/events/sse")]
+    #[tokio::test]
+    async fn disabled_features_are_not_implemented(#[case] feature: Feature, #[case] uri: &str) {
+        let config = Config {
+            disabled_features: vec![feature],
+            ..Default::default()
+        };
+
+        let response = app_with_config(InMemoryHeroesRepository::default(), config)
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+        assert_eq!(
+            body_json(response).await["message"],
+            format!("{} is disabled on this server", feature.name())
+        );
+    }
+
+    #[tokio::test]
+    async fn reloaded_config_applies_from_the_next_request() {
+        const CSP: header::HeaderName = header::CONTENT_SECURITY_POLICY;
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(Config::default())
+        };
+        let config = state.config.clone();
+        let app = build_app(state);
+
+        let before = app.clone().oneshot(send_get_request("/heroes/export.csv"));
+        let before = before.await.unwrap();
+        config.reload(Config {
+            disabled_features: vec![Feature::CsvExport],
+            content_security_policy: Some("default-src 'none'".to_string()),
+            ..Default::default()
+        });
+        let after = app.oneshot(send_get_request("/heroes/export.csv"));
+        let after = after.await.unwrap();
+
+        assert_eq!(before.status(), StatusCode::OK);
+        assert!(!before.headers().contains_key(CSP));
+        // read by a handler
+        assert_eq!(after.status(), StatusCode::NOT_IMPLEMENTED);
+        // and by a middleware
+        assert_eq!(after.headers()[CSP], "default-src 'none'");
+    }
+
+    fn admin_request(uri: &str, body: Value) -> Request<Body> {
+        let mut request = send_json_request("POST", uri, body);
+        request
+            .headers_mut()
+            .insert("authorization", "Bearer s3cr3t".parse().unwrap());
+        request
+    }
+
+    fn app_with_admin_token() -> Router {
+        let config = Config {
+            admin_token: Some("s3cr3t".to_string()),
+            ..Default::default()
+        };
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(config)
+        };
+        build_app(state)
+    }
+
+    #[rstest]
+    #[case("/api/v1/heroes/1", "")]
+    #[case("/api/v1/heroes/?name=Wonder&shape=map", "/1")]
+    #[case("/api/v1/heroes/1/context", "/current")]
+    #[tokio::test]
+    async fn restricted_fields_are_only_sent_to_admins(#[case] uri: &str, #[case] hero: &str) {
+        let config = Config {
+            admin_token: Some("s3cr3t".to_string()),
+            restricted_fields: vec!["power_level".to_string(), "updated_at".to_string()],
+            ..Default::default()
+        };
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(config)
+        };
+        let app = build_app(state);
+        // Wonder Woman, wherever she is in the body
+        let wonder_woman = |body: Value| body.pointer(hero).unwrap().clone();
+
+        let request = Request::builder()
+            .uri(uri)
+            .header(header::AUTHORIZATION, "Bearer s3cr3t")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let admin = wonder_woman(body_json(response).await);
+        assert_eq!(admin["power_level"], 92);
+        assert!(admin["updated_at"].is_u64());
+
+        let response = app.oneshot(send_get_request(uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let anonymous = wonder_woman(body_json(response).await);
+        assert_eq!(anonymous["name"], "Wonder Woman");
+        let fields: Vec<&String> = anonymous.as_object().unwrap().keys().collect();
+        assert_eq!(fields, ["id", "name"]);
+    }
+
+    fn app_with_restricted_fields() -> (Router, HeroEvents) {
+        let config = Config {
+            admin_token: Some("s3cr3t".to_string()),
+            restricted_fields: vec!["power_level".to_string(), "updated_at".to_string()],
+            ..Default::default()
+        };
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(config)
+        };
+        let events = state.events.clone();
+        (build_app(state), events)
+    }
+
+    #[rstest]
+    #[case("text/csv", "id,name\r\n1,Wonder Woman\r\n")]
+    #[case(
+        "application/xml",
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?><list><item><id>1</id><name>Wonder Woman</name></item></list>"
+    )]
+    #[tokio::test]
+    async fn restricted_fields_are_left_out_of_every_format(
+        #[case] accept: &str,
+        #[case] expected: &str,
+    ) {
+        let (app, _) = app_with_restricted_fields();
+        let request = Request::builder()
+            .uri("/api/v1/heroes/?name=Wonder")
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(String::from_utf8(body.to_vec()).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn restricted_fields_are_left_out_of_server_sent_events() {
+        use hyper::body::HttpBody;
+        let (app, events) = app_with_restricted_fields();
+        let response = app
+            .oneshot(send_get_request("/api/v1/heroes/events/sse"))
+            .await
+            .unwrap();
+        let storm = Hero {
+            id: "7".to_string(),
+            name: HeroName::new("Storm").unwrap(),
+            updated_at: None,
+            tags: vec![],
+            power_level: 85,
+        };
+
+        events.publish(AuditAction::Create, &storm);
+
+        let mut body = response.into_body();
+        let frame = time::timeout(Duration::from_secs(1), body.data())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let frame = String::from_utf8(frame.to_vec()).unwrap();
+        assert!(frame.contains(r#""name":"Storm""#));
+        assert!(!frame.contains("power_level"));
+    }
+
+    #[tokio::test]
+    async fn heroes_are_served_under_api_v1_and_deprecated_elsewhere() {
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(Config::default())
+        };
+        let app = build_app(state);
+
+        let legacy = app.clone().oneshot(send_get_request("/heroes/1")).await;
+        let current = app.oneshot(send_get_request("/api/v1/heroes/1")).await;
+
+        let (legacy, current) = (legacy.unwrap(), current.unwrap());
+        assert_eq!(legacy.status(), StatusCode::OK);
+        assert_eq!(legacy.headers()["deprecation"], "true");
+        assert_eq!(current.status(), StatusCode::OK);
+        assert!(!current.headers().contains_key("deprecation"));
+        assert_eq!(body_json(legacy).await, body_json(current).await);
+    }
+
+    #[rstest]
+    #[case("fr-FR, en;q=0.5", "héros introuvable")]
+    #[case("en-GB", "hero not found")]
+    #[case("tlh", "hero not found")]
+    #[tokio::test]
+    async fn error_messages_are_localized(#[case] accept_language: &str, #[case] message: &str) {
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(Config::default())
+        };
+        let request = Request::builder()
+            .uri("/api/v1/heroes/42")
+            .header(header::ACCEPT_LANGUAGE, accept_language)
+            .body(Body::empty())
+            .unwrap();
+
+        let response = build_app(state).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = body_json(response).await;
+        assert_eq!(body["error"], "not_found");
+        assert_eq!(body["message"], message);
+    }
+
+    #[tokio::test]
+    async fn reloaded_dataset_is_served() {
+        let app = app_with_admin_token();
+
+        let dataset = serde_json::json!([{ "id": "7", "name": "Storm" }]);
+        let response = app
+            .clone()
+            .oneshot(admin_request("/admin/heroes/reload", dataset))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = app.oneshot(send_get_request("/heroes/")).await.unwrap();
+        assert_eq!(
+            body_json(response).await,
+            serde_json::json!([{ "id": "7", "name": "Storm", "power_level": 0 }])
+        );
+    }
+
+    #[tokio::test]
+    async fn heroes_left_out_of_a_reload_are_gone() {
+        let app = app_with_admin_token();
+
+        let dataset = serde_json::json!([{ "id": "7", "name": "Storm" }]);
+        app.clone()
+            .oneshot(admin_request("/admin/heroes/reload", dataset))
+            .await
+            .unwrap();
+
+        let response = app.oneshot(send_get_request("/heroes/1")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    async fn replacing_the_dataset_forgets_the_views_of_dropped_heroes() {
+        let repo = InMemoryHeroesRepository::default();
+        let wonder_woman = repo.get_by_id("1").await.unwrap();
+        repo.record_view("1").await.unwrap();
+
+        repo.replace_all(vec![]).await.unwrap();
+        assert!(matches!(
+            repo.get_by_id("1").await,
+            Err(DataAccessError::Gone)
+        ));
+        repo.replace_all(vec![wonder_woman]).await.unwrap();
+
+        let restored = repo.get_by_id("1").await.unwrap();
+        assert_eq!(restored.name.as_str(), "Wonder Woman");
+        assert_eq!(repo.record_view("1").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn malformed_dataset_is_rejected_without_changes() {
+        let app = app_with_admin_token();
+
+        let dataset = serde_json::json!([
+            { "id": "7", "name": "Storm" },
+            { "id": "7", "name": "Rogue" }
+        ]);
+        let response = app
+            .clone()
+            .oneshot(admin_request("/admin/heroes/reload", dataset))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let response = app.oneshot(send_get_request("/heroes/")).await.unwrap();
+        assert_eq!(body_json(response).await.as_array().unwrap().len(), 2);
+    }
+
+    #[rstest]
+    #[case(
+        serde_json::json!({ "id": "7", "name": "100% Bad\u{7}" }),
+        "hero at index 0 is invalid: name must not contain '%'; name must not contain control characters"
+    )]
+    #[case(
+        serde_json::json!({ "id": "7", "name": "Storm".repeat(21) }),
+        "hero at index 0 is invalid: name must not exceed 100 characters"
+    )]
+    #[case(
+        serde_json::json!({ "id": "7", "name": "Storm", "tags": ["x-men", " "] }),
+        "hero at index 0 is invalid: tags must not be blank"
+    )]
+    #[tokio::test]
+    async fn dataset_with_an_invalid_hero_is_rejected(#[case] hero: Value, #[case] message: &str) {
+        let dataset = serde_json::json!([hero]);
+        let response = app_with_admin_token()
+            .oneshot(admin_request("/admin/heroes/reload", dataset))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body_json(response).await["message"], message);
+    }
+
+    #[tokio::test]
+    async fn reloaded_tags_are_normalized() {
+        let app = app_with_admin_token();
+
+        let dataset = serde_json::json!([
+            { "id": "7", "name": "Storm", "tags": [" X-Men", "x-men", "Mutant"] }
+        ]);
+        app.clone()
+            .oneshot(admin_request("/admin/heroes/reload", dataset))
+            .await
+            .unwrap();
+
+        let response = app.oneshot(send_get_request("/heroes/7")).await.unwrap();
+        assert_eq!(
+            body_json(response).await["tags"],
+            serde_json::json!(["mutant", "x-men"])
+        );
+    }
+
+    #[rstest]
+    #[case("/batch?id=3&id=1&id=42&id=2", &["1", "2", "3"])]
+    #[case("/batch?id=3&id=1&id=42&id=2&ordered=true", &["3", "1", "2"])]
+    #[case("/batch?id=2&id=1&ordered=false", &["1", "2"])]
+    #[tokio::test]
+    async fn batch_fetch_follows_the_requested_order_when_asked(
+        #[case] uri: &str,
+        #[case] expected: &[&str],
+    ) {
+        let repo = heroes_named(&["Storm", "Rogue", "Gambit"]);
+
+        let response = app(repo).oneshot(send_get_request(uri)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let ids: Vec<Value> = body_json(response)
+            .await
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hero| hero["id"].clone())
+            .collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[rstest]
+    #[case("/batch?id=3&id=42&id=1", None)]
+    #[case("/batch?id=3&id=42&id=1&strict=false", None)]
+    #[case(
+        "/batch?id=3&id=42&id=1&id=7&id=42&strict=true",
+        Some("no heroes have the ids 42, 7")
+    )]
+    #[case("/batch?id=3&id=1&strict=true", None)]
+    #[tokio::test]
+    async fn strict_batch_fetch_refuses_unknown_ids(
+        #[case] uri: &str,
+        #[case] refusal: Option<&str>,
+    ) {
+        let response = app(heroes_named(&["Storm", "Rogue", "Gambit"]))
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = body_json(response).await;
+        match refusal {
+            None => {
+                assert_eq!(status, StatusCode::OK);
+                assert_eq!(body.as_array().unwrap().len(), 2);
+            }
+            Some(message) => {
+                assert_eq!(status, StatusCode::NOT_FOUND);
+                assert_eq!(body["message"], message);
+            }
+        }
+    }
+
+    #[rstest]
+    #[case("/batch", "at least one id is required")]
+    #[case(
+        "/batch?id=1&ordered=maybe",
+        "ordered must be true, false, 1, 0, yes or no"
+    )]
+    #[case(
+        "/batch?id=1&strict=always",
+        "strict must be true, false, 1, 0, yes or no"
+    )]
+    #[tokio::test]
+    async fn invalid_batch_fetch_is_a_bad_request(#[case] uri: &str, #[case] message: &str) {
+        let response = app(heroes_named(&["Storm"]))
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(body_json(response).await["message"], message);
+    }
+
+    #[tokio::test]
+    async fn batch_upsert_counts_created_and_updated_heroes() {
+        let app = app(InMemoryHeroesRepository::default());
+
+        let batch = serde_json::json!([
+            { "id": "2", "name": "Storm" },
+            { "id": "7", "name": "Rogue" },
+            { "id": "8", "name": "Cyclops" },
+            { "id": "7", "name": "Gambit" }
+        ]);
+        let response = app
+            .clone()
+            .oneshot(send_json_request("PUT", "/batch", batch))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(response).await,
+            serde_json::json!({ "created": 2, "updated": 1 })
+        );
+
+        // the last occurrence of a duplicated id wins
+        let response = app.oneshot(send_get_request("/7")).await.unwrap();
+        assert_eq!(body_json(response).await["name"], "Gambit");
+    }
+
+    #[tokio::test]
+    async fn batch_upsert_rejects_heroes_without_id() {
+        let repo = FnHeroesRepository::new();
+
+        let batch = serde_json::json!([
+            { "id": "7", "name": "Rogue" },
+            { "id": "", "name": "Storm" }
+        ]);
+        let response = app(repo)
+            .oneshot(send_json_request("PUT", "/batch", batch))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[rstest]
+    #[case(
+        serde_json::json!({ "id": "9", "name": "100% Bad\u{7}" }),
+        "hero at index 1 is invalid: name must not contain '%'; name must not contain control characters"
+    )]
+    #[case(
+        serde_json::json!({ "id": "9", "name": "Storm", "tags": ["  VILLAIN ", ""] }),
+        "hero at index 1 is invalid: tags must not be blank"
+    )]
+    #[tokio::test]
+    async fn batch_upsert_rejects_invalid_heroes(#[case] hero: Value, #[case] message: &str) {
+        let batch = serde_json::json!([{ "id": "7", "name": "Rogue" }, hero]);
+
+        let response = app(FnHeroesRepository::new())
+            .oneshot(send_json_request("PUT", "/batch", batch))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body_json(response).await["message"], message);
+    }
+
+    #[tokio::test]
+    async fn batch_upsert_stores_heroes_like_other_writes() {
+        let app = app(InMemoryHeroesRepository::default());
+
+        let batch = serde_json::json!([
+            { "id": "9", "name": "Storm", "tags": ["  VILLAIN ", "VILLAIN", "X-Men"], "updated_at": 1 }
+        ]);
+        let response = app
+            .clone()
+            .oneshot(send_json_request("PUT", "/batch", batch))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let hero = body_json(app.oneshot(send_get_request("/9")).await.unwrap()).await;
+        assert_eq!(hero["tags"], serde_json::json!(["villain", "x-men"]));
+        assert_ne!(hero["updated_at"], 1);
+    }
+
+    #[tokio::test]
+    async fn batch_upsert_fails_when_the_heroes_before_it_can_not_be_read() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock
+            .expect_get_by_ids()
+            .returning(|_| Err(DataAccessError::TechnicalError));
+        repo_mock.expect_upsert_many().never();
+
+        let batch = serde_json::json!([{ "id": "7", "name": "Rogue" }]);
+        let response = app(repo_mock)
+            .oneshot(send_json_request("PUT", "/batch", batch))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn expired_deadline_is_a_gateway_timeout() {
+        // the in-memory repository takes 100ms to answer get_by_name
+        let request = Request::builder()
+            .uri("/?name=Wonder")
+            .header("x-request-deadline-ms", "10")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[rstest]
+    #[case(50, 1_000, StatusCode::GATEWAY_TIMEOUT, StatusCode::CREATED)]
+    #[case(1_000, 50, StatusCode::OK, StatusCode::GATEWAY_TIMEOUT)]
+    #[tokio::test]
+    async fn reads_and_writes_have_their_own_timeouts(
+        #[case] read_timeout_ms: u64,
+        #[case] write_timeout_ms: u64,
+        #[case] read_status: StatusCode,
+        #[case] write_status: StatusCode,
+    ) {
+        // both the listing and the duplicate check of creations call get_by_name, 100ms
+        let config = Config {
+            read_timeout_ms: Some(read_timeout_ms),
+            write_timeout_ms: Some(write_timeout_ms),
+            ..Default::default()
+        };
+        let app = app_with_config(InMemoryHeroesRepository::default(), config);
+
+        let read = app
+            .clone()
+            .oneshot(send_get_request("/?name=Wonder"))
+            .await
+            .unwrap();
+        let create = send_json_request("POST", "/", serde_json::json!({ "name": "Storm" }));
+        let write = app.oneshot(create).await.unwrap();
+
+        assert_eq!(read.status(), read_status);
+        assert_eq!(write.status(), write_status);
+    }
+
+    #[rstest]
+    #[case("/1", StatusCode::OK)]
+    #[case("/42", StatusCode::NOT_FOUND)] // never existed
+    #[case("/2", StatusCode::GONE)] // deleted
+    #[tokio::test]
+    async fn deleted_heroes_are_gone(#[case] uri: &str, #[case] expected_status: StatusCode) {
+        let app = app(InMemoryHeroesRepository::default());
+        let delete = send_json_request("DELETE", "/2", serde_json::json!({}));
+        app.clone().oneshot(delete).await.unwrap();
+
+        let response = app.oneshot(send_get_request(uri)).await.unwrap();
+
+        assert_eq!(response.status(), expected_status);
+    }
+
+    #[tokio::test]
+    async fn created_name_is_normalized() {
+        let create = send_json_request(
+            "POST",
+            "/",
+            serde_json::json!({ "name": "  Wonder   Woman " }),
+        );
+
+        // the default dataset already has a Wonder Woman
+        let response = app(InMemoryHeroesRepository::new(vec![]))
+            .oneshot(create)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(body_json(response).await["name"], "Wonder Woman");
+    }
+
+    fn create_if_absent_request(body: Value) -> Request<Body> {
+        let mut request = send_json_request("POST", "/", body);
+        request
+            .headers_mut()
+            .insert(header::IF_NONE_MATCH, HeaderValue::from_static("*"));
+        request
+    }
+
+    #[tokio::test]
+    async fn create_if_absent_stores_the_hero_under_its_id() {
+        let repo = Arc::new(InMemoryHeroesRepository::new(vec![]));
+        let create =
+            create_if_absent_request(serde_json::json!({ "id": "wonder", "name": "Wonder Woman" }));
+
+        let response = app(repo.clone()).oneshot(create).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(body_json(response).await["id"], "wonder");
+        assert_eq!(
+            repo.get_by_id("wonder").await.unwrap().name.as_str(),
+            "Wonder Woman"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_if_absent_fails_when_the_id_is_taken() {
+        let repo = Arc::new(heroes_named(&["Wonder Woman"]));
+        let create = create_if_absent_request(serde_json::json!({ "id": "1", "name": "Storm" }));
+
+        let response = app(repo.clone()).oneshot(create).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(body_json(response).await["error"], "precondition_failed");
+        assert_eq!(
+            repo.get_by_id("1").await.unwrap().name.as_str(),
+            "Wonder Woman"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_if_absent_needs_an_id() {
+        let create = create_if_absent_request(serde_json::json!({ "name": "Storm" }));
+
+        let response = app(InMemoryHeroesRepository::new(vec![]))
+            .oneshot(create)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn chosen_numeric_ids_are_not_generated_again() {
+        let repo = InMemoryHeroesRepository::new(vec![]);
+        let chosen = HeroPayload {
+            name: HeroName::new("Storm").unwrap(),
+            power_level: 0,
+        };
+        repo.create_if_absent("5", chosen.clone()).await.unwrap();
+
+        let created = repo.create(chosen).await.unwrap();
+
+        assert_eq!(created.id, "6");
+    }
+
+    #[rstest]
+    #[case("true")]
+    #[case("1")]
+    #[case("yes")]
+    #[tokio::test]
+    async fn confirmed_bulk_delete_removes_the_matching_heroes(#[case] confirm: &str) {
+        let repo = Arc::new(heroes_named(&["Test 1", "Storm", "Test 2"]));
+        let delete = Request::builder()
+            .method("DELETE")
+            .uri(format!("/?name=Test&confirm={}", confirm))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(repo.clone()).oneshot(delete).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(response).await,
+            serde_json::json!({ "deleted": 2 })
+        );
+        assert_eq!(ids(repo.get_by_name("%").await.unwrap()), ["2"]);
+        assert!(matches!(
+            repo.get_by_id("1").await,
+            Err(DataAccessError::Gone)
+        ));
+    }
+
+    #[rstest]
+    #[case("/?name=Test")]
+    #[case("/?name=Test&confirm=false")]
+    #[case("/?name=Test&confirm=no")]
+    #[case("/?name=Test&confirm=maybe")]
+    #[tokio::test]
+    async fn bulk_delete_needs_a_confirmation(#[case] uri: &str) {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock.expect_delete_by_name().never();
+        let delete = Request::builder()
+            .method("DELETE")
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(repo_mock).oneshot(delete).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(body_json(response).await["error"], "bad_request");
+    }
+
+    #[tokio::test]
+    async fn blank_name_is_not_created() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock.expect_create().never();
+
+        let create = send_json_request("POST", "/", serde_json::json!({ "name": "   " }));
+        let response = app(repo_mock).oneshot(create).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn version_is_reported() {
+        let response = build_app(state_with_config(Config::default()))
+            .oneshot(send_get_request("/version"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert!(body["built_at"].is_string());
+    }
+
+    #[rstest]
+    #[case("/?q=1", "Wonder Woman")] // by id
+    #[case("/?q=Wonder", "Wonder Woman")] // by name prefix
+    #[case("/?q=Dead", "Deadpool")]
+    #[tokio::test]
+    async fn search_matches_id_or_name(#[case] uri: &'static str, #[case] expected_name: &str) {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let heroes = body_json(response).await;
+        assert_eq!(heroes.as_array().unwrap().len(), 1);
+        assert_eq!(heroes[0]["name"], expected_name);
+    }
+
+    fn query(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[rstest]
+    #[case("/?name_regex=%5Ewonder.*an$", StatusCode::OK)]
+    #[case("/?name_regex=%5EWoman", StatusCode::NOT_FOUND)]
+    #[case("/?name_regex=Wonder%5B", StatusCode::BAD_REQUEST)]
+    #[tokio::test]
+    async fn heroes_are_listed_by_name_regex(
+        #[case] uri: &str,
+        #[case] expected_status: StatusCode,
+    ) {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), expected_status);
+        if expected_status == StatusCode::OK {
+            let heroes = body_json(response).await;
+            assert_eq!(heroes.as_array().unwrap().len(), 1);
+            assert_eq!(heroes[0]["name"], "Wonder Woman");
+        }
+    }
+
+    #[tokio::test]
+    async fn conflicting_listing_params_are_a_bad_request() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request("/?q=Dead&name=Dead"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_json(response).await["message"],
+            "q and name can't be combined"
+        );
+    }
+
+    #[rstest]
+    #[case("/1?pretty=true", true)]
+    #[case("/1?pretty=yes", true)]
+    #[case("/1?pretty=0", false)]
+    #[case("/1", false)]
+    #[tokio::test]
+    async fn json_is_indented_on_request(#[case] uri: &'static str, #[case] indented: bool) {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(String::from_utf8_lossy(&body).contains('\n'), indented);
+    }
+
+    #[tokio::test]
+    async fn root_links_the_top_level_resources() {
+        let app = build_app(state_with_config(Config::default()));
+
+        let response = app.clone().oneshot(send_get_request("/")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            body_json(response).await["links"],
+            serde_json::json!({
+                "heroes": "/api/v1/heroes/",
+                "health": "/health",
+                "metrics": "/metrics",
+                "version": "/version",
+            })
+        );
+
+        let mut request = send_get_request("/");
+        let host = HeaderValue::from_static("heroes.example");
+        request.headers_mut().insert(header::HOST, host);
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(
+            body_json(response).await["links"]["heroes"],
+            "http://heroes.example/api/v1/heroes/"
+        );
+    }
+
+    #[tokio::test]
+    async fn server_error_carries_a_correlation_id() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock
+            .expect_get_by_id()
+            .return_once(|_| Err(DataAccessError::TechnicalError));
+        let state = AppState {
+            repo: Arc::new(repo_mock),
+            ..state_with_config(Config::default())
+        };
+
+        let response = build_app(state)
+            .oneshot(send_get_request("/heroes/1"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let header = response.headers()["x-request-id"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(!header.is_empty());
+        assert_eq!(body_json(response).await["request_id"], header);
+    }
+
+    #[rstest]
+    #[case(true, Some("client-chosen-id"))]
+    #[case(false, None)]
+    #[tokio::test]
+    async fn successful_responses_carry_the_request_id_when_configured(
+        #[case] echo_request_id: bool,
+        #[case] expected: Option<&str>,
+    ) {
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(Config {
+                echo_request_id,
+                ..Default::default()
+            })
+        };
+        let mut request = send_get_request("/heroes/");
+        request.headers_mut().insert(
+            request_id::REQUEST_ID_HEADER,
+            HeaderValue::from_static("client-chosen-id"),
+        );
+
+        let response = build_app(state).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let header = response.headers().get(request_id::REQUEST_ID_HEADER);
+        assert_eq!(header.map(|id| id.to_str().unwrap()), expected);
+    }
+
+    #[tokio::test]
+    async fn sensitive_values_are_redacted_from_request_logs() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock
+            .expect_get_by_id()
+            .return_once(|_| Err(DataAccessError::TechnicalError));
+        let state = AppState {
+            repo: Arc::new(repo_mock),
+            ..state_with_config(Config {
+                log_redact: vec!["x-api-key".to_string(), "token".to_string()],
+                ..Default::default()
+            })
+        };
+        let request = Request::builder()
+            .uri("/heroes/1?token=t0k3n&pretty=true")
+            .header("x-api-key", "k3y")
+            .header("authorization", "Bearer s3cr3t")
+            .body(Body::empty())
+            .unwrap();
+        let (logs, _guard) = logging::capture();
+
+        let response = build_app(state).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(logs.contains("request failed"));
+        assert!(logs.contains("token=***&pretty=true"));
+        assert!(logs.contains("x-api-key: ***"));
+        for secret in ["t0k3n", "k3y", "s3cr3t"] {
+            assert!(!logs.contains(secret));
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_to_any_hero_are_traced_under_the_same_route() {
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(Config::default())
+        };
+        let app = build_app(state);
+        let (logs, _guard) = logging::capture();
+
+        for uri in ["/api/v1/heroes/1", "/api/v1/heroes/42"] {
+            app.clone().oneshot(send_get_request(uri)).await.unwrap();
+        }
+
+        let traced: Vec<String> = logs
+            .lines()
+            .into_iter()
+            .filter(|line| line.contains("request traced"))
+            .collect();
+        assert_eq!(traced.len(), 2);
+        for line in traced {
+            assert!(line.contains("route=\"/api/v1/heroes/:id\""), "{}", line);
+        }
+    }
+
+    fn international_heroes() -> InMemoryHeroesRepository {
+        heroes_named(&["Élodie la Grande", "Ōkami", "Чудо-женщина", "Wonder Woman"])
+    }
+
+    #[rstest]
+    #[case("Élodie%", "Élodie la Grande")]
+    #[case("élodie%", "Élodie la Grande")] // case folding of a precomposed accented letter
+    #[case("ÉLODIE LA GRANDE", "Élodie la Grande")]
+    #[case("ōkami", "Ōkami")]
+    #[case("чудо%", "Чудо-женщина")]
+    #[case("ЧУДО-ЖЕНЩИНА", "Чудо-женщина")]
+    #[tokio::test]
+    async fn non_ascii_names_match_regardless_of_case(
+        #[case] filter: &str,
+        #[case] expected_name: &str,
+    ) {
+        let heroes = international_heroes().get_by_name(filter).await.unwrap();
+
+        assert_eq!(heroes.len(), 1);
+        assert_eq!(heroes[0].name.as_str(), expected_name);
+    }
+
+    #[tokio::test]
+    async fn lookalike_letters_do_not_match() {
+        // "О" (cyrillic) looks like "O" but is a different character
+        let result = international_heroes().get_by_name("Оkami%").await;
+
+        assert!(matches!(result, Err(DataAccessError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn rate_limited_request_gets_retry_after_and_request_id() {
+        let app = build_app(state_with_config(Config {
+            rate_limit_requests: Some(0),
+            ..Default::default()
+        }));
+
+        let response = app.oneshot(send_get_request("/version")).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after = response.headers()[header::RETRY_AFTER].to_str().unwrap();
+        assert!(retry_after.parse::<u64>().is_ok());
+        assert!(body_json(response).await["request_id"].is_string());
+    }
+
+    #[tokio::test]
+    async fn overloaded_request_gets_configured_retry_after() {
+        let app = build_app(state_with_config(Config {
+            max_concurrent_requests: Some(0),
+            overload_retry_after_secs: 7,
+            ..Default::default()
+        }));
+
+        let response = app.oneshot(send_get_request("/version")).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "7");
+    }
+
+    #[tokio::test]
+    async fn logged_name_filter_is_escaped() {
+        let mut repo = MockHeroesRepositoryTrait::new();
+        repo.expect_get_by_name()
+            .returning(|_| Ok(vec![Hero::default()]));
+        let (logs, _guard) = logging::capture();
+
+        let response = app(repo)
+            .oneshot(send_get_request("/?name=Wonder%0AERROR%20forged%20line"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(logs.contains("filter=Wonder\\nERROR forged line%"));
+        assert!(logs.lines().iter().all(|line| !line.contains('\n')));
+    }
+
+    #[test]
+    fn startup_log_summarizes_the_config_without_secrets() {
+        let config = Config {
+            port: 9090,
+            admin_token: Some("s3cr3t".to_string()),
+            ..Default::default()
+        };
+        let (logs, _guard) = logging::capture();
+
+        log_startup(SocketAddr::from(([0, 0, 0, 0], config.port)), &config);
+
+        assert_eq!(logs.lines().len(), 1);
+        assert!(logs.contains("listen=0.0.0.0:9090"));
+        assert!(logs.contains(r#""request_timeout_ms":5000"#));
+        assert!(logs.contains(r#""admin_token":"***""#));
+        assert!(!logs.contains("s3cr3t"));
+    }
+
+    fn multi_tenant_app() -> Router {
+        let audit_log: DynAuditLog = Arc::new(InMemoryAuditLog::default());
+        build_app(AppState {
+            repo: Arc::new(AuditedHeroesRepository::new(
+                TenantScopedHeroesRepository::new(2, InMemoryHeroesRepository::default),
+                audit_log.clone(),
+            )),
+            audit_log,
+            metrics: Default::default(),
+            events: Default::default(),
+            started: Default::default(),
+            tasks: Default::default(),
+            warm_up: Default::default(),
+            breaker: None,
+            config: Arc::new(LiveConfig::new(Config {
+                require_tenant: true,
+                ..Default::default()
+            })),
+        })
+    }
+
+    fn tenant_request(tenant: &str, method: &str, uri: &str, body: Value) -> Request<Body> {
+        let mut request = send_json_request(method, uri, body);
+        request
+            .headers_mut()
+            .insert(tenant::TENANT_HEADER, tenant.parse().unwrap());
+        request
+    }
+
+    #[tokio::test]
+    async fn tenants_only_see_their_own_heroes() {
+        let app = multi_tenant_app();
+
+        let created = app
+            .clone()
+            .oneshot(tenant_request(
+                "tenant-a",
+                "POST",
+                "/heroes/",
+                serde_json::json!({ "name": "Storm" }),
+            ))
+            .await
+            .unwrap();
+        let id = body_json(created).await["id"].as_str().unwrap().to_string();
+        let seen_by_a = app
+            .clone()
+            .oneshot(tenant_request(
+                "tenant-a",
+                "GET",
+                "/heroes/?name=Storm",
+                Value::Null,
+            ))
+            .await
+            .unwrap();
+        let seen_by_b = app
+            .clone()
+            .oneshot(tenant_request(
+                "tenant-b",
+                "GET",
+                "/heroes/?name=Storm",
+                Value::Null,
+            ))
+            .await
+            .unwrap();
+        let history_for_b = app
+            .oneshot(tenant_request(
+                "tenant-b",
+                "GET",
+                &format!("/heroes/{}/history", id),
+                Value::Null,
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(seen_by_a.status(), StatusCode::OK);
+        assert_eq!(seen_by_b.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body_json(history_for_b).await, serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn tenants_beyond_the_limit_are_refused() {
+        let app = multi_tenant_app();
+        let listing = |tenant| tenant_request(tenant, "GET", "/heroes/?name=Wonder", Value::Null);
+
+        for tenant in ["tenant-a", "tenant-b"] {
+            let response = app.clone().oneshot(listing(tenant)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        let refused = app.clone().oneshot(listing("tenant-c")).await.unwrap();
+        let known = app.oneshot(listing("tenant-a")).await.unwrap();
+
+        assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+        assert_eq!(body_json(refused).await["error"], "tenant_limit_reached");
+        assert_eq!(known.status(), StatusCode::OK);
+    }
+
+    #[rstest]
+    #[case(None)]
+    #[case(Some("not/valid"))]
+    #[tokio::test]
+    async fn missing_or_invalid_tenant_is_a_bad_request(#[case] tenant: Option<&str>) {
+        let request = match tenant {
+            Some(tenant) => tenant_request(tenant, "GET", "/heroes/?name=Storm", Value::Null),
+            None => send_get_request("/heroes/?name=Storm"),
+        };
+
+        let response = multi_tenant_app().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    fn cached_listing_app(repo: MockHeroesRepositoryTrait) -> Router {
+        build_app(AppState {
+            repo: Arc::new(repo),
+            audit_log: Arc::new(InMemoryAuditLog::default()),
+            metrics: Default::default(),
+            events: Default::default(),
+            started: Default::default(),
+            tasks: Default::default(),
+            warm_up: Default::default(),
+            breaker: None,
+            config: Arc::new(LiveConfig::new(Config {
+                cache_control: Some("public, max-age=60".to_string()),
+                ..Default::default()
+            })),
+        })
+    }
+
+    #[tokio::test]
+    async fn successful_listing_is_cacheable() {
+        let mut repo = MockHeroesRepositoryTrait::new();
+        repo.expect_get_by_name()
+            .returning(|_| Ok(vec![Hero::default()]));
+
+        let response = cached_listing_app(repo)
+            .oneshot(send_get_request("/heroes/?name=Wonder"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=60"
+        );
+        assert_eq!(response.headers()[header::VARY], tenant::TENANT_HEADER);
+    }
+
+    #[rstest]
+    #[case(send_get_request("/heroes/?name=Wonder"))] // the mock fails
+    #[case(send_json_request("POST", "/heroes/", serde_json::json!({ "name": "Storm" })))]
+    #[tokio::test]
+    async fn errors_and_writes_are_not_cacheable(#[case] request: Request<Body>) {
+        let mut repo = MockHeroesRepositoryTrait::new();
+        repo.expect_get_by_name()
+            .returning(|_| Err(DataAccessError::TechnicalError));
+        repo.expect_create().returning(|_| Ok(Hero::default()));
+
+        let response = cached_listing_app(repo).oneshot(request).await.unwrap();
+
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-store");
+    }
+
+    fn hero_updated_at(id: &str, updated_at: u64) -> Hero {
+        Hero {
+            id: id.to_string(),
+            name: HeroName::new("Wonder Woman").unwrap(),
+            updated_at: Some(updated_at),
+            tags: vec![],
+            power_level: 0,
+        }
+    }
+
+    fn conditional_request(uri: &str, if_modified_since: &str) -> Request<Body> {
+        Request::builder()
+            .uri(uri)
+            .header(header::IF_MODIFIED_SINCE, if_modified_since)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn listing_is_dated_by_its_latest_update() {
+        let mut repo = MockHeroesRepositoryTrait::new();
+        repo.expect_get_by_name().returning(|_| {
+            // 2023-11-14T22:13:20Z and 2023-11-14T22:15:00.5Z
+            Ok(vec![
+                hero_updated_at("1", 1_700_000_000_000),
+                hero_updated_at("2", 1_700_000_100_500),
+            ])
+        });
+
+        let response = app(repo).oneshot(send_get_request("/")).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::LAST_MODIFIED],
+            "Tue, 14 Nov 2023 22:15:00 GMT"
+        );
+    }
+
+    #[rstest]
+    #[case("Tue, 14 Nov 2023 22:15:00 GMT", StatusCode::NOT_MODIFIED)]
+    #[case("Wed, 15 Nov 2023 08:00:00 GMT", StatusCode::NOT_MODIFIED)]
+    #[case("Tue, 14 Nov 2023 22:14:59 GMT", StatusCode::OK)]
+    #[case("not a date", StatusCode::OK)]
+    #[tokio::test]
+    async fn if_modified_since_is_honored(
+        #[case] if_modified_since: &str,
+        #[case] expected_status: StatusCode,
+    ) {
+        let mut repo = MockHeroesRepositoryTrait::new();
+        repo.expect_get_by_name()
+            .returning(|_| Ok(vec![hero_updated_at("1", 1_700_000_100_500)]));
+
+        let response = app(repo)
+            .oneshot(conditional_request("/?name=Wonder", if_modified_since))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), expected_status);
+        assert!(response.headers().contains_key(header::LAST_MODIFIED));
+    }
+
+    #[rstest]
+    #[case("/?updated_at_gte=1700000000000&updated_at_lte=1700000100000", StatusCode::OK, &["1", "2"])]
+    #[case("/?updated_at_gte=1700000050000", StatusCode::OK, &["2", "3"])]
+    #[case("/?updated_at_lte=1600000000000", StatusCode::NOT_FOUND, &[])]
+    #[case("/?updated_at_gte=1700000100000&updated_at_lte=1700000000000", StatusCode::BAD_REQUEST, &[])]
+    #[case("/?updated_at_lte=soon", StatusCode::BAD_REQUEST, &[])]
+    #[tokio::test]
+    async fn listings_are_narrowed_by_numeric_ranges(
+        #[case] uri: &str,
+        #[case] expected_status: StatusCode,
+        #[case] expected_ids: &[&str],
+    ) {
+        let repo = InMemoryHeroesRepository::new(vec![
+            hero_updated_at("1", 1_700_000_000_000),
+            hero_updated_at("2", 1_700_000_100_000),
+            hero_updated_at("3", 1_700_000_200_000),
+        ]);
+
+        let response = app(repo).oneshot(send_get_request(uri)).await.unwrap();
+
+        assert_eq!(response.status(), expected_status);
+        if expected_status == StatusCode::OK {
+            let body = body_json(response).await;
+            let ids: Vec<&str> = body
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|hero| hero["id"].as_str().unwrap())
+                .collect();
+            assert_eq!(ids, expected_ids);
+        }
+    }
+
+    #[tokio::test]
+    async fn listing_is_rendered_in_the_accepted_format() {
+        let mut repo = MockHeroesRepositoryTrait::new();
+        repo.expect_get_by_name().returning(|_| {
+            Ok(vec![Hero {
+                id: "1".to_string(),
+                name: HeroName::new("Wonder Woman").unwrap(),
+                updated_at: None,
+                tags: vec![],
+                power_level: 0,
+            }])
+        });
+        let request = Request::builder()
+            .uri("/?name=Wonder")
+            .header(header::ACCEPT, "text/csv")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app(repo).oneshot(request).await.unwrap();
+
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/csv; charset=utf-8"
+        );
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"id,name,power_level\r\n1,Wonder Woman,0\r\n");
+    }
+
+    #[rstest]
+    #[case(serde_json::json!({ "name": "Storm" }), serde_json::json!({ "valid": true }))]
+    #[case(
+        serde_json::json!({ "name": "wonder woman" }),
+        serde_json::json!({ "valid": false, "errors": ["a hero named 'wonder woman' already exists"] })
+    )]
+    #[case(
+        serde_json::json!({ "name": "100% Hero" }),
+        serde_json::json!({ "valid": false, "errors": ["name must not contain '%'"] })
+    )]
+    #[tokio::test]
+    async fn payload_is_validated_without_being_created(
+        #[case] payload: Value,
+        #[case] expected: Value,
+    ) {
+        let app = app(InMemoryHeroesRepository::default());
+
+        let response = app
+            .clone()
+            .oneshot(send_json_request("POST", "/validate", payload))
+            .await
+            .unwrap();
+        let listing = app.oneshot(send_get_request("/")).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await, expected);
+        assert_eq!(body_json(listing).await.as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn blank_name_is_reported_as_invalid() {
+        let response = app(MockHeroesRepositoryTrait::new())
+            .oneshot(send_json_request(
+                "POST",
+                "/validate",
+                serde_json::json!({ "name": " " }),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_json(response).await;
+        assert_eq!(body["valid"], false);
+        assert_eq!(body["errors"][0], "hero name must not be blank");
+    }
+
+    #[tokio::test]
+    async fn duplicate_name_is_not_created() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_json_request(
+                "POST",
+                "/",
+                serde_json::json!({ "name": "Deadpool" }),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body_json(response).await["error"], "invalid_hero");
+    }
+
+    #[rstest]
+    #[case("POST", "/")]
+    #[case("PUT", "/1")]
+    #[tokio::test]
+    async fn power_level_above_100_is_unprocessable(#[case] method: &str, #[case] uri: &str) {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_json_request(
+                method,
+                uri,
+                serde_json::json!({ "name": "Storm", "power_level": 101 }),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = body_json(response).await;
+        assert_eq!(body["error"], "invalid_hero");
+        assert_eq!(body["message"], "power_level must be between 0 and 100");
+    }
+
+    #[rstest]
+    #[case("PUT", "/heroes/batch", "invalid_batch")]
+    #[case("POST", "/admin/heroes/reload", "invalid_dataset")]
+    #[tokio::test]
+    async fn power_level_above_100_is_refused_by_dataset_writes(
+        #[case] method: &str,
+        #[case] uri: &str,
+        #[case] error: &str,
+    ) {
+        let heroes = serde_json::json!([{ "id": "7", "name": "Storm", "power_level": 5000 }]);
+        let mut request = send_json_request(method, uri, heroes);
+        request
+            .headers_mut()
+            .insert("authorization", "Bearer s3cr3t".parse().unwrap());
+
+        let response = app_with_admin_token().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = body_json(response).await;
+        assert_eq!(body["error"], error);
+        assert_eq!(
+            body["message"],
+            "hero at index 0 is invalid: power_level must be between 0 and 100"
+        );
+    }
+
+    #[tokio::test]
+    async fn power_level_is_stored_and_serialized() {
+        let app = app(InMemoryHeroesRepository::default());
+
+        let response = app
+            .clone()
+            .oneshot(send_json_request(
+                "POST",
+                "/",
+                serde_json::json!({ "name": "Storm", "power_level": 88 }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created = body_json(response).await;
+        assert_eq!(created["power_level"], 88);
+        let uri = format!("/{}", created["id"].as_str().unwrap());
+
+        let response = app.oneshot(send_get_request(&uri)).await.unwrap();
+        let body = body_json(response).await;
+        assert_eq!(body["name"], "Storm");
+        assert_eq!(body["power_level"], 88);
+    }
+
+    #[tokio::test]
+    async fn not_found_is_counted_in_metrics() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock
+            .expect_get_by_id()
+            .returning(|_| Err(DataAccessError::NotFound));
+        let app = build_app(AppState {
+            repo: Arc::new(repo_mock),
+            ..state_with_config(Config::default())
+        });
+
+        let response = app
+            .clone()
+            .oneshot(send_get_request("/heroes/42"))
+            .await
+            .unwrap();
+        let metrics = app.oneshot(send_get_request("/metrics")).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = hyper::body::to_bytes(metrics.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.lines().any(|line| line == "heroes_not_found_total 1"));
+    }
+
+    #[tokio::test]
+    async fn changes_are_published_to_subscribers() {
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(Config::default())
+        };
+        let mut events = state.events.subscribe();
+        let app = heroes_routes(&mut RouteManifest::default()).with_state(state);
+
+        let create = send_json_request("POST", "/", serde_json::json!({ "name": "Storm" }));
+        app.clone().oneshot(create).await.unwrap();
+        app.oneshot(send_json_request("DELETE", "/2", serde_json::json!({})))
+            .await
+            .unwrap();
+
+        let created = events.try_recv().unwrap();
+        assert_eq!(created.action, AuditAction::Create);
+        assert_eq!(created.hero.name.as_str(), "Storm");
+        let deleted = events.try_recv().unwrap();
+        assert_eq!(deleted.action, AuditAction::Delete);
+        assert_eq!(deleted.hero.id, "2");
+    }
+
+    /// Action and hero id of the events `events` received so far, by hero id
+    fn published_by_id(
+        events: &mut tokio::sync::broadcast::Receiver<events::HeroEvent>,
+    ) -> Vec<(AuditAction, String)> {
+        let mut published: Vec<(AuditAction, String)> =
+            std::iter::from_fn(|| events.try_recv().ok())
+                .map(|event| (event.action, event.hero.id))
+                .collect();
+        published.sort_by(|a, b| a.1.cmp(&b.1));
+        published
+    }
+
+    #[tokio::test]
+    async fn bulk_deletions_are_published_to_subscribers() {
+        let state = AppState {
+            repo: Arc::new(heroes_named(&["Test 1", "Storm", "Test 2"])),
+            ..state_with_config(Config::default())
+        };
+        let mut events = state.events.subscribe();
+        let app = heroes_routes(&mut RouteManifest::default()).with_state(state);
+
+        let delete = Request::builder()
+            .method("DELETE")
+            .uri("/?name=Test&confirm=true")
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(delete).await.unwrap();
+
+        assert_eq!(
+            published_by_id(&mut events),
+            [
+                (AuditAction::Delete, "1".to_string()),
+                (AuditAction::Delete, "3".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn batch_upserts_are_published_to_subscribers() {
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(Config::default())
+        };
+        let mut events = state.events.subscribe();
+        let app = heroes_routes(&mut RouteManifest::default()).with_state(state);
+
+        let batch = serde_json::json!([
+            { "id": "1", "name": "Diana Prince" },
+            { "id": "7", "name": "Storm" }
+        ]);
+        app.oneshot(send_json_request("PUT", "/batch", batch))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            published_by_id(&mut events),
+            [
+                (AuditAction::Update, "1".to_string()),
+                (AuditAction::Create, "7".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reloaded_dataset_is_published_to_subscribers() {
+        let config = Config {
+            admin_token: Some("s3cr3t".to_string()),
+            ..Default::default()
+        };
+        let state = AppState {
+            repo: Arc::new(InMemoryHeroesRepository::default()),
+            ..state_with_config(config)
+        };
+        let mut events = state.events.subscribe();
+
+        let dataset = serde_json::json!([{ "id": "1", "name": "Diana Prince" }]);
+        let response = build_app(state)
+            .oneshot(admin_request("/admin/heroes/reload", dataset))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        assert_eq!(
+            published_by_id(&mut events),
+            [
+                (AuditAction::Update, "1".to_string()),
+                (AuditAction::Delete, "2".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn changes_are_streamed_as_server_sent_events() {
+        use hyper::body::HttpBody;
+        let app = app(InMemoryHeroesRepository::default());
+
+        let response = app
+            .clone()
+            .oneshot(send_get_request("/events/sse"))
+            .await
+            .unwrap();
+        let update = send_json_request("PUT", "/1", serde_json::json!({ "name": "Diana Prince" }));
+        app.oneshot(update).await.unwrap();
+
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        let mut body = response.into_body();
+        let frame = time::timeout(Duration::from_secs(1), body.data())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let frame = String::from_utf8(frame.to_vec()).unwrap();
+        assert!(frame.starts_with("event:update\ndata:{"));
+        assert!(frame.contains(r#""name":"Diana Prince""#));
+    }
+
+    #[tokio::test]
+    async fn unrecognized_flag_is_a_bad_request() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request("/1?pretty=maybe"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_json(response).await["message"],
+            "pretty must be true, false, 1, 0, yes or no"
+        );
+    }
+
+    #[tokio::test]
+    async fn read_only_mode_serves_reads_and_refuses_writes() {
+        let app = app(ReadOnlyHeroesRepository::new(
+            InMemoryHeroesRepository::default(),
+        ));
+
+        let read = app.clone().oneshot(send_get_request("/1")).await.unwrap();
+        let write = app
+            .clone()
+            .oneshot(send_json_request(
+                "PUT",
+                "/1",
+                serde_json::json!({ "name": "Diana Prince" }),
+            ))
+            .await
+            .unwrap();
+        let delete = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/2")
+                    .method("DELETE")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let after = app.oneshot(send_get_request("/1")).await.unwrap();
+
+        assert_eq!(read.status(), StatusCode::OK);
+        assert_eq!(write.status(), StatusCode::FORBIDDEN);
+        assert_eq!(body_json(write).await["error"], "read_only");
+        assert_eq!(delete.status(), StatusCode::FORBIDDEN);
+        assert_eq!(body_json(after).await["name"], "Wonder Woman");
+    }
+
+    fn heroes_named(names: &[&str]) -> InMemoryHeroesRepository {
+        InMemoryHeroesRepository::new(
+            names
+                .iter()
+                .enumerate()
+                .map(|(index, name)| Hero {
+                    id: (index + 1).to_string(),
+                    name: HeroName::new(name).unwrap(),
+                    updated_at: None,
+                    tags: vec![],
+                    power_level: 0,
+                })
+                .collect(),
+        )
+    }
+
+    #[tokio::test]
+    async fn pages_do_not_shift_when_heroes_are_created() {
+        let names: Vec<String> = (1..=10).map(|n| format!("Hero {}", n)).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let app = app(heroes_named(&names));
+        let page = |offset: usize| {
+            let app = app.clone();
+            async move {
+                let uri = format!("/?limit=4&offset={}", offset);
+                let response = app.oneshot(send_get_request(&uri)).await.unwrap();
+                let page = body_json(response).await;
+                page.as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|hero| hero["id"].as_str().unwrap().to_string())
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        let mut listed = page(0).await;
+        let create = send_json_request("POST", "/", serde_json::json!({ "name": "Newcomer" }));
+        app.clone().oneshot(create).await.unwrap();
+        listed.extend(page(4).await);
+        listed.extend(page(8).await);
+
+        let expected: Vec<String> = (1..=11).map(|id| id.to_string()).collect();
+        assert_eq!(listed, expected);
+    }
+
+    #[tokio::test]
+    async fn page_comes_with_the_total_of_matching_heroes() {
+        let mut names: Vec<String> = (1..=10).map(|n| format!("Hero {}", n)).collect();
+        names.push("Storm".to_string());
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+
+        let response = app(heroes_named(&names))
+            .oneshot(send_get_request("/page?name=hero&limit=3&offset=3"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let page = body_json(response).await;
+        let ids: Vec<&str> = page["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hero| hero["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["4", "5", "6"]);
+        assert_eq!(page["total"], 10);
+        assert_eq!(page["limit"], 3);
+        assert_eq!(page["offset"], 3);
+    }
+
+    #[rstest]
+    #[case(PageFormat::Headers, "application/json")]
+    #[case(PageFormat::Envelope, "application/json; pagination=headers")]
+    #[tokio::test]
+    async fn page_can_be_a_bare_array_with_pagination_headers(
+        #[case] page_format: PageFormat,
+        #[case] accept: &str,
+    ) {
+        let names: Vec<String> = (1..=10).map(|n| format!("Hero {}", n)).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let config = Config {
+            page_format,
+            ..Default::default()
+        };
+        let request = Request::builder()
+            .uri("/page?name=hero&limit=3&offset=3")
+            .header("host", "heroes.example")
+            .header("accept", accept)
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app_with_config(heroes_named(&names), config)
+            .oneshot(request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-total-count"], "10");
+        assert_eq!(
+            response.headers()["link"],
+            [
+                r#"<http://heroes.example/page?name=hero&limit=3&offset=0>; rel="first""#,
+                r#"<http://heroes.example/page?name=hero&limit=3&offset=0>; rel="prev""#,
+                r#"<http://heroes.example/page?name=hero&limit=3&offset=6>; rel="next""#,
+                r#"<http://heroes.example/page?name=hero&limit=3&offset=9>; rel="last""#,
+            ]
+            .join(", ")
+        );
+        let page = body_json(response).await;
+        assert_eq!(page.as_array().unwrap().len(), 3);
+        assert_eq!(page[0]["id"], "4");
+    }
+
+    #[tokio::test]
+    async fn page_without_any_match_is_not_found() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request("/page?name=Batman"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn in_memory_heroes_are_kept_in_id_order() {
+        let hero = |id: &str| Hero {
+            id: id.to_string(),
+            name: HeroName::new("Storm").unwrap(),
+            ..Default::default()
+        };
+        let repo = InMemoryHeroesRepository::new(vec![hero("b"), hero("10"), hero("9"), hero("a")]);
+
+        let heroes = repo.get_by_name("%").await.unwrap();
+
+        let ids: Vec<&str> = heroes.iter().map(|hero| hero.id.as_str()).collect();
+        assert_eq!(ids, ["9", "10", "a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn similar_heroes_are_listed_most_similar_first() {
+        let repo = heroes_named(&["Spider-Man", "Batman", "Superman", "Spider-Woman"]);
+
+        let response = app(repo)
+            .oneshot(send_get_request("/1/similar"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let names: Vec<Value> = body_json(response)
+            .await
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hero| hero["name"].clone())
+            .collect();
+        assert_eq!(names, vec!["Spider-Woman", "Superman"]);
+    }
+
+    #[rstest]
+    #[case(None, "/", ["Storm", "Batman", "cyclops"])] // repository order
+    #[case(Some("name"), "/", ["Batman", "cyclops", "Storm"])]
+    #[case(Some("-name"), "/", ["Storm", "cyclops", "Batman"])]
+    #[case(Some("name"), "/?sort=-id", ["cyclops", "Batman", "Storm"])]
+    #[case(None, "/?sort=-updated_at,name", ["Batman", "cyclops", "Storm"])] // none updated
+    #[tokio::test]
+    async fn listings_follow_the_default_sort_unless_asked_otherwise(
+        #[case] default_sort: Option<&str>,
+        #[case] uri: &str,
+        #[case] expected: [&str; 3],
+    ) {
+        let config = Config {
+            default_sort: default_sort.map(|sort| sort.parse().unwrap()),
+            ..Default::default()
+        };
+        let repo = heroes_named(&["Storm", "Batman", "cyclops"]);
+
+        let response = app_with_config(repo, config)
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let names: Vec<Value> = body_json(response)
+            .await
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hero| hero["name"].clone())
+            .collect();
+        assert_eq!(names, expected);
+    }
+
+    #[rstest]
+    #[case("/?sort=power_level", &["Cyclops", "Storm", "Magneto"])]
+    #[case("/?sort=-power_level", &["Magneto", "Storm", "Cyclops"])]
+    #[case("/?power_gte=50&power_lte=90&sort=name", &["Cyclops", "Storm"])]
+    #[tokio::test]
+    async fn listings_are_sorted_and_filtered_by_power_level(
+        #[case] uri: &str,
+        #[case] expected: &[&str],
+    ) {
+        let hero = |id: &str, name: &str, power_level: u32| Hero {
+            id: id.to_string(),
+            name: HeroName::new(name).unwrap(),
+            power_level,
+            ..Default::default()
+        };
+        let repo = InMemoryHeroesRepository::new(vec![
+            hero("1", "Storm", 85),
+            hero("2", "Magneto", 95),
+            hero("3", "Cyclops", 70),
+        ]);
+
+        let response = app(repo).oneshot(send_get_request(uri)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let names: Vec<Value> = body_json(response)
+            .await
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hero| hero["name"].clone())
+            .collect();
+        assert_eq!(names, expected);
+    }
+
+    #[tokio::test]
+    async fn invalid_second_sort_key_is_a_bad_request() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request("/?sort=name,-power"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_json(response).await["message"],
+            "can't sort by 'power', expected id, name, updated_at or power_level"
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_sort_field_is_a_bad_request() {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(send_get_request("/?sort=power"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body_json(response).await["message"],
+            "can't sort by 'power', expected id, name, updated_at or power_level"
+        );
+    }
+
+    #[tokio::test]
+    async fn similar_heroes_of_a_missing_hero_are_not_found() {
+        let response = app(heroes_named(&["Spider-Man"]))
+            .oneshot(send_get_request("/42/similar"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn listing_is_capped_to_max_results() {
+        let mut repo = MockHeroesRepositoryTrait::new();
+        repo.expect_get_by_name()
+            .returning(|_| Ok(vec![Hero::default(); 5]));
+        let config = Config {
+            max_results: 3,
+            ..Default::default()
+        };
+        let (logs, _guard) = logging::capture();
+
+        let response = app_with_config(repo, config)
+            .oneshot(send_get_request("/"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[RESULT_TRUNCATED_HEADER], "true");
+        assert_eq!(body_json(response).await.as_array().unwrap().len(), 3);
+        assert!(logs.contains("listing truncated found=5 max_results=3"));
+    }
+
+    #[tokio::test]
+    async fn listing_under_the_cap_is_not_marked_truncated() {
+        let mut repo = MockHeroesRepositoryTrait::new();
+        repo.expect_get_by_name()
+            .returning(|_| Ok(vec![Hero::default(); 3]));
+        let config = Config {
+            max_results: 3,
+            ..Default::default()
+        };
+
+        let response = app_with_config(repo, config)
+            .oneshot(send_get_request("/"))
+            .await
+            .unwrap();
+
+        assert!(!response.headers().contains_key(RESULT_TRUNCATED_HEADER));
+    }
+
+    #[rstest]
+    #[case("2", Some("Batman"), "deadpool", Some("Wonder Woman"))] // names are sorted ignoring case
+    #[case("3", None, "Batman", Some("deadpool"))]
+    #[case("1", Some("deadpool"), "Wonder Woman", None)]
+    #[tokio::test]
+    async fn hero_is_given_with_its_neighbors(
+        #[case] id: &str,
+        #[case] prev: Option<&str>,
+        #[case] current: &str,
+        #[case] next: Option<&str>,
+    ) {
+        let repo = heroes_named(&["Wonder Woman", "deadpool", "Batman"]);
+
+        let response = app(repo)
+            .oneshot(send_get_request(&format!("/{}/context", id)))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let context = body_json(response).await;
+        assert_eq!(context["prev"]["name"].as_str(), prev);
+        assert_eq!(context["current"]["name"], current);
+        assert_eq!(context["next"]["name"].as_str(), next);
+    }
+
+    #[rstest]
+    #[case("/?name=Storm")]
+    #[case("/?q=Storm")]
+    #[tokio::test]
+    async fn empty_result_is_not_found(#[case] uri: &str) {
+        let mut repo = MockHeroesRepositoryTrait::new();
+        repo.expect_get_by_name().returning(|_| Ok(vec![]));
+        repo.expect_search().returning(|_| Ok(vec![]));
+
+        let response = app(repo).oneshot(send_get_request(uri)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(body_json(response).await["error"], "not_found");
+    }
+
+    #[tokio::test]
+    async fn listing_can_be_keyed_by_id() {
+        let app = app(heroes_named(&["Storm", "Rogue"]));
+
+        let map = app
+            .clone()
+            .oneshot(send_get_request("/?shape=map"))
+            .await
+            .unwrap();
+        let array = app.oneshot(send_get_request("/")).await.unwrap();
+
+        let map = body_json(map).await;
+        assert_eq!(map.as_object().unwrap().len(), 2);
+        assert_eq!(map["1"]["name"], "Storm");
+        assert_eq!(map["2"]["name"], "Rogue");
+        let array = body_json(array).await;
+        assert_eq!(array.as_array().unwrap().len(), 2);
+        assert_eq!(array[0]["id"], "1");
+    }
+
+    #[tokio::test]
+    async fn page_past_the_end_is_empty_not_missing() {
+        let mut repo = MockHeroesRepositoryTrait::new();
+        repo.expect_get_by_name()
+            .returning(|_| Ok(vec![Hero::default()]));
+
+        let response = app(repo)
+            .oneshot(send_get_request("/?limit=10&offset=5"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_json(response).await, serde_json::json!([]));
+    }
+
+    fn merge_patch_request(id: &str, content_type: &str, patch: Value) -> Request<Body> {
+        Request::builder()
+            .method("PATCH")
+            .uri(format!("/{}", id))
+            .header("content-type", content_type)
+            .body(Body::from(patch.to_string()))
+            .unwrap()
+    }
+
+    async fn merge_patched(patch: Value) -> Value {
+        let repo = InMemoryHeroesRepository::new(vec![Hero {
+            id: "2".to_string(),
+            name: HeroName::new("Deadpool").unwrap(),
+            tags: vec!["antihero".to_string(), "mercenary".to_string()],
+            ..Default::default()
+        }]);
+
+        let response = app(repo)
+            .oneshot(merge_patch_request("2", merge_patch::CONTENT_TYPE, patch))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        body_json(response).await
+    }
+
+    #[tokio::test]
+    async fn merge_patch_sets_fields() {
+        let hero = merge_patched(serde_json::json!({ "name": "Lady Deadpool" })).await;
+
+        assert_eq!(hero["name"], "Lady Deadpool");
+    }
+
+    #[tokio::test]
+    async fn merge_patch_clears_null_fields() {
+        let hero = merge_patched(serde_json::json!({ "tags": null })).await;
+
+        assert!(hero.get("tags").is_none());
+        assert_eq!(hero["name"], "Deadpool");
+    }
+
+    #[tokio::test]
+    async fn merge_patch_leaves_absent_fields_untouched() {
+        let hero = merge_patched(serde_json::json!({ "tags": ["Villain"] })).await;
+
+        assert_eq!(hero["name"], "Deadpool");
+        assert_eq!(hero["tags"], serde_json::json!(["villain"]));
+    }
+
+    #[rstest]
+    #[case(send_json_request("PUT", "/1", serde_json::json!({ "name": "Wonder Woman", "power_level": 92 })))]
+    #[case(merge_patch_request("1", merge_patch::CONTENT_TYPE, serde_json::json!({ "name": "Wonder Woman" })))]
+    #[tokio::test]
+    async fn update_changing_nothing_is_not_written(#[case] update: Request<Body>) {
+        let app = app(InMemoryHeroesRepository::new(vec![Hero {
+            id: "1".to_string(),
+            name: HeroName::new("Wonder Woman").unwrap(),
+            updated_at: Some(1),
+            power_level: 92,
+            ..Default::default()
+        }]));
+
+        let response = app.clone().oneshot(update).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert!(body.is_empty());
+
+        let response = app.oneshot(send_get_request("/1")).await.unwrap();
+        assert_eq!(body_json(response).await["updated_at"], 1);
+    }
+
+    #[tokio::test]
+    async fn update_is_compared_by_the_write_itself() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock.expect_get_by_id().never();
+        repo_mock
+            .expect_update()
+            .times(1)
+            .returning(|_, _| Err(DataAccessError::Unchanged));
+        let update = send_json_request("PUT", "/1", serde_json::json!({ "name": "Wonder Woman" }));
+
+        let response = app(repo_mock).oneshot(update).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn merge_patch_is_a_single_write() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock.expect_get_by_id().times(1).returning(|id| {
+            Ok(Hero {
+                id: id.to_string(),
+                name: HeroName::new("Deadpool").unwrap(),
+                ..Default::default()
+            })
+        });
+        repo_mock.expect_update().never();
+        repo_mock.expect_update_tags().never();
+        repo_mock
+            .expect_update_with_tags()
+            .withf(|id, hero, tags| {
+                id == "2" && hero.name.as_str() == "Lady Deadpool" && *tags == ["villain"]
+            })
+            .times(1)
+            .returning(|id, hero, tags| {
+                Ok(Hero {
+                    id: id.to_string(),
+                    name: hero.name,
+                    tags,
+                    ..Default::default()
+                })
+            });
+        let patch = serde_json::json!({ "name": "Lady Deadpool", "tags": ["villain"] });
+
+        let response = app(repo_mock)
+            .oneshot(merge_patch_request("2", merge_patch::CONTENT_TYPE, patch))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn in_memory_update_with_tags_logs_one_change() {
+        let repo = InMemoryHeroesRepository::default();
+        let since = repo.get_changes_since(0).await.unwrap().len();
+        let hero = HeroPayload {
+            name: HeroName::new("Diana").unwrap(),
+            power_level: 93,
+        };
+
+        let updated = repo
+            .update_with_tags("1", hero, vec!["amazon".to_string(), "amazon".to_string()])
+            .await
+            .unwrap();
+
+        assert_eq!(updated.name.as_str(), "Diana");
+        assert_eq!(updated.tags, ["amazon"]);
+        assert_eq!(repo.get_changes_since(0).await.unwrap().len(), since + 1);
+    }
+
+    #[tokio::test]
+    async fn in_memory_update_changing_nothing_is_refused() {
+        let repo = InMemoryHeroesRepository::default();
+        let before = repo.get_by_id("1").await.unwrap();
+        let same = HeroPayload {
+            name: before.name.clone(),
+            power_level: before.power_level,
+        };
+
+        let result = repo.update("1", same).await;
+
+        assert!(matches!(result, Err(DataAccessError::Unchanged)));
+        assert_eq!(repo.get_by_id("1").await.unwrap(), before);
+    }
+
+    #[rstest]
+    #[case("application/json", serde_json::json!({ "name": "Storm" }), StatusCode::UNSUPPORTED_MEDIA_TYPE)]
+    #[case(merge_patch::CONTENT_TYPE, serde_json::json!({ "name": null }), StatusCode::UNPROCESSABLE_ENTITY)]
+    #[tokio::test]
+    async fn invalid_merge_patch_is_refused(
+        #[case] content_type: &str,
+        #[case] patch: Value,
+        #[case] expected_status: StatusCode,
+    ) {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(merge_patch_request("1", content_type, patch))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), expected_status);
+    }
+
+    fn tags_request(id: &str, changes: Value) -> Request<Body> {
+        send_json_request("PATCH", &format!("/{}/tags", id), changes)
+    }
+
+    #[tokio::test]
+    async fn tags_are_added_and_removed() {
+        let app = app(InMemoryHeroesRepository::default());
+
+        let added = app
+            .clone()
+            .oneshot(tags_request(
+                "2",
+                serde_json::json!({ "add": ["Mercenary", "antihero", "funny"] }),
+            ))
+            .await
+            .unwrap();
+        let removed = app
+            .clone()
+            .oneshot(tags_request(
+                "2",
+                serde_json::json!({ "add": ["antihero"], "remove": ["funny"] }),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            body_json(added).await["tags"],
+            serde_json::json!(["antihero", "funny", "mercenary"])
+        );
+        assert_eq!(
+            body_json(removed).await["tags"],
+            serde_json::json!(["antihero", "mercenary"])
+        );
+    }
+
+    #[rstest]
+    #[case(serde_json::json!({ "remove": ["villain"] }))]
+    #[case(serde_json::json!({ "add": ["Antihero"] }))]
+    #[tokio::test]
+    async fn tag_changes_changing_nothing_are_not_written(#[case] changes: Value) {
+        let app = app(InMemoryHeroesRepository::new(vec![Hero {
+            id: "2".to_string(),
+            name: HeroName::new("Deadpool").unwrap(),
+            updated_at: Some(1),
+            tags: vec!["antihero".to_string()],
+            ..Default::default()
+        }]));
+
+        let response = app
+            .clone()
+            .oneshot(tags_request("2", changes))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let response = app.oneshot(send_get_request("/2")).await.unwrap();
+        assert_eq!(body_json(response).await["updated_at"], 1);
+    }
+
+    fn view_request(id: &str) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri(format!("/{}/view", id))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn views_are_counted_per_hero() {
+        let app = app(InMemoryHeroesRepository::default());
+
+        for expected in 1..=3 {
+            let response = app.clone().oneshot(view_request("1")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                body_json(response).await,
+                serde_json::json!({ "id": "1", "views": expected })
+            );
+        }
+        let other = app.oneshot(view_request("2")).await.unwrap();
+        assert_eq!(body_json(other).await["views"], 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_views_are_all_counted() {
+        let repo = Arc::new(InMemoryHeroesRepository::default());
+
+        let views = (0..50).map(|_| {
+            let repo = repo.clone();
+            tokio::spawn(async move { repo.record_view("1").await.unwrap() })
+        });
+        futures::future::join_all(views).await;
+
+        assert_eq!(repo.record_view("1").await.unwrap(), 51);
+    }
+
+    #[rstest]
+    #[case("42", StatusCode::NOT_FOUND)]
+    #[case("2", StatusCode::GONE)] // deleted first
+    #[tokio::test]
+    async fn views_of_missing_heroes_are_refused(
+        #[case] id: &str,
+        #[case] expected_status: StatusCode,
+    ) {
+        let repo = InMemoryHeroesRepository::default();
+        repo.delete("2").await.unwrap();
+
+        let response = app(repo).oneshot(view_request(id)).await.unwrap();
+
+        assert_eq!(response.status(), expected_status);
+    }
+
+    /// `(seq, action, hero id)` of the changes after `since`
+    async fn changes_since(app: &Router, since: u64) -> Vec<(u64, String, String)> {
+        let response = app
+            .clone()
+            .oneshot(send_get_request(&format!("/changes?since={}", since)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let changes = body_json(response).await;
+        changes
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|change| {
+                (
+                    change["seq"].as_u64().unwrap(),
+                    change["action"].as_str().unwrap().to_string(),
+                    change["hero"]["id"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn changes_are_followed_with_their_watermark() {
+        let app = app(InMemoryHeroesRepository::default());
+        let change = |seq, action: &str, id: &str| (seq, action.to_string(), id.to_string());
+
+        // the seeded heroes are the first changes
+        assert_eq!(
+            changes_since(&app, 0).await,
+            [change(1, "create", "1"), change(2, "create", "2")]
+        );
+
+        let storm = serde_json::json!({ "name": "Storm" });
+        let renamed = serde_json::json!({ "name": "Diana Prince" });
+        for request in [
+            send_json_request("POST", "/", storm),
+            send_json_request("PUT", "/1", renamed),
+            Request::builder()
+                .method("DELETE")
+                .uri("/2")
+                .body(Body::empty())
+                .unwrap(),
+        ] {
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert!(response.status().is_success(), "{}", response.status());
+        }
+
+        assert_eq!(
+            changes_since(&app, 2).await,
+            [
+                change(3, "create", "3"),
+                change(4, "update", "1"),
+                change(5, "delete", "2"),
+            ]
+        );
+        assert_eq!(changes_since(&app, 4).await, [change(5, "delete", "2")]);
+        assert_eq!(changes_since(&app, 5).await, []);
+        assert_eq!(changes_since(&app, 99).await, []);
+    }
+
+    #[tokio::test]
+    async fn replacing_the_dataset_logs_each_hero_change() {
+        let repo = InMemoryHeroesRepository::default();
+        let storm = Hero {
+            id: "3".to_string(),
+            name: HeroName::new("Storm").unwrap(),
+            ..Default::default()
+        };
+        let wonder_woman = repo.get_by_id("1").await.unwrap();
+
+        repo.replace_all(vec![wonder_woman, storm]).await.unwrap();
+
+        let changes = repo.get_changes_since(2).await.unwrap();
+        let changes: Vec<(u64, AuditAction, &str)> = changes
+            .iter()
+            .map(|change| (change.seq, change.action, change.hero.id.as_str()))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                (3, AuditAction::Delete, "2"),
+                (4, AuditAction::Update, "1"),
+                (5, AuditAction::Create, "3"),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("1", serde_json::json!({ "add": [" "] }), StatusCode::UNPROCESSABLE_ENTITY)]
+    #[case("42", serde_json::json!({ "add": ["hero"] }), StatusCode::NOT_FOUND)]
+    #[tokio::test]
+    async fn invalid_tag_update_is_refused(
+        #[case] id: &str,
+        #[case] changes: Value,
+        #[case] expected_status: StatusCode,
+    ) {
+        let response = app(InMemoryHeroesRepository::default())
+            .oneshot(tags_request(id, changes))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), expected_status);
+    }
+
+    fn tagged_heroes() -> InMemoryHeroesRepository {
+        let tagged = |id: &str, name: &str, tags: &[&str]| Hero {
+            id: id.to_string(),
+            name: HeroName::new(name).unwrap(),
+            updated_at: None,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            power_level: 0,
+        };
+        InMemoryHeroesRepository::new(vec![
+            tagged("1", "Wonder Woman", &["amazon", "hero"]),
+            tagged("2", "Deadpool", &["antihero", "mercenary"]),
+            tagged("3", "Deathstroke", &["mercenary", "villain"]),
+            tagged("4", "Joker", &["villain"]),
+        ])
+    }
+
+    #[rstest]
+    #[case("/?tag=villain", &["3", "4"])]
+    #[case("/?tag=Villain&name=De", &["3"])] // combined with the name filter
+    #[case("/?tag=mercenary&tag=villain", &["3"])] // all tags by default
+    #[case("/?tag=hero&tag=villain&tag_mode=any", &["1", "3", "4"])]
+    #[case("/?q=D&tag=antihero", &["2"])]
+    #[tokio::test]
+    async fn heroes_are_filtered_by_tags(#[case] uri: &str, #[case] expected_ids: &[&str]) {
+        let response = app(tagged_heroes())
+            .oneshot(send_get_request(uri))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let ids: Vec<Value> = body_json(response)
+            .await
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hero| hero["id"].clone())
+            .collect();
+        assert_eq!(ids, expected_ids);
+    }
+
+    #[tokio::test]
+    async fn unknown_tag_matches_nothing() {
+        let response = app(tagged_heroes())
+            .oneshot(send_get_request("/?tag=sidekick"))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    /// Repository implementing only the required methods, leaving the others to their defaults
+    struct PrimitivesOnly(InMemoryHeroesRepository);
+
+    #[async_trait]
+    impl HeroesRepositoryTrait for PrimitivesOnly {
+        async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+            self.0.get_by_name(name).await
+        }
+
+        async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+            self.0.get_by_id(id).await
+        }
+
+        async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+            self.0.create(hero).await
+        }
+
+        async fn create_if_absent(
+            &self,
+            id: &str,
+            hero: HeroPayload,
+        ) -> Result<Hero, DataAccessError> {
+            self.0.create_if_absent(id, hero).await
+        }
+
+        async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+            self.0.update(id, hero).await
+        }
+
+        async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+            self.0.delete(id).await
+        }
+
+        fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+            self.0.stream_all()
+        }
+
+        async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+            self.0.replace_all(heroes).await
+        }
+
+        async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+            self.0.upsert_many(heroes).await
+        }
+
+        async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+            self.0.get_with_neighbors(id).await
+        }
+
+        async fn update_tags(
+            &self,
+            id: &str,
+            changes: TagChanges,
+        ) -> Result<Hero, DataAccessError> {
+            self.0.update_tags(id, changes).await
+        }
+
+        async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+            self.0.record_view(id).await
+        }
+
+        async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+            self.0.get_changes_since(since).await
+        }
+    }
+
+    fn ids(heroes: Vec<Hero>) -> Vec<String> {
+        heroes.into_iter().map(|hero| hero.id).collect()
+    }
+
+    #[tokio::test]
+    async fn default_methods_are_built_on_the_primitives() {
+        let repo = PrimitivesOnly(tagged_heroes());
+
+        assert_eq!(ids(repo.search("3").await.unwrap()), ["3"]);
+        assert_eq!(ids(repo.search("dea").await.unwrap()), ["2", "3"]);
+        assert!(matches!(
+            repo.search("Batman").await,
+            Err(DataAccessError::NotFound)
+        ));
+        let wanted = ["4", "42", "1"].map(String::from);
+        assert_eq!(ids(repo.get_by_ids(&wanted).await.unwrap()), ["4", "1"]);
+        assert_eq!(ids(repo.get_by_tag("Mercenary").await.unwrap()), ["2", "3"]);
+        let (page, total) = repo.get_page("De%", Some(1), 1).await.unwrap();
+        assert_eq!((ids(page), total), (vec!["3".to_string()], 2));
+        assert_eq!(
+            repo.count_by_initial().await.unwrap(),
+            [('D', 2), ('J', 1), ('W', 1)]
+        );
+        let stats = repo.stats().await.unwrap();
+        assert_eq!(stats.total, 4);
+        assert_eq!(stats.per_tag["mercenary"], 2);
+        assert_eq!(stats.shortest_name.as_deref(), Some("Joker"));
+        assert_eq!(
+            repo.dataset_digest().await.unwrap(),
+            tagged_heroes().dataset_digest().await.unwrap()
+        );
+        assert_eq!(ids(repo.delete_by_name("De%").await.unwrap()), ["2", "3"]);
+        assert!(repo.delete_by_name("De%").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn shared_repositories_forward_range_queries() {
+        let mut repo_mock = MockHeroesRepositoryTrait::new();
+        repo_mock
+            .expect_get_by_range()
+            .times(1)
+            .returning(|_, _| Ok(vec![]));
+        repo_mock.expect_stream_all().never();
+        let repo: DynHeroesRepository = Arc::new(repo_mock);
+        let range = NumericRange {
+            gte: Some(50),
+            lte: None,
+        };
+
+        let heroes = repo.get_by_range(NumericField::PowerLevel, range).await;
+
+        assert!(heroes.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn search_by_id_lists_the_hero_once() {
+        let repo = PrimitivesOnly(heroes_named(&["1st Avenger", "Hulk"]));
+
+        assert_eq!(ids(repo.search("1").await.unwrap()), ["1"]);
+    }
+
+    #[tokio::test]
+    async fn handlers_can_be_driven_by_closures() {
+        let repo = FnHeroesRepository::new().on_get_by_name(|name| match name {
+            "Wonder%" => Ok(vec![Hero {
+                id: "1".to_string(),
+                name: HeroName::new("Wonder Woman").unwrap(),
+                ..Default::default()
+            }]),
+            _ => Err(DataAccessError::NotFound),
+        });
+        let app = app(repo);
+
+        let found = app
+            .clone()
+            .oneshot(send_get_request("/?name=Wonder"))
+            .await
+            .unwrap();
+        let missing = app.oneshot(send_get_request("/?name=Bat")).await.unwrap();
+
+        assert_eq!(found.status(), StatusCode::OK);
+        assert_eq!(body_json(found).await[0]["name"], "Wonder Woman");
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
     }
 }
@@ -0,0 +1,96 @@
+use crate::error::ApiError;
+use axum::{
+    async_trait,
+    body::{Body, Bytes},
+    extract::FromRequest,
+    http::{header, Request, StatusCode},
+};
+use serde_json::Value;
+
+/// Media type of JSON merge patches
+pub const CONTENT_TYPE: &str = "application/merge-patch+json";
+
+/// Apply the JSON merge `patch` to `target`, as defined by RFC 7386
+///
+/// Members of an object patch replace those of the target, recursively; `null` ones remove
+/// them and absent ones are left untouched. Any other patch replaces the whole target.
+pub fn apply(target: &mut Value, patch: &Value) {
+    let Value::Object(members) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    if let Value::Object(target) = target {
+        for (name, value) in members {
+            if value.is_null() {
+                target.remove(name);
+            } else {
+                apply(target.entry(name).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Body of a request sent as `application/merge-patch+json`
+///
+/// Other content types are rejected with `415`, so a plain JSON body isn't mistaken for a
+/// patch, and malformed JSON with `400`.
+pub struct MergePatch(pub Value);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequest<S, Body> for MergePatch {
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request<Body>, state: &S) -> Result<Self, Self::Rejection> {
+        let content_type = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .map(str::trim);
+        if !content_type.is_some_and(|value| value.eq_ignore_ascii_case(CONTENT_TYPE)) {
+            return Err(ApiError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                format!("expected a {} body", CONTENT_TYPE),
+            ));
+        }
+        let body = Bytes::from_request(request, state)
+            .await
+            .map_err(|rejection| ApiError::bad_request(rejection.body_text()))?;
+        serde_json::from_slice(&body)
+            .map(MergePatch)
+            .map_err(|error| ApiError::bad_request(format!("invalid merge patch: {}", error)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    // examples of RFC 7386, appendix A
+    #[rstest]
+    #[case(json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"}))]
+    #[case(json!({"a": "b"}), json!({"b": "c"}), json!({"a": "b", "b": "c"}))]
+    #[case(json!({"a": "b"}), json!({"a": null}), json!({}))]
+    #[case(json!({"a": {"b": "c"}}), json!({"a": {"b": "d", "c": null}}), json!({"a": {"b": "d"}}))]
+    #[case(json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"}))]
+    #[case(json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"]))]
+    #[case(json!({"a": "foo"}), json!(null), json!(null))]
+    #[case(json!({}), json!({"a": {"bb": {"ccc": null}}}), json!({"a": {"bb": {}}}))]
+    fn patches_follow_the_rfc(
+        #[case] target: Value,
+        #[case] patch: Value,
+        #[case] expected: Value,
+    ) {
+        let mut target = target;
+
+        apply(&mut target, &patch);
+
+        assert_eq!(target, expected);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::hero_query::NumericField;
+use crate::metrics::AppMetrics;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::{
+    DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats, HeroesRepositoryTrait,
+    TagChanges, UpsertReport,
+};
+use axum::async_trait;
+use futures::stream::BoxStream;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::time::Instant;
+
+/// Repository decorator recording the latency of every call in the
+/// `repository_call_duration_seconds` histogram of `metrics`, labelled with the method
+///
+/// Failed calls are recorded too. `stream_all` isn't: its cost is paid while consuming.
+pub struct MeteredHeroesRepository<R> {
+    inner: R,
+    metrics: Arc<AppMetrics>,
+}
+
+impl<R> MeteredHeroesRepository<R> {
+    pub fn new(inner: R, metrics: Arc<AppMetrics>) -> Self {
+        MeteredHeroesRepository { inner, metrics }
+    }
+
+    async fn metered<T>(&self, method: &'static str, call: impl Future<Output = T>) -> T {
+        let started = Instant::now();
+        let result = call.await;
+        self.metrics
+            .observe_repository_call(method, started.elapsed());
+        result
+    }
+}
+
+#[async_trait]
+impl<R: HeroesRepositoryTrait + Send + Sync> HeroesRepositoryTrait for MeteredHeroesRepository<R> {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.metered("get_by_name", self.inner.get_by_name(name))
+            .await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.metered("get_by_id", self.inner.get_by_id(id)).await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.metered("create", self.inner.create(hero)).await
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.metered("create_if_absent", self.inner.create_if_absent(id, hero))
+            .await
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.metered("update", self.inner.update(id, hero)).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.metered("delete", self.inner.delete(id)).await
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.metered("delete_by_name", self.inner.delete_by_name(name))
+            .await
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.metered("count_by_initial", self.inner.count_by_initial())
+            .await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.metered("stats", self.inner.stats()).await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.metered("dataset_digest", self.inner.dataset_digest())
+            .await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        self.inner.stream_all()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.metered("search", self.inner.search(term)).await
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        self.metered("replace_all", self.inner.replace_all(heroes))
+            .await
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        self.metered("upsert_many", self.inner.upsert_many(heroes))
+            .await
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.metered("get_by_tag", self.inner.get_by_tag(tag)).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.metered("get_by_ids", self.inner.get_by_ids(ids)).await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.metered("get_page", self.inner.get_page(name, limit, offset))
+            .await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.metered("get_by_name_regex", self.inner.get_by_name_regex(regex))
+            .await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.metered("get_by_range", self.inner.get_by_range(field, range))
+            .await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.metered("ping", self.inner.ping()).await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        self.metered("update_tags", self.inner.update_tags(id, changes))
+            .await
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        self.metered(
+            "update_with_tags",
+            self.inner.update_with_tags(id, hero, tags),
+        )
+        .await
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.metered("record_view", self.inner.record_view(id))
+            .await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.metered("get_changes_since", self.inner.get_changes_since(since))
+            .await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.metered("get_with_neighbors", self.inner.get_with_neighbors(id))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InMemoryHeroesRepository;
+
+    #[tokio::test(start_paused = true)]
+    async fn calls_are_recorded_per_method() {
+        let metrics = Arc::new(AppMetrics::default());
+        let repo =
+            MeteredHeroesRepository::new(InMemoryHeroesRepository::default(), metrics.clone());
+
+        // the in-memory repository simulates a 100ms database read in get_by_name only
+        repo.get_by_name("Wonder%").await.unwrap();
+        let _ = repo.get_by_id("42").await;
+
+        let get_by_name = metrics.repository_latency("get_by_name").unwrap();
+        assert_eq!(get_by_name.count, 1);
+        assert!(get_by_name.sum_secs >= 0.1);
+        assert_eq!(metrics.repository_latency("get_by_id").unwrap().count, 1);
+        assert!(metrics.repository_latency("create").is_none());
+    }
+}
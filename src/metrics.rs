@@ -0,0 +1,265 @@
+use crate::DataAccessError;
+use axum::{body::Body, extract::State, http::Request, middleware::Next, response::Response};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the buckets of latency histograms
+const LATENCY_BUCKETS: [f64; 10] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Observations of a latency, counted per bucket of `LATENCY_BUCKETS`
+#[derive(Debug, Default, Clone)]
+pub struct Histogram {
+    /// observations of each bucket alone, not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    pub sum_secs: f64,
+    pub count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| secs <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum_secs += secs;
+        self.count += 1;
+    }
+}
+
+/// Business counters, incremented by handlers and exposed at `/metrics`
+#[derive(Debug, Default)]
+pub struct AppMetrics {
+    pub heroes_created: AtomicU64,
+    pub heroes_updated: AtomicU64,
+    pub heroes_deleted: AtomicU64,
+    pub heroes_not_found: AtomicU64,
+    /// name queries which shared the call of an identical one in flight
+    pub coalesced: AtomicU64,
+    /// name queries which reached the repository behind the coalescing decorator
+    pub backend_calls: AtomicU64,
+    /// requests being handled, a gauge kept by `track_active`
+    active_requests: AtomicU64,
+    /// bytes of response bodies sent, per route
+    response_bytes: Mutex<BTreeMap<String, u64>>,
+    /// latency of repository calls, per method
+    repository_latency: Mutex<BTreeMap<&'static str, Histogram>>,
+}
+
+impl AppMetrics {
+    pub fn increment(counter: &AtomicU64) {
+        Self::add(counter, 1);
+    }
+
+    pub fn add(counter: &AtomicU64, amount: u64) {
+        counter.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    /// Pass `result` through, counting it if it's a `NotFound`
+    pub(crate) fn observe<T>(
+        &self,
+        result: Result<T, DataAccessError>,
+    ) -> Result<T, DataAccessError> {
+        if let Err(DataAccessError::NotFound) = result {
+            Self::increment(&self.heroes_not_found);
+        }
+        result
+    }
+
+    /// Requests being handled right now
+    pub fn active_requests(&self) -> u64 {
+        self.active_requests.load(Ordering::Relaxed)
+    }
+
+    pub fn add_response_bytes(&self, route: &str, amount: u64) {
+        if let Ok(mut response_bytes) = self.response_bytes.lock() {
+            *response_bytes.entry(route.to_string()).or_default() += amount;
+        }
+    }
+
+    /// Bytes of response bodies sent by `route`, like `/heroes/:id`
+    pub fn response_bytes(&self, route: &str) -> u64 {
+        let response_bytes = self.response_bytes.lock();
+        response_bytes.map_or(0, |bytes| bytes.get(route).copied().unwrap_or_default())
+    }
+
+    pub fn observe_repository_call(&self, method: &'static str, elapsed: Duration) {
+        if let Ok(mut latency) = self.repository_latency.lock() {
+            latency.entry(method).or_default().observe(elapsed);
+        }
+    }
+
+    /// Latencies of the calls to the repository method `method`, if it was called
+    pub fn repository_latency(&self, method: &str) -> Option<Histogram> {
+        let latency = self.repository_latency.lock().ok()?;
+        latency.get(method).cloned()
+    }
+
+    /// Counters in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let counters = [
+            (
+                "heroes_created_total",
+                "Heroes created",
+                &self.heroes_created,
+            ),
+            (
+                "heroes_updated_total",
+                "Heroes updated",
+                &self.heroes_updated,
+            ),
+            (
+                "heroes_deleted_total",
+                "Heroes deleted",
+                &self.heroes_deleted,
+            ),
+            (
+                "heroes_not_found_total",
+                "Lookups and writes of heroes which don't exist",
+                &self.heroes_not_found,
+            ),
+            (
+                "coalesced_total",
+                "Name queries answered by an identical query already in flight",
+                &self.coalesced,
+            ),
+            (
+                "backend_calls_total",
+                "Name queries which reached the repository",
+                &self.backend_calls,
+            ),
+        ];
+
+        let mut output = String::new();
+        for (name, help, counter) in counters {
+            let _ = writeln!(output, "# HELP {} {}", name, help);
+            let _ = writeln!(output, "# TYPE {} counter", name);
+            let _ = writeln!(output, "{} {}", name, counter.load(Ordering::Relaxed));
+        }
+
+        let name = "http_requests_active";
+        let _ = writeln!(output, "# HELP {} Requests being handled", name);
+        let _ = writeln!(output, "# TYPE {} gauge", name);
+        let _ = writeln!(output, "{} {}", name, self.active_requests());
+
+        let name = "http_response_body_bytes_total";
+        let _ = writeln!(output, "# HELP {} Bytes of response bodies sent", name);
+        let _ = writeln!(output, "# TYPE {} counter", name);
+        if let Ok(response_bytes) = self.response_bytes.lock() {
+            for (route, bytes) in response_bytes.iter() {
+                let _ = writeln!(output, "{}{{route={:?}}} {}", name, route, bytes);
+            }
+        }
+
+        let name = "repository_call_duration_seconds";
+        let _ = writeln!(output, "# HELP {} Latency of repository calls", name);
+        let _ = writeln!(output, "# TYPE {} histogram", name);
+        if let Ok(latency) = self.repository_latency.lock() {
+            for (method, histogram) in latency.iter() {
+                let mut cumulative = 0;
+                for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                    cumulative += count;
+                    let _ = writeln!(
+                        output,
+                        "{}_bucket{{method={:?},le=\"{}\"}} {}",
+                        name, method, bound, cumulative
+                    );
+                }
+                let labels = format!("{{method={:?}}}", method);
+                let _ = writeln!(
+                    output,
+                    "{}_bucket{{method={:?},le=\"+Inf\"}} {}",
+                    name, method, histogram.count
+                );
+                let _ = writeln!(output, "{}_sum{} {}", name, labels, histogram.sum_secs);
+                let _ = writeln!(output, "{}_count{} {}", name, labels, histogram.count);
+            }
+        }
+        output
+    }
+}
+
+/// Takes a request off the active ones when it ends, even if it's aborted
+struct Active(Arc<AppMetrics>);
+
+impl Drop for Active {
+    fn drop(&mut self) {
+        self.0.active_requests.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Middleware counting the requests being handled in `active_requests`
+pub async fn track_active(
+    State(metrics): State<Arc<AppMetrics>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    metrics.active_requests.fetch_add(1, Ordering::Relaxed);
+    let _active = Active(metrics);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use tokio::sync::Notify;
+    use tower::ServiceExt;
+
+    #[test]
+    fn counters_are_rendered() {
+        let metrics = AppMetrics::default();
+        AppMetrics::increment(&metrics.heroes_created);
+        let _ = metrics.observe::<()>(Err(DataAccessError::NotFound));
+        let _ = metrics.observe::<()>(Err(DataAccessError::TechnicalError));
+        metrics.add_response_bytes("/heroes/:id", 42);
+        metrics.observe_repository_call("get_by_id", Duration::from_millis(20));
+
+        let output = metrics.render();
+
+        assert!(output.contains("# TYPE heroes_created_total counter\nheroes_created_total 1\n"));
+        assert!(output.contains("\nheroes_not_found_total 1\n"));
+        assert!(output.contains("\nheroes_deleted_total 0\n"));
+        assert!(output.contains("\nhttp_response_body_bytes_total{route=\"/heroes/:id\"} 42\n"));
+        assert!(output.contains(
+            "\nrepository_call_duration_seconds_bucket{method=\"get_by_id\",le=\"0.01\"} 0\n"
+        ));
+        assert!(output.contains(
+            "\nrepository_call_duration_seconds_bucket{method=\"get_by_id\",le=\"0.025\"} 1\n"
+        ));
+        assert!(
+            output.contains("\nrepository_call_duration_seconds_count{method=\"get_by_id\"} 1\n")
+        );
+        assert!(output.contains("# TYPE http_requests_active gauge\nhttp_requests_active 0\n"));
+    }
+
+    #[tokio::test]
+    async fn active_requests_are_counted_until_they_end() {
+        let metrics = Arc::new(AppMetrics::default());
+        let release = Arc::new(Notify::new());
+        let released = release.clone();
+        let app = Router::new()
+            .route(
+                "/slow",
+                get(move || async move { released.notified().await }),
+            )
+            .layer(middleware::from_fn_with_state(
+                metrics.clone(),
+                track_active,
+            ));
+        let request = Request::builder().uri("/slow").body(Body::empty()).unwrap();
+
+        let slow = tokio::spawn(app.oneshot(request));
+        while metrics.active_requests() == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        assert_eq!(metrics.active_requests(), 1);
+        assert!(metrics.render().contains("\nhttp_requests_active 1\n"));
+        release.notify_one();
+        slow.await.unwrap().unwrap();
+        assert_eq!(metrics.active_requests(), 0);
+    }
+}
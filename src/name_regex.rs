@@ -0,0 +1,95 @@
+use crate::error::ApiError;
+use regex::{Regex, RegexBuilder};
+use std::fmt;
+
+/// Longest accepted pattern, in bytes
+pub const MAX_PATTERN_LEN: usize = 256;
+
+/// Most memory, in bytes, a compiled pattern may take
+///
+/// The regex engine matches in linear time whatever the pattern, so no pattern can make a
+/// match hang; this bounds the other cost of hostile patterns like `\w{1000}`: their size.
+const SIZE_LIMIT: usize = 64 * 1024;
+
+/// Regular expression heroes names are matched with, ignoring case like name filters
+///
+/// Only built by `new`, which rejects patterns too long or too costly to compile.
+#[derive(Clone)]
+pub struct NameRegex {
+    regex: Regex,
+}
+
+impl NameRegex {
+    /// Compile the `name_regex` parameter, `400` when it's invalid or too big
+    pub fn new(pattern: &str) -> Result<Self, ApiError> {
+        if pattern.len() > MAX_PATTERN_LEN {
+            return Err(ApiError::bad_request(format!(
+                "name_regex must not be longer than {} bytes",
+                MAX_PATTERN_LEN
+            )));
+        }
+        RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .size_limit(SIZE_LIMIT)
+            .dfa_size_limit(SIZE_LIMIT)
+            .build()
+            .map(|regex| NameRegex { regex })
+            .map_err(|error| match error {
+                regex::Error::CompiledTooBig(_) => {
+                    ApiError::bad_request("name_regex is too complex")
+                }
+                error => {
+                    // the last line of parse errors is the reason, the others show where
+                    let error = error.to_string();
+                    let reason = error.lines().last().unwrap_or_default();
+                    let reason = reason.trim_start_matches("error: ");
+                    ApiError::bad_request(format!("invalid name_regex: {}", reason))
+                }
+            })
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
+impl PartialEq for NameRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl fmt::Debug for NameRegex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NameRegex({:?})", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn names_are_matched_ignoring_case() {
+        let regex = NameRegex::new("^wonder.*n$").unwrap();
+
+        assert!(regex.is_match("Wonder Woman"));
+        assert!(!regex.is_match("Wonder Girl"));
+    }
+
+    #[rstest]
+    #[case("(Wonder", "invalid name_regex: unclosed group")]
+    #[case(r"\w{1000}", "name_regex is too complex")]
+    #[case(&"a".repeat(257), "name_regex must not be longer than 256 bytes")]
+    fn unusable_patterns_are_a_bad_request(#[case] pattern: &str, #[case] message: &str) {
+        let error = NameRegex::new(pattern).unwrap_err();
+
+        assert_eq!(error.status, axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(error.message, message);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::error::ApiError;
+use serde::Serialize;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Inclusive bounds on a numeric field, from the `<field>_gte` and `<field>_lte` query
+/// parameters; either bound may be left out
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub struct NumericRange<T> {
+    pub gte: Option<T>,
+    pub lte: Option<T>,
+}
+
+impl<T: FromStr + PartialOrd + Copy + Display> NumericRange<T> {
+    /// Range of `field` among the query parameters `pairs`, `None` when neither bound is
+    /// given; `400` for a bound which isn't a number, or a `gte` above the `lte`
+    pub fn parse(pairs: &[(String, String)], field: &str) -> Result<Option<Self>, ApiError> {
+        let bound = |suffix: &str| {
+            let param = format!("{}_{}", field, suffix);
+            match pairs.iter().find(|(key, _)| *key == param) {
+                None => Ok(None),
+                Some((_, value)) => value.trim().parse().map(Some).map_err(|_| {
+                    ApiError::bad_request(format!("{} must be a number, not '{}'", param, value))
+                }),
+            }
+        };
+        let range = NumericRange {
+            gte: bound("gte")?,
+            lte: bound("lte")?,
+        };
+        match (range.gte, range.lte) {
+            (None, None) => Ok(None),
+            (Some(gte), Some(lte)) if gte > lte => Err(ApiError::bad_request(format!(
+                "{}_gte ({}) must not be greater than {}_lte ({})",
+                field, gte, field, lte
+            ))),
+            _ => Ok(Some(range)),
+        }
+    }
+}
+
+impl<T: PartialOrd + Copy> NumericRange<T> {
+    pub fn contains(&self, value: T) -> bool {
+        self.gte.is_none_or(|gte| value >= gte) && self.lte.is_none_or(|lte| value <= lte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+    use rstest::rstest;
+
+    fn parse(pairs: &[(&str, &str)]) -> Result<Option<NumericRange<u64>>, ApiError> {
+        let pairs: Vec<(String, String)> = pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        NumericRange::parse(&pairs, "power")
+    }
+
+    #[rstest]
+    #[case(&[("power_gte", "50"), ("power_lte", "90")], Some(50), Some(90))]
+    #[case(&[("power_gte", "50")], Some(50), None)]
+    #[case(&[("power_lte", "90")], None, Some(90))]
+    #[case(&[("power_gte", "70"), ("power_lte", "70")], Some(70), Some(70))]
+    fn bounds_are_parsed(
+        #[case] pairs: &[(&str, &str)],
+        #[case] gte: Option<u64>,
+        #[case] lte: Option<u64>,
+    ) {
+        assert_eq!(parse(pairs).unwrap(), Some(NumericRange { gte, lte }));
+    }
+
+    #[test]
+    fn no_bound_is_no_range() {
+        assert_eq!(parse(&[("name", "Storm")]).unwrap(), None);
+    }
+
+    #[rstest]
+    #[case(
+        &[("power_gte", "90"), ("power_lte", "50")],
+        "power_gte (90) must not be greater than power_lte (50)"
+    )]
+    #[case(&[("power_gte", "high")], "power_gte must be a number, not 'high'")]
+    #[case(&[("power_lte", "-1")], "power_lte must be a number, not '-1'")]
+    fn invalid_bounds_are_a_bad_request(#[case] pairs: &[(&str, &str)], #[case] message: &str) {
+        let error = parse(pairs).unwrap_err();
+
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert_eq!(error.message, message);
+    }
+
+    #[test]
+    fn bounds_are_inclusive() {
+        let range = NumericRange {
+            gte: Some(50),
+            lte: Some(90),
+        };
+
+        assert!(range.contains(50) && range.contains(90));
+        assert!(!range.contains(49) && !range.contains(91));
+        assert!(NumericRange {
+            gte: None,
+            lte: None
+        }
+        .contains(0));
+    }
+}
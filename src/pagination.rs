@@ -0,0 +1,371 @@
+use crate::error::ApiError;
+use axum::http::{header, HeaderMap, StatusCode, Uri};
+use serde::Serialize;
+use std::str::FromStr;
+
+pub const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Unit of `Range` and `Content-Range` headers selecting heroes of a listing
+pub const RANGE_UNIT: &str = "heroes";
+
+/// Validated `limit`/`offset` pair of a listing request
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Pagination {
+    /// maximum number of items to return, `None` returning everything
+    pub limit: Option<u64>,
+    pub offset: u64,
+}
+
+impl Pagination {
+    /// Validate raw query parameters; `max_offset` bounds how deep clients may page
+    pub fn parse(
+        limit: Option<i64>,
+        offset: Option<i64>,
+        max_offset: u64,
+    ) -> Result<Pagination, ApiError> {
+        let limit = match limit {
+            None => None,
+            Some(limit) if limit <= 0 => {
+                return Err(ApiError::bad_request("limit must be greater than 0"))
+            }
+            Some(limit) => Some(limit as u64),
+        };
+
+        let offset = match (offset, limit) {
+            (None, _) => 0,
+            (Some(_), None) => {
+                return Err(ApiError::bad_request("offset requires a limit"));
+            }
+            (Some(offset), Some(_)) if offset < 0 => {
+                return Err(ApiError::bad_request("offset must not be negative"));
+            }
+            (Some(offset), Some(_)) if offset as u64 > max_offset => {
+                return Err(ApiError::bad_request(format!(
+                    "offset must not exceed {}",
+                    max_offset
+                )));
+            }
+            (Some(offset), Some(_)) => offset as u64,
+        };
+
+        Ok(Pagination { limit, offset })
+    }
+
+    /// Keep the requested page of `items`
+    pub fn apply<T>(&self, items: Vec<T>) -> Vec<T> {
+        let page = items.into_iter().skip(self.offset as usize);
+        match self.limit {
+            Some(limit) => page.take(limit as usize).collect(),
+            None => page.collect(),
+        }
+    }
+}
+
+/// `Range: heroes=first-last` of a listing, positions starting at 0 and `last` included;
+/// `heroes=20-` selects every hero from the 21st on
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct HeroRange {
+    pub first: u64,
+    pub last: Option<u64>,
+}
+
+impl HeroRange {
+    /// Range asked for with the `Range` header of `headers`; ranges of other units are
+    /// ignored, as HTTP allows
+    pub fn from_headers(headers: &HeaderMap, max_offset: u64) -> Result<Option<Self>, ApiError> {
+        let Some(value) = headers.get(header::RANGE) else {
+            return Ok(None);
+        };
+        let value = value.to_str().unwrap_or_default();
+        let Some(range) = value
+            .split_once('=')
+            .filter(|(unit, _)| unit.trim() == RANGE_UNIT)
+            .map(|(_, range)| range.trim())
+        else {
+            return Ok(None);
+        };
+        let invalid = || {
+            ApiError::bad_request(format!(
+                "Range must be a single {}=first-last range, got '{}'",
+                RANGE_UNIT, value
+            ))
+        };
+        let (first, last) = range.split_once('-').ok_or_else(invalid)?;
+        let first: u64 = first.parse().map_err(|_| invalid())?;
+        let last = match last {
+            "" => None,
+            last => Some(last.parse::<u64>().map_err(|_| invalid())?),
+        };
+        if last.is_some_and(|last| last < first) {
+            return Err(invalid());
+        }
+        if first > max_offset {
+            let message = format!("Range must not start after {}", max_offset);
+            return Err(ApiError::bad_request(message));
+        }
+        Ok(Some(HeroRange { first, last }))
+    }
+
+    pub fn pagination(&self) -> Pagination {
+        Pagination {
+            // `heroes=0-18446744073709551615` selects all of them rather than overflowing
+            limit: self.last.map(|last| (last - self.first).saturating_add(1)),
+            offset: self.first,
+        }
+    }
+
+    /// `Content-Range` of the `count` heroes served out of `total` matching ones, or `416`
+    /// with the `Content-Range` to send along when the range starts after the last hero
+    pub fn content_range(&self, count: u64, total: u64) -> Result<String, (ApiError, String)> {
+        if self.first >= total {
+            let error = ApiError::new(
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                "range_not_satisfiable",
+                format!("Range starts after the last of the {} heroes", total),
+            );
+            return Err((error, format!("{} */{}", RANGE_UNIT, total)));
+        }
+        let last = self.first + count.max(1) - 1;
+        Ok(format!("{} {}-{}/{}", RANGE_UNIT, self.first, last, total))
+    }
+}
+
+/// Where paged responses put the pagination: around the items, or in headers
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageFormat {
+    /// `{ "items": [...], "total": 42, "limit": 10, "offset": 0 }`
+    #[default]
+    Envelope,
+    /// the bare array of items, with `X-Total-Count` and `Link` headers
+    Headers,
+}
+
+impl FromStr for PageFormat {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "envelope" => Ok(PageFormat::Envelope),
+            "headers" => Ok(PageFormat::Headers),
+            _ => Err(()),
+        }
+    }
+}
+
+impl PageFormat {
+    /// Format asked for with a `pagination` parameter of `Accept`, e.g.
+    /// `application/json; pagination=headers`, `default` otherwise
+    pub fn negotiate(headers: &HeaderMap, default: PageFormat) -> PageFormat {
+        headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .split([',', ';'])
+            .filter_map(|parameter| parameter.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("pagination"))
+            .and_then(|(_, value)| value.trim().trim_matches('"').parse().ok())
+            .unwrap_or(default)
+    }
+}
+
+/// Scheme and authority clients reach the service at, like `https://heroes.example`, from
+/// the `Host` and `X-Forwarded-Proto` headers; empty when there's no `Host`
+pub fn origin(headers: &HeaderMap) -> String {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|value: &header::HeaderValue| value.to_str().ok())
+    };
+    let scheme = match header("x-forwarded-proto") {
+        Some("https") => "https",
+        _ => "http",
+    };
+    header(header::HOST.as_str())
+        .map(|host| format!("{}://{}", scheme, host))
+        .unwrap_or_default()
+}
+
+/// RFC 8288 (formerly 5988) `Link` header of a page of `uri`: its `first`, `prev`, `next`
+/// and `last` pages, absolute when `origin` isn't empty
+///
+/// Rels which don't apply are left out: `first` and `prev` on the first page, `next` and
+/// `last` on the last one. The other query parameters of `uri`, like a filter or a sort,
+/// are kept as sent.
+pub fn links(origin: &str, uri: &Uri, limit: u64, offset: u64, total: u64) -> Option<String> {
+    let page_uri = |offset: u64| {
+        let mut query: Vec<String> = uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| {
+                let name = pair.split('=').next().unwrap_or_default();
+                !pair.is_empty() && name != "limit" && name != "offset"
+            })
+            .map(str::to_string)
+            .collect();
+        query.push(format!("limit={}", limit));
+        query.push(format!("offset={}", offset));
+        format!("<{}{}?{}>", origin, uri.path(), query.join("&"))
+    };
+
+    let mut links = Vec::new();
+    if offset > 0 {
+        links.push(format!("{}; rel=\"first\"", page_uri(0)));
+        let prev = offset.saturating_sub(limit);
+        links.push(format!("{}; rel=\"prev\"", page_uri(prev)));
+    }
+    if offset + limit < total {
+        links.push(format!("{}; rel=\"next\"", page_uri(offset + limit)));
+        // pages stay aligned on the current one
+        let last = offset + (total - 1 - offset) / limit * limit;
+        links.push(format!("{}; rel=\"last\"", page_uri(last)));
+    }
+    (!links.is_empty()).then(|| links.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Some(0), None, "limit must be greater than 0")]
+    #[case(Some(-5), None, "limit must be greater than 0")]
+    #[case(None, Some(10), "offset requires a limit")]
+    #[case(Some(10), Some(-1), "offset must not be negative")]
+    #[case(Some(10), Some(101), "offset must not exceed 100")]
+    fn invalid_combinations_are_rejected(
+        #[case] limit: Option<i64>,
+        #[case] offset: Option<i64>,
+        #[case] expected_message: &str,
+    ) {
+        let error = Pagination::parse(limit, offset, 100).unwrap_err();
+
+        assert_eq!(error.status, axum::http::StatusCode::BAD_REQUEST);
+        assert_eq!(error.message, expected_message);
+    }
+
+    #[test]
+    fn valid_pagination_selects_the_page() {
+        let pagination = Pagination::parse(Some(2), Some(1), 100).unwrap();
+
+        assert_eq!(
+            pagination,
+            Pagination {
+                limit: Some(2),
+                offset: 1
+            }
+        );
+        assert_eq!(pagination.apply(vec![1, 2, 3, 4]), vec![2, 3]);
+    }
+
+    fn range_of(value: &str) -> Result<Option<HeroRange>, ApiError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, value.parse().unwrap());
+        HeroRange::from_headers(&headers, 100)
+    }
+
+    #[rstest]
+    #[case("heroes=0-19", 0, Some(20))]
+    #[case("heroes=20-", 20, None)]
+    #[case(" heroes = 5-5", 5, Some(1))]
+    #[case("heroes=0-18446744073709551615", 0, Some(u64::MAX))]
+    fn ranges_map_to_pagination(
+        #[case] value: &str,
+        #[case] offset: u64,
+        #[case] limit: Option<u64>,
+    ) {
+        let range = range_of(value).unwrap().unwrap();
+
+        assert_eq!(range.pagination(), Pagination { limit, offset });
+    }
+
+    #[rstest]
+    #[case("heroes=-5")]
+    #[case("heroes=5-2")]
+    #[case("heroes=0-4,10-14")]
+    #[case("heroes=101-110")]
+    fn unsupported_ranges_are_rejected(#[case] value: &str) {
+        let error = range_of(value).unwrap_err();
+
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn ranges_of_other_units_are_ignored() {
+        assert_eq!(range_of("bytes=0-99").unwrap(), None);
+    }
+
+    #[test]
+    fn content_range_reports_the_served_heroes() {
+        let range = HeroRange {
+            first: 2,
+            last: Some(9),
+        };
+
+        assert_eq!(range.content_range(3, 5).unwrap(), "heroes 2-4/5");
+        let (error, content_range) = range.content_range(0, 2).unwrap_err();
+        assert_eq!(error.status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(content_range, "heroes */2");
+    }
+
+    #[rstest]
+    #[case("application/json; pagination=headers", PageFormat::Headers)]
+    #[case(
+        "text/csv, application/json;pagination=\"Envelope\"",
+        PageFormat::Envelope
+    )]
+    #[case("application/json", PageFormat::Headers)] // the default
+    #[case("application/json; pagination=pages", PageFormat::Headers)]
+    fn page_format_is_negotiated(#[case] accept: &str, #[case] expected: PageFormat) {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, accept.parse().unwrap());
+
+        assert_eq!(
+            PageFormat::negotiate(&headers, PageFormat::Headers),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case(
+        0,
+        10,
+        Some(r#"</heroes/page?name=W&limit=5&offset=5>; rel="next", </heroes/page?name=W&limit=5&offset=5>; rel="last""#)
+    )]
+    #[case(
+        5,
+        17,
+        Some(r#"</heroes/page?name=W&limit=5&offset=0>; rel="first", </heroes/page?name=W&limit=5&offset=0>; rel="prev", </heroes/page?name=W&limit=5&offset=10>; rel="next", </heroes/page?name=W&limit=5&offset=15>; rel="last""#)
+    )]
+    #[case(
+        10,
+        12,
+        Some(r#"</heroes/page?name=W&limit=5&offset=0>; rel="first", </heroes/page?name=W&limit=5&offset=5>; rel="prev""#)
+    )]
+    #[case(0, 5, None)]
+    fn links_point_to_the_neighbor_pages(
+        #[case] offset: u64,
+        #[case] total: u64,
+        #[case] expected: Option<&str>,
+    ) {
+        let uri: Uri = "/heroes/page?name=W&offset=3&limit=5".parse().unwrap();
+
+        assert_eq!(links("", &uri, 5, offset, total).as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case(None, "http://heroes.example")]
+    #[case(Some("https"), "https://heroes.example")]
+    fn origin_is_the_one_clients_use(#[case] proto: Option<&str>, #[case] expected: &str) {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "heroes.example".parse().unwrap());
+        if let Some(proto) = proto {
+            headers.insert("x-forwarded-proto", proto.parse().unwrap());
+        }
+
+        assert_eq!(origin(&headers), expected);
+        assert_eq!(origin(&HeaderMap::new()), "");
+    }
+}
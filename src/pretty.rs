@@ -0,0 +1,61 @@
+use crate::{error::ApiError, flag};
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+
+/// Whether the client asked for indented json with `?pretty=true` (or any form of `flag::parse`)
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct Pretty(pub bool);
+
+impl Pretty {
+    /// Respond with `value` as json, indented if requested
+    pub fn json<T: Serialize>(self, value: T) -> PrettyJson<T> {
+        PrettyJson {
+            value,
+            pretty: self.0,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PrettyQuery {
+    #[serde(default, deserialize_with = "flag::deserialize")]
+    pretty: Option<bool>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Pretty {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Query::<PrettyQuery>::from_request_parts(parts, state)
+            .await
+            .map(|query| Pretty(query.pretty.unwrap_or_default()))
+            .map_err(|_| ApiError::bad_request(format!("pretty must be {}", flag::ACCEPTED)))
+    }
+}
+
+/// Json response, compact by default; see `Pretty`
+pub struct PrettyJson<T> {
+    value: T,
+    pretty: bool,
+}
+
+impl<T: Serialize> IntoResponse for PrettyJson<T> {
+    fn into_response(self) -> Response {
+        let body = if self.pretty {
+            serde_json::to_string_pretty(&self.value)
+        } else {
+            serde_json::to_string(&self.value)
+        };
+
+        match body {
+            Ok(body) => ([(header::CONTENT_TYPE, "application/json")], body).into_response(),
+            Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use crate::hero_query::NumericField;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::{
+    DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats, HeroesRepositoryTrait,
+    TagChanges, UpsertReport,
+};
+use axum::async_trait;
+use futures::stream::{BoxStream, TryStreamExt};
+use std::collections::HashSet;
+use tokio::sync::Mutex;
+
+/// Repository decorator capping how many heroes each tenant stores
+///
+/// Wraps a `TenantScopedHeroesRepository`, whose datasets are the ones counted. Writes adding
+/// heroes beyond `max_heroes` fail with `DataAccessError::QuotaExceeded`; reads, updates and
+/// deletes are passed through.
+pub struct QuotaHeroesRepository<R> {
+    inner: R,
+    /// unlimited when `None`
+    max_heroes: Option<u64>,
+    /// held from counting to writing, so concurrent creates can't both take the last slot
+    writing: Mutex<()>,
+}
+
+impl<R: HeroesRepositoryTrait> QuotaHeroesRepository<R> {
+    pub fn new(inner: R, max_heroes: Option<u64>) -> Self {
+        QuotaHeroesRepository {
+            inner,
+            max_heroes,
+            writing: Mutex::new(()),
+        }
+    }
+
+    /// Ids of the heroes of the current tenant
+    async fn stored_ids(&self) -> Result<HashSet<String>, DataAccessError> {
+        self.inner
+            .stream_all()
+            .map_ok(|hero| hero.id)
+            .try_collect()
+            .await
+    }
+
+    /// `QuotaExceeded` unless the current tenant may store `added` more heroes than
+    /// the `stored` ones
+    fn check(&self, stored: usize, added: usize) -> Result<(), DataAccessError> {
+        match self.max_heroes {
+            Some(max) if (stored + added) as u64 > max => Err(DataAccessError::QuotaExceeded),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl<R: HeroesRepositoryTrait + Send + Sync> HeroesRepositoryTrait for QuotaHeroesRepository<R> {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_name(name).await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.inner.get_by_id(id).await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        if self.max_heroes.is_none() {
+            return self.inner.create(hero).await;
+        }
+        let _writing = self.writing.lock().await;
+        self.check(self.stored_ids().await?.len(), 1)?;
+        self.inner.create(hero).await
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        if self.max_heroes.is_none() {
+            return self.inner.create_if_absent(id, hero).await;
+        }
+        let _writing = self.writing.lock().await;
+        let stored = self.stored_ids().await?;
+        // taken ids are refused by the inner repository, their heroes count already
+        if !stored.contains(id) {
+            self.check(stored.len(), 1)?;
+        }
+        self.inner.create_if_absent(id, hero).await
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.inner.update(id, hero).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.inner.delete(id).await
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.delete_by_name(name).await
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.inner.count_by_initial().await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.inner.stats().await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.inner.dataset_digest().await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        self.inner.stream_all()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.search(term).await
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        let ids: HashSet<&str> = heroes.iter().map(|hero| hero.id.as_str()).collect();
+        self.check(0, ids.len())?;
+        self.inner.replace_all(heroes).await
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        if self.max_heroes.is_none() {
+            return self.inner.upsert_many(heroes).await;
+        }
+        let _writing = self.writing.lock().await;
+        let stored = self.stored_ids().await?;
+        let added: HashSet<&str> = heroes
+            .iter()
+            .map(|hero| hero.id.as_str())
+            .filter(|id| !stored.contains(*id))
+            .collect();
+        self.check(stored.len(), added.len())?;
+        self.inner.upsert_many(heroes).await
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_tag(tag).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_ids(ids).await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.inner.get_page(name, limit, offset).await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_name_regex(regex).await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_range(field, range).await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.inner.ping().await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        self.inner.update_tags(id, changes).await
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        self.inner.update_with_tags(id, hero, tags).await
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.inner.record_view(id).await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.inner.get_changes_since(since).await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.inner.get_with_neighbors(id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tenant::TenantScopedHeroesRepository;
+    use crate::{HeroName, InMemoryHeroesRepository};
+
+    fn payload(name: &str) -> HeroPayload {
+        HeroPayload {
+            name: HeroName::new(name).unwrap(),
+            power_level: 0,
+        }
+    }
+
+    // the default in-memory repository stores 2 heroes
+    fn repository(max_heroes: u64) -> QuotaHeroesRepository<impl HeroesRepositoryTrait> {
+        QuotaHeroesRepository::new(
+            TenantScopedHeroesRepository::new(1, InMemoryHeroesRepository::default),
+            Some(max_heroes),
+        )
+    }
+
+    #[tokio::test]
+    async fn create_under_quota_is_stored() {
+        let repo = repository(3);
+
+        let created = repo.create(payload("Storm")).await.unwrap();
+
+        assert_eq!(repo.get_by_id(&created.id).await.unwrap(), created);
+    }
+
+    #[tokio::test]
+    async fn create_over_quota_is_refused() {
+        let repo = repository(2);
+
+        let result = repo.create(payload("Storm")).await;
+
+        assert!(matches!(result, Err(DataAccessError::QuotaExceeded)));
+        assert_eq!(repo.get_by_name("%").await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn upserts_only_count_new_heroes() {
+        let repo = repository(3);
+        let existing = repo.get_by_id("1").await.unwrap();
+        let new = |id: &str| Hero {
+            id: id.to_string(),
+            name: HeroName::new("Storm").unwrap(),
+            ..Default::default()
+        };
+
+        let report = repo.upsert_many(&[existing, new("3")]).await.unwrap();
+        assert_eq!(report.created, 1);
+
+        let result = repo.upsert_many(&[new("4")]).await;
+        assert!(matches!(result, Err(DataAccessError::QuotaExceeded)));
+    }
+}
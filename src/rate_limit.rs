@@ -0,0 +1,274 @@
+use crate::error::ApiError;
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, State},
+    http::{Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Fixed window limiter: at most `limit` requests are accepted per window
+pub struct RateLimiter {
+    limit: u64,
+    window: Duration,
+    current: Mutex<Window>,
+}
+
+struct Window {
+    started: Instant,
+    accepted: u64,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u64, window: Duration) -> Self {
+        RateLimiter {
+            limit,
+            window,
+            current: Mutex::new(Window {
+                started: Instant::now(),
+                accepted: 0,
+            }),
+        }
+    }
+
+    /// Count a request arriving at `now`; when over the limit, tell how long until the next window
+    fn acquire(&self, now: Instant) -> Result<(), Duration> {
+        let mut current = self
+            .current
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if now.duration_since(current.started) >= self.window {
+            *current = Window {
+                started: now,
+                accepted: 0,
+            };
+        }
+        if current.accepted < self.limit {
+            current.accepted += 1;
+            Ok(())
+        } else {
+            Err(self.window - now.duration_since(current.started))
+        }
+    }
+
+    /// Whether the window is over at `now`, so forgetting the limiter loses nothing
+    fn is_idle(&self, now: Instant) -> bool {
+        let current = self
+            .current
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        now.duration_since(current.started) >= self.window
+    }
+}
+
+/// Routes limited together: reads (`GET` and `HEAD`) and writes (every other method)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RouteGroup {
+    Read,
+    Write,
+}
+
+impl RouteGroup {
+    pub fn of(method: &Method) -> Self {
+        match *method {
+            Method::GET | Method::HEAD => RouteGroup::Read,
+            _ => RouteGroup::Write,
+        }
+    }
+}
+
+/// Route group of a request and address of its client, when known
+type ClientKey = (RouteGroup, Option<IpAddr>);
+
+/// A `RateLimiter` per route group and client address, each group having its own limit
+///
+/// Groups without a limit aren't limited. Requests whose address is unknown, e.g. handled
+/// without a connection in tests, share the budget of one client.
+pub struct GroupRateLimiter {
+    read: Option<u64>,
+    write: Option<u64>,
+    window: Duration,
+    limiters: Mutex<HashMap<ClientKey, Arc<RateLimiter>>>,
+}
+
+impl GroupRateLimiter {
+    pub fn new(read: Option<u64>, write: Option<u64>, window: Duration) -> Self {
+        GroupRateLimiter {
+            read,
+            write,
+            window,
+            limiters: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn limit(&self, group: RouteGroup) -> Option<u64> {
+        match group {
+            RouteGroup::Read => self.read,
+            RouteGroup::Write => self.write,
+        }
+    }
+
+    /// Count a request of `client` to `group` arriving at `now`, like `RateLimiter::acquire`
+    fn acquire(
+        &self,
+        group: RouteGroup,
+        client: Option<IpAddr>,
+        now: Instant,
+    ) -> Result<(), Duration> {
+        let Some(limit) = self.limit(group) else {
+            return Ok(());
+        };
+        let limiter = {
+            let mut limiters = self
+                .limiters
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            if !limiters.contains_key(&(group, client)) {
+                // clients come and go, only those within their window are worth keeping
+                limiters.retain(|_, limiter| !limiter.is_idle(now));
+            }
+            limiters
+                .entry((group, client))
+                .or_insert_with(|| Arc::new(RateLimiter::new(limit, self.window)))
+                .clone()
+        };
+        limiter.acquire(now)
+    }
+}
+
+/// Middleware answering `429` once the window's budget is spent
+///
+/// `Retry-After` holds the seconds left until the window resets, rounded up so a client
+/// following it never comes back too early.
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    match limiter.acquire(Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => too_many_requests(wait),
+    }
+}
+
+/// Middleware answering `429`, like `rate_limit`, once a client spent the budget of the
+/// route group of the request
+pub async fn rate_limit_per_group(
+    State(limiter): State<Arc<GroupRateLimiter>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let group = RouteGroup::of(request.method());
+    let client = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    match limiter.acquire(group, client, Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(wait) => too_many_requests(wait),
+    }
+}
+
+fn too_many_requests(wait: Duration) -> Response {
+    ApiError::new(
+        StatusCode::TOO_MANY_REQUESTS,
+        "rate_limited",
+        "too many requests, slow down",
+    )
+    .with_retry_after(retry_after_secs(wait))
+    .into_response()
+}
+
+fn retry_after_secs(wait: Duration) -> u64 {
+    let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    seconds.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::header, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn limited_app(limiter: RateLimiter) -> Router {
+        Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(
+                Arc::new(limiter),
+                rate_limit,
+            ))
+    }
+
+    fn request() -> Request<Body> {
+        Request::builder().uri("/").body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn budget_is_restored_by_the_next_window() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(10));
+        let start = Instant::now();
+
+        assert_eq!(limiter.acquire(start), Ok(()));
+        let wait = limiter.acquire(start + Duration::from_secs(4)).unwrap_err();
+        assert!(wait <= Duration::from_secs(6));
+        assert_eq!(limiter.acquire(start + Duration::from_secs(10)), Ok(()));
+    }
+
+    #[test]
+    fn clients_have_their_own_budget() {
+        let limiter = GroupRateLimiter::new(Some(1), None, Duration::from_secs(10));
+        let (a, b) = (Some([10, 0, 0, 1].into()), Some([10, 0, 0, 2].into()));
+        let now = Instant::now();
+
+        assert_eq!(limiter.acquire(RouteGroup::Read, a, now), Ok(()));
+        assert!(limiter.acquire(RouteGroup::Read, a, now).is_err());
+        assert_eq!(limiter.acquire(RouteGroup::Read, b, now), Ok(()));
+        // writes aren't limited
+        assert_eq!(limiter.acquire(RouteGroup::Write, a, now), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn spent_write_budget_leaves_reads_available() {
+        let limiter = GroupRateLimiter::new(Some(10), Some(1), Duration::from_secs(30));
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }).post(|| async { "created" }))
+            .layer(middleware::from_fn_with_state(
+                Arc::new(limiter),
+                rate_limit_per_group,
+            ));
+        let write = || {
+            let request = Request::builder().method("POST").uri("/");
+            request.body(Body::empty()).unwrap()
+        };
+
+        let accepted = app.clone().oneshot(write()).await.unwrap();
+        let rejected = app.clone().oneshot(write()).await.unwrap();
+        let read = app.oneshot(request()).await.unwrap();
+
+        assert_eq!(accepted.status(), StatusCode::OK);
+        assert_eq!(rejected.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(rejected.headers().contains_key(header::RETRY_AFTER));
+        assert_eq!(read.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn over_the_limit_is_answered_with_retry_after() {
+        let app = limited_app(RateLimiter::new(1, Duration::from_secs(30)));
+
+        let accepted = app.clone().oneshot(request()).await.unwrap();
+        let rejected = app.oneshot(request()).await.unwrap();
+
+        assert_eq!(accepted.status(), StatusCode::OK);
+        assert_eq!(rejected.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = rejected.headers()[header::RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((1..=30).contains(&retry_after));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::hero_query::NumericField;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::{
+    DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats, HeroesRepositoryTrait,
+    TagChanges, UpsertReport,
+};
+use axum::async_trait;
+use futures::stream::BoxStream;
+
+/// Repository decorator refusing every write with `DataAccessError::ReadOnly`
+///
+/// Reads are passed through, writes never reach the inner repository.
+pub struct ReadOnlyHeroesRepository<R> {
+    inner: R,
+}
+
+impl<R> ReadOnlyHeroesRepository<R> {
+    pub fn new(inner: R) -> Self {
+        ReadOnlyHeroesRepository { inner }
+    }
+}
+
+#[async_trait]
+impl<R: HeroesRepositoryTrait + Send + Sync> HeroesRepositoryTrait for ReadOnlyHeroesRepository<R> {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_name(name).await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.inner.get_by_id(id).await
+    }
+
+    async fn create(&self, _hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        Err(DataAccessError::ReadOnly)
+    }
+
+    async fn create_if_absent(
+        &self,
+        _id: &str,
+        _hero: HeroPayload,
+    ) -> Result<Hero, DataAccessError> {
+        Err(DataAccessError::ReadOnly)
+    }
+
+    async fn update(&self, _id: &str, _hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        Err(DataAccessError::ReadOnly)
+    }
+
+    async fn delete(&self, _id: &str) -> Result<Hero, DataAccessError> {
+        Err(DataAccessError::ReadOnly)
+    }
+
+    async fn delete_by_name(&self, _name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        Err(DataAccessError::ReadOnly)
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.inner.count_by_initial().await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.inner.stats().await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.inner.dataset_digest().await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        self.inner.stream_all()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.search(term).await
+    }
+
+    async fn replace_all(&self, _heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        Err(DataAccessError::ReadOnly)
+    }
+
+    async fn upsert_many(&self, _heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        Err(DataAccessError::ReadOnly)
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_tag(tag).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_ids(ids).await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.inner.get_page(name, limit, offset).await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_name_regex(regex).await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.get_by_range(field, range).await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.inner.ping().await
+    }
+
+    async fn update_tags(&self, _id: &str, _changes: TagChanges) -> Result<Hero, DataAccessError> {
+        Err(DataAccessError::ReadOnly)
+    }
+
+    async fn update_with_tags(
+        &self,
+        _id: &str,
+        _hero: HeroPayload,
+        _tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        Err(DataAccessError::ReadOnly)
+    }
+
+    async fn record_view(&self, _id: &str) -> Result<u64, DataAccessError> {
+        Err(DataAccessError::ReadOnly)
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.inner.get_changes_since(since).await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.inner.get_with_neighbors(id).await
+    }
+}
@@ -0,0 +1,337 @@
+use crate::hero_query::NumericField;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::{
+    DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats, HeroesRepositoryTrait,
+    TagChanges, UpsertReport,
+};
+use axum::{async_trait, body::Body, http::Request, middleware::Next, response::Response};
+use futures::stream::BoxStream;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const SESSION_HEADER: &str = "x-session-id";
+
+tokio::task_local! {
+    static SESSION: String;
+}
+
+/// Session of the request being handled by the current task, if it named one
+pub fn current() -> Option<String> {
+    SESSION.try_with(|session| session.clone()).ok()
+}
+
+/// Run `future` on behalf of `session`, e.g. in a background task working for a request
+pub async fn run_as<F: Future>(session: Option<String>, future: F) -> F::Output {
+    match session {
+        Some(session) => SESSION.scope(session, future).await,
+        None => future.await,
+    }
+}
+
+/// Middleware running the request on behalf of the session named by `X-Session-Id`
+///
+/// The header is opaque, requests without it or with a non ascii one have no session.
+pub async fn session(request: Request<Body>, next: Next<Body>) -> Response {
+    let session = request
+        .headers()
+        .get(SESSION_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|session| !session.is_empty())
+        .map(str::to_string);
+    run_as(session, next.run(request)).await
+}
+
+/// Repository reading from a replica, but from the primary for sessions which wrote lately
+///
+/// Writes always go to the primary, and mark the session writing for `ttl`: until then its
+/// reads see the primary, so a client reads what it wrote whatever the replication lag.
+/// Reads of requests without a session always go to the replica.
+pub struct ReadYourWritesHeroesRepository<P, R> {
+    primary: P,
+    replica: R,
+    ttl: Duration,
+    /// when each session last wrote
+    writers: Mutex<HashMap<String, Instant>>,
+}
+
+impl<P, R> ReadYourWritesHeroesRepository<P, R> {
+    pub fn new(primary: P, replica: R, ttl: Duration) -> Self {
+        ReadYourWritesHeroesRepository {
+            primary,
+            replica,
+            ttl,
+            writers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the current session wrote less than `ttl` ago
+    fn wrote_lately(&self) -> bool {
+        let Some(session) = current() else {
+            return false;
+        };
+        let Ok(writers) = self.writers.lock() else {
+            return false;
+        };
+        writers
+            .get(&session)
+            .is_some_and(|wrote| wrote.elapsed() < self.ttl)
+    }
+
+    /// Remember the current session wrote, forgetting the sessions whose window is over
+    fn wrote(&self) {
+        let Some(session) = current() else {
+            return;
+        };
+        if let Ok(mut writers) = self.writers.lock() {
+            writers.retain(|_, wrote| wrote.elapsed() < self.ttl);
+            writers.insert(session, Instant::now());
+        }
+    }
+
+    /// `write` on the primary. The session is marked even when it fails, as a timed out
+    /// write may still have reached the primary
+    async fn write<T>(
+        &self,
+        write: impl Future<Output = Result<T, DataAccessError>>,
+    ) -> Result<T, DataAccessError> {
+        let result = write.await;
+        self.wrote();
+        result
+    }
+}
+
+impl<P, R> ReadYourWritesHeroesRepository<P, R>
+where
+    P: HeroesRepositoryTrait + Send + Sync,
+    R: HeroesRepositoryTrait + Send + Sync,
+{
+    /// The repository the reads of the current session go to
+    fn reader(&self) -> &(dyn HeroesRepositoryTrait + Send + Sync + '_) {
+        if self.wrote_lately() {
+            &self.primary
+        } else {
+            &self.replica
+        }
+    }
+}
+
+#[async_trait]
+impl<P, R> HeroesRepositoryTrait for ReadYourWritesHeroesRepository<P, R>
+where
+    P: HeroesRepositoryTrait + Send + Sync,
+    R: HeroesRepositoryTrait + Send + Sync,
+{
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.reader().get_by_name(name).await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.reader().get_by_id(id).await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.write(self.primary.create(hero)).await
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.write(self.primary.create_if_absent(id, hero)).await
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.write(self.primary.update(id, hero)).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.write(self.primary.delete(id)).await
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.write(self.primary.delete_by_name(name)).await
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.reader().count_by_initial().await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.reader().stats().await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.reader().dataset_digest().await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        self.reader().stream_all()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.reader().search(term).await
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        self.write(self.primary.replace_all(heroes)).await
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        self.write(self.primary.upsert_many(heroes)).await
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.reader().get_by_tag(tag).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.reader().get_by_ids(ids).await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.reader().get_page(name, limit, offset).await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.reader().get_by_name_regex(regex).await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.reader().get_by_range(field, range).await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.reader().ping().await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        self.write(self.primary.update_tags(id, changes)).await
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        self.write(self.primary.update_with_tags(id, hero, tags))
+            .await
+    }
+
+    // counting a view is a write, but not one the session expects to read back
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.primary.record_view(id).await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.reader().get_changes_since(since).await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.reader().get_with_neighbors(id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hero_name::HeroName;
+    use crate::MockHeroesRepositoryTrait;
+
+    fn repo(
+        primary: MockHeroesRepositoryTrait,
+        replica: MockHeroesRepositoryTrait,
+        ttl: Duration,
+    ) -> ReadYourWritesHeroesRepository<MockHeroesRepositoryTrait, MockHeroesRepositoryTrait> {
+        ReadYourWritesHeroesRepository::new(primary, replica, ttl)
+    }
+
+    fn payload() -> HeroPayload {
+        HeroPayload {
+            name: HeroName::new("Storm").unwrap(),
+            power_level: 0,
+        }
+    }
+
+    fn session(name: &str) -> Option<String> {
+        Some(name.to_string())
+    }
+
+    #[tokio::test]
+    async fn read_after_a_write_of_the_session_hits_the_primary() {
+        let mut primary = MockHeroesRepositoryTrait::new();
+        primary
+            .expect_update()
+            .returning(|_, _| Ok(Hero::default()));
+        primary
+            .expect_get_by_id()
+            .times(1)
+            .returning(|_| Ok(Hero::default()));
+        let mut replica = MockHeroesRepositoryTrait::new();
+        replica.expect_get_by_id().never();
+        let repo = repo(primary, replica, Duration::from_secs(5));
+
+        let result = run_as(session("writer"), async {
+            repo.update("1", payload()).await.unwrap();
+            repo.get_by_id("1").await
+        })
+        .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn other_sessions_read_the_replica() {
+        let mut primary = MockHeroesRepositoryTrait::new();
+        primary
+            .expect_update()
+            .returning(|_, _| Ok(Hero::default()));
+        primary.expect_get_by_id().never();
+        let mut replica = MockHeroesRepositoryTrait::new();
+        replica
+            .expect_get_by_id()
+            .times(2)
+            .returning(|_| Ok(Hero::default()));
+        let repo = repo(primary, replica, Duration::from_secs(5));
+
+        run_as(session("writer"), repo.update("1", payload()))
+            .await
+            .unwrap();
+
+        assert!(run_as(session("reader"), repo.get_by_id("1")).await.is_ok());
+        assert!(repo.get_by_id("1").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn reads_go_back_to_the_replica_once_the_window_is_over() {
+        let mut primary = MockHeroesRepositoryTrait::new();
+        primary
+            .expect_update()
+            .returning(|_, _| Ok(Hero::default()));
+        primary.expect_get_by_id().never();
+        let mut replica = MockHeroesRepositoryTrait::new();
+        replica
+            .expect_get_by_id()
+            .times(1)
+            .returning(|_| Ok(Hero::default()));
+        let repo = repo(primary, replica, Duration::from_millis(20));
+
+        let result = run_as(session("writer"), async {
+            repo.update("1", payload()).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            repo.get_by_id("1").await
+        })
+        .await;
+
+        assert!(result.is_ok());
+    }
+}
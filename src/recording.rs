@@ -0,0 +1,564 @@
+use crate::hero_query::NumericField;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::{
+    DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats, HeroesRepositoryTrait,
+    TagChanges, UpsertReport,
+};
+use axum::async_trait;
+use futures::stream::{self, BoxStream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// One repository call and its result, a line of a recording
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Interaction {
+    pub method: String,
+    pub arguments: Value,
+    pub result: Result<Value, DataAccessError>,
+}
+
+type Writer = Arc<Mutex<Box<dyn Write + Send>>>;
+
+fn append(writer: &Writer, interaction: &Interaction) {
+    let written = match writer.lock() {
+        Ok(mut writer) => serde_json::to_writer(&mut **writer, interaction)
+            .map_err(io::Error::from)
+            .and_then(|()| writer.write_all(b"\n"))
+            .and_then(|()| writer.flush()),
+        Err(_) => Err(io::Error::other("poisoned recording")),
+    };
+    if let Err(error) = written {
+        // the call itself went fine, only its recording is missing
+        tracing::warn!(method = %interaction.method, %error, "repository call not recorded");
+    }
+}
+
+/// Repository decorator writing every call and its result to a recording, one JSON
+/// `Interaction` per line, for `ReplayingHeroesRepository` to serve them again
+///
+/// A `stream_all` is recorded once fully consumed, with every hero in a single result.
+pub struct RecordingHeroesRepository<R> {
+    inner: R,
+    writer: Writer,
+}
+
+impl<R> RecordingHeroesRepository<R> {
+    pub fn new(inner: R, writer: impl Write + Send + 'static) -> Self {
+        RecordingHeroesRepository {
+            inner,
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+        }
+    }
+
+    /// Record to the file at `path`, appending to it if it exists
+    pub fn create(inner: R, path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(inner, file))
+    }
+
+    async fn record<T: Serialize>(
+        &self,
+        method: &str,
+        arguments: Value,
+        call: impl Future<Output = Result<T, DataAccessError>>,
+    ) -> Result<T, DataAccessError> {
+        let result = call.await;
+        let interaction = Interaction {
+            method: method.to_string(),
+            arguments,
+            result: result
+                .as_ref()
+                .map(|value| serde_json::to_value(value).unwrap_or_default())
+                .map_err(Clone::clone),
+        };
+        append(&self.writer, &interaction);
+        result
+    }
+}
+
+#[async_trait]
+impl<R: HeroesRepositoryTrait + Send + Sync> HeroesRepositoryTrait
+    for RecordingHeroesRepository<R>
+{
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.record(
+            "get_by_name",
+            json!({ "name": name }),
+            self.inner.get_by_name(name),
+        )
+        .await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.record("get_by_id", json!({ "id": id }), self.inner.get_by_id(id))
+            .await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.record("create", json!({ "hero": hero }), self.inner.create(hero))
+            .await
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.record(
+            "create_if_absent",
+            json!({ "id": id, "hero": hero }),
+            self.inner.create_if_absent(id, hero),
+        )
+        .await
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.record(
+            "update",
+            json!({ "id": id, "hero": hero }),
+            self.inner.update(id, hero),
+        )
+        .await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.record("delete", json!({ "id": id }), self.inner.delete(id))
+            .await
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.record(
+            "delete_by_name",
+            json!({ "name": name }),
+            self.inner.delete_by_name(name),
+        )
+        .await
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.record("count_by_initial", json!({}), self.inner.count_by_initial())
+            .await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.record("stats", json!({}), self.inner.stats()).await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.record("dataset_digest", json!({}), self.inner.dataset_digest())
+            .await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        let state = (self.inner.stream_all(), Some(vec![]), self.writer.clone());
+        stream::unfold(state, |(mut heroes, mut seen, writer)| async move {
+            let next = heroes.next().await;
+            let result = match (&next, seen.as_mut()) {
+                (Some(Ok(hero)), Some(seen)) => {
+                    seen.push(serde_json::to_value(hero).unwrap_or_default());
+                    None
+                }
+                (Some(Err(error)), Some(_)) => Some(Err(error.clone())),
+                (None, Some(seen)) => Some(Ok(Value::Array(std::mem::take(seen)))),
+                // a failure was recorded already
+                (_, None) => None,
+            };
+            if let Some(result) = result {
+                let interaction = Interaction {
+                    method: "stream_all".to_string(),
+                    arguments: json!({}),
+                    result,
+                };
+                append(&writer, &interaction);
+                seen = None;
+            }
+            next.map(|hero| (hero, (heroes, seen, writer)))
+        })
+        .boxed()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.record("search", json!({ "term": term }), self.inner.search(term))
+            .await
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        self.record(
+            "replace_all",
+            json!({ "heroes": heroes }),
+            self.inner.replace_all(heroes),
+        )
+        .await
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        self.record(
+            "upsert_many",
+            json!({ "heroes": heroes }),
+            self.inner.upsert_many(heroes),
+        )
+        .await
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.record(
+            "get_by_tag",
+            json!({ "tag": tag }),
+            self.inner.get_by_tag(tag),
+        )
+        .await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.record(
+            "get_by_ids",
+            json!({ "ids": ids }),
+            self.inner.get_by_ids(ids),
+        )
+        .await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.record(
+            "get_page",
+            json!({ "name": name, "limit": limit, "offset": offset }),
+            self.inner.get_page(name, limit, offset),
+        )
+        .await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.record(
+            "get_by_name_regex",
+            json!({ "pattern": regex.as_str() }),
+            self.inner.get_by_name_regex(regex),
+        )
+        .await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.record(
+            "get_by_range",
+            json!({ "field": field, "range": range }),
+            self.inner.get_by_range(field, range),
+        )
+        .await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.record("ping", json!({}), self.inner.ping()).await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        self.record(
+            "update_tags",
+            json!({ "id": id, "changes": changes }),
+            self.inner.update_tags(id, changes),
+        )
+        .await
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        self.record(
+            "update_with_tags",
+            json!({ "id": id, "hero": hero, "tags": tags }),
+            self.inner.update_with_tags(id, hero, tags),
+        )
+        .await
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.record(
+            "record_view",
+            json!({ "id": id }),
+            self.inner.record_view(id),
+        )
+        .await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.record(
+            "get_changes_since",
+            json!({ "since": since }),
+            self.inner.get_changes_since(since),
+        )
+        .await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.record(
+            "get_with_neighbors",
+            json!({ "id": id }),
+            self.inner.get_with_neighbors(id),
+        )
+        .await
+    }
+}
+
+/// Results recorded for each method and (serialized) arguments, oldest first
+type Recorded = HashMap<(String, String), VecDeque<Result<Value, DataAccessError>>>;
+
+/// Repository answering from a recording of `RecordingHeroesRepository`, to replay real
+/// traffic in tests
+///
+/// A call is answered with the result recorded for the same method and arguments, in
+/// recording order when it was made several times. Calls missing from the recording fail
+/// with `TechnicalError`; nothing is ever stored, so writes only replay their results.
+pub struct ReplayingHeroesRepository {
+    recorded: Mutex<Recorded>,
+}
+
+impl ReplayingHeroesRepository {
+    pub fn from_reader(reader: impl BufRead) -> io::Result<Self> {
+        let mut recorded = Recorded::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let interaction: Interaction = serde_json::from_str(&line)?;
+            recorded
+                .entry((interaction.method, interaction.arguments.to_string()))
+                .or_default()
+                .push_back(interaction.result);
+        }
+        Ok(ReplayingHeroesRepository {
+            recorded: Mutex::new(recorded),
+        })
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    fn replay<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        arguments: Value,
+    ) -> Result<T, DataAccessError> {
+        let key = (method.to_string(), arguments.to_string());
+        let result = self
+            .recorded
+            .lock()
+            .map_err(|_| DataAccessError::TechnicalError)?
+            .get_mut(&key)
+            .and_then(VecDeque::pop_front);
+        match result {
+            Some(result) => result.and_then(|value| {
+                serde_json::from_value(value).map_err(|_| DataAccessError::TechnicalError)
+            }),
+            None => {
+                tracing::warn!(method, %arguments, "repository call missing from the recording");
+                Err(DataAccessError::TechnicalError)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl HeroesRepositoryTrait for ReplayingHeroesRepository {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.replay("get_by_name", json!({ "name": name }))
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.replay("get_by_id", json!({ "id": id }))
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.replay("create", json!({ "hero": hero }))
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.replay("create_if_absent", json!({ "id": id, "hero": hero }))
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.replay("update", json!({ "id": id, "hero": hero }))
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.replay("delete", json!({ "id": id }))
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.replay("delete_by_name", json!({ "name": name }))
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.replay("count_by_initial", json!({}))
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.replay("stats", json!({}))
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.replay("dataset_digest", json!({}))
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        match self.replay::<Vec<Hero>>("stream_all", json!({})) {
+            Ok(heroes) => stream::iter(heroes.into_iter().map(Ok)).boxed(),
+            Err(error) => stream::once(async { Err(error) }).boxed(),
+        }
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.replay("search", json!({ "term": term }))
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        self.replay("replace_all", json!({ "heroes": heroes }))
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        self.replay("upsert_many", json!({ "heroes": heroes }))
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.replay("get_by_tag", json!({ "tag": tag }))
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.replay("get_by_ids", json!({ "ids": ids }))
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.replay(
+            "get_page",
+            json!({ "name": name, "limit": limit, "offset": offset }),
+        )
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.replay("get_by_name_regex", json!({ "pattern": regex.as_str() }))
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.replay("get_by_range", json!({ "field": field, "range": range }))
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.replay("ping", json!({}))
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        self.replay("update_tags", json!({ "id": id, "changes": changes }))
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        self.replay(
+            "update_with_tags",
+            json!({ "id": id, "hero": hero, "tags": tags }),
+        )
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.replay("record_view", json!({ "id": id }))
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.replay("get_changes_since", json!({ "since": since }))
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.replay("get_with_neighbors", json!({ "id": id }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HeroName, InMemoryHeroesRepository};
+    use futures::TryStreamExt;
+
+    fn hero(id: &str, name: &str) -> Hero {
+        Hero {
+            id: id.to_string(),
+            name: HeroName::new(name).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn replayed_calls_answer_like_recorded_ones() {
+        let path =
+            std::env::temp_dir().join(format!("heroes-recording-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let repo = InMemoryHeroesRepository::new(vec![hero("1", "Storm"), hero("2", "Rogue")]);
+        let recording = RecordingHeroesRepository::create(repo, &path).unwrap();
+        let payload = HeroPayload {
+            name: HeroName::new("Cyclops").unwrap(),
+            power_level: 0,
+        };
+
+        let by_id = recording.get_by_id("1").await.unwrap();
+        let missing = recording.get_by_id("42").await;
+        let created = recording.create(payload.clone()).await.unwrap();
+        let all: Vec<Hero> = recording.stream_all().try_collect().await.unwrap();
+        let page = recording.get_page("%", Some(1), 1).await.unwrap();
+
+        let replaying = ReplayingHeroesRepository::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(replaying.get_by_id("1").await.unwrap(), by_id);
+        assert!(matches!(missing, Err(DataAccessError::NotFound)));
+        assert!(matches!(
+            replaying.get_by_id("42").await,
+            Err(DataAccessError::NotFound)
+        ));
+        assert_eq!(replaying.create(payload).await.unwrap(), created);
+        let replayed: Vec<Hero> = replaying.stream_all().try_collect().await.unwrap();
+        assert_eq!(replayed, all);
+        assert_eq!(replaying.get_page("%", Some(1), 1).await.unwrap(), page);
+    }
+
+    #[tokio::test]
+    async fn calls_missing_from_the_recording_fail() {
+        let line = r#"{"method":"get_by_id","arguments":{"id":"1"},"result":{"Err":"Gone"}}"#;
+        let replaying = ReplayingHeroesRepository::from_reader(line.as_bytes()).unwrap();
+
+        assert!(matches!(
+            replaying.get_by_id("1").await,
+            Err(DataAccessError::Gone)
+        ));
+        // each recorded call is answered once
+        assert!(matches!(
+            replaying.get_by_id("1").await,
+            Err(DataAccessError::TechnicalError)
+        ));
+    }
+}
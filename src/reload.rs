@@ -0,0 +1,158 @@
+use crate::config::Config;
+use std::sync::{Arc, RwLock};
+
+/// Configuration which `SIGHUP` reloads while the service runs
+///
+/// Requests read the `current` configuration as they're extracted, so timeouts, feature
+/// flags, headers and the like change from the next request on. Settings only read at
+/// startup, to bind the socket or to build the repository and the middlewares, keep the
+/// values they started with; `reload` tells which ones it ignored.
+pub struct LiveConfig(RwLock<Arc<Config>>);
+
+/// Copy the startup only settings of `current` to `fresh`, returning the variables of those
+/// which differed
+macro_rules! keep_startup_settings {
+    ($current:expr, $fresh:expr, $($variable:literal => $($field:ident).+),* $(,)?) => {{
+        let mut ignored = vec![];
+        $(
+            if $fresh.$($field).+ != $current.$($field).+ {
+                ignored.push($variable);
+                $fresh.$($field).+ = $current.$($field).+.clone();
+            }
+        )*
+        ignored
+    }};
+}
+
+impl LiveConfig {
+    pub fn new(config: Config) -> Self {
+        LiveConfig(RwLock::new(Arc::new(config)))
+    }
+
+    /// The configuration as of the last reload
+    pub fn current(&self) -> Arc<Config> {
+        match self.0.read() {
+            Ok(config) => config.clone(),
+            // a reload only swaps the `Arc`, it can't leave a half written configuration
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Replace the configuration with `fresh`, but for the settings only read at startup;
+    /// returns the variables of those which `fresh` changed, which are ignored
+    pub fn reload(&self, mut fresh: Config) -> Vec<&'static str> {
+        let current = self.current();
+        let ignored = keep_startup_settings!(current, fresh,
+            "PORT" => port,
+            "TCP_KEEPALIVE_SECS" => tcp_keepalive_secs,
+            "HEADER_READ_TIMEOUT_MS" => header_read_timeout_ms,
+            "HTTP1_KEEPALIVE" => http1_keepalive,
+            "SHUTDOWN_DRAIN_SECS" => shutdown_drain_secs,
+            "WARM_UP" => warm_up,
+            "CRITICAL_TASK_PANIC" => critical_task_panic,
+            "CACHE_TTL_MS" => cache_ttl_ms,
+            "CACHE_REFRESH_INTERVAL_MS" => cache_refresh_interval_ms,
+            "UPSTREAM_URL" => upstream_url,
+            "UPSTREAM_TIMEOUT_MS" => upstream_timeout_ms,
+            "SEED_FILE" => seed_file,
+            "SLOW_QUERY_MS" => slow_query_ms,
+            "REPOSITORY_TIMEOUTS_MS" => repository_timeouts_ms,
+            "REPOSITORY_RETRIES" => repository_retries,
+            "RETRY_BUDGET" => retry_budget,
+            "RETRY_BUDGET_WINDOW_SECS" => retry_budget_window_secs,
+            "CIRCUIT_BREAKER_THRESHOLD" => circuit_breaker_threshold,
+            "CIRCUIT_BREAKER_COOLDOWN_MS" => circuit_breaker_cooldown_ms,
+            "RATE_LIMIT_REQUESTS" => rate_limit_requests,
+            "RATE_LIMIT_READ_REQUESTS" => rate_limit_read_requests,
+            "RATE_LIMIT_WRITE_REQUESTS" => rate_limit_write_requests,
+            "RATE_LIMIT_WINDOW_SECS" => rate_limit_window_secs,
+            "MAX_CONCURRENT_REQUESTS" => max_concurrent_requests,
+            "OVERLOAD_RETRY_AFTER_SECS" => overload_retry_after_secs,
+            "MAX_HEROES_PER_TENANT" => max_heroes_per_tenant,
+            "MAX_TENANTS" => max_tenants,
+            "READ_ONLY" => read_only,
+            "ACCESS_LOG" => access_log,
+            "CORS_ALLOWED_ORIGINS" => cors.allowed_origins,
+            "CORS_HEROES_ONLY" => cors.heroes_only,
+        );
+        match self.0.write() {
+            Ok(mut config) => *config = Arc::new(fresh),
+            Err(poisoned) => *poisoned.into_inner() = Arc::new(fresh),
+        }
+        ignored
+    }
+}
+
+/// Reload `config` from the environment and `CONFIG_FILE` on every `SIGHUP`, until the
+/// process ends
+///
+/// An invalid configuration is logged and leaves the current one as it was. Only unix has
+/// the signal; elsewhere the configuration never changes.
+pub async fn reload_on_hangup(config: Arc<LiveConfig>) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let Ok(mut hangups) = signal(SignalKind::hangup()) else {
+            tracing::warn!("can't listen to SIGHUP, the configuration won't be reloaded");
+            return;
+        };
+        while hangups.recv().await.is_some() {
+            match Config::from_env() {
+                Ok(fresh) => {
+                    let ignored = config.reload(fresh);
+                    if !ignored.is_empty() {
+                        tracing::warn!(
+                            ignored = ignored.join(", "),
+                            "settings only read at startup were changed, restart to apply them"
+                        );
+                    }
+                    tracing::info!(config = %config.current().redacted(), "configuration reloaded");
+                }
+                Err(error) => tracing::error!("configuration not reloaded: {}", error),
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    drop(config);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reload_replaces_the_configuration() {
+        let config = LiveConfig::new(Config::default());
+        let before = config.current();
+
+        let ignored = config.reload(Config {
+            echo_request_id: false,
+            request_timeout_ms: 10,
+            ..Default::default()
+        });
+
+        assert!(ignored.is_empty());
+        assert!(!config.current().echo_request_id);
+        assert_eq!(config.current().request_timeout_ms, 10);
+        // requests under way keep what they read
+        assert!(before.echo_request_id);
+    }
+
+    #[test]
+    fn startup_settings_are_kept_and_reported() {
+        let config = LiveConfig::new(Config::default());
+
+        let ignored = config.reload(Config {
+            port: 9090,
+            rate_limit_requests: Some(5),
+            debug_timing: true,
+            ..Default::default()
+        });
+
+        assert_eq!(ignored, ["PORT", "RATE_LIMIT_REQUESTS"]);
+        let current = config.current();
+        assert_eq!(current.port, Config::default().port);
+        assert_eq!(current.rate_limit_requests, None);
+        assert!(current.debug_timing);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::{config::Config, logging};
+use axum::{
+    body::Body,
+    extract::State,
+    http::{HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request being handled by the current task, if any
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Middleware giving every request an id, taken from `X-Request-Id` or generated
+///
+/// The id is available through `current()` while the request is handled, so error bodies
+/// can carry it. Server errors also get it as a response header and are logged with it,
+/// letting support teams correlate a failure seen by a client with the logs. The logged
+/// query and headers have the values of the `LOG_REDACT` names replaced by `***`. Other
+/// responses get the header too unless `ECHO_REQUEST_ID` is off.
+pub async fn request_id(
+    State(config): State<Arc<Config>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty() && value.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(generate);
+    let method = request.method().clone();
+    let path = logging::sanitize(request.uri().path()).into_owned();
+    let query = logging::redact_query(request.uri().query().unwrap_or(""), &config.log_redact);
+    let headers = logging::redact_headers(request.headers(), &config.log_redact);
+
+    let mut response = REQUEST_ID.scope(id.clone(), next.run(request)).await;
+
+    if response.status().is_server_error() {
+        tracing::error!(
+            request_id = id.as_str(),
+            status = response.status().as_u16(),
+            %method,
+            path,
+            query,
+            headers,
+            "request failed"
+        );
+    }
+    if response.status().is_server_error() || config.echo_request_id {
+        if let Ok(value) = HeaderValue::from_str(&id) {
+            response.headers_mut().insert(REQUEST_ID_HEADER, value);
+        }
+    }
+    response
+}
+
+/// 16 hex digits, unique per process and unpredictable enough for correlation purposes
+fn generate() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    COUNTER.fetch_add(1, Ordering::Relaxed).hash(&mut hasher);
+    SystemTime::now().hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
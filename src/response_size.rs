@@ -0,0 +1,195 @@
+use crate::{config::Config, metrics::AppMetrics, trace};
+use axum::{
+    body::{self, Body, BoxBody, Bytes, HttpBody},
+    extract::State,
+    http::{HeaderMap, HeaderValue, Method, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// Debug header with the size of the body, when `COMPUTED_LENGTH_HEADER` is set
+pub const COMPUTED_LENGTH_HEADER: &str = "x-content-length-computed";
+
+/// State of `response_size`
+#[derive(Clone)]
+pub struct ResponseSize {
+    pub metrics: Arc<AppMetrics>,
+    pub config: Arc<Config>,
+}
+
+/// Route middleware adding the size of response bodies to the bytes sent by their route
+///
+/// Buffered bodies are counted up front and, when configured, their size is sent in
+/// `X-Content-Length-Computed`. Streamed ones, whose size isn't known before they end, are
+/// counted as they're sent, without the header. Responses to `HEAD` go without their body,
+/// so they count for nothing, though the header still tells the size `GET` would send.
+pub async fn response_size(
+    State(state): State<ResponseSize>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let route = trace::route(&request).to_string();
+    // the body is only stripped from responses to `HEAD` after route layers
+    let head = request.method() == Method::HEAD;
+    let (mut parts, body) = next.run(request).await.into_parts();
+
+    match body.size_hint().exact() {
+        Some(size) => {
+            if !head {
+                state.metrics.add_response_bytes(&route, size);
+            }
+            if state.config.computed_length_header {
+                parts
+                    .headers
+                    .insert(COMPUTED_LENGTH_HEADER, HeaderValue::from(size));
+            }
+            Response::from_parts(parts, body)
+        }
+        None => {
+            let body = CountedBody {
+                inner: body,
+                sent: 0,
+                route,
+                metrics: Some(state.metrics),
+            };
+            Response::from_parts(parts, body::boxed(body))
+        }
+    }
+}
+
+/// Body counting the bytes going through it, added to the metrics once it ends or is dropped
+struct CountedBody {
+    inner: BoxBody,
+    sent: u64,
+    route: String,
+    /// taken when the count is recorded, so it only is once
+    metrics: Option<Arc<AppMetrics>>,
+}
+
+impl CountedBody {
+    fn record(&mut self) {
+        if let Some(metrics) = self.metrics.take() {
+            metrics.add_response_bytes(&self.route, self.sent);
+        }
+    }
+}
+
+impl HttpBody for CountedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_data(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(data))) => self.sent += data.len() as u64,
+            Poll::Ready(None) => self.record(),
+            _ => {}
+        }
+        poll
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+// bodies of aborted responses are dropped before their end
+impl Drop for CountedBody {
+    fn drop(&mut self) {
+        self.record();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use futures::stream;
+    use tower::ServiceExt;
+
+    fn app(config: Config) -> (Router, Arc<AppMetrics>) {
+        let metrics = Arc::new(AppMetrics::default());
+        let state = ResponseSize {
+            metrics: metrics.clone(),
+            config: Arc::new(config),
+        };
+        let chunks = || async {
+            let chunks = ["ab", "cde"].map(Ok::<_, std::io::Error>);
+            axum::body::StreamBody::new(stream::iter(chunks))
+        };
+        let app = Router::new()
+            .route("/heroes/:id", get(|| async { "Storm" }))
+            .route("/stream", get(chunks))
+            .route_layer(middleware::from_fn_with_state(state, response_size));
+        (app, metrics)
+    }
+
+    async fn get_body(app: Router, uri: &str) -> (HeaderMap, Bytes) {
+        send(app, Method::GET, uri).await
+    }
+
+    async fn send(app: Router, method: Method, uri: &str) -> (HeaderMap, Bytes) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        let (parts, body) = app.oneshot(request).await.unwrap().into_parts();
+        (parts.headers, hyper::body::to_bytes(body).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn buffered_bodies_are_counted_per_route() {
+        let (app, metrics) = app(Config {
+            computed_length_header: true,
+            ..Default::default()
+        });
+
+        let (headers, _) = get_body(app.clone(), "/heroes/1").await;
+        assert_eq!(metrics.response_bytes("/heroes/:id"), 5);
+        assert_eq!(headers[COMPUTED_LENGTH_HEADER], "5");
+
+        get_body(app, "/heroes/2").await;
+        assert_eq!(metrics.response_bytes("/heroes/:id"), 10);
+    }
+
+    #[tokio::test]
+    async fn streamed_bodies_are_counted_once_sent() {
+        let (app, metrics) = app(Config::default());
+
+        let (headers, body) = get_body(app, "/stream").await;
+
+        assert_eq!(body, "abcde");
+        assert_eq!(metrics.response_bytes("/stream"), 5);
+        assert!(!headers.contains_key(COMPUTED_LENGTH_HEADER));
+    }
+
+    #[tokio::test]
+    async fn bodies_not_sent_to_head_requests_are_not_counted() {
+        let (app, metrics) = app(Config {
+            computed_length_header: true,
+            ..Default::default()
+        });
+
+        let (headers, body) = send(app.clone(), Method::HEAD, "/heroes/1").await;
+        assert!(body.is_empty());
+        assert_eq!(headers[COMPUTED_LENGTH_HEADER], "5");
+
+        send(app, Method::HEAD, "/stream").await;
+        assert_eq!(metrics.response_bytes("/heroes/:id"), 0);
+        assert_eq!(metrics.response_bytes("/stream"), 0);
+    }
+}
@@ -0,0 +1,310 @@
+use crate::hero_query::NumericField;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::{
+    DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats, HeroesRepositoryTrait,
+    TagChanges, UpsertReport,
+};
+use axum::async_trait;
+use futures::stream::BoxStream;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Retries allowed per window, shared by every request
+///
+/// Refilled at the start of each window; once spent, failed calls aren't retried until the
+/// next one, so an outage costs the repository at most `per_window` extra calls per window
+/// instead of one per failed call.
+pub struct RetryBudget {
+    per_window: u64,
+    window: Duration,
+    current: Mutex<Window>,
+}
+
+struct Window {
+    started: Instant,
+    spent: u64,
+}
+
+impl RetryBudget {
+    pub fn new(per_window: u64, window: Duration) -> Self {
+        RetryBudget {
+            per_window,
+            window,
+            current: Mutex::new(Window {
+                started: Instant::now(),
+                spent: 0,
+            }),
+        }
+    }
+
+    /// Take a retry from the budget of the window of `now`, false when none is left
+    fn try_spend(&self, now: Instant) -> bool {
+        let mut current = self
+            .current
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if now.duration_since(current.started) >= self.window {
+            *current = Window {
+                started: now,
+                spent: 0,
+            };
+        }
+        if current.spent < self.per_window {
+            current.spent += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Repository decorator retrying reads which failed with an error worth retrying
+///
+/// Each read is tried again up to `retries` times, a retry at a time taken from the
+/// budget; once it's spent, reads fail with their first error. Writes aren't retried: a
+/// write lost after reaching the repository would be applied twice.
+pub struct RetryingHeroesRepository<R> {
+    inner: R,
+    retries: u32,
+    budget: RetryBudget,
+}
+
+impl<R> RetryingHeroesRepository<R> {
+    pub fn new(inner: R, retries: u32, budget: RetryBudget) -> Self {
+        RetryingHeroesRepository {
+            inner,
+            retries,
+            budget,
+        }
+    }
+
+    async fn retried<T, F: Future<Output = Result<T, DataAccessError>>>(
+        &self,
+        method: &'static str,
+        call: impl Fn() -> F,
+    ) -> Result<T, DataAccessError> {
+        let mut attempt = 0;
+        loop {
+            let error = match call().await {
+                Err(error @ (DataAccessError::Unavailable | DataAccessError::TechnicalError)) => {
+                    error
+                }
+                result => return result,
+            };
+            if attempt == self.retries {
+                return Err(error);
+            }
+            if !self.budget.try_spend(Instant::now()) {
+                tracing::warn!(method, "retry budget spent, failing without retrying");
+                return Err(error);
+            }
+            attempt += 1;
+            tracing::debug!(method, attempt, "retrying repository call");
+        }
+    }
+}
+
+#[async_trait]
+impl<R: HeroesRepositoryTrait + Send + Sync> HeroesRepositoryTrait for RetryingHeroesRepository<R> {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.retried("get_by_name", || self.inner.get_by_name(name))
+            .await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.retried("get_by_id", || self.inner.get_by_id(id)).await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.inner.create(hero).await
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.inner.create_if_absent(id, hero).await
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.inner.update(id, hero).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.inner.delete(id).await
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.inner.delete_by_name(name).await
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.retried("count_by_initial", || self.inner.count_by_initial())
+            .await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.retried("stats", || self.inner.stats()).await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.retried("dataset_digest", || self.inner.dataset_digest())
+            .await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        self.inner.stream_all()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.retried("search", || self.inner.search(term)).await
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        self.inner.replace_all(heroes).await
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        self.inner.upsert_many(heroes).await
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.retried("get_by_tag", || self.inner.get_by_tag(tag))
+            .await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.retried("get_by_ids", || self.inner.get_by_ids(ids))
+            .await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.retried("get_page", || self.inner.get_page(name, limit, offset))
+            .await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.retried("get_by_name_regex", || self.inner.get_by_name_regex(regex))
+            .await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.retried("get_by_range", || self.inner.get_by_range(field, range))
+            .await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.retried("ping", || self.inner.ping()).await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        self.inner.update_tags(id, changes).await
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        self.inner.update_with_tags(id, hero, tags).await
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.inner.record_view(id).await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.retried("get_changes_since", || self.inner.get_changes_since(since))
+            .await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.retried("get_with_neighbors", || self.inner.get_with_neighbors(id))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fn_repository::FnHeroesRepository;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    // a repository down for good, counting the calls it gets
+    fn unavailable(
+        retries: u32,
+        budget: u64,
+    ) -> (RetryingHeroesRepository<FnHeroesRepository>, Arc<AtomicU64>) {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counted = calls.clone();
+        let inner = FnHeroesRepository::new().on_get_by_id(move |_| {
+            counted.fetch_add(1, Ordering::SeqCst);
+            Err(DataAccessError::Unavailable)
+        });
+        let budget = RetryBudget::new(budget, Duration::from_secs(60));
+        (RetryingHeroesRepository::new(inner, retries, budget), calls)
+    }
+
+    #[tokio::test]
+    async fn failed_reads_are_retried() {
+        let (repository, calls) = unavailable(2, 10);
+
+        let result = repository.get_by_id("1").await;
+
+        assert!(matches!(result, Err(DataAccessError::Unavailable)));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn reads_are_not_retried_once_the_budget_is_spent() {
+        let (repository, calls) = unavailable(2, 3);
+
+        repository.get_by_id("1").await.unwrap_err();
+        repository.get_by_id("1").await.unwrap_err();
+        assert_eq!(calls.load(Ordering::SeqCst), 3 + 2);
+
+        calls.store(0, Ordering::SeqCst);
+        let result = repository.get_by_id("1").await;
+
+        assert!(matches!(result, Err(DataAccessError::Unavailable)));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn missing_heroes_are_not_retried() {
+        let calls = Arc::new(AtomicU64::new(0));
+        let counted = calls.clone();
+        let inner = FnHeroesRepository::new().on_get_by_id(move |_| {
+            counted.fetch_add(1, Ordering::SeqCst);
+            Err(DataAccessError::NotFound)
+        });
+        let budget = RetryBudget::new(10, Duration::from_secs(60));
+        let repository = RetryingHeroesRepository::new(inner, 2, budget);
+
+        assert!(matches!(
+            repository.get_by_id("1").await,
+            Err(DataAccessError::NotFound)
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn budget_is_refilled_every_window() {
+        let budget = RetryBudget::new(1, Duration::from_secs(10));
+        let start = Instant::now();
+
+        assert!(budget.try_spend(start));
+        assert!(!budget.try_spend(start + Duration::from_secs(9)));
+        assert!(budget.try_spend(start + Duration::from_secs(10)));
+    }
+}
@@ -0,0 +1,70 @@
+use serde::Serialize;
+
+/// Method and path of a registered route, like `GET /api/v1/heroes/:id`
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct RouteEntry {
+    pub method: &'static str,
+    pub path: String,
+}
+
+/// Routes of the application, which an axum `Router` can't list
+///
+/// Every `Router::route` of `build_app` goes through `add`, keeping the manifest next to
+/// the registration it describes.
+#[derive(Debug, Default)]
+pub struct RouteManifest {
+    /// prefix of the router being built, without its trailing `/`
+    prefix: String,
+    routes: Vec<RouteEntry>,
+}
+
+impl RouteManifest {
+    /// Record `path` as answering `methods`, returning it for `Router::route`
+    pub fn add<'p>(&mut self, methods: &[&'static str], path: &'p str) -> &'p str {
+        for method in methods {
+            self.routes.push(RouteEntry {
+                method,
+                path: format!("{}{}", self.prefix, path),
+            });
+        }
+        path
+    }
+
+    /// Run `build`, whose routes are nested under `prefix`, recording them with the prefix
+    pub fn nest<T>(&mut self, prefix: &str, build: impl FnOnce(&mut Self) -> T) -> T {
+        let outer = self.prefix.clone();
+        self.prefix = format!("{}{}", outer, prefix.trim_end_matches('/'));
+        let nested = build(self);
+        self.prefix = outer;
+        nested
+    }
+
+    pub fn routes(&self) -> &[RouteEntry] {
+        &self.routes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_routes_are_recorded_with_their_prefix() {
+        let mut manifest = RouteManifest::default();
+
+        manifest.add(&["GET"], "/version");
+        manifest.nest("/api/", |manifest| {
+            manifest.nest("/v1/", |manifest| manifest.add(&["GET", "PUT"], "/:id"))
+        });
+
+        let routes: Vec<String> = manifest
+            .routes()
+            .iter()
+            .map(|route| format!("{} {}", route.method, route.path))
+            .collect();
+        assert_eq!(
+            routes,
+            ["GET /version", "GET /api/v1/:id", "PUT /api/v1/:id"]
+        );
+    }
+}
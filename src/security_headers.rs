@@ -0,0 +1,92 @@
+use crate::config::Config;
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Middleware adding the usual security headers to every response
+///
+/// `X-Content-Type-Options: nosniff` and `X-Frame-Options: DENY` are always sent, the
+/// configured `Content-Security-Policy` when there is one. `Strict-Transport-Security`
+/// needs `HSTS_MAX_AGE_SECS`: browsers only honor it over HTTPS, and a deployment served
+/// over plain HTTP mustn't pin itself to it. Headers set by the route are kept.
+pub async fn security_headers(
+    State(config): State<Arc<Config>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let mut response = next.run(request).await;
+
+    let headers = response.headers_mut();
+    headers
+        .entry(header::X_CONTENT_TYPE_OPTIONS)
+        .or_insert(HeaderValue::from_static("nosniff"));
+    headers
+        .entry(header::X_FRAME_OPTIONS)
+        .or_insert(HeaderValue::from_static("DENY"));
+    let policy = config
+        .content_security_policy
+        .as_deref()
+        .and_then(|value| HeaderValue::from_str(value).ok());
+    if let Some(policy) = policy {
+        headers
+            .entry(header::CONTENT_SECURITY_POLICY)
+            .or_insert(policy);
+    }
+    let hsts = config
+        .hsts_max_age_secs
+        .and_then(|max_age| HeaderValue::try_from(format!("max-age={}", max_age)).ok());
+    if let Some(hsts) = hsts {
+        headers.insert(header::STRICT_TRANSPORT_SECURITY, hsts);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn headers_with(config: Config) -> axum::http::HeaderMap {
+        let app = Router::new().route("/", get(|| async { "Storm" })).layer(
+            middleware::from_fn_with_state(Arc::new(config), security_headers),
+        );
+        let request = Request::builder().uri("/").body(Body::empty()).unwrap();
+        app.oneshot(request).await.unwrap().headers().clone()
+    }
+
+    #[tokio::test]
+    async fn security_headers_are_added() {
+        let headers = headers_with(Config {
+            content_security_policy: Some("default-src 'none'".to_string()),
+            hsts_max_age_secs: Some(31_536_000),
+            ..Default::default()
+        })
+        .await;
+
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[header::X_FRAME_OPTIONS], "DENY");
+        assert_eq!(
+            headers[header::CONTENT_SECURITY_POLICY],
+            "default-src 'none'"
+        );
+        assert_eq!(
+            headers[header::STRICT_TRANSPORT_SECURITY],
+            "max-age=31536000"
+        );
+    }
+
+    #[tokio::test]
+    async fn hsts_and_csp_need_their_setting() {
+        let headers = headers_with(Config::default()).await;
+
+        assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+        assert!(!headers.contains_key(header::CONTENT_SECURITY_POLICY));
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{checked_dataset, Hero};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Reason why the dataset of `SEED_FILE` can't be loaded, which stops the startup
+#[derive(Debug)]
+pub enum SeedError {
+    Unreadable {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// Not a JSON array of heroes, or one which `replace_all` would refuse
+    Malformed {
+        path: PathBuf,
+        reason: String,
+    },
+}
+
+impl fmt::Display for SeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeedError::Unreadable { path, error } => {
+                write!(f, "can't read SEED_FILE {}: {}", path.display(), error)
+            }
+            SeedError::Malformed { path, reason } => {
+                write!(f, "malformed SEED_FILE {}: {}", path.display(), reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SeedError {}
+
+/// Heroes of the JSON file at `path`, an array like the body of `POST /admin/heroes/reload`
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<Hero>, SeedError> {
+    let path = path.as_ref();
+    let malformed = |reason: String| SeedError::Malformed {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let content = fs::read_to_string(path).map_err(|error| SeedError::Unreadable {
+        path: path.to_path_buf(),
+        error,
+    })?;
+    let heroes: Vec<Hero> =
+        serde_json::from_str(&content).map_err(|error| malformed(error.to_string()))?;
+    checked_dataset(heroes).map_err(malformed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HeroesRepositoryTrait, InMemoryHeroesRepository};
+
+    fn seed_file(name: &str, content: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("heroes-seed-{}-{}.json", name, std::process::id()));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn seeded_heroes_are_queryable() {
+        let path = seed_file(
+            "valid",
+            r#"[{ "id": "7", "name": "Storm" }, { "id": "8", "name": "Rogue", "tags": ["x-men"] }]"#,
+        );
+
+        let heroes = load(&path).unwrap();
+        let repo = InMemoryHeroesRepository::new(heroes);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(repo.get_by_id("7").await.unwrap().name.as_str(), "Storm");
+        assert_eq!(repo.get_by_tag("x-men").await.unwrap()[0].id, "8");
+    }
+
+    #[test]
+    fn malformed_seed_files_are_refused() {
+        let invalid_json = seed_file("json", r#"[{ "id": "7" }]"#);
+        let duplicated_id = seed_file(
+            "duplicate",
+            r#"[{ "id": "7", "name": "Storm" }, { "id": "7", "name": "Rogue" }]"#,
+        );
+
+        let invalid_json_error = load(&invalid_json).unwrap_err().to_string();
+        let duplicated_id_error = load(&duplicated_id).unwrap_err().to_string();
+        fs::remove_file(&invalid_json).unwrap();
+        fs::remove_file(&duplicated_id).unwrap();
+
+        assert!(invalid_json_error.starts_with("malformed SEED_FILE"));
+        assert!(invalid_json_error.contains("missing field `name`"));
+        assert!(
+            duplicated_id_error.ends_with("hero at index 1 duplicates the id of a previous hero")
+        );
+    }
+
+    #[test]
+    fn missing_seed_file_is_unreadable() {
+        let error = load("/nonexistent/heroes.json").unwrap_err();
+
+        assert!(matches!(error, SeedError::Unreadable { .. }));
+    }
+}
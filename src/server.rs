@@ -0,0 +1,314 @@
+use crate::config::Config;
+use crate::metrics::{self, AppMetrics};
+use axum::{middleware, Router};
+use hyper::server::{conn::AddrIncoming, Builder};
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task::AbortHandle;
+
+/// Failure to open the listening socket
+#[derive(Debug)]
+pub struct BindError {
+    pub addr: SocketAddr,
+    pub source: io::Error,
+}
+
+impl fmt::Display for BindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.source.kind() {
+            io::ErrorKind::AddrInUse => write!(
+                f,
+                "can't listen on {}: the port is already in use, stop the other process or set PORT",
+                self.addr
+            ),
+            io::ErrorKind::PermissionDenied => write!(
+                f,
+                "can't listen on {}: permission denied, ports below 1024 usually need privileges",
+                self.addr
+            ),
+            _ => write!(f, "can't listen on {}: {}", self.addr, self.source),
+        }
+    }
+}
+
+impl std::error::Error for BindError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Open the listening socket the server will accept connections on
+pub fn bind(addr: SocketAddr) -> Result<TcpListener, BindError> {
+    TcpListener::bind(addr).map_err(|source| BindError { addr, source })
+}
+
+/// Server accepting connections on `listener`, with the connection settings of `config`
+///
+/// Only HTTP/1.1 is served: HTTP/2 support isn't compiled in. Connections, not requests,
+/// are timed out here: handlers are by `Deadline`.
+pub fn build(
+    listener: TcpListener,
+    config: &Config,
+) -> Result<Builder<AddrIncoming>, hyper::Error> {
+    let mut server = axum::Server::from_tcp(listener)?
+        .tcp_keepalive(config.tcp_keepalive_secs.map(Duration::from_secs))
+        .http1_keepalive(config.http1_keepalive);
+    if let Some(ms) = config.header_read_timeout_ms {
+        server = server.http1_header_read_timeout(Duration::from_millis(ms));
+    }
+    Ok(server)
+}
+
+/// How often the requests left are logged while draining
+const DRAIN_LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Completes on Ctrl-C or, on unix, `SIGTERM` as sent by orchestrators
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Executor of connection tasks keeping a handle on them, to abort those still running
+#[derive(Clone, Default)]
+struct AbortableTasks(Arc<Mutex<Vec<AbortHandle>>>);
+
+impl<F> hyper::rt::Executor<F> for AbortableTasks
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, task: F) {
+        let handle = tokio::spawn(task).abort_handle();
+        if let Ok(mut tasks) = self.0.lock() {
+            tasks.retain(|task| !task.is_finished());
+            tasks.push(handle);
+        }
+    }
+}
+
+impl AbortableTasks {
+    fn abort_all(&self) {
+        if let Ok(tasks) = self.0.lock() {
+            tasks.iter().for_each(AbortHandle::abort);
+        }
+    }
+}
+
+/// Serve `app` until `shutdown` completes, then stop accepting connections and give the
+/// requests in flight `drain` to finish
+///
+/// Requests being handled are counted in the `active_requests` of `metrics`, and logged
+/// every `DRAIN_LOG_INTERVAL` while draining, to size `SHUTDOWN_DRAIN_SECS` by. Requests
+/// still running after `drain` are aborted, their number being logged, so a stuck request
+/// can't hold the process forever.
+pub async fn serve(
+    server: Builder<AddrIncoming>,
+    app: Router,
+    shutdown: impl Future<Output = ()>,
+    drain: Duration,
+    metrics: Arc<AppMetrics>,
+) -> Result<(), hyper::Error> {
+    let app = app.layer(middleware::from_fn_with_state(
+        metrics.clone(),
+        metrics::track_active,
+    ));
+    let tasks = AbortableTasks::default();
+    let (draining, drain_started) = oneshot::channel();
+    let server = server
+        .executor(tasks.clone())
+        // rate limits tell clients apart by address
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(async move {
+            shutdown.await;
+            let _ = draining.send(());
+        });
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => return result,
+        _ = drain_started => {}
+    }
+    tracing::info!(
+        in_flight = metrics.active_requests(),
+        "shutting down, draining requests"
+    );
+    let drained = async {
+        let start = tokio::time::Instant::now() + DRAIN_LOG_INTERVAL;
+        let mut progress = tokio::time::interval_at(start, DRAIN_LOG_INTERVAL);
+        loop {
+            tokio::select! {
+                result = &mut server => return result,
+                _ = progress.tick() => {
+                    tracing::info!(in_flight = metrics.active_requests(), "draining requests");
+                }
+            }
+        }
+    };
+    match tokio::time::timeout(drain, drained).await {
+        Ok(result) => result,
+        Err(_) => {
+            tracing::warn!(
+                dropped = metrics.active_requests(),
+                "drain deadline reached, aborting the remaining requests"
+            );
+            tasks.abort_all();
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging;
+    use axum::routing::get;
+    use std::time::Instant;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    #[test]
+    fn port_in_use_is_described() {
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = taken.local_addr().unwrap();
+
+        let error = bind(addr).unwrap_err();
+
+        assert_eq!(error.source.kind(), io::ErrorKind::AddrInUse);
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "can't listen on {}: the port is already in use, stop the other process or set PORT",
+                addr
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn kept_alive_connection_serves_several_requests() {
+        let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = build(listener, &Config::default()).unwrap().serve(
+            Router::new()
+                .route("/", get(|| async { "ok" }))
+                .into_make_service(),
+        );
+        tokio::spawn(server);
+
+        let mut connection = TcpStream::connect(addr).await.unwrap();
+        let mut received = String::new();
+        for _ in 0..2 {
+            connection
+                .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await
+                .unwrap();
+            let mut buffer = [0; 1024];
+            let read = connection.read(&mut buffer).await.unwrap();
+            received.push_str(&String::from_utf8_lossy(&buffer[..read]));
+        }
+
+        assert_eq!(received.matches("HTTP/1.1 200 OK").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn connection_is_closed_when_headers_are_too_slow() {
+        let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let config = Config {
+            header_read_timeout_ms: Some(100),
+            ..Default::default()
+        };
+        let server = build(listener, &config).unwrap().serve(
+            Router::new()
+                .route("/", get(|| async { "ok" }))
+                .into_make_service(),
+        );
+        tokio::spawn(server);
+
+        let mut connection = TcpStream::connect(addr).await.unwrap();
+        connection
+            .write_all(b"GET / HTTP/1.1\r\nHost: loc")
+            .await
+            .unwrap();
+        let mut buffer = [0; 1024];
+        let read = tokio::time::timeout(Duration::from_secs(2), connection.read(&mut buffer))
+            .await
+            .expect("connection closed");
+
+        // closed without an answer
+        assert!(read.map_or(true, |read| read == 0));
+    }
+
+    #[tokio::test]
+    async fn shutdown_aborts_requests_outliving_the_drain_deadline() {
+        let listener = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route(
+            "/slow",
+            get(|| async {
+                tokio::time::sleep(Duration::from_secs(30)).await;
+                "too late"
+            }),
+        );
+        let (stop, stopped) = oneshot::channel::<()>();
+        let server = build(listener, &Config::default()).unwrap();
+        let (logs, _guard) = logging::capture();
+
+        let client = tokio::spawn(async move {
+            let mut connection = TcpStream::connect(addr).await.unwrap();
+            connection
+                .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            let _ = stop.send(());
+            // keep the connection open until the server gives up on it
+            let mut buffer = [0; 1024];
+            let _ = connection.read(&mut buffer).await;
+        });
+        let started = Instant::now();
+        let result = serve(
+            server,
+            app,
+            async {
+                let _ = stopped.await;
+            },
+            Duration::from_millis(200),
+            Arc::default(),
+        )
+        .await;
+
+        assert!(result.is_ok());
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(logs.contains("drain deadline reached, aborting the remaining requests dropped=1"));
+        // the aborted connection is closed rather than left to the handler
+        tokio::time::timeout(Duration::from_secs(2), client)
+            .await
+            .expect("connection closed")
+            .unwrap();
+    }
+}
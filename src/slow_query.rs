@@ -0,0 +1,210 @@
+use crate::hero_query::NumericField;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::{
+    DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats, HeroesRepositoryTrait,
+    TagChanges, UpsertReport,
+};
+use axum::async_trait;
+use futures::stream::BoxStream;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// Repository decorator logging a warning for every call slower than a threshold
+///
+/// Results are returned untouched. `stream_all` isn't timed: its cost is paid while consuming.
+pub struct SlowQueryHeroesRepository<R> {
+    inner: R,
+    threshold: Duration,
+}
+
+impl<R> SlowQueryHeroesRepository<R> {
+    pub fn new(inner: R, threshold: Duration) -> Self {
+        SlowQueryHeroesRepository { inner, threshold }
+    }
+
+    async fn timed<T>(&self, method: &'static str, call: impl Future<Output = T>) -> T {
+        let started = Instant::now();
+        let result = call.await;
+        let elapsed = started.elapsed();
+        if elapsed > self.threshold {
+            tracing::warn!(
+                method,
+                elapsed_ms = elapsed.as_millis() as u64,
+                "slow repository call"
+            );
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<R: HeroesRepositoryTrait + Send + Sync> HeroesRepositoryTrait
+    for SlowQueryHeroesRepository<R>
+{
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.timed("get_by_name", self.inner.get_by_name(name))
+            .await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.timed("get_by_id", self.inner.get_by_id(id)).await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.timed("create", self.inner.create(hero)).await
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.timed("create_if_absent", self.inner.create_if_absent(id, hero))
+            .await
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.timed("update", self.inner.update(id, hero)).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.timed("delete", self.inner.delete(id)).await
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.timed("delete_by_name", self.inner.delete_by_name(name))
+            .await
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.timed("count_by_initial", self.inner.count_by_initial())
+            .await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.timed("stats", self.inner.stats()).await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.timed("dataset_digest", self.inner.dataset_digest())
+            .await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        self.inner.stream_all()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.timed("search", self.inner.search(term)).await
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        self.timed("replace_all", self.inner.replace_all(heroes))
+            .await
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        self.timed("upsert_many", self.inner.upsert_many(heroes))
+            .await
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.timed("get_by_tag", self.inner.get_by_tag(tag)).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.timed("get_by_ids", self.inner.get_by_ids(ids)).await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.timed("get_page", self.inner.get_page(name, limit, offset))
+            .await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.timed("get_by_name_regex", self.inner.get_by_name_regex(regex))
+            .await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.timed("get_by_range", self.inner.get_by_range(field, range))
+            .await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.timed("ping", self.inner.ping()).await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        self.timed("update_tags", self.inner.update_tags(id, changes))
+            .await
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        self.timed(
+            "update_with_tags",
+            self.inner.update_with_tags(id, hero, tags),
+        )
+        .await
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.timed("record_view", self.inner.record_view(id)).await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.timed("get_changes_since", self.inner.get_changes_since(since))
+            .await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.timed("get_with_neighbors", self.inner.get_with_neighbors(id))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{logging, InMemoryHeroesRepository};
+
+    // the in-memory repository simulates a 100ms database read in get_by_name only
+    fn repository() -> SlowQueryHeroesRepository<InMemoryHeroesRepository> {
+        SlowQueryHeroesRepository::new(
+            InMemoryHeroesRepository::default(),
+            Duration::from_millis(50),
+        )
+    }
+
+    #[tokio::test]
+    async fn slow_call_is_logged() {
+        let (logs, _guard) = logging::capture();
+
+        let result = repository().get_by_name("Wonder%").await;
+
+        assert!(result.is_ok());
+        assert!(logs.contains("WARN"));
+        assert!(logs.contains("slow repository call method=\"get_by_name\""));
+    }
+
+    #[tokio::test]
+    async fn fast_call_is_not_logged() {
+        let (logs, _guard) = logging::capture();
+
+        let result = repository().get_by_id("1").await;
+
+        assert!(result.is_ok());
+        assert!(!logs.contains("slow repository call"));
+    }
+}
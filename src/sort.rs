@@ -0,0 +1,181 @@
+use crate::{hero_name, Hero};
+use serde::{Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// Field hero listings can be sorted by
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SortField {
+    /// numeric ids by value, like the in-memory repository orders them
+    Id,
+    /// ignoring case, like name filters
+    Name,
+    /// heroes never updated come first
+    UpdatedAt,
+    PowerLevel,
+}
+
+/// One field of a sort order, written `name` or `-name` for descending order
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (descending, field) = match value.strip_prefix('-') {
+            Some(field) => (true, field),
+            None => (false, value),
+        };
+        let field = match field {
+            "id" => SortField::Id,
+            "name" => SortField::Name,
+            "updated_at" => SortField::UpdatedAt,
+            "power_level" => SortField::PowerLevel,
+            _ => {
+                return Err(format!(
+                    "can't sort by '{}', expected id, name, updated_at or power_level",
+                    field
+                ))
+            }
+        };
+        Ok(SortKey { field, descending })
+    }
+}
+
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let field = match self.field {
+            SortField::Id => "id",
+            SortField::Name => "name",
+            SortField::UpdatedAt => "updated_at",
+            SortField::PowerLevel => "power_level",
+        };
+        let direction = if self.descending { "-" } else { "" };
+        write!(f, "{}{}", direction, field)
+    }
+}
+
+impl SortKey {
+    fn compare(&self, a: &Hero, b: &Hero) -> Ordering {
+        let ordering = match self.field {
+            SortField::Id => crate::id_order(&a.id, &b.id),
+            SortField::Name => {
+                hero_name::fold_case(a.name.as_str()).cmp(&hero_name::fold_case(b.name.as_str()))
+            }
+            SortField::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+            SortField::PowerLevel => a.power_level.cmp(&b.power_level),
+        };
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+/// Order of a hero listing: comma separated keys, each breaking the ties of the previous
+/// ones, e.g. `name,-updated_at`
+///
+/// Requested with `?sort=`, or configured for every listing with `DEFAULT_SORT`;
+/// without either, heroes are listed in repository order.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SortOrder(pub Vec<SortKey>);
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split(',')
+            .map(|key| key.trim().parse())
+            .collect::<Result<_, _>>()
+            .map(SortOrder)
+    }
+}
+
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, key) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", key)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for SortOrder {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl SortOrder {
+    /// Sort `heroes` in place; heroes equal on every key keep their relative order
+    pub fn apply(&self, heroes: &mut [Hero]) {
+        heroes.sort_by(|a, b| {
+            self.0
+                .iter()
+                .map(|key| key.compare(a, b))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("name", SortField::Name, false)]
+    #[case("-updated_at", SortField::UpdatedAt, true)]
+    #[case("id", SortField::Id, false)]
+    #[case("-power_level", SortField::PowerLevel, true)]
+    fn keys_are_parsed(#[case] value: &str, #[case] field: SortField, #[case] descending: bool) {
+        let key: SortKey = value.parse().unwrap();
+
+        assert_eq!(key, SortKey { field, descending });
+        assert_eq!(key.to_string(), value);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("power")]
+    #[case("--name")]
+    fn unknown_fields_are_rejected(#[case] value: &str) {
+        assert!(value.parse::<SortKey>().is_err());
+    }
+
+    #[test]
+    fn keys_are_applied_in_order() {
+        let hero = |id: &str, name: &str| Hero {
+            id: id.to_string(),
+            name: crate::HeroName::new(name).unwrap(),
+            ..Default::default()
+        };
+        let mut heroes = vec![hero("1", "Storm"), hero("2", "Batman"), hero("3", "storm")];
+
+        let order: SortOrder = "name, -id".parse().unwrap();
+        order.apply(&mut heroes);
+
+        let ids: Vec<&str> = heroes.iter().map(|hero| hero.id.as_str()).collect();
+        assert_eq!(ids, ["2", "3", "1"]);
+        assert_eq!(order.to_string(), "name,-id");
+    }
+
+    #[test]
+    fn invalid_key_is_named_wherever_it_is() {
+        assert_eq!(
+            "name,power".parse::<SortOrder>(),
+            Err("can't sort by 'power', expected id, name, updated_at or power_level".to_string())
+        );
+    }
+}
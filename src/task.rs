@@ -0,0 +1,178 @@
+use futures::FutureExt;
+use serde::Serialize;
+use std::any::Any;
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+/// Pause before a panicked task is started again, so a task panicking right away doesn't
+/// spin
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// What happens once a critical background task panicked, besides logging it
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PanicPolicy {
+    /// nothing, the task stays stopped
+    #[default]
+    Log,
+    /// start the task again
+    Restart,
+    /// fail the readiness check, so the orchestrator replaces the instance
+    Unready,
+}
+
+impl FromStr for PanicPolicy {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "log" => Ok(PanicPolicy::Log),
+            "restart" => Ok(PanicPolicy::Restart),
+            "unready" => Ok(PanicPolicy::Unready),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Spawner of the tasks running beside requests, like the cache refresh
+///
+/// A panic in a task spawned with `tokio::spawn` only ends up in its `JoinHandle`, which
+/// nobody awaits until shutdown: the task dies silently. Tasks spawned here have their
+/// panics logged at error level, critical ones being handled by the `PanicPolicy` too.
+#[derive(Debug, Default)]
+pub struct BackgroundTasks {
+    on_panic: PanicPolicy,
+    /// critical tasks which panicked under `PanicPolicy::Unready`
+    failed: Mutex<BTreeSet<&'static str>>,
+}
+
+impl BackgroundTasks {
+    pub fn new(on_panic: PanicPolicy) -> Self {
+        BackgroundTasks {
+            on_panic,
+            failed: Mutex::default(),
+        }
+    }
+
+    pub fn on_panic(&self) -> PanicPolicy {
+        self.on_panic
+    }
+
+    /// Critical tasks which panicked and make the instance unready
+    pub fn failed(&self) -> Vec<&'static str> {
+        self.failed
+            .lock()
+            .map(|failed| failed.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Spawn a task whose panic is only logged
+    pub fn spawn(
+        &self,
+        name: &'static str,
+        task: impl Future<Output = ()> + Send + 'static,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            if let Err(panic) = AssertUnwindSafe(task).catch_unwind().await {
+                log_panic(name, panic);
+            }
+        })
+    }
+
+    /// Spawn the task made by `make`, whose panics are handled by the `PanicPolicy`; `make`
+    /// is called again for each restart
+    pub fn spawn_critical<F, T>(self: &Arc<Self>, name: &'static str, mut make: F) -> JoinHandle<()>
+    where
+        F: FnMut() -> T + Send + 'static,
+        T: Future<Output = ()> + Send + 'static,
+    {
+        let tasks = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let Err(panic) = AssertUnwindSafe(make()).catch_unwind().await else {
+                    return;
+                };
+                log_panic(name, panic);
+                match tasks.on_panic {
+                    PanicPolicy::Log => return,
+                    PanicPolicy::Restart => {
+                        tracing::warn!(task = name, "restarting background task");
+                        tokio::time::sleep(RESTART_DELAY).await;
+                    }
+                    PanicPolicy::Unready => {
+                        if let Ok(mut failed) = tasks.failed.lock() {
+                            failed.insert(name);
+                        }
+                        return;
+                    }
+                }
+            }
+        })
+    }
+}
+
+fn log_panic(name: &'static str, panic: Box<dyn Any + Send>) {
+    // `panic!` payloads are a `&str` or, when formatted, a `String`
+    let message = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("?");
+    tracing::error!(task = name, panic = message, "background task panicked");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[tokio::test]
+    async fn panics_are_logged_without_stopping_the_process() {
+        let (logs, _guard) = logging::capture();
+        let tasks = BackgroundTasks::default();
+
+        let panicked = tasks.spawn("doomed", async { panic!("out of heroes") });
+
+        assert!(panicked.await.is_ok());
+        assert!(logs.contains("ERROR"));
+        assert!(logs.contains("background task panicked task=\"doomed\" panic=\"out of heroes\""));
+        // the runtime, and this test, carry on
+        assert!(tasks.spawn("healthy", async {}).await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn critical_tasks_are_restarted() {
+        let tasks = Arc::new(BackgroundTasks::new(PanicPolicy::Restart));
+        let runs = Arc::new(AtomicU64::new(0));
+        let counted = runs.clone();
+
+        let task = tasks.spawn_critical("flaky", move || {
+            let run = counted.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if run < 2 {
+                    panic!("run {} failed", run);
+                }
+            }
+        });
+
+        task.await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert!(tasks.failed().is_empty());
+    }
+
+    #[tokio::test]
+    async fn critical_tasks_can_make_the_instance_unready() {
+        let tasks = Arc::new(BackgroundTasks::new(PanicPolicy::Unready));
+
+        let task = tasks.spawn_critical("cache_refresh", || async { panic!("poisoned") });
+
+        task.await.unwrap();
+        assert_eq!(tasks.failed(), ["cache_refresh"]);
+    }
+}
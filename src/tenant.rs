@@ -0,0 +1,259 @@
+use crate::hero_query::NumericField;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::{config::Config, error::ApiError};
+use crate::{
+    DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats, HeroesRepositoryTrait,
+    TagChanges, UpsertReport,
+};
+use axum::{
+    async_trait,
+    body::Body,
+    extract::State,
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::stream::BoxStream;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+
+pub const TENANT_HEADER: &str = "x-tenant-id";
+
+tokio::task_local! {
+    static TENANT: String;
+}
+
+/// Tenant of the request being handled by the current task, if it named one
+pub fn current() -> Option<String> {
+    TENANT.try_with(|tenant| tenant.clone()).ok()
+}
+
+/// Run `future` on behalf of `tenant`, e.g. in a background task working for a request
+pub async fn run_as<F: Future>(tenant: Option<String>, future: F) -> F::Output {
+    match tenant {
+        Some(tenant) => TENANT.scope(tenant, future).await,
+        None => future.await,
+    }
+}
+
+/// `key` qualified with the current tenant, for stores shared by every tenant
+pub fn scoped(key: &str) -> String {
+    match current() {
+        // tenant ids can't contain '/', so scoped keys never collide
+        Some(tenant) => format!("{}/{}", tenant, key),
+        None => key.to_string(),
+    }
+}
+
+fn is_valid(tenant: &str) -> bool {
+    (1..=64).contains(&tenant.len())
+        && tenant
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Middleware running the request on behalf of the tenant named by `X-Tenant-Id`
+///
+/// Requests without the header are answered with `400` when `REQUIRE_TENANT` is set,
+/// otherwise they use the shared, tenant-less dataset.
+pub async fn tenant(
+    State(config): State<Arc<Config>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let tenant = match request.headers().get(TENANT_HEADER) {
+        None if config.require_tenant => {
+            return ApiError::bad_request("the X-Tenant-Id header is required").into_response()
+        }
+        None => return next.run(request).await,
+        Some(value) => value.to_str().ok().filter(|tenant| is_valid(tenant)),
+    };
+
+    match tenant {
+        Some(tenant) => {
+            let tenant = tenant.to_string();
+            TENANT.scope(tenant, next.run(request)).await
+        }
+        None => {
+            ApiError::bad_request("X-Tenant-Id must be 1 to 64 ascii letters, digits, '-' or '_'")
+                .into_response()
+        }
+    }
+}
+
+/// Repository giving every tenant its own dataset, created on first use
+///
+/// Datasets are never dropped, so at most `max_partitions` are created, the tenant-less one
+/// included: calls of further tenants fail with `TenantLimitReached` rather than letting
+/// clients rotating `X-Tenant-Id` use up the memory.
+///
+/// Meant to wrap the innermost, storing repository: a database implementation would rather
+/// filter on a tenant column.
+pub struct TenantScopedHeroesRepository<R> {
+    partitions: RwLock<HashMap<String, Arc<R>>>,
+    max_partitions: usize,
+    new_partition: Box<dyn Fn() -> R + Send + Sync>,
+}
+
+impl<R> TenantScopedHeroesRepository<R> {
+    pub fn new(
+        max_partitions: usize,
+        new_partition: impl Fn() -> R + Send + Sync + 'static,
+    ) -> Self {
+        TenantScopedHeroesRepository {
+            partitions: RwLock::new(HashMap::new()),
+            max_partitions,
+            new_partition: Box::new(new_partition),
+        }
+    }
+
+    fn partition(&self) -> Result<Arc<R>, DataAccessError> {
+        let tenant = current().unwrap_or_default();
+        let existing = self
+            .partitions
+            .read()
+            .map_err(|_| DataAccessError::TechnicalError)?
+            .get(&tenant)
+            .cloned();
+        match existing {
+            Some(partition) => Ok(partition),
+            None => {
+                let mut partitions = self
+                    .partitions
+                    .write()
+                    .map_err(|_| DataAccessError::TechnicalError)?;
+                if !partitions.contains_key(&tenant) && partitions.len() >= self.max_partitions {
+                    return Err(DataAccessError::TenantLimitReached);
+                }
+                Ok(partitions
+                    .entry(tenant)
+                    .or_insert_with(|| Arc::new((self.new_partition)()))
+                    .clone())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<R: HeroesRepositoryTrait + Send + Sync + 'static> HeroesRepositoryTrait
+    for TenantScopedHeroesRepository<R>
+{
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.partition()?.get_by_name(name).await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.partition()?.get_by_id(id).await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.partition()?.create(hero).await
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.partition()?.create_if_absent(id, hero).await
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.partition()?.update(id, hero).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.partition()?.delete(id).await
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.partition()?.delete_by_name(name).await
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.partition()?.count_by_initial().await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.partition()?.stats().await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.partition()?.dataset_digest().await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        match self.partition() {
+            Ok(partition) => partition.stream_all(),
+            Err(error) => Box::pin(futures::stream::once(async move { Err(error) })),
+        }
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.partition()?.search(term).await
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        self.partition()?.replace_all(heroes).await
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        self.partition()?.upsert_many(heroes).await
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.partition()?.get_by_tag(tag).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.partition()?.get_by_ids(ids).await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.partition()?.get_page(name, limit, offset).await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.partition()?.get_by_name_regex(regex).await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.partition()?.get_by_range(field, range).await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.partition()?.ping().await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        self.partition()?.update_tags(id, changes).await
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        self.partition()?.update_with_tags(id, hero, tags).await
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.partition()?.record_view(id).await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.partition()?.get_changes_since(since).await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.partition()?.get_with_neighbors(id).await
+    }
+}
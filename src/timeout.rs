@@ -0,0 +1,297 @@
+use crate::hero_query::NumericField;
+use crate::name_regex::NameRegex;
+use crate::numeric_range::NumericRange;
+use crate::{
+    DataAccessError, Hero, HeroChange, HeroContext, HeroPayload, HeroStats, HeroesRepositoryTrait,
+    TagChanges, UpsertReport,
+};
+use axum::async_trait;
+use futures::stream::BoxStream;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Repository methods which can be given a timeout, all but `stream_all`
+pub const METHODS: [&str; 24] = [
+    "get_by_name",
+    "get_by_id",
+    "create",
+    "create_if_absent",
+    "update",
+    "delete",
+    "delete_by_name",
+    "count_by_initial",
+    "stats",
+    "dataset_digest",
+    "search",
+    "replace_all",
+    "upsert_many",
+    "get_by_tag",
+    "get_by_ids",
+    "get_page",
+    "get_by_name_regex",
+    "get_by_range",
+    "ping",
+    "update_tags",
+    "update_with_tags",
+    "record_view",
+    "get_changes_since",
+    "get_with_neighbors",
+];
+
+/// Milliseconds each repository method may take, parsed from `get_by_name=200,create=1000`
+///
+/// Methods left out aren't limited.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MethodTimeouts(BTreeMap<&'static str, u64>);
+
+impl MethodTimeouts {
+    pub fn get(&self, method: &str) -> Option<Duration> {
+        self.0.get(method).copied().map(Duration::from_millis)
+    }
+}
+
+impl FromStr for MethodTimeouts {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| {
+                let (method, ms) = item.split_once('=').ok_or(())?;
+                let method = METHODS
+                    .into_iter()
+                    .find(|known| *known == method.trim())
+                    .ok_or(())?;
+                let ms = ms.trim().parse().map_err(|_| ())?;
+                Ok((method, ms))
+            })
+            .collect::<Result<_, _>>()
+            .map(MethodTimeouts)
+    }
+}
+
+/// Repository decorator failing calls which outlast their method's timeout with
+/// `DataAccessError::Unavailable`
+///
+/// Unlike the request deadline, each method has its own limit, so e.g. writes may be given
+/// more time than reads. `stream_all` isn't limited: its cost is paid while consuming.
+pub struct TimeoutHeroesRepository<R> {
+    inner: R,
+    timeouts: MethodTimeouts,
+}
+
+impl<R> TimeoutHeroesRepository<R> {
+    pub fn new(inner: R, timeouts: MethodTimeouts) -> Self {
+        TimeoutHeroesRepository { inner, timeouts }
+    }
+
+    async fn limited<T>(
+        &self,
+        method: &'static str,
+        call: impl Future<Output = Result<T, DataAccessError>>,
+    ) -> Result<T, DataAccessError> {
+        let Some(timeout) = self.timeouts.get(method) else {
+            return call.await;
+        };
+        tokio::time::timeout(timeout, call)
+            .await
+            .unwrap_or_else(|_| {
+                tracing::warn!(
+                    method,
+                    timeout_ms = timeout.as_millis() as u64,
+                    "repository call timed out"
+                );
+                Err(DataAccessError::Unavailable)
+            })
+    }
+}
+
+#[async_trait]
+impl<R: HeroesRepositoryTrait + Send + Sync> HeroesRepositoryTrait for TimeoutHeroesRepository<R> {
+    async fn get_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.limited("get_by_name", self.inner.get_by_name(name))
+            .await
+    }
+
+    async fn get_by_id(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.limited("get_by_id", self.inner.get_by_id(id)).await
+    }
+
+    async fn create(&self, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.limited("create", self.inner.create(hero)).await
+    }
+
+    async fn create_if_absent(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.limited("create_if_absent", self.inner.create_if_absent(id, hero))
+            .await
+    }
+
+    async fn update(&self, id: &str, hero: HeroPayload) -> Result<Hero, DataAccessError> {
+        self.limited("update", self.inner.update(id, hero)).await
+    }
+
+    async fn delete(&self, id: &str) -> Result<Hero, DataAccessError> {
+        self.limited("delete", self.inner.delete(id)).await
+    }
+
+    async fn delete_by_name(&self, name: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.limited("delete_by_name", self.inner.delete_by_name(name))
+            .await
+    }
+
+    async fn count_by_initial(&self) -> Result<Vec<(char, u64)>, DataAccessError> {
+        self.limited("count_by_initial", self.inner.count_by_initial())
+            .await
+    }
+
+    async fn stats(&self) -> Result<HeroStats, DataAccessError> {
+        self.limited("stats", self.inner.stats()).await
+    }
+
+    async fn dataset_digest(&self) -> Result<String, DataAccessError> {
+        self.limited("dataset_digest", self.inner.dataset_digest())
+            .await
+    }
+
+    fn stream_all(&self) -> BoxStream<'static, Result<Hero, DataAccessError>> {
+        self.inner.stream_all()
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.limited("search", self.inner.search(term)).await
+    }
+
+    async fn replace_all(&self, heroes: Vec<Hero>) -> Result<(), DataAccessError> {
+        self.limited("replace_all", self.inner.replace_all(heroes))
+            .await
+    }
+
+    async fn upsert_many(&self, heroes: &[Hero]) -> Result<UpsertReport, DataAccessError> {
+        self.limited("upsert_many", self.inner.upsert_many(heroes))
+            .await
+    }
+
+    async fn get_by_tag(&self, tag: &str) -> Result<Vec<Hero>, DataAccessError> {
+        self.limited("get_by_tag", self.inner.get_by_tag(tag)).await
+    }
+
+    async fn get_by_ids(&self, ids: &[String]) -> Result<Vec<Hero>, DataAccessError> {
+        self.limited("get_by_ids", self.inner.get_by_ids(ids)).await
+    }
+
+    async fn get_page(
+        &self,
+        name: &str,
+        limit: Option<u64>,
+        offset: u64,
+    ) -> Result<(Vec<Hero>, u64), DataAccessError> {
+        self.limited("get_page", self.inner.get_page(name, limit, offset))
+            .await
+    }
+
+    async fn get_by_name_regex(&self, regex: &NameRegex) -> Result<Vec<Hero>, DataAccessError> {
+        self.limited("get_by_name_regex", self.inner.get_by_name_regex(regex))
+            .await
+    }
+
+    async fn get_by_range(
+        &self,
+        field: NumericField,
+        range: NumericRange<u64>,
+    ) -> Result<Vec<Hero>, DataAccessError> {
+        self.limited("get_by_range", self.inner.get_by_range(field, range))
+            .await
+    }
+
+    async fn ping(&self) -> Result<(), DataAccessError> {
+        self.limited("ping", self.inner.ping()).await
+    }
+
+    async fn update_tags(&self, id: &str, changes: TagChanges) -> Result<Hero, DataAccessError> {
+        self.limited("update_tags", self.inner.update_tags(id, changes))
+            .await
+    }
+
+    async fn update_with_tags(
+        &self,
+        id: &str,
+        hero: HeroPayload,
+        tags: Vec<String>,
+    ) -> Result<Hero, DataAccessError> {
+        self.limited(
+            "update_with_tags",
+            self.inner.update_with_tags(id, hero, tags),
+        )
+        .await
+    }
+
+    async fn record_view(&self, id: &str) -> Result<u64, DataAccessError> {
+        self.limited("record_view", self.inner.record_view(id))
+            .await
+    }
+
+    async fn get_changes_since(&self, since: u64) -> Result<Vec<HeroChange>, DataAccessError> {
+        self.limited("get_changes_since", self.inner.get_changes_since(since))
+            .await
+    }
+
+    async fn get_with_neighbors(&self, id: &str) -> Result<HeroContext, DataAccessError> {
+        self.limited("get_with_neighbors", self.inner.get_with_neighbors(id))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::InMemoryHeroesRepository;
+    use rstest::rstest;
+
+    // the in-memory repository simulates a 100ms database read in get_by_name only
+    fn repository(timeouts: &str) -> TimeoutHeroesRepository<InMemoryHeroesRepository> {
+        TimeoutHeroesRepository::new(
+            InMemoryHeroesRepository::default(),
+            timeouts.parse().unwrap(),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_call_times_out_as_unavailable() {
+        let repository = repository("get_by_name=50,get_by_id=50");
+
+        let slow = repository.get_by_name("Wonder%").await;
+        let fast = repository.get_by_id("1").await;
+
+        assert!(matches!(slow, Err(DataAccessError::Unavailable)));
+        assert!(fast.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn each_method_has_its_own_timeout() {
+        let repository = repository("get_by_name=200,get_by_id=50");
+
+        assert!(repository.get_by_name("Wonder%").await.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn methods_without_a_timeout_are_not_limited() {
+        let repository = repository("create=10");
+
+        assert!(repository.get_by_name("Wonder%").await.is_ok());
+    }
+
+    #[rstest]
+    #[case("get_by_name")]
+    #[case("get_by_name=")]
+    #[case("get_by_name=fast")]
+    #[case("stream_all=100")]
+    #[case("teleport=100")]
+    fn invalid_timeouts_are_rejected(#[case] value: &str) {
+        assert!(value.parse::<MethodTimeouts>().is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::{config::Config, error::ApiError, pretty::Pretty};
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::time::{Duration, Instant};
+
+/// Steps of a request whose time `Timing` reports
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Phase {
+    Validation,
+    Repository,
+    Serialization,
+}
+
+/// Time spent in each `Phase`, in milliseconds
+#[derive(Debug, Default, Serialize)]
+struct Phases {
+    validation_ms: f64,
+    repository_ms: f64,
+    serialization_ms: f64,
+}
+
+impl Phases {
+    fn add(&mut self, phase: Phase, elapsed: Duration) {
+        let total = match phase {
+            Phase::Validation => &mut self.validation_ms,
+            Phase::Repository => &mut self.repository_ms,
+            Phase::Serialization => &mut self.serialization_ms,
+        };
+        *total += elapsed.as_secs_f64() * 1000.0;
+    }
+}
+
+/// Breakdown of the time a request took, asked for with `?debug=timing`
+///
+/// Only honored when `DEBUG_TIMING` is set, the parameter being ignored otherwise; the
+/// breakdown is then sent as a `_timing` object beside the fields of the response. The
+/// response is indented following `Pretty` too, which is extracted along.
+#[derive(Debug, Default)]
+pub struct Timing {
+    pretty: Pretty,
+    phases: Option<Phases>,
+}
+
+impl Timing {
+    /// Run `call`, counting the time it took as `phase` if timing was asked for
+    pub async fn measure<T>(&mut self, phase: Phase, call: impl Future<Output = T>) -> T {
+        let Some(phases) = &mut self.phases else {
+            return call.await;
+        };
+        let started = Instant::now();
+        let result = call.await;
+        phases.add(phase, started.elapsed());
+        result
+    }
+
+    /// Respond with `value` as json like `Pretty::json`, along with the `_timing` object
+    /// when timing was asked for and `value` is an object
+    pub fn json<T: Serialize>(self, value: T) -> Response {
+        let pretty = self.pretty;
+        let Some(mut phases) = self.phases else {
+            return pretty.json(value).into_response();
+        };
+        let started = Instant::now();
+        let Ok(mut value) = serde_json::to_value(value) else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+        phases.add(Phase::Serialization, started.elapsed());
+        if let Value::Object(fields) = &mut value {
+            fields.insert(
+                "_timing".to_string(),
+                serde_json::to_value(phases).unwrap_or_default(),
+            );
+        }
+        pretty.json(value).into_response()
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Timing
+where
+    Arc<Config>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let pretty = Pretty::from_request_parts(parts, state).await?;
+        if !Arc::<Config>::from_ref(state).debug_timing {
+            return Ok(Timing {
+                pretty,
+                phases: None,
+            });
+        }
+        let query = parts.uri.query().unwrap_or_default();
+        let asked = serde_urlencoded::from_str::<Vec<(String, String)>>(query)
+            .unwrap_or_default()
+            .iter()
+            .any(|(name, value)| name == "debug" && value == "timing");
+        Ok(Timing {
+            pretty,
+            phases: asked.then(Phases::default),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body(response: Response) -> Value {
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn phases_are_timed_in_milliseconds() {
+        let mut timing = Timing {
+            pretty: Pretty(false),
+            phases: Some(Phases::default()),
+        };
+
+        timing
+            .measure(
+                Phase::Validation,
+                tokio::time::sleep(Duration::from_millis(2)),
+            )
+            .await;
+        timing
+            .measure(
+                Phase::Repository,
+                tokio::time::sleep(Duration::from_millis(5)),
+            )
+            .await;
+        let body = body(timing.json(serde_json::json!({"name": "Storm"}))).await;
+
+        assert_eq!(body["name"], "Storm");
+        assert_eq!(body["_timing"]["validation_ms"], 2.0);
+        assert_eq!(body["_timing"]["repository_ms"], 5.0);
+        assert!(body["_timing"]["serialization_ms"].is_f64());
+    }
+
+    #[tokio::test]
+    async fn nothing_is_added_unless_asked() {
+        let mut timing = Timing::default();
+
+        timing.measure(Phase::Repository, async {}).await;
+        let body = body(timing.json(serde_json::json!({"name": "Storm"}))).await;
+
+        assert_eq!(body, serde_json::json!({"name": "Storm"}));
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{config::Config, request_id};
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    http::{Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::Instrument;
+
+/// Share of successful requests traced, from `0` (none) to `1` (all)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SampleRate(f64);
+
+// parsing rejects NaN, the only value breaking reflexivity
+impl Eq for SampleRate {}
+
+impl SampleRate {
+    pub const ALL: SampleRate = SampleRate(1.0);
+    pub const NONE: SampleRate = SampleRate(0.0);
+
+    /// Whether the request identified by `key` is part of the sample
+    ///
+    /// The same key is always given the same answer, so services sharing request ids
+    /// sample the same requests.
+    fn includes(self, key: &str) -> bool {
+        if self.0 >= 1.0 {
+            return true;
+        }
+        // `DefaultHasher::new` uses fixed keys, unlike `RandomState`
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() as f64 / u64::MAX as f64) < self.0
+    }
+}
+
+impl FromStr for SampleRate {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.parse::<f64>() {
+            Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(SampleRate(rate)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Whether a request answered with `status` is traced: errors always are, client and server
+/// ones alike, while successes are sampled at `rate`
+pub fn is_traced(status: StatusCode, request_id: &str, rate: SampleRate) -> bool {
+    status.is_client_error() || status.is_server_error() || rate.includes(request_id)
+}
+
+/// Route of requests no route matched, whatever their path
+pub const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Template of the route matching `request`, like `/heroes/:id`, which logs and metrics
+/// label requests with: there's one per route, while paths are as many as heroes
+pub fn route<B>(request: &Request<B>) -> &str {
+    request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(UNMATCHED_ROUTE, MatchedPath::as_str)
+}
+
+/// Middleware logging a trace of the handled request, unless sampled out by `is_traced`
+///
+/// Runs inside `request_id::request_id`, whose id the sampling is keyed on. The request
+/// is handled within a `request` span carrying its method and route.
+pub async fn trace(
+    State(config): State<Arc<Config>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let started = Instant::now();
+    let method = request.method().clone();
+    let route = route(&request).to_string();
+    let span = tracing::info_span!("request", %method, route = route.as_str());
+
+    let response = next.run(request).instrument(span).await;
+
+    let id = request_id::current().unwrap_or_default();
+    let status = response.status();
+    if is_traced(status, &id, config.trace_sample_rate) {
+        tracing::info!(
+            request_id = id.as_str(),
+            %method,
+            route,
+            status = status.as_u16(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "request traced"
+        );
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging;
+    use axum::{middleware, routing::get, Router};
+    use rstest::rstest;
+    use tower::ServiceExt;
+
+    fn app(rate: SampleRate) -> Router {
+        let config = Config {
+            trace_sample_rate: rate,
+            ..Default::default()
+        };
+        Router::new()
+            .route("/ok", get(|| async { StatusCode::OK }))
+            .route("/missing", get(|| async { StatusCode::NOT_FOUND }))
+            .route("/broken", get(|| async { StatusCode::BAD_GATEWAY }))
+            .route("/heroes/:id", get(|| async { StatusCode::OK }))
+            .layer(middleware::from_fn_with_state(Arc::new(config), trace))
+    }
+
+    async fn is_logged(rate: SampleRate, uri: &str) -> bool {
+        let (logs, _guard) = logging::capture();
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        app(rate).oneshot(request).await.unwrap();
+
+        logs.contains("request traced")
+    }
+
+    #[rstest]
+    #[case("/missing")]
+    #[case("/broken")]
+    #[tokio::test]
+    async fn errors_are_traced_whatever_the_sample_rate(#[case] uri: &str) {
+        assert!(is_logged(SampleRate::NONE, uri).await);
+        assert!(is_logged(SampleRate::ALL, uri).await);
+    }
+
+    #[tokio::test]
+    async fn successes_follow_the_sample_rate() {
+        assert!(!is_logged(SampleRate::NONE, "/ok").await);
+        assert!(is_logged(SampleRate::ALL, "/ok").await);
+    }
+
+    #[test]
+    fn partial_rate_samples_part_of_the_requests() {
+        let rate: SampleRate = "0.5".parse().unwrap();
+        let ids: Vec<String> = (0..1000).map(|id| format!("{:016x}", id)).collect();
+
+        let sampled = ids
+            .iter()
+            .filter(|id| is_traced(StatusCode::OK, id, rate))
+            .count();
+
+        assert!((350..650).contains(&sampled), "{} sampled", sampled);
+    }
+
+    #[rstest]
+    #[case("1.5")]
+    #[case("-0.1")]
+    #[case("NaN")]
+    #[case("half")]
+    fn invalid_rates_are_rejected(#[case] value: &str) {
+        assert!(value.parse::<SampleRate>().is_err());
+    }
+
+    async fn traced_route(uri: &str) -> String {
+        let (logs, _guard) = logging::capture();
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        app(SampleRate::ALL).oneshot(request).await.unwrap();
+
+        let line = logs.lines().pop().unwrap();
+        let route = line.split(" route=").nth(1).unwrap();
+        route.split(' ').next().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn requests_are_labelled_with_their_route_not_their_path() {
+        assert_eq!(traced_route("/heroes/1").await, "\"/heroes/:id\"");
+        assert_eq!(traced_route("/heroes/42").await, "\"/heroes/:id\"");
+        assert_eq!(traced_route("/nowhere/42").await, "\"unmatched\"");
+    }
+}